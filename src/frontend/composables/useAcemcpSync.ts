@@ -1,5 +1,7 @@
-import type { ProjectIndexStatus, ProjectsIndexStatus } from '../types/tauri'
+import type { UnlistenFn } from '@tauri-apps/api/event'
+import type { IndexOutcome, IndexProgressEvent, ProjectIndexStatus, ProjectsIndexStatus, ServerStats } from '../types/tauri'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { computed, onUnmounted, ref } from 'vue'
 
 // 全局状态
@@ -8,8 +10,51 @@ const currentProjectRoot = ref<string>('')
 const isPolling = ref(false)
 const autoIndexEnabled = ref(true)
 const watchingProjects = ref<string[]>([])
+const serverStats = ref<ServerStats | null>(null)
+const serverStatsError = ref<string>('')
 
 let pollingTimer: number | null = null
+let progressUnlisten: UnlistenFn | null = null
+
+// 根据推送的进度事件更新本地状态（状态文件仍是最终依据，完成或失败时重新拉取）
+function applyProgressEvent(event: IndexProgressEvent) {
+  const status = allProjectsStatus.value.projects[event.project_root]
+  switch (event.type) {
+    case 'collection_started':
+      if (status) {
+        status.status = 'indexing'
+        status.progress = 0
+      }
+      break
+    case 'collection_done':
+      if (status) {
+        status.total_files = event.files
+        status.progress = 20
+      }
+      break
+    case 'batch_done':
+      if (status)
+        status.progress = 20 + Math.floor(event.index * 75 / Math.max(event.total, 1))
+      break
+    case 'completed':
+    case 'failed':
+      invoke<ProjectIndexStatus>('get_acemcp_index_status', { projectRootPath: event.project_root })
+        .then((result) => {
+          allProjectsStatus.value.projects[event.project_root] = result
+        })
+        .catch(err => console.error('获取项目索引状态失败:', err))
+      break
+  }
+}
+
+// 订阅索引进度推送（全局只订阅一次）
+async function subscribeProgressEvents() {
+  if (progressUnlisten)
+    return
+  progressUnlisten = await listen<IndexProgressEvent>('acemcp-index-progress', (event) => {
+    applyProgressEvent(event.payload)
+  })
+}
 
 /**
  * Acemcp 索引同步状态管理
@@ -89,10 +134,22 @@ export function useAcemcpSync() {
     }
   }
 
+  // 获取服务端存储用量（服务端不支持 /stats 时记录错误信息）
+  async function fetchServerStats() {
+    try {
+      serverStats.value = await invoke<ServerStats>('get_acemcp_server_stats')
+      serverStatsError.value = ''
+    }
+    catch (err) {
+      serverStats.value = null
+      serverStatsError.value = String(err)
+    }
+  }
+
   // 手动触发索引更新
   async function triggerIndexUpdate(projectRoot: string) {
     try {
-      const result = await invoke<string>('trigger_acemcp_index_update', {
+      const result = await invoke<IndexOutcome>('trigger_acemcp_index_update', {
         projectRootPath: projectRoot,
       })
       // 立即刷新状态
@@ -104,6 +161,16 @@ export function useAcemcpSync() {
     }
   }
 
+  // 将索引结果格式化为提示文本
+  function formatIndexOutcome(outcome: IndexOutcome) {
+    const failed = outcome.failed_batches.length > 0 ? `，失败批次 ${outcome.failed_batches.length} 个` : ''
+    const diff = outcome.diff
+    const changes = diff && (diff.added.length || diff.modified.length || diff.removed.length)
+      ? `；文件新增 ${diff.added.length}，修改 ${diff.modified.length}，删除 ${diff.removed.length}`
+      : ''
+    return `索引更新成功，共 ${outcome.total_blobs} 个 blobs（新上传 ${outcome.uploaded}，已存在 ${outcome.skipped_existing}${failed}）${changes}`
+  }
+
   // 获取全局自动索引开关状态
   async function fetchAutoIndexEnabled() {
     try {
@@ -175,6 +242,8 @@ export function useAcemcpSync() {
   }
 
   // 开始轮询（用于 MCP 弹窗）
+  // 应用内触发的索引通过 acemcp-index-progress 事件实时推送；
+  // MCP 服务进程中的索引无法推送到界面，仍需定时拉取状态文件
   function startPolling(projectRoot?: string, intervalMs = 3000) {
     if (isPolling.value)
       return
@@ -184,6 +253,8 @@ export function useAcemcpSync() {
 
     isPolling.value = true
 
+    subscribeProgressEvents()
+
     // 立即执行一次
     fetchAllStatus()
     fetchAutoIndexEnabled()
@@ -223,6 +294,8 @@ export function useAcemcpSync() {
     isPolling,
     autoIndexEnabled,
     watchingProjects,
+    serverStats,
+    serverStatsError,
 
     // 计算属性
     statusSummary,
@@ -230,9 +303,12 @@ export function useAcemcpSync() {
     isIndexing,
 
     // 方法
+    subscribeProgressEvents,
     fetchAllStatus,
     fetchProjectStatus,
+    fetchServerStats,
     triggerIndexUpdate,
+    formatIndexOutcome,
     fetchAutoIndexEnabled,
     setAutoIndexEnabled,
     fetchWatchingProjects,
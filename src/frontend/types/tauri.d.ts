@@ -17,12 +17,113 @@ export interface ProjectIndexStatus {
   last_failure_time: string | null
   last_error: string | null
   directory_stats: Record<string, [number, number]> // 目录路径 -> [总文件数, 已索引文件数]
+  encoding_breakdown: Record<string, number> // 解码方式 -> 文件数（lossy 非零说明存在乱码风险）
+  indexed_bytes: number // 最近一次完整索引的内容总字节数
+  uploaded_bytes: number // 最近一次索引新上传的字节数
+  status_history: StatusEvent[] // 最近的状态变迁（最多 50 条）
+  compressible_files: CompressibleFile[] // 内容高度重复（压缩比 < 0.1）的文件
+}
+
+export interface CompressibleFile {
+  path: string
+  ratio: number // zlib 压缩后长度 / 原始长度
+}
+
+export interface StatusEvent {
+  timestamp: string
+  from_status: IndexStatus
+  to_status: IndexStatus
+  detail: string | null
 }
 
 export interface ProjectsIndexStatus {
   projects: Record<string, ProjectIndexStatus>
 }
 
+// 手动触发索引更新的结构化结果
+export interface IndexOutcome {
+  uploaded: number
+  skipped_existing: number
+  skipped_small: number
+  secrets_skipped: number
+  secrets_redacted: number
+  total_blobs: number
+  failed_batches: number[]
+  duration_ms: number
+  diff: IndexDiff
+}
+
+// 服务端存储用量（/stats 接口，后端缓存 60 秒）
+export interface ServerStats {
+  total_blobs: number
+  total_bytes: number
+  project_count: number
+  server_version: string
+}
+
+// 与上次索引相比的文件级变更
+export interface IndexDiff {
+  added: string[]
+  modified: string[]
+  removed: string[]
+}
+
+// 索引校验结果（validate_acemcp_index，只读）
+export interface IndexValidation {
+  project_root: string
+  stored_blobs: number
+  current_blobs: number
+  stale_blobs: number
+  unindexed_files: number
+  unindexed_paths: string[]
+  reindex_recommended: boolean
+}
+
+// 文件未被索引的原因（explain_acemcp_exclusion）
+export type ExclusionReason
+  = | { kind: 'git_ignored' }
+    | { kind: 'hidden' }
+    | { kind: 'secret_file' }
+    | { kind: 'exclude_pattern', pattern: string }
+    | { kind: 'outside_include_dirs' }
+    | { kind: 'too_deep' }
+    | { kind: 'wrong_extension' }
+    | { kind: 'not_committed' }
+    | { kind: 'not_changed_since' }
+    | { kind: 'file_too_large' }
+    | { kind: 'unreadable' }
+    | { kind: 'binary' }
+    | { kind: 'too_short' }
+    | { kind: 'secret_content' }
+    | { kind: 'not_excluded' }
+
+// 后台索引队列状态（get_index_queue_stats）
+export interface IndexQueueStats {
+  queued: number
+  running: number
+  max_concurrent_jobs: number
+}
+
+// Acemcp 索引进度推送事件（acemcp-index-progress）
+export type IndexProgressEvent
+  = | { type: 'collection_started', project_root: string }
+    | { type: 'collection_done', project_root: string, files: number }
+    | { type: 'batch_done', project_root: string, index: number, total: number, success: boolean }
+    | { type: 'completed', project_root: string, outcome: IndexOutcome }
+    | { type: 'failed', project_root: string, error: string }
+
+// Acemcp 配置校验结果（kind 对应后端枚举变体，按配置字段高亮）
+export interface ConfigIssue {
+  kind: string
+  value?: string | number
+  message?: string
+}
+
+export interface ConfigValidation {
+  warnings: ConfigIssue[]
+  errors: ConfigIssue[]
+}
+
 // Acemcp 文件级索引状态类型定义
 export type FileIndexStatusType = 'indexed' | 'pending'
 
@@ -35,3 +136,28 @@ export interface ProjectFilesStatus {
   project_root: string
   files: FileIndexStatus[]
 }
+
+// 记忆条目（add_memory_cmd / set_memory_priority_cmd / set_memory_expiry_cmd 的返回值）
+export type MemoryCategory = 'Rule' | 'Preference' | 'Pattern' | 'Context'
+
+export interface MemoryEntry {
+  id: string
+  content: string
+  category: MemoryCategory
+  created_at: string
+  updated_at: string
+  expires_at: string | null
+  access_count: number
+  pinned: boolean
+  tags: string[]
+  file: string | null
+  line: number | null
+  priority: number
+}
+
+// 记忆远程同步结果（sync_memories_to_remote_cmd 的返回值）
+export interface SyncReport {
+  project: string
+  total: number
+  upserted: number
+}
@@ -81,16 +81,36 @@ pub fn build_tauri_app() -> Builder<tauri::Wry> {
             crate::mcp::tools::acemcp::commands::debug_acemcp_search,
             crate::mcp::tools::acemcp::commands::execute_acemcp_tool,
             crate::mcp::tools::acemcp::commands::get_acemcp_index_status,
+            crate::mcp::tools::acemcp::commands::validate_acemcp_config,
             crate::mcp::tools::acemcp::commands::get_all_acemcp_index_status,
+            crate::mcp::tools::acemcp::commands::get_acemcp_index_status_history,
             crate::mcp::tools::acemcp::commands::get_acemcp_project_files_status,
             crate::mcp::tools::acemcp::commands::trigger_acemcp_index_update,
+            crate::mcp::tools::acemcp::commands::get_acemcp_server_stats,
+            crate::mcp::tools::acemcp::commands::compare_acemcp_index_versions,
+            crate::mcp::tools::acemcp::commands::validate_acemcp_index,
+            crate::mcp::tools::acemcp::commands::explain_acemcp_exclusion,
+            crate::mcp::tools::acemcp::commands::get_acemcp_index_summary,
+            crate::mcp::tools::acemcp::commands::rebuild_acemcp_projects_json,
+            crate::mcp::tools::acemcp::commands::prune_acemcp_deleted_projects,
+            crate::mcp::tools::acemcp::commands::delete_acemcp_remote_index,
+            crate::mcp::tools::acemcp::commands::run_acemcp_doctor,
             crate::mcp::tools::acemcp::commands::get_auto_index_enabled,
             crate::mcp::tools::acemcp::commands::set_auto_index_enabled,
             crate::mcp::tools::acemcp::commands::get_watching_projects,
+            crate::mcp::tools::acemcp::commands::get_watcher_stats,
+            crate::mcp::tools::acemcp::commands::get_index_queue_stats,
             crate::mcp::tools::acemcp::commands::is_project_watching,
             crate::mcp::tools::acemcp::commands::stop_project_watching,
             crate::mcp::tools::acemcp::commands::stop_all_watching,
 
+            // 记忆命令（返回结构化的记忆条目）
+            crate::mcp::tools::memory::commands::add_memory_cmd,
+            crate::mcp::tools::memory::commands::set_memory_priority_cmd,
+            crate::mcp::tools::memory::commands::set_memory_expiry_cmd,
+            crate::mcp::tools::memory::commands::sync_memories_to_remote_cmd,
+            crate::mcp::tools::memory::commands::restore_memories_from_remote_cmd,
+
             // 自定义prompt命令
             get_custom_prompt_config,
             add_custom_prompt,
@@ -8,6 +8,15 @@ use tauri::{AppHandle, Manager};
 pub async fn setup_application(app_handle: &AppHandle) -> Result<(), String> {
     let state = app_handle.state::<AppState>();
 
+    // 迁移旧版本配置文件中改名的字段，需在读取配置之前进行
+    if let Ok(config_path) = crate::config::get_standalone_config_path() {
+        if config_path.exists() {
+            if let Err(e) = crate::mcp::tools::AcemcpTool::migrate_config_schema(&config_path) {
+                log_important!(warn, "迁移配置文件失败: {}", e);
+            }
+        }
+    }
+
     // 加载配置并应用窗口设置
     if let Err(e) = load_config_and_apply_window_settings(&state, app_handle).await {
         log_important!(warn, "加载配置失败: {}", e);
@@ -26,5 +35,20 @@ pub async fn setup_application(app_handle: &AppHandle) -> Result<(), String> {
         log_important!(warn, "设置退出处理器失败: {}", e);
     }
 
+    // 推送 acemcp 索引进度事件
+    crate::mcp::tools::acemcp::commands::forward_index_progress_events(app_handle);
+
+    // 清理已删除项目的 acemcp 索引记录
+    if let Err(e) = crate::mcp::tools::AcemcpTool::prune_deleted_projects() {
+        log_important!(warn, "清理已删除项目的索引记录失败: {}", e);
+    }
+
+    // 恢复上次运行时正在监听的项目
+    tauri::async_runtime::spawn(async {
+        if let Err(e) = crate::mcp::tools::AcemcpTool::restore_watched_projects().await {
+            log_important!(warn, "恢复文件监听失败: {}", e);
+        }
+    });
+
     Ok(())
 }
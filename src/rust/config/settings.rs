@@ -116,6 +116,66 @@ pub struct McpConfig {
     pub acemcp_max_lines_per_blob: Option<u32>, // acemcp最大行数/块
     pub acemcp_text_extensions: Option<Vec<String>>, // acemcp文件扩展名
     pub acemcp_exclude_patterns: Option<Vec<String>>, // acemcp排除模式
+    pub acemcp_exclude_extensions: Option<Vec<String>>, // acemcp排除的文件扩展名（文件扩展名为空时生效）
+    pub acemcp_min_blob_chars: Option<u32>, // acemcp最小blob字符数（低于该值的blob不上传）
+    pub acemcp_index_depth_limit: Option<usize>, // acemcp目录遍历最大深度
+    pub acemcp_search_audit: Option<bool>, // acemcp是否记录搜索审计日志
+    pub acemcp_smart_split_lookahead_lines: Option<usize>, // acemcp智能分块向后查找行数（为空则按行硬切分）
+    pub acemcp_encoding_override: Option<String>, // acemcp强制优先使用的文件编码（如 gbk）
+    pub acemcp_chunk_naming: Option<String>, // acemcp分块命名方式：line_range(默认) / legacy
+    pub acemcp_token_refresh_url: Option<String>, // acemcp token刷新端点（收到401时自动续期）
+    pub acemcp_token_refresh_payload: Option<HashMap<String, String>>, // acemcp token刷新请求体
+    pub acemcp_only_committed_files: Option<bool>, // acemcp仅索引 git 跟踪的文件
+    pub acemcp_request_timeout_ms: Option<u64>, // acemcp上传请求超时（毫秒）
+    pub acemcp_search_timeout_ms: Option<u64>, // acemcp搜索请求超时（毫秒）
+    pub acemcp_case_insensitive_paths: Option<bool>, // acemcp路径匹配是否忽略大小写（默认跟随平台）
+    pub acemcp_min_blob_content_length: Option<usize>, // acemcp最小 blob 内容字节数
+    pub acemcp_max_file_bytes: Option<u64>, // acemcp单个文件大小上限（字节）
+    pub acemcp_max_response_bytes: Option<usize>, // acemcp响应体大小上限（字节）
+    pub acemcp_project_namespace: Option<String>, // acemcp项目命名空间
+    pub acemcp_project_namespace_salt: Option<String>, // acemcp命名空间哈希盐值
+    pub acemcp_normalize_line_endings: Option<bool>, // acemcp哈希前统一换行符（默认开启）
+    pub acemcp_index_on_save_debounce_ms: Option<u64>, // acemcp文件监听防抖窗口（毫秒）
+    pub acemcp_strict_blob_names: Option<bool>, // acemcp上传响应blob_names不一致时判定批次失败
+    pub acemcp_requests_per_second: Option<u32>, // acemcp全局请求速率上限（次/秒）
+    pub acemcp_include_dirs: Option<Vec<String>>, // acemcp只索引的目录（允许列表）
+    pub acemcp_ca_cert_path: Option<String>, // acemcp自定义根证书（PEM）路径
+    #[serde(alias = "acemcp_tls_insecure_skip_verify")]
+    pub acemcp_danger_accept_invalid_certs: Option<bool>, // acemcp跳过TLS证书校验（仅限调试）
+    pub acemcp_use_idempotency_keys: Option<bool>, // acemcp上传批次携带Idempotency-Key请求头
+    pub acemcp_query_expansion: Option<bool>, // acemcp按项目同义词表扩展检索查询
+    pub acemcp_max_watched_projects: Option<usize>, // acemcp最多同时监听的项目数
+    pub acemcp_reconcile_interval_secs: Option<u64>, // acemcp监听项目的定期全量对账间隔（秒，0为关闭）
+    pub acemcp_max_chars_per_blob: Option<usize>, // acemcp每个blob的最大字符数（与最大行数同时生效）
+    pub acemcp_follow_symlinks: Option<bool>, // acemcp遍历目录时是否跟随符号链接（默认跟随）
+    pub acemcp_search_result_max_length: Option<usize>, // acemcp检索结果的最大字符数（同时是请求可指定的上限）
+    pub acemcp_merge_adjacent_hits: Option<bool>, // acemcp合并同一文件中行区间相邻或重叠的检索结果
+    pub acemcp_max_concurrent_index_jobs: Option<usize>, // acemcp同时运行的后台索引数（默认2）
+    pub acemcp_circuit_breaker_enabled: Option<bool>, // acemcp对连续失败的服务端熔断（默认关闭）
+    pub acemcp_webhook_url: Option<String>, // acemcp索引完成后POST通知的地址
+    pub acemcp_webhook_headers: Option<HashMap<String, String>>, // acemcp索引完成通知的请求头（如认证）
+    pub acemcp_skip_hidden: Option<bool>, // acemcp跳过以.开头的文件和目录（默认true）
+    pub acemcp_secret_scan_policy: Option<String>, // acemcp blob内容包含密钥时的处理方式：skip（默认）、redact、off
+    pub acemcp_upload_chunk_encoding: Option<String>, // acemcp上传时blob路径与内容的编码：plain（默认）/ base64
+    pub acemcp_preflight_probe: Option<bool>, // acemcp索引前探测token与base_url是否可用（默认true）
+    pub acemcp_project_overrides: Option<HashMap<String, AcemcpProjectOverride>>, // acemcp按项目根目录覆盖的配置
+    pub acemcp_audit_log: Option<bool>, // 是否将每次工具调用记录到 acemcp 数据目录下的 audit.jsonl
+    pub acemcp_query_enrichment: Option<bool>, // acemcp搜索前是否将项目的规则与模式记忆追加到查询
+    pub acemcp_query_enrichment_max_chars: Option<usize>, // acemcp追加到查询的记忆最大字符数
+    pub acemcp_index_only_changed_since: Option<String>, // acemcp完整索引时只收集该时间之后修改过的文件（RFC3339 或 YYYY-MM-DD）
+    pub acemcp_user_agent: Option<String>, // acemcp请求的 User-Agent（默认 sanshu/<版本>）
+    pub acemcp_custom_splitter_script: Option<String>, // acemcp自定义分块脚本路径（失败时回退内置分块）
+    pub memory_store_dir: Option<String>, // 记忆存储根目录（为空时使用项目内 .sanshu-memory）
+    pub memory_backend: Option<String>, // 记忆存储后端：markdown（默认）或 sqlite
+}
+
+// acemcp单个项目的覆盖配置（键为项目根目录）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct AcemcpProjectOverride {
+    #[serde(default)]
+    pub auto_index: Option<bool>, // 搜索时是否自动后台索引并监听文件变更（默认true），false时只检索已有索引
+    #[serde(default)]
+    pub additional_roots: Option<Vec<String>>, // 与项目一起索引的其他根目录（相对项目根目录或绝对路径），blob 路径以目录名为前缀
 }
 
 // 自定义prompt结构
@@ -281,6 +341,56 @@ pub fn default_mcp_config() -> McpConfig {
         acemcp_max_lines_per_blob: None,
         acemcp_text_extensions: None,
         acemcp_exclude_patterns: None,
+        acemcp_exclude_extensions: None,
+        acemcp_min_blob_chars: None,
+        acemcp_index_depth_limit: None,
+        acemcp_search_audit: None,
+        acemcp_smart_split_lookahead_lines: None,
+        acemcp_encoding_override: None,
+        acemcp_chunk_naming: None,
+        acemcp_token_refresh_url: None,
+        acemcp_token_refresh_payload: None,
+        acemcp_only_committed_files: None,
+        acemcp_request_timeout_ms: None,
+        acemcp_search_timeout_ms: None,
+        acemcp_case_insensitive_paths: None,
+        acemcp_min_blob_content_length: None,
+        acemcp_max_file_bytes: None,
+        acemcp_max_response_bytes: None,
+        acemcp_project_namespace: None,
+        acemcp_project_namespace_salt: None,
+        acemcp_normalize_line_endings: None,
+        acemcp_index_on_save_debounce_ms: None,
+        acemcp_strict_blob_names: None,
+        acemcp_requests_per_second: None,
+        acemcp_include_dirs: None,
+        acemcp_ca_cert_path: None,
+        acemcp_danger_accept_invalid_certs: None,
+        acemcp_use_idempotency_keys: None,
+        acemcp_query_expansion: None,
+        acemcp_max_watched_projects: None,
+        acemcp_reconcile_interval_secs: None,
+        acemcp_max_chars_per_blob: None,
+        acemcp_follow_symlinks: None,
+        acemcp_search_result_max_length: None,
+        acemcp_merge_adjacent_hits: None,
+        acemcp_max_concurrent_index_jobs: None,
+        acemcp_circuit_breaker_enabled: None,
+        acemcp_webhook_url: None,
+        acemcp_webhook_headers: None,
+        acemcp_skip_hidden: None,
+        acemcp_secret_scan_policy: None,
+        acemcp_upload_chunk_encoding: None,
+        acemcp_preflight_probe: None,
+        acemcp_project_overrides: None,
+        acemcp_audit_log: None,
+        acemcp_query_enrichment: None,
+        acemcp_query_enrichment_max_chars: None,
+        acemcp_index_only_changed_since: None,
+        acemcp_user_agent: None,
+        acemcp_custom_splitter_script: None,
+        memory_store_dir: None,
+        memory_backend: None,
     }
 }
 
@@ -137,6 +137,14 @@ pub fn load_standalone_config() -> Result<AppConfig> {
     }
 }
 
+/// 独立保存配置文件（用于MCP服务器等独立进程回写配置，如刷新后的 token）
+pub fn save_standalone_config(config: &AppConfig) -> Result<()> {
+    let config_path = get_standalone_config_path()?;
+    let config_json = serde_json::to_string_pretty(config)?;
+    fs::write(&config_path, config_json)?;
+    Ok(())
+}
+
 /// 独立加载Telegram配置（用于MCP模式下的配置检查）
 pub fn load_standalone_telegram_config() -> Result<super::settings::TelegramConfig> {
     let config = load_standalone_config()?;
@@ -144,7 +152,7 @@ pub fn load_standalone_telegram_config() -> Result<super::settings::TelegramConf
 }
 
 /// 获取独立配置文件路径（不依赖Tauri）
-fn get_standalone_config_path() -> Result<PathBuf> {
+pub fn get_standalone_config_path() -> Result<PathBuf> {
     // 使用标准的配置目录
     let config_dir = dirs::config_dir()
         .ok_or_else(|| anyhow::anyhow!("无法获取配置目录"))?
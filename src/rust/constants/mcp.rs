@@ -27,6 +27,15 @@ pub const REQUEST_TIMEOUT_MS: u64 = 30000;
 /// MCP 重试次数
 pub const MAX_RETRY_COUNT: u32 = 3;
 
+/// 项目内默认记忆目录名
+pub const DEFAULT_MEMORY_DIR_NAME: &str = ".sanshu-memory";
+
+/// 覆盖记忆存储根目录的环境变量（优先级高于配置文件中的 memory_store_dir）
+pub const MEMORY_STORE_DIR_ENV: &str = "SANSHU_MEMORY_DIR";
+
+/// SQLite 记忆后端的数据库文件名（位于记忆目录内）
+pub const SQLITE_MEMORY_DB_NAME: &str = "memories.db";
+
 // MCP 工具配置结构体
 #[derive(Debug, Clone)]
 pub struct McpToolConfig {
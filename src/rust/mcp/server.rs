@@ -7,8 +7,10 @@ use rmcp::{
 };
 use rmcp::model::*;
 use std::collections::HashMap;
+use std::time::Instant;
 
 use super::tools::{InteractionTool, MemoryTool, AcemcpTool};
+use super::tools::acemcp::audit::AuditRecord;
 use super::types::{ZhiRequest, JiyiRequest};
 use crate::config::load_standalone_config;
 use crate::{log_important, log_debug};
@@ -131,7 +133,7 @@ impl ServerHandler for ZhiServer {
                 "properties": {
                     "action": {
                         "type": "string",
-                        "description": "操作类型：记忆(添加记忆), 回忆(获取项目信息)"
+                        "description": "操作类型：记忆(添加记忆), 回忆(获取项目信息), 统计(记忆条数与词数/字符数), 设置过期(为记忆设置过期时间), 优先级(设置记忆的回忆优先级), 导入文档(从 Markdown 规范文档批量导入记忆), 关联(在两条记忆之间建立关联), 取消关联(删除两条记忆之间的关联), 复制(将当前项目的全部记忆复制到另一个项目，跳过重复)"
                     },
                     "project_path": {
                         "type": "string",
@@ -139,11 +141,27 @@ impl ServerHandler for ZhiServer {
                     },
                     "content": {
                         "type": "string",
-                        "description": "记忆内容（记忆操作时必需）"
+                        "description": "记忆内容（记忆操作时必需）；设置过期操作时为 RFC3339 格式的过期时间；优先级操作时为 0-255 的整数（默认 128，越大越靠前）；导入文档操作时为项目目录内的文档路径（相对项目路径或绝对路径）；关联、取消关联操作时为另一条记忆的 ID；复制操作时为目标项目路径"
                     },
                     "category": {
                         "type": "string",
                         "description": "记忆分类：rule(规范规则), preference(用户偏好), pattern(最佳实践), context(项目上下文)"
+                    },
+                    "memory_id": {
+                        "type": "string",
+                        "description": "记忆 ID（设置过期、优先级、关联、取消关联操作时必需）"
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "关联的文件路径（可选，相对项目根目录），记忆操作时将记忆作为该文件的代码标注"
+                    },
+                    "line": {
+                        "type": "integer",
+                        "description": "关联的行号（可选，从 1 开始），需同时提供 file"
+                    },
+                    "relation": {
+                        "type": "string",
+                        "description": "关联关系（可选，关联操作时使用，如 实现、限制；默认 相关）"
                     }
                 },
                 "required": ["action", "project_path"]
@@ -214,7 +232,12 @@ impl ServerHandler for ZhiServer {
                     .map_err(|e| McpError::invalid_params(format!("参数解析失败: {}", e), None))?;
 
                 // 调用记忆工具
-                MemoryTool::jiyi(ji_request).await
+                let started = Instant::now();
+                let project_root = ji_request.project_path.clone();
+                let query_len = ji_request.content.chars().count();
+                let result = MemoryTool::jiyi(ji_request).await;
+                audit_tool_call("jiyi", project_root, query_len, started, &result);
+                result
             }
             "sou" => {
                 // 检查代码搜索工具是否启用
@@ -235,7 +258,12 @@ impl ServerHandler for ZhiServer {
                     .map_err(|e| McpError::invalid_params(format!("参数解析失败: {}", e), None))?;
 
                 // 调用代码搜索工具
-                AcemcpTool::search_context(acemcp_request).await
+                let started = Instant::now();
+                let project_root = acemcp_request.project_root_path.clone();
+                let query_len = acemcp_request.query.chars().count();
+                let result = AcemcpTool::search_context_with_rewrite(acemcp_request).await;
+                audit_tool_call("sou", project_root, query_len, started, &result);
+                result
             }
             _ => {
                 Err(McpError::invalid_request(
@@ -249,6 +277,36 @@ impl ServerHandler for ZhiServer {
 
 
 
+/// 记录工具调用的审计日志；工具返回 `is_error` 的结果同样视为失败
+fn audit_tool_call(
+    tool_name: &str,
+    project_root: String,
+    query_len: usize,
+    started: Instant,
+    result: &Result<CallToolResult, McpError>,
+) {
+    let error = match result {
+        Ok(r) if r.is_error == Some(true) => Some(
+            r.content
+                .first()
+                .and_then(|c| c.as_text())
+                .map(|t| t.text.clone())
+                .unwrap_or_default(),
+        ),
+        Ok(_) => None,
+        Err(e) => Some(e.message.to_string()),
+    };
+    AcemcpTool::audit_log(&AuditRecord {
+        timestamp: chrono::Utc::now(),
+        tool_name: tool_name.to_string(),
+        project_root,
+        query_len,
+        duration_ms: started.elapsed().as_millis() as u64,
+        success: error.is_none(),
+        error,
+    });
+}
+
 /// 启动MCP服务器
 pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
     // 创建并运行服务器
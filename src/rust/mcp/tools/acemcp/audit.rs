@@ -0,0 +1,98 @@
+// 工具调用审计日志
+// 启用 `audit_log_enabled` 后，每次 sou / jiyi 工具调用以 JSON Lines 追加到 acemcp 数据目录下的 `audit.jsonl`；
+// 为保护隐私只记录查询长度，不记录查询内容。文件超过上限时轮转为 `audit.jsonl.1`
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::log_debug;
+
+/// 审计日志的单文件大小上限（超过后轮转为 .1）
+const AUDIT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// 串行化审计日志（包括搜索审计日志）的写入，避免并发调用的记录交错或同时轮转
+static AUDIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// 一次工具调用的审计记录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// 工具名称："sou" 或 "jiyi"
+    pub tool_name: String,
+    pub project_root: String,
+    /// 查询（或记忆内容）的字符数
+    pub query_len: usize,
+    pub duration_ms: u64,
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// 审计日志路径：acemcp 数据目录（`ACEMCP_DATA_DIR` 或 `~/.acemcp/data`）下的 `audit.jsonl`
+pub(crate) fn audit_log_path() -> PathBuf {
+    super::mcp::acemcp_data_dir().join("audit.jsonl")
+}
+
+/// 向日志文件追加一行，写入前文件超过 `max_bytes` 时先轮转为同名加 `.1` 后缀的文件（覆盖上一次轮转的文件）
+pub(crate) fn append_line_rotating(path: &Path, line: &str, max_bytes: u64) -> std::io::Result<()> {
+    let _guard = AUDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::metadata(path).map(|m| m.len() >= max_bytes).unwrap_or(false) {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        fs::rename(path, rotated)?;
+    }
+
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// 追加一条审计记录，写入前文件超过 `max_bytes` 时先轮转
+pub(crate) fn append_record(path: &Path, record: &AuditRecord, max_bytes: u64) -> std::io::Result<()> {
+    append_line_rotating(path, &serde_json::to_string(record)?, max_bytes)
+}
+
+/// 写入审计日志；失败只记录调试日志，不影响工具调用本身
+pub(crate) fn write(record: &AuditRecord) {
+    let path = audit_log_path();
+    if let Err(e) = append_record(&path, record, AUDIT_LOG_MAX_BYTES) {
+        log_debug!("写入审计日志失败: {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_record_rotates_when_over_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            tool_name: "sou".to_string(),
+            project_root: "/work/demo".to_string(),
+            query_len: 12,
+            duration_ms: 34,
+            success: false,
+            error: Some("Acemcp搜索失败".to_string()),
+        };
+
+        append_record(&path, &record, 1).unwrap();
+        append_record(&path, &record, 1).unwrap();
+
+        let rotated = fs::read_to_string(dir.path().join("audit.jsonl.1")).unwrap();
+        let current = fs::read_to_string(&path).unwrap();
+        assert_eq!(rotated.lines().count(), 1);
+        assert_eq!(current.lines().count(), 1);
+        let parsed: AuditRecord = serde_json::from_str(current.trim()).unwrap();
+        assert_eq!(parsed, record);
+    }
+}
@@ -0,0 +1,143 @@
+// Acemcp 熔断器
+// 按服务端主机记录连续失败次数：连续失败达到阈值后熔断一段时间，期间的请求直接失败而不再发起 HTTP 请求；
+// 熔断到期后放行一个探测请求（半开），成功则恢复，失败则重新熔断
+
+use dashmap::DashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use super::error::AcemcpError;
+use super::types::AcemcpConfig;
+
+/// 连续失败多少次后熔断
+const FAILURE_THRESHOLD: u32 = 5;
+/// 熔断持续时间
+const OPEN_DURATION: Duration = Duration::from_secs(60);
+
+/// 某个主机的熔断状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitBreakerState {
+    /// 正常放行，记录连续失败次数
+    Closed { consecutive_failures: u32 },
+    /// 熔断中，到期前的请求直接失败
+    Open { until: Instant },
+    /// 熔断到期，已放行一个探测请求，等待其结果
+    HalfOpen { since: Instant },
+}
+
+/// 按主机划分的熔断器集合
+pub struct CircuitBreakers {
+    states: DashMap<String, CircuitBreakerState>,
+    threshold: u32,
+    open_for: Duration,
+}
+
+impl CircuitBreakers {
+    pub fn new(threshold: u32, open_for: Duration) -> Self {
+        Self { states: DashMap::new(), threshold: threshold.max(1), open_for }
+    }
+
+    /// 发起请求前调用：熔断中时返回错误；熔断到期时转为半开并放行本次请求作为探测
+    pub fn check(&self, host: &str) -> Result<(), AcemcpError> {
+        let mut state = self.states.entry(host.to_string()).or_insert(CircuitBreakerState::Closed { consecutive_failures: 0 });
+        let now = Instant::now();
+        match *state {
+            CircuitBreakerState::Closed { .. } => Ok(()),
+            CircuitBreakerState::Open { until } if now < until => Err(AcemcpError::CircuitOpen {
+                host: host.to_string(),
+                retry_after_secs: (until - now).as_secs().max(1),
+            }),
+            // 探测请求迟迟没有结果（如调用方提前取消）时，允许新的探测
+            CircuitBreakerState::HalfOpen { since } if now.duration_since(since) < self.open_for => Err(AcemcpError::CircuitOpen {
+                host: host.to_string(),
+                retry_after_secs: (self.open_for - now.duration_since(since)).as_secs().max(1),
+            }),
+            _ => {
+                *state = CircuitBreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    /// 请求成功：恢复为正常状态
+    pub fn record_success(&self, host: &str) {
+        self.states.insert(host.to_string(), CircuitBreakerState::Closed { consecutive_failures: 0 });
+    }
+
+    /// 请求失败：累计连续失败次数，达到阈值或半开探测失败时熔断；返回是否进入熔断
+    pub fn record_failure(&self, host: &str) -> bool {
+        let mut state = self.states.entry(host.to_string()).or_insert(CircuitBreakerState::Closed { consecutive_failures: 0 });
+        let failures = match *state {
+            CircuitBreakerState::Closed { consecutive_failures } => consecutive_failures + 1,
+            CircuitBreakerState::HalfOpen { .. } => self.threshold,
+            CircuitBreakerState::Open { .. } => return false,
+        };
+        if failures >= self.threshold {
+            *state = CircuitBreakerState::Open { until: Instant::now() + self.open_for };
+            true
+        } else {
+            *state = CircuitBreakerState::Closed { consecutive_failures: failures };
+            false
+        }
+    }
+
+    /// 主机当前的熔断状态
+    pub fn state(&self, host: &str) -> CircuitBreakerState {
+        self.states.get(host).map(|s| *s).unwrap_or(CircuitBreakerState::Closed { consecutive_failures: 0 })
+    }
+}
+
+static GLOBAL_BREAKERS: OnceLock<CircuitBreakers> = OnceLock::new();
+
+/// 全局共享的熔断器，所有项目对同一主机的请求共用一个状态
+pub(crate) fn global() -> &'static CircuitBreakers {
+    GLOBAL_BREAKERS.get_or_init(|| CircuitBreakers::new(FAILURE_THRESHOLD, OPEN_DURATION))
+}
+
+/// 启用 `circuit_breaker_enabled` 时返回请求地址对应的熔断键（主机:端口），未启用或地址无法解析时返回 None
+pub(crate) fn breaker_key(config: &AcemcpConfig, url: &str) -> Option<String> {
+    if !config.circuit_breaker_enabled.unwrap_or(false) {
+        return None;
+    }
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port_or_known_default() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_consecutive_failures_and_half_opens() {
+        let breakers = CircuitBreakers::new(3, Duration::from_millis(50));
+        let host = "api.example.com:443";
+
+        breakers.record_failure(host);
+        breakers.record_failure(host);
+        // 成功会清零连续失败次数
+        breakers.record_success(host);
+        breakers.record_failure(host);
+        breakers.record_failure(host);
+        assert!(breakers.check(host).is_ok());
+        assert!(breakers.record_failure(host));
+        assert!(matches!(breakers.check(host), Err(AcemcpError::CircuitOpen { .. })));
+
+        // 到期后只放行一个探测请求，探测失败立即重新熔断
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breakers.check(host).is_ok());
+        assert!(breakers.check(host).is_err());
+        assert!(breakers.record_failure(host));
+        assert!(breakers.check(host).is_err());
+
+        // 探测成功后恢复正常
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breakers.check(host).is_ok());
+        breakers.record_success(host);
+        assert_eq!(breakers.state(host), CircuitBreakerState::Closed { consecutive_failures: 0 });
+        assert!(breakers.check("other.example.com:443").is_ok());
+    }
+}
@@ -1,8 +1,8 @@
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, State};
 
 use crate::config::{AppState, save_config};
 use super::AcemcpTool;
-use super::types::{AcemcpRequest, ProjectIndexStatus, ProjectsIndexStatus, ProjectFilesStatus};
+use super::types::{AcemcpRequest, OutputFormat, ProjectIndexStatus, ProjectsIndexStatus, ProjectFilesStatus, IndexOutcome, ConfigValidation, ServerStats, IndexVersionDiff, IndexValidation, DoctorReport, WatcherStats, IndexQueueStats, StatusEvent, ExclusionReason};
 use reqwest;
 
 #[derive(Debug, serde::Deserialize)]
@@ -50,6 +50,11 @@ pub async fn save_acemcp_config(
         .await
         .map_err(|e| format!("保存配置失败: {}", e))?;
 
+    // 立即刷新内存中的配置，不等待配置文件监听事件
+    if let Err(e) = super::config_reload::reload() {
+        crate::log_important!(warn, "保存后重新加载 acemcp 配置失败: {}", e);
+    }
+
     Ok(())
 }
 
@@ -73,14 +78,20 @@ pub async fn test_acemcp_connection(
     state: State<'_, AppState>,
 ) -> Result<TestConnectionResult, String> {
     // 获取配置并立即释放锁
-    let (effective_base_url, effective_token) = {
+    let (effective_base_url, effective_token, tls_config) = {
         let config = state.config
             .lock()
             .map_err(|e| format!("获取配置失败: {}", e))?;
         
         let base_url = config.mcp_config.acemcp_base_url.as_ref().unwrap_or(&args.base_url).clone();
         let token = config.mcp_config.acemcp_token.as_ref().unwrap_or(&args.token).clone();
-        (base_url, token)
+        let tls_config = super::types::AcemcpConfig {
+            ca_cert_path: config.mcp_config.acemcp_ca_cert_path.clone(),
+            danger_accept_invalid_certs: config.mcp_config.acemcp_danger_accept_invalid_certs,
+            user_agent: config.mcp_config.acemcp_user_agent.clone(),
+            ..Default::default()
+        };
+        (base_url, token, tls_config)
     };
     
     // 验证 URL 格式
@@ -109,10 +120,18 @@ pub async fn test_acemcp_connection(
     };
     
     // 实际测试连接 - 发送一个简单的健康检查请求
-    let client = reqwest::Client::builder()
+    let builder = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+        .user_agent(super::mcp::user_agent(&tls_config));
+    let client = match super::mcp::apply_tls_options(builder, &tls_config) {
+        Ok(builder) => builder.build().map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?,
+        Err(e) => {
+            return Ok(TestConnectionResult {
+                success: false,
+                message: e.to_string(),
+            });
+        }
+    };
     
     // 尝试访问一个常见的端点（如果存在健康检查端点）
     let test_url = format!("{}/health", normalized_url);
@@ -143,14 +162,7 @@ pub async fn test_acemcp_connection(
     let search_url = format!("{}/agents/codebase-retrieval", normalized_url);
     
     // 发送一个最小的测试请求
-    let test_payload = serde_json::json!({
-        "information_request": "test",
-        "blobs": {"checkpoint_id": null, "added_blobs": [], "deleted_blobs": []},
-        "dialog": [],
-        "max_output_length": 0,
-        "disable_codebase_retrieval": false,
-        "enable_commit_retrieval": false,
-    });
+    let test_payload = super::mcp::probe_retrieval_payload();
     
     match client
         .post(&search_url)
@@ -227,13 +239,13 @@ pub async fn read_acemcp_logs(_state: State<'_, AppState>) -> Result<Vec<String>
 
 #[tauri::command]
 pub async fn clear_acemcp_cache(_state: State<'_, AppState>) -> Result<String, String> {
-    let cache_dir = std::env::var("HOME").unwrap_or_default() + "/.acemcp/data";
-    if std::path::Path::new(&cache_dir).exists() {
+    let cache_dir = super::mcp::acemcp_data_dir();
+    if cache_dir.exists() {
         std::fs::remove_dir_all(&cache_dir).map_err(|e| e.to_string())?;
     }
     std::fs::create_dir_all(&cache_dir).map_err(|e| e.to_string())?;
-    log::info!("acemcp缓存已清除: {}", cache_dir);
-    Ok(cache_dir)
+    log::info!("acemcp缓存已清除: {}", cache_dir.display());
+    Ok(cache_dir.to_string_lossy().to_string())
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -258,20 +270,8 @@ pub async fn get_acemcp_config(state: State<'_, AppState>) -> Result<AcemcpConfi
         max_lines_per_blob: config.mcp_config.acemcp_max_lines_per_blob.unwrap_or(800),
         // 默认文件扩展名列表（与前端 McpToolsTab.vue 保持一致）
         // 用户首次打开设置界面时，所有扩展名默认全部勾选
-        text_extensions: config.mcp_config.acemcp_text_extensions.clone().unwrap_or_else(|| {
-            vec![
-                ".py".to_string(), ".js".to_string(), ".ts".to_string(),
-                ".jsx".to_string(), ".tsx".to_string(), ".java".to_string(),
-                ".go".to_string(), ".rs".to_string(), ".cpp".to_string(),
-                ".c".to_string(), ".h".to_string(), ".hpp".to_string(),
-                ".cs".to_string(), ".rb".to_string(), ".php".to_string(),
-                ".md".to_string(), ".txt".to_string(), ".json".to_string(),
-                ".yaml".to_string(), ".yml".to_string(), ".toml".to_string(),
-                ".xml".to_string(), ".html".to_string(), ".css".to_string(),
-                ".scss".to_string(), ".sql".to_string(), ".sh".to_string(),
-                ".bash".to_string()
-            ]
-        }),
+        text_extensions: config.mcp_config.acemcp_text_extensions.clone()
+            .unwrap_or_else(super::types::AcemcpConfig::default_text_extensions),
         exclude_patterns: config.mcp_config.acemcp_exclude_patterns.clone().unwrap_or_else(|| {
             vec!["node_modules".to_string(), ".git".to_string(), "target".to_string(), "dist".to_string()]
         }),
@@ -292,7 +292,7 @@ pub async fn debug_acemcp_search(
     query: String,
     _app: AppHandle,
 ) -> Result<DebugSearchResult, String> {
-    let req = AcemcpRequest { project_root_path, query };
+    let req = AcemcpRequest { project_root_path, query, top_k: None, max_output_length: None, exclude_paths: None, local_rerank: false, output_format: Default::default() };
     
     // 调用搜索函数（日志会通过 log crate 输出到 stderr）
     let search_result = AcemcpTool::search_context(req).await;
@@ -348,7 +348,17 @@ pub async fn execute_acemcp_tool(
                 .to_string();
             
             // 执行搜索
-            let req = AcemcpRequest { project_root_path, query };
+            let top_k = arguments.get("top_k").and_then(|v| v.as_u64()).map(|v| v as usize);
+            let max_output_length = arguments.get("max_output_length").and_then(|v| v.as_u64()).map(|v| v as usize);
+            let exclude_paths = arguments.get("exclude_paths").and_then(|v| v.as_array()).map(|paths| {
+                paths.iter().filter_map(|p| p.as_str().map(|s| s.to_string())).collect()
+            });
+            let local_rerank = arguments.get("local_rerank").and_then(|v| v.as_bool()).unwrap_or(false);
+            let output_format = match arguments.get("output_format").filter(|v| !v.is_null()) {
+                Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("output_format 参数无效: {}", e))?,
+                None => OutputFormat::default(),
+            };
+            let req = AcemcpRequest { project_root_path, query, top_k, max_output_length, exclude_paths, local_rerank, output_format };
             match AcemcpTool::search_context(req).await {
                 Ok(result) => {
                     // 转换结果为JSON
@@ -371,18 +381,53 @@ pub async fn execute_acemcp_tool(
     }
 }
 
+/// 将索引进度事件转发为前端的 `acemcp-index-progress` 事件，应用启动时调用一次
+pub fn forward_index_progress_events(app: &AppHandle) {
+    let app = app.clone();
+    let mut receiver = super::mcp::subscribe_index_progress();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if let Err(e) = app.emit("acemcp-index-progress", &event) {
+                        crate::log_debug!("推送索引进度事件失败: {}", e);
+                    }
+                }
+                // 积压过多时丢弃旧事件，前端可通过状态接口补齐
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
 /// 获取指定项目的索引状态
 #[tauri::command]
 pub fn get_acemcp_index_status(project_root_path: String) -> Result<ProjectIndexStatus, String> {
     Ok(AcemcpTool::get_index_status(project_root_path))
 }
 
+/// 校验已保存的 acemcp 配置（不发起网络请求），供前端按字段高亮
+#[tauri::command]
+pub fn validate_acemcp_config() -> Result<ConfigValidation, String> {
+    Ok(match AcemcpTool::validate_config() {
+        Ok(warnings) => ConfigValidation { warnings, errors: Vec::new() },
+        Err(errors) => ConfigValidation { warnings: Vec::new(), errors },
+    })
+}
+
 /// 获取所有项目的索引状态
 #[tauri::command]
 pub fn get_all_acemcp_index_status() -> Result<ProjectsIndexStatus, String> {
     Ok(AcemcpTool::get_all_index_status())
 }
 
+/// 获取指定项目最近的状态变迁记录（默认最近 50 条）
+#[tauri::command]
+pub fn get_acemcp_index_status_history(project_root_path: String, limit: Option<usize>) -> Result<Vec<StatusEvent>, String> {
+    Ok(AcemcpTool::index_status_history(project_root_path, limit.unwrap_or(50)))
+}
+
 /// 获取指定项目内所有可索引文件的索引状态，用于前端构建文件树
 #[tauri::command]
 pub async fn get_acemcp_project_files_status(
@@ -395,8 +440,78 @@ pub async fn get_acemcp_project_files_status(
 
 /// 手动触发索引更新
 #[tauri::command]
-pub async fn trigger_acemcp_index_update(project_root_path: String) -> Result<String, String> {
-    AcemcpTool::trigger_index_update(project_root_path)
+pub async fn trigger_acemcp_index_update(project_root_path: String) -> Result<IndexOutcome, String> {
+    AcemcpTool::trigger_index_update_typed(project_root_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取服务端存储用量（缓存 60 秒），服务端不支持 `/stats` 时返回错误
+#[tauri::command]
+pub async fn get_acemcp_server_stats() -> Result<ServerStats, String> {
+    AcemcpTool::get_server_stats()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 比较两份 projects.json 快照中指定项目的 blob 差异
+#[tauri::command]
+pub fn compare_acemcp_index_versions(
+    project_root_path: String,
+    snapshot_a_path: String,
+    snapshot_b_path: String,
+) -> Result<IndexVersionDiff, String> {
+    AcemcpTool::compare_index_versions(project_root_path, snapshot_a_path, snapshot_b_path).map_err(|e| e.to_string())
+}
+
+/// 校验指定项目的索引是否与当前文件一致（只读，不访问服务端）
+#[tauri::command]
+pub async fn validate_acemcp_index(project_root_path: String) -> Result<IndexValidation, String> {
+    AcemcpTool::validate_index(project_root_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 解释指定文件为什么没有被索引（只检查本地过滤规则）
+#[tauri::command]
+pub async fn explain_acemcp_exclusion(project_root_path: String, file_path: String) -> Result<ExclusionReason, String> {
+    AcemcpTool::explain_exclusion(project_root_path, file_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取最近一次成功索引的项目的文本概要
+#[tauri::command]
+pub async fn get_acemcp_index_summary() -> Result<String, String> {
+    AcemcpTool::index_summary()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 从服务端已确认的 blob 名称重建指定项目在 projects.json 中的记录（不上传）
+#[tauri::command]
+pub async fn rebuild_acemcp_projects_json(project_root_path: String) -> Result<String, String> {
+    AcemcpTool::rebuild_projects_json(project_root_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 检查索引记录与索引状态是否一致，`repair` 为 true 时自动修复简单情形
+#[tauri::command]
+pub fn run_acemcp_doctor(repair: bool) -> Result<DoctorReport, String> {
+    AcemcpTool::doctor(repair).map_err(|e| e.to_string())
+}
+
+/// 清理根目录已不存在的项目索引记录
+#[tauri::command]
+pub fn prune_acemcp_deleted_projects() -> Result<Vec<String>, String> {
+    AcemcpTool::prune_deleted_projects().map_err(|e| e.to_string())
+}
+
+/// 删除指定项目在服务端的索引并清除本地记录，返回服务端确认删除的 blob 数量
+#[tauri::command]
+pub async fn delete_acemcp_remote_index(project_root_path: String) -> Result<usize, String> {
+    AcemcpTool::delete_remote_index(project_root_path)
         .await
         .map_err(|e| e.to_string())
 }
@@ -423,6 +538,19 @@ pub fn get_watching_projects() -> Result<Vec<String>, String> {
     Ok(watcher_manager.get_watching_projects())
 }
 
+/// 获取当前监听数量统计（事件监听数、定期重新索引的项目数与上限）
+#[tauri::command]
+pub fn get_watcher_stats() -> Result<WatcherStats, String> {
+    let watcher_manager = super::watcher::get_watcher_manager();
+    Ok(watcher_manager.stats())
+}
+
+/// 获取后台索引队列状态（排队数、运行数与并发上限）
+#[tauri::command]
+pub fn get_index_queue_stats() -> Result<IndexQueueStats, String> {
+    Ok(super::index_queue::queue_stats())
+}
+
 /// 检查指定项目是否正在监听
 #[tauri::command]
 pub fn is_project_watching(project_root_path: String) -> Result<bool, String> {
@@ -0,0 +1,104 @@
+// Acemcp 配置热加载
+// 首次读取配置时开始监听配置文件，文件修改时间变化后重新读取并整体替换内存中的配置；
+// 之后的请求直接读取内存中的配置，修改 batch_size、exclude_patterns 等无需重启进程
+
+use anyhow::Result;
+use notify_debouncer_full::{
+    notify::{RecommendedWatcher, RecursiveMode},
+    Debouncer, FileIdMap,
+};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+use super::mcp::AcemcpTool;
+use super::types::AcemcpConfig;
+use crate::log_debug;
+use crate::log_important;
+
+/// 配置文件事件的合并间隔；编辑器保存时常产生多次写入/重命名事件
+const CONFIG_COALESCE_MS: u64 = 200;
+
+/// 内存中的配置及读取时配置文件的修改时间
+struct LoadedConfig {
+    config: AcemcpConfig,
+    modified: Option<SystemTime>,
+}
+
+static CURRENT: OnceLock<RwLock<Option<LoadedConfig>>> = OnceLock::new();
+/// 配置文件监听器，需要保持存活；启动失败时为 None，此时每次请求都重新读取配置文件
+static WATCHER: OnceLock<Mutex<Option<Debouncer<RecommendedWatcher, FileIdMap>>>> = OnceLock::new();
+
+fn current_slot() -> &'static RwLock<Option<LoadedConfig>> {
+    CURRENT.get_or_init(|| RwLock::new(None))
+}
+
+/// 获取当前的 acemcp 配置（未做校验与默认值填充）
+pub fn current_config() -> Result<AcemcpConfig> {
+    if ensure_watching() {
+        if let Some(loaded) = current_slot().read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            return Ok(loaded.config.clone());
+        }
+    }
+    reload()
+}
+
+/// 立即从配置文件重新读取并替换内存中的配置（如保存配置后，无需等待文件事件）
+pub(crate) fn reload() -> Result<AcemcpConfig> {
+    let modified = config_modified_time();
+    let config = AcemcpTool::load_acemcp_config_file()?;
+    *current_slot().write().unwrap_or_else(|e| e.into_inner()) = Some(LoadedConfig { config: config.clone(), modified });
+    Ok(config)
+}
+
+fn config_modified_time() -> Option<SystemTime> {
+    let path = crate::config::get_standalone_config_path().ok()?;
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// 首次调用时启动配置文件监听，返回监听是否在运行
+fn ensure_watching() -> bool {
+    let watcher = WATCHER.get_or_init(|| {
+        Mutex::new(match start_watching() {
+            Ok(debouncer) => Some(debouncer),
+            Err(e) => {
+                log_important!(warn, "启动配置文件监听失败，将在每次请求时读取配置文件: {}", e);
+                None
+            }
+        })
+    });
+    watcher.lock().map(|w| w.is_some()).unwrap_or(false)
+}
+
+/// 监听配置文件所在目录（编辑器常以重命名方式替换文件，直接监听文件会丢失后续事件）
+fn start_watching() -> Result<Debouncer<RecommendedWatcher, FileIdMap>> {
+    let config_path = crate::config::get_standalone_config_path()?;
+    let dir = config_path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+
+    let watched = config_path.clone();
+    let debouncer = super::watcher::watch_path(&dir, RecursiveMode::NonRecursive, Duration::from_millis(CONFIG_COALESCE_MS), move |paths| {
+        if paths.iter().any(|p| p == &watched) {
+            on_config_changed();
+        }
+    })?;
+
+    log_debug!("已启动配置文件监听: {}", config_path.display());
+    Ok(debouncer)
+}
+
+/// 配置文件修改时间变化时重新加载；读取失败（如写入到一半）时保留原配置
+fn on_config_changed() {
+    let modified = config_modified_time();
+    let unchanged = current_slot()
+        .read()
+        .map(|slot| slot.as_ref().map(|loaded| loaded.modified == modified).unwrap_or(false))
+        .unwrap_or(false);
+    if unchanged {
+        return;
+    }
+
+    match reload() {
+        Ok(_) => log_important!(info, "检测到配置文件变更，已重新加载 acemcp 配置"),
+        Err(e) => log_important!(warn, "配置文件已变更但读取失败，继续使用原配置: {}", e),
+    }
+}
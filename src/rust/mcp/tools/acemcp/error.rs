@@ -0,0 +1,111 @@
+/// Acemcp 错误类型模块
+///
+/// 内部按错误类别决定是否重试、是否刷新 token，对外仍通过 anyhow 传递
+
+/// Acemcp 错误类型枚举
+#[derive(Debug, thiserror::Error)]
+pub enum AcemcpError {
+    #[error("网络错误: {0}")]
+    Network(String),
+
+    #[error("请求超时: {0}")]
+    Timeout(String),
+
+    #[error("HTTP {status} {body}")]
+    Http { status: u16, body: String },
+
+    #[error("认证失败: {0}")]
+    Auth(String),
+
+    #[error("配置错误: {msg}")]
+    Config { msg: String },
+
+    #[error("{0}")]
+    NoBlobs(String),
+
+    /// 项目尚未索引或索引为空：应先建立索引，重试检索不会成功
+    #[error("{0}")]
+    NotIndexed(String),
+
+    #[error("服务端响应异常: {msg}")]
+    Server { msg: String },
+
+    /// 状态码成功但响应体不是有效的 JSON，通常是代理中途断开导致响应被截断
+    #[error("服务端返回了格式错误的响应: {0}")]
+    MalformedResponse(String),
+
+    #[error("服务端 {host} 连续请求失败，已暂停请求，约 {retry_after_secs} 秒后重试")]
+    CircuitOpen { host: String, retry_after_secs: u64 },
+}
+
+impl AcemcpError {
+    /// 网络错误、超时、被截断的响应、HTTP 429 与 5xx 可以重试，其余错误重试也不会成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AcemcpError::Network(_) | AcemcpError::Timeout(_) | AcemcpError::MalformedResponse(_) => true,
+            AcemcpError::Http { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    pub fn config(msg: impl Into<String>) -> Self {
+        AcemcpError::Config { msg: msg.into() }
+    }
+
+    pub fn server(msg: impl Into<String>) -> Self {
+        AcemcpError::Server { msg: msg.into() }
+    }
+
+    /// 在网络错误与超时的信息中附加请求 ID，便于与服务端日志对照
+    pub fn with_request_id(self, request_id: &str) -> Self {
+        match self {
+            AcemcpError::Network(msg) => AcemcpError::Network(format!("{} (X-Request-Id: {})", msg, request_id)),
+            AcemcpError::Timeout(msg) => AcemcpError::Timeout(format!("{} (X-Request-Id: {})", msg, request_id)),
+            other => other,
+        }
+    }
+
+    /// 根据响应状态码构造错误，401/403 归为认证失败
+    pub fn from_status(status: reqwest::StatusCode, body: String) -> Self {
+        match status.as_u16() {
+            401 | 403 => AcemcpError::Auth(format!("HTTP {} {}", status, body)),
+            code => AcemcpError::Http { status: code, body },
+        }
+    }
+}
+
+impl From<reqwest::Error> for AcemcpError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            AcemcpError::Timeout(error.to_string())
+        } else if error.is_connect() || error.is_request() || error.is_body() {
+            AcemcpError::Network(error.to_string())
+        } else if let Some(status) = error.status() {
+            AcemcpError::from_status(status, error.to_string())
+        } else if error.is_decode() {
+            AcemcpError::server(error.to_string())
+        } else {
+            AcemcpError::Network(error.to_string())
+        }
+    }
+}
+
+/// 判断 anyhow 错误是否为项目尚未索引（`AcemcpError::NotIndexed`）
+pub(crate) fn is_not_indexed(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| matches!(cause.downcast_ref::<AcemcpError>(), Some(AcemcpError::NotIndexed(_))))
+}
+
+/// 判断 anyhow 错误是否可重试：优先使用错误链中的 `AcemcpError` / `reqwest::Error` 类型
+pub(crate) fn is_retryable(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<AcemcpError>() {
+            return e.is_retryable();
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_timeout() || e.is_connect() || e.status().is_some_and(|s| s.as_u16() == 429 || s.is_server_error());
+        }
+    }
+    false
+}
@@ -0,0 +1,221 @@
+// Acemcp 后台索引队列
+// 后台首次索引经由有界的工作池执行：同时运行的索引数受 `max_concurrent_index_jobs` 限制，
+// 其余请求排队等待；同一项目已在排队或运行时不重复提交
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::types::{AcemcpConfig, IndexQueueStats};
+use crate::log_important;
+
+/// 默认同时运行的后台索引数
+pub const DEFAULT_MAX_CONCURRENT_INDEX_JOBS: usize = 2;
+
+/// 有界的后台任务队列
+pub struct IndexQueue {
+    /// 所有任务共用的信号量；并发上限变化时增减许可数，而不是替换信号量
+    semaphore: Arc<Semaphore>,
+    /// 当前并发上限，首次提交前为 None
+    limit: Mutex<Option<usize>>,
+    /// 调小并发上限时尚未收回的许可数：运行中的任务结束时丢弃许可而不是归还
+    shrink_debt: AtomicUsize,
+    /// 排队或运行中的项目
+    pending: Mutex<HashSet<String>>,
+    running: AtomicUsize,
+}
+
+/// 任务结束（包括 panic 或被取消）时释放排队标记、运行计数与许可
+struct JobGuard {
+    queue: &'static IndexQueue,
+    key: String,
+    permit: Option<OwnedSemaphorePermit>,
+    running: bool,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        if self.running {
+            self.queue.running.fetch_sub(1, Ordering::SeqCst);
+        }
+        if let Some(permit) = self.permit.take() {
+            if self.queue.shrink_debt.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |d| d.checked_sub(1)).is_ok() {
+                permit.forget();
+            }
+        }
+        self.queue.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.key);
+    }
+}
+
+impl IndexQueue {
+    pub fn new() -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(0)),
+            limit: Mutex::new(None),
+            shrink_debt: AtomicUsize::new(0),
+            pending: Mutex::new(HashSet::new()),
+            running: AtomicUsize::new(0),
+        }
+    }
+
+    /// 将信号量的许可总数调整为 `limit`；调小时优先收回空闲许可，其余在运行中的任务结束时收回
+    fn resize(&self, limit: usize) {
+        let mut current = self.limit.lock().unwrap_or_else(|e| e.into_inner());
+        let old = current.unwrap_or(0);
+        if limit > old {
+            let grow = limit - old;
+            let repaid = self
+                .shrink_debt
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |d| Some(d - d.min(grow)))
+                .map_or(0, |d| d.min(grow));
+            self.semaphore.add_permits(grow - repaid);
+        } else if limit < old {
+            let shrink = old - limit;
+            let forgotten = self.semaphore.forget_permits(shrink);
+            self.shrink_debt.fetch_add(shrink - forgotten, Ordering::SeqCst);
+        }
+        *current = Some(limit);
+    }
+
+    /// 提交任务，最多 `limit` 个任务同时运行；`key` 已在排队或运行时不提交并返回 false
+    pub fn submit<F, Fut>(&'static self, key: String, limit: usize, job: F) -> bool
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if !self.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(key.clone()) {
+            return false;
+        }
+
+        self.resize(limit.max(1));
+        let semaphore = self.semaphore.clone();
+        tokio::spawn(async move {
+            let mut guard = JobGuard { queue: self, key, permit: None, running: false };
+            // 信号量不会被关闭，获取失败时直接运行
+            guard.permit = semaphore.acquire_owned().await.ok();
+            self.running.fetch_add(1, Ordering::SeqCst);
+            guard.running = true;
+            job().await;
+        });
+        true
+    }
+
+    /// 最近一次提交时使用的并发上限
+    fn limit(&self) -> Option<usize> {
+        *self.limit.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// (排队等待的任务数, 运行中的任务数)
+    pub fn depth(&self) -> (usize, usize) {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner()).len();
+        let running = self.running.load(Ordering::SeqCst);
+        (pending.saturating_sub(running), running)
+    }
+}
+
+impl Default for IndexQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static GLOBAL_QUEUE: OnceLock<IndexQueue> = OnceLock::new();
+
+fn global_queue() -> &'static IndexQueue {
+    GLOBAL_QUEUE.get_or_init(IndexQueue::new)
+}
+
+fn max_concurrent_jobs(config: &AcemcpConfig) -> usize {
+    config.max_concurrent_index_jobs.filter(|n| *n > 0).unwrap_or(DEFAULT_MAX_CONCURRENT_INDEX_JOBS)
+}
+
+/// 将项目的后台索引加入队列；同一项目已在排队或运行时返回 false
+pub(crate) fn enqueue_index(config: &AcemcpConfig, project_root: &str) -> bool {
+    let key = super::mcp::normalize_project_root(project_root);
+    let config = config.clone();
+    let project_root = project_root.to_string();
+    let submitted = global_queue().submit(key, max_concurrent_jobs(&config), move || async move {
+        log_important!(info, "后台索引任务启动: project_root={}", project_root);
+        if let Err(e) = super::mcp::update_index(&config, &project_root).await {
+            log_important!(info, "后台索引失败: project_root={}, error={}", project_root, e);
+        } else {
+            log_important!(info, "后台索引成功: project_root={}", project_root);
+        }
+    });
+    if submitted {
+        let (queued, running) = global_queue().depth();
+        crate::log_debug!("后台索引已入队: 排队 {} 个，运行中 {} 个", queued, running);
+    }
+    submitted
+}
+
+/// 后台索引队列的当前状态（并发上限为最近一次入队时的配置）
+pub fn queue_stats() -> IndexQueueStats {
+    let queue = global_queue();
+    let (queued, running) = queue.depth();
+    IndexQueueStats {
+        queued,
+        running,
+        max_concurrent_jobs: queue.limit().unwrap_or(DEFAULT_MAX_CONCURRENT_INDEX_JOBS),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_limits_concurrency_and_deduplicates() {
+        let queue: &'static IndexQueue = Box::leak(Box::new(IndexQueue::new()));
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        for i in 0..5 {
+            let (active, peak) = (active.clone(), peak.clone());
+            assert!(queue.submit(format!("project-{}", i), 2, move || async move {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        // 同一项目排队中时不重复提交
+        assert!(!queue.submit("project-4".to_string(), 2, || async {}));
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(queue.depth(), (3, 2));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(queue.depth(), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_job_is_released_and_limit_shrinks() {
+        let queue: &'static IndexQueue = Box::leak(Box::new(IndexQueue::new()));
+        assert!(queue.submit("project".to_string(), 3, || async { panic!("索引失败") }));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.depth(), (0, 0));
+
+        // panic 后同一项目可以再次提交；并发上限调小后只保留一个许可
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        for key in ["project", "other"] {
+            let (active, peak) = (active.clone(), peak.clone());
+            assert!(queue.submit(key.to_string(), 1, move || async move {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert_eq!(queue.limit(), Some(1));
+        assert_eq!(queue.semaphore.available_permits(), 1);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
@@ -13,22 +13,60 @@ use reqwest::Client;
 use ring::digest::{Context as ShaContext, SHA256};
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use serde::{Deserialize, Serialize};
-use encoding_rs::{GBK, WINDOWS_1252, UTF_8};
-use globset::{Glob, GlobSet, GlobSetBuilder};
-
+use encoding_rs::{Encoding, GBK, WINDOWS_1252, UTF_8, UTF_16BE, UTF_16LE};
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use base64::Engine;
+
+use super::audit::{self, AuditRecord};
+use super::error::{self as acemcp_error, AcemcpError};
+use super::rate_limit;
+use super::circuit_breaker;
+use super::secret_scan::{scan_content, SecretPolicy, SecretScanOutcome};
 use super::types::{
     AcemcpRequest,
     AcemcpConfig,
+    OutputFormat,
     ProjectIndexStatus,
     ProjectsIndexStatus,
     IndexStatus,
     ProjectFilesStatus,
     FileIndexStatus,
     FileIndexStatusKind,
+    IndexOutcome,
+    IndexDiff,
+    IndexProgressEvent,
+    ConfigWarning,
+    ConfigError,
+    ServerStats,
+    IndexVersionDiff,
+    IndexValidation,
+    ConfigMigration,
+    MigrationReport,
+    IndexInconsistency,
+    DoctorReport,
+    StatusEvent,
+    CompressibleFile,
+    ExclusionReason,
+    STATUS_HISTORY_LIMIT,
 };
 use crate::log_debug;
 use crate::log_important;
 
+/// 目录遍历的默认最大深度
+const DEFAULT_INDEX_DEPTH_LIMIT: usize = 20;
+
+/// 搜索审计日志的单文件大小上限（超过后轮转为 .1）
+const SEARCH_AUDIT_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// 搜索结果默认的最大字符数，避免一次返回过多内容占满调用方上下文
+const DEFAULT_SEARCH_MAX_OUTPUT_LENGTH: usize = 20_000;
+
+/// 追加到查询的记忆默认最大字符数
+const DEFAULT_QUERY_ENRICHMENT_MAX_CHARS: usize = 1000;
+
+/// 搜索结果被截断时追加的标记
+const TRUNCATION_MARKER: &str = "[...truncated]";
+
 /// Acemcp工具实现
 pub struct AcemcpTool;
 
@@ -51,12 +89,29 @@ impl AcemcpTool {
             acemcp_config.base_url = Some(normalized);
         }
 
-        // 首次搜索时自动启动文件监听（如果尚未启动）
+        let (auto_index, hint_message) = Self::prepare_search(&acemcp_config, &request.project_root_path).await;
+
+        // 执行搜索（不触发索引）
+        let exclude_paths = request.exclude_paths.clone().unwrap_or_default();
+        let result = search_only(&acemcp_config, &request.project_root_path, &request.query, SearchLimits::from_request(&request, &acemcp_config), &exclude_paths).await;
+        Ok(Self::search_tool_result(&request, &acemcp_config, auto_index, &hint_message, result))
+    }
+
+    /// 检索前的准备：按项目的 `auto_index` 设置启动文件监听与后台索引，索引进行中时按 `smart_wait_range` 等待
+    /// 返回 (项目是否开启自动索引, 需附加到检索结果后的提示)
+    async fn prepare_search(acemcp_config: &AcemcpConfig, project_root_path: &str) -> (bool, String) {
+        // 项目关闭了 auto_index 时为纯检索模式：不启动后台索引与文件监听，只检索已有的索引
+        let auto_index = acemcp_config.auto_index_enabled(project_root_path);
+        if !auto_index {
+            log_debug!("项目已关闭 auto_index，仅检索已有索引: {}", project_root_path);
+        }
+
+        // 首次搜索时自动启动文件监听（如果尚未启动）；已在监听时刷新项目的活跃时间
         let watcher_manager = super::watcher::get_watcher_manager();
-        if !watcher_manager.is_watching(&request.project_root_path) {
+        if auto_index && !watcher_manager.mark_active(project_root_path) {
             log_debug!("首次搜索，尝试启动文件监听");
             if let Err(e) = watcher_manager.start_watching(
-                request.project_root_path.clone(),
+                project_root_path.to_string(),
                 acemcp_config.clone()
             ).await {
                 log_debug!("启动文件监听失败（不影响搜索）: {}", e);
@@ -64,30 +119,35 @@ impl AcemcpTool {
         }
 
         // 1. 检查初始索引状态
-        let initial_state = get_initial_index_state(&request.project_root_path);
+        let initial_state = get_initial_index_state(project_root_path);
         log_debug!("项目索引状态: {:?}", initial_state);
 
         // 2. 根据状态执行相应操作
         let mut hint_message = String::new();
         match initial_state {
+            InitialIndexState::Missing | InitialIndexState::Idle | InitialIndexState::Failed if !auto_index => {
+                log_debug!("项目已关闭 auto_index，跳过后台索引");
+            }
             InitialIndexState::Missing | InitialIndexState::Idle | InitialIndexState::Failed => {
                 // 启动后台索引
-                if let Err(e) = ensure_initial_index_background(&acemcp_config, &request.project_root_path).await {
+                if let Err(e) = ensure_initial_index_background(acemcp_config, project_root_path).await {
                     log_debug!("启动后台索引失败（不影响搜索）: {}", e);
                 } else {
                     hint_message = "\n\n💡 提示：当前项目索引尚未完全初始化，已在后台启动索引，稍后搜索结果会更完整。".to_string();
                 }
             }
             InitialIndexState::Indexing => {
-                // 正在索引中，应用智能等待
-                if let Some((min_wait, max_wait)) = acemcp_config.smart_wait_range {
-                    use rand::Rng;
-                    let wait_secs = rand::thread_rng().gen_range(min_wait..=max_wait);
-
-                    log_important!(info, "检测到索引正在进行中，智能等待 {} 秒后执行搜索", wait_secs);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(wait_secs)).await;
-
-                    hint_message = format!("\n\n💡 提示：检测到索引正在进行中，已等待 {} 秒以获取更完整的搜索结果。", wait_secs);
+                // 正在索引中，应用智能等待：轮询索引状态，索引结束即提前返回，最多等待 max_wait 秒
+                if let Some((_, max_wait)) = acemcp_config.smart_wait_range {
+                    log_important!(info, "检测到索引正在进行中，最多等待 {} 秒后执行搜索", max_wait);
+                    let (waited, state) = wait_for_index_settled(project_root_path, Duration::from_secs(max_wait)).await;
+                    let waited_secs = waited.as_secs_f64();
+
+                    hint_message = if state == InitialIndexState::Synced {
+                        format!("\n\n💡 提示：检测到索引正在进行中，等待 {:.1} 秒后索引已完成。", waited_secs)
+                    } else {
+                        format!("\n\n💡 提示：检测到索引正在进行中，已等待 {:.1} 秒，索引尚未完成，搜索结果可能不完整。", waited_secs)
+                    };
                 }
             }
             InitialIndexState::Synced => {
@@ -95,33 +155,136 @@ impl AcemcpTool {
                 log_debug!("项目索引已完成，直接执行搜索");
             }
         }
+        (auto_index, hint_message)
+    }
 
-        // 3. 执行搜索（不触发索引）
-        let search_result = match search_only(&acemcp_config, &request.project_root_path, &request.query).await {
+    /// 将检索结果转为工具返回值：失败时返回带错误标记的结果（项目尚未索引时按 `auto_index` 启动后台索引），
+    /// 成功时附加 `prepare_search` 给出的提示
+    fn search_tool_result(request: &AcemcpRequest, acemcp_config: &AcemcpConfig, auto_index: bool, hint_message: &str, result: anyhow::Result<String>) -> CallToolResult {
+        let search_result = match result {
             Ok(text) => text,
+            Err(e) if acemcp_error::is_not_indexed(&e) => {
+                // 尚未索引与网络等临时故障区分开：启动后台索引（已在队列中时不重复提交），并告知调用方稍后重试而非立即重试
+                let hint = if auto_index {
+                    super::index_queue::enqueue_index(acemcp_config, &request.project_root_path);
+                    "\n💡 项目尚未建立索引，已在后台启动索引，请稍后再搜索；这不是网络或配置问题，立即重试不会有结果。"
+                } else {
+                    "\n💡 项目尚未建立索引，且该项目已关闭自动索引（auto_index = false），请先通过外部流程或手动索引建立索引。"
+                };
+                return CallToolResult {
+                    content: vec![Content::text(format!("Acemcp搜索失败（项目未索引）: {}{}", e, hint))],
+                    is_error: Some(true),
+                    meta: None,
+                    structured_content: Some(serde_json::json!({
+                        "error_kind": "not_indexed",
+                        "indexing_started": auto_index,
+                    })),
+                };
+            }
             Err(e) => {
-                return Ok(CallToolResult {
-                    content: vec![Content::text(format!("Acemcp搜索失败: {}", e))],
+                let hint = if auto_index {
+                    String::new()
+                } else {
+                    "\n💡 该项目已关闭自动索引（auto_index = false），只检索已有的索引，请通过外部流程或手动索引维护该项目的索引。".to_string()
+                };
+                return CallToolResult {
+                    content: vec![Content::text(format!("Acemcp搜索失败: {}{}", e, hint))],
                     is_error: Some(true),
                     meta: None,
                     structured_content: None,
-                });
+                };
             }
         };
 
-        // 4. 附加提示信息
+        // 附加提示信息（json 格式写入 `hint` 字段，保持输出可解析）
         let final_result = if hint_message.is_empty() {
             search_result
+        } else if request.output_format == OutputFormat::Json {
+            match serde_json::from_str::<serde_json::Value>(&search_result) {
+                Ok(mut value) => {
+                    value["hint"] = serde_json::Value::String(hint_message.trim().to_string());
+                    value.to_string()
+                }
+                Err(_) => search_result,
+            }
         } else {
             format!("{}{}", search_result, hint_message)
         };
 
-        Ok(CallToolResult { 
+        CallToolResult { 
             content: vec![Content::text(final_result)], 
             is_error: None,
             meta: None,
             structured_content: None,
-        })
+        }
+    }
+
+    /// 执行代码库搜索，并以查询词对返回的各片段做 BM25 打分后重排，每个片段注明得分
+    pub async fn search_context_ranked(mut request: AcemcpRequest) -> Result<CallToolResult, McpError> {
+        request.local_rerank = true;
+        Self::search_context(request).await
+    }
+
+    /// 追问式搜索：排除之前结果中已出现过的文件，让检索返回互补的代码上下文
+    pub async fn search_context_with_history(mut request: AcemcpRequest, seen_paths: Vec<String>) -> Result<CallToolResult, McpError> {
+        let mut exclude_paths = request.exclude_paths.take().unwrap_or_default();
+        for path in seen_paths {
+            if !exclude_paths.contains(&path) {
+                exclude_paths.push(path);
+            }
+        }
+        request.exclude_paths = Some(exclude_paths);
+        Self::search_context(request).await
+    }
+
+    /// 查询改写搜索：启用 `query_enrichment_enabled` 时，将项目的规则与模式记忆作为上下文追加到查询后再搜索
+    pub async fn search_context_with_rewrite(mut request: AcemcpRequest) -> Result<CallToolResult, McpError> {
+        let config = Self::load_raw_acemcp_config()
+            .map_err(|e| McpError::internal_error(format!("获取acemcp配置失败: {}", e), None))?;
+
+        if config.query_enrichment_enabled.unwrap_or(false) {
+            let memories = load_enrichment_memories(&request.project_root_path).await;
+            let max_chars = config.query_enrichment_max_chars.unwrap_or(DEFAULT_QUERY_ENRICHMENT_MAX_CHARS);
+            if let Some(enriched) = enrich_query(&request.query, &memories, max_chars) {
+                log_debug!("查询已追加项目记忆: {}", enriched);
+                request.query = enriched;
+            }
+        }
+        Self::search_context(request).await
+    }
+
+    /// 批量执行代码库搜索，结果顺序与请求顺序一致
+    ///
+    /// 每个项目只做一次检索前的准备（按各自的 `auto_index` 设置启动后台索引与文件监听、智能等待），
+    /// 检索过程见 `search_batch_with_config`；某个查询失败时在对应位置返回带错误标记的结果，不影响其他查询
+    pub async fn search_context_batch(requests: Vec<AcemcpRequest>) -> Result<Vec<CallToolResult>, McpError> {
+        log_important!(info, "Acemcp批量搜索请求: 查询数={}", requests.len());
+
+        let mut acemcp_config = Self::get_acemcp_config()
+            .await
+            .map_err(|e| McpError::internal_error(format!("获取acemcp配置失败: {}", e), None))?;
+        if let Some(base) = &acemcp_config.base_url {
+            acemcp_config.base_url = Some(normalize_base_url(base));
+        }
+
+        let mut prepared: HashMap<String, (bool, String)> = HashMap::new();
+        for request in &requests {
+            let normalized_root = normalize_project_root(&request.project_root_path);
+            if !prepared.contains_key(&normalized_root) {
+                let state = Self::prepare_search(&acemcp_config, &request.project_root_path).await;
+                prepared.insert(normalized_root, state);
+            }
+        }
+
+        let results = search_batch_with_config(&acemcp_config, &requests).await;
+        Ok(requests
+            .iter()
+            .zip(results)
+            .map(|(request, result)| {
+                let (auto_index, hint_message) = &prepared[&normalize_project_root(&request.project_root_path)];
+                Self::search_tool_result(request, &acemcp_config, *auto_index, hint_message, result)
+            })
+            .collect())
     }
 
     /// 执行索引更新（向后兼容的索引+搜索一体化接口）
@@ -146,7 +309,8 @@ impl AcemcpTool {
         match update_index(&acemcp_config, &request.project_root_path).await {
             Ok(_blob_names) => {
                 // 索引成功后执行搜索
-                match search_only(&acemcp_config, &request.project_root_path, &request.query).await {
+                let exclude_paths = request.exclude_paths.clone().unwrap_or_default();
+                match search_only(&acemcp_config, &request.project_root_path, &request.query, SearchLimits::from_request(&request, &acemcp_config), &exclude_paths).await {
                     Ok(text) => Ok(CallToolResult { 
                         content: vec![Content::text(text)], 
                         is_error: None,
@@ -170,27 +334,126 @@ impl AcemcpTool {
         }
     }
 
-    /// 手动触发索引更新（供 Tauri 命令调用）
+    /// 流式检索（仅搜索，不触发索引），结果片段通过返回的 channel 逐块到达，供界面渐进展示
+    pub async fn search_context_stream(request: AcemcpRequest) -> Result<tokio::sync::mpsc::Receiver<Result<String>>> {
+        let acemcp_config = Self::get_acemcp_config().await?;
+        search_stream_with_config(&acemcp_config, &request).await
+    }
+
+    /// 手动触发索引更新（向后兼容的字符串结果）
     pub async fn trigger_index_update(project_root_path: String) -> Result<String> {
+        let outcome = Self::trigger_index_update_typed(project_root_path).await?;
+        Ok(format!("索引更新成功，共 {} 个 blobs", outcome.total_blobs))
+    }
+
+    /// 手动触发索引更新，返回结构化结果（供 Tauri 命令调用）
+    pub async fn trigger_index_update_typed(project_root_path: String) -> Result<IndexOutcome> {
         log_important!(info, "手动触发索引更新: project_root_path={}", project_root_path);
 
         let acemcp_config = Self::get_acemcp_config().await?;
 
-        match update_index(&acemcp_config, &project_root_path).await {
-            Ok(blob_names) => {
-                Ok(format!("索引更新成功，共 {} 个 blobs", blob_names.len()))
-            }
+        match update_index_with_outcome(&acemcp_config, &project_root_path).await {
+            Ok((_blob_names, outcome)) => Ok(outcome),
             Err(e) => {
                 Err(anyhow::anyhow!("索引更新失败: {}", e))
             }
         }
     }
 
+    /// 仅对匹配 glob 的文件重建索引，适合集中修改少量文件后快速刷新
+    pub async fn reindex_glob(project_root_path: String, glob: String) -> Result<IndexOutcome> {
+        log_important!(info, "局部重建索引: project_root_path={}, glob={}", project_root_path, glob);
+
+        let acemcp_config = Self::get_acemcp_config().await?;
+
+        reindex_glob(&acemcp_config, &project_root_path, &glob)
+            .await
+            .map_err(|e| anyhow::anyhow!("局部重建索引失败: {}", e))
+    }
+
+    /// 获取服务端存储用量（供 Tauri 命令调用）
+    ///
+    /// 结果缓存 60 秒；服务端不提供 `/stats` 接口（HTTP 404）时返回错误
+    pub async fn get_server_stats() -> Result<ServerStats> {
+        if let Some(stats) = cached_server_stats() {
+            return Ok(stats);
+        }
+
+        let acemcp_config = Self::get_acemcp_config().await?;
+        let stats = fetch_server_stats(&acemcp_config).await?;
+        if let Ok(mut guard) = server_stats_slot().lock() {
+            *guard = Some((std::time::Instant::now(), stats.clone()));
+        }
+        Ok(stats)
+    }
+
+    /// projects.json 丢失或损坏时，从服务端已确认的 blob 名称重建指定项目的记录，避免重新上传全部文件
+    pub async fn rebuild_projects_json(project_root_path: String) -> Result<String> {
+        let acemcp_config = Self::get_acemcp_config().await?;
+        rebuild_projects_json_with_config(&acemcp_config, &project_root_path).await
+    }
+
+    /// 将旧版本配置文件中改名的 acemcp 字段迁移为当前字段名并写回文件，返回已应用的迁移与无法识别的字段
+    pub fn migrate_config_schema(config_path: &Path) -> Result<MigrationReport> {
+        migrate_config_file(config_path)
+    }
+
+    /// 比较两份 projects.json 快照中指定项目的 blob 集合，用于排查重建索引后检索效果的变化
+    pub fn compare_index_versions(project_root_path: String, snapshot_a_path: String, snapshot_b_path: String) -> Result<IndexVersionDiff> {
+        compare_index_versions(&project_root_path, Path::new(&snapshot_a_path), Path::new(&snapshot_b_path))
+    }
+
+    /// 校验项目索引是否与当前文件一致：统计过期 blob 与未索引文件，判断是否需要重新索引
+    ///
+    /// 只在本地遍历文件并计算哈希，不修改 projects.json，也不访问服务端
+    pub async fn validate_index(project_root_path: String) -> Result<IndexValidation> {
+        let acemcp_config = Self::get_acemcp_config().await?;
+        // 遍历文件与计算哈希是阻塞操作
+        tokio::task::spawn_blocking(move || validate_index_with_config(&acemcp_config, &project_root_path))
+            .await
+            .map_err(|e| anyhow::anyhow!("校验索引的任务异常退出: {}", e))?
+    }
+
+    /// 解释指定文件为什么没有被索引：使用与索引相同的过滤规则，返回第一个命中的原因
+    ///
+    /// `file_path` 可以是绝对路径或相对项目根目录的路径；只检查本地过滤规则，不访问服务端
+    pub async fn explain_exclusion(project_root_path: String, file_path: String) -> Result<ExclusionReason> {
+        let acemcp_config = Self::get_acemcp_config().await?;
+        // 读取文件与执行 git ls-files 是阻塞操作
+        tokio::task::spawn_blocking(move || {
+            explain_file_exclusion(Path::new(&project_root_path), Path::new(&file_path), &CollectOptions::from_config(&acemcp_config))
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("检查排除原因的任务异常退出: {}", e))?
+    }
+
+    /// 最近一次成功索引的项目的文本概要：文件数、blob 数、blob 最多的文件、排除最多的目录、索引时间与耗时
+    pub async fn index_summary() -> Result<String> {
+        let acemcp_config = Self::get_acemcp_config().await?;
+        index_summary_with_config(&acemcp_config)
+    }
+
+    /// 应用启动时恢复上次运行时正在监听的项目（无需先搜索），返回恢复的项目数
+    pub async fn restore_watched_projects() -> Result<usize> {
+        let mut acemcp_config = Self::get_acemcp_config().await?;
+        if let Some(base) = &acemcp_config.base_url {
+            acemcp_config.base_url = Some(normalize_base_url(base));
+        }
+        Ok(super::watcher::get_watcher_manager().restore_watching(acemcp_config).await)
+    }
+
     /// 获取项目索引状态（供 Tauri 命令调用）
     pub fn get_index_status(project_root_path: String) -> ProjectIndexStatus {
         get_project_status(&project_root_path)
     }
 
+    /// 获取项目最近的 `limit` 条状态变迁（按时间先后），用于排查状态反复切换或连续失败
+    pub fn index_status_history(project_root_path: String, limit: usize) -> Vec<StatusEvent> {
+        let history = get_project_status(&project_root_path).status_history;
+        let skip = history.len().saturating_sub(limit);
+        history.into_iter().skip(skip).collect()
+    }
+
     /// 获取所有项目的索引状态（供 Tauri 命令调用）
     pub fn get_all_index_status() -> ProjectsIndexStatus {
         load_projects_status()
@@ -200,9 +463,7 @@ impl AcemcpTool {
     pub async fn get_project_files_status(project_root_path: String) -> anyhow::Result<ProjectFilesStatus> {
         // 读取 Acemcp 配置，主要用于获取扩展名、排除规则和分块行数
         let acemcp_config = Self::get_acemcp_config().await?;
-        let max_lines = acemcp_config.max_lines_per_blob.unwrap_or(800) as usize;
-        let text_exts = acemcp_config.text_extensions.clone().unwrap_or_default();
-        let exclude_patterns = acemcp_config.exclude_patterns.clone().unwrap_or_default();
+        let collect_opts = CollectOptions::from_config(&acemcp_config);
 
         // 读取 projects.json，获取已索引的 blob 名称集合
         let projects_path = home_projects_file();
@@ -229,9 +490,7 @@ impl AcemcpTool {
 
         let files = collect_file_statuses(
             &project_root_path,
-            &text_exts,
-            &exclude_patterns,
-            max_lines,
+            &collect_opts,
             &existing_blob_names,
         )?;
 
@@ -241,8 +500,141 @@ impl AcemcpTool {
         })
     }
 
+    /// 清理 projects.json 中根目录已不存在的项目记录
+    /// 同时移除 projects_status.json 中对应的状态，返回被清理的项目路径列表
+    pub fn prune_deleted_projects() -> Result<Vec<String>> {
+        let mut projects = load_projects_file();
+
+        let pruned: Vec<String> = projects
+            .0
+            .keys()
+            .filter(|root| {
+                let path = PathBuf::from(root.as_str());
+                !path.canonicalize().unwrap_or(path).exists()
+            })
+            .cloned()
+            .collect();
+
+        if pruned.is_empty() {
+            log_debug!("projects.json 中没有需要清理的项目");
+            return Ok(pruned);
+        }
+
+        for root in &pruned {
+            projects.0.remove(root);
+        }
+        save_projects_file(&projects)?;
+
+        let mut all_status = load_projects_status();
+        for root in &pruned {
+            all_status.projects.remove(root);
+        }
+        save_projects_status(&all_status)?;
+
+        log_important!(info, "已清理 {} 个不存在的项目索引记录: {:?}", pruned.len(), pruned);
+        Ok(pruned)
+    }
+
+    /// 删除项目在服务端的索引（用于更换令牌或放弃项目），并清除本地记录，返回服务端确认删除的 blob 数量
+    pub async fn delete_remote_index(project_root_path: String) -> Result<usize> {
+        let acemcp_config = Self::get_acemcp_config().await?;
+        delete_remote_index_with_config(&acemcp_config, &project_root_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("删除服务端索引失败: {}", e))
+    }
+
+    /// 检查 projects.json 与 projects_status.json 是否一致，`repair` 为 true 时自动修复简单情形：
+    /// - 已同步但没有 blob：状态重置为空闲，下次搜索时重新索引
+    /// - 有 blob 但没有状态：补写已同步状态
+    /// - 有状态但没有 blob 且项目目录已删除：移除该状态
+    pub fn doctor(repair: bool) -> Result<DoctorReport> {
+        let projects = load_projects_file();
+        let mut all_status = load_projects_status();
+        let issues = find_index_inconsistencies(&projects, &all_status);
+        for issue in &issues {
+            log_important!(warn, "索引记录不一致: {:?}，建议: {}", issue, issue.suggestion());
+        }
+
+        let mut report = DoctorReport { issues, repaired: Vec::new() };
+        if !repair || report.issues.is_empty() {
+            return Ok(report);
+        }
+
+        for issue in &report.issues {
+            let root = issue.project_root().to_string();
+            match issue {
+                IndexInconsistency::SyncedButEmpty { .. } => {
+                    if let Some(status) = all_status.projects.get_mut(&root) {
+                        status.status = IndexStatus::Idle;
+                        status.progress = 0;
+                        status.last_error = Some("projects.json 中没有 blob 记录，需要重新索引".to_string());
+                    }
+                }
+                IndexInconsistency::BlobsWithoutStatus { .. } => {
+                    let mut status = ProjectIndexStatus::default();
+                    status.project_root = root.clone();
+                    status.status = IndexStatus::Synced;
+                    status.progress = 100;
+                    all_status.projects.insert(root, status);
+                }
+                IndexInconsistency::StatusWithoutBlobs { .. } => {
+                    all_status.projects.remove(&root);
+                }
+            }
+            report.repaired.push(issue.clone());
+        }
+        save_projects_status(&all_status)?;
+
+        log_important!(info, "已修复 {} 处索引记录不一致", report.repaired.len());
+        Ok(report)
+    }
+
+    /// 记录一次工具调用的审计日志（需启用 `audit_log_enabled`）
+    pub fn audit_log(record: &AuditRecord) {
+        let enabled = Self::load_raw_acemcp_config()
+            .map(|config| config.audit_log_enabled.unwrap_or(false))
+            .unwrap_or(false);
+        if enabled {
+            audit::write(record);
+        }
+    }
+
     /// 获取acemcp配置
-    async fn get_acemcp_config() -> Result<AcemcpConfig> {
+    pub(crate) async fn get_acemcp_config() -> Result<AcemcpConfig> {
+        let mut acemcp_config = Self::load_raw_acemcp_config()?;
+
+        if let Some(name) = &acemcp_config.encoding_override {
+            parse_encoding_override(name)?;
+        }
+        if let Some(path) = configured_ca_cert_path(&acemcp_config) {
+            load_ca_certificate(path)?;
+        }
+        if acemcp_config.danger_accept_invalid_certs.unwrap_or(false) {
+            log_important!(warn, "已关闭 TLS 证书校验（danger_accept_invalid_certs），连接可能被中间人劫持");
+        }
+
+        if acemcp_config.apply_default_text_extensions() {
+            log_important!(info, "未配置文件扩展名，使用默认扩展名列表");
+        }
+
+        Ok(acemcp_config)
+    }
+
+    /// 校验配置文件中的 acemcp 配置（不发起任何网络请求）
+    /// 没有错误时返回警告列表，否则返回错误列表
+    pub fn validate_config() -> std::result::Result<Vec<ConfigWarning>, Vec<ConfigError>> {
+        let config = Self::load_acemcp_config_file()
+            .map_err(|e| vec![ConfigError::Unreadable { message: e.to_string() }])?;
+        validate_acemcp_config(&config)
+    }
+
+    /// 获取内存中的 acemcp 配置（配置文件变更后自动热加载），不做校验与默认值填充
+    fn load_raw_acemcp_config() -> Result<AcemcpConfig> {
+        super::config_reload::current_config()
+    }
+
+    /// 从配置文件读取 acemcp 配置，不做校验与默认值填充
+    pub(crate) fn load_acemcp_config_file() -> Result<AcemcpConfig> {
         // 从配置文件中读取acemcp配置
         let config = crate::config::load_standalone_config()
             .map_err(|e| anyhow::anyhow!("读取配置文件失败: {}", e))?;
@@ -254,8 +646,56 @@ impl AcemcpTool {
             max_lines_per_blob: config.mcp_config.acemcp_max_lines_per_blob,
             text_extensions: config.mcp_config.acemcp_text_extensions,
             exclude_patterns: config.mcp_config.acemcp_exclude_patterns,
+            exclude_extensions: config.mcp_config.acemcp_exclude_extensions,
             // 智能等待默认值：1-5 秒随机等待
             smart_wait_range: Some((1, 5)),
+            min_blob_chars: config.mcp_config.acemcp_min_blob_chars,
+            min_blob_content_length: config.mcp_config.acemcp_min_blob_content_length,
+            max_file_bytes: config.mcp_config.acemcp_max_file_bytes,
+            index_depth_limit: config.mcp_config.acemcp_index_depth_limit,
+            search_audit_enabled: config.mcp_config.acemcp_search_audit,
+            smart_split_lookahead_lines: config.mcp_config.acemcp_smart_split_lookahead_lines,
+            encoding_override: config.mcp_config.acemcp_encoding_override,
+            chunk_naming: config.mcp_config.acemcp_chunk_naming,
+            token_refresh_url: config.mcp_config.acemcp_token_refresh_url,
+            token_refresh_payload: config.mcp_config.acemcp_token_refresh_payload,
+            only_committed_files: config.mcp_config.acemcp_only_committed_files,
+            request_timeout_ms: config.mcp_config.acemcp_request_timeout_ms,
+            search_timeout_ms: config.mcp_config.acemcp_search_timeout_ms,
+            case_insensitive_paths: config.mcp_config.acemcp_case_insensitive_paths,
+            max_response_bytes: config.mcp_config.acemcp_max_response_bytes,
+            project_namespace: config.mcp_config.acemcp_project_namespace,
+            project_namespace_salt: config.mcp_config.acemcp_project_namespace_salt,
+            normalize_line_endings: config.mcp_config.acemcp_normalize_line_endings,
+            index_on_save_debounce_ms: config.mcp_config.acemcp_index_on_save_debounce_ms,
+            strict_blob_names: config.mcp_config.acemcp_strict_blob_names,
+            requests_per_second: config.mcp_config.acemcp_requests_per_second,
+            include_dirs: config.mcp_config.acemcp_include_dirs,
+            ca_cert_path: config.mcp_config.acemcp_ca_cert_path,
+            danger_accept_invalid_certs: config.mcp_config.acemcp_danger_accept_invalid_certs,
+            use_idempotency_keys: config.mcp_config.acemcp_use_idempotency_keys,
+            query_expansion: config.mcp_config.acemcp_query_expansion,
+            max_watched_projects: config.mcp_config.acemcp_max_watched_projects,
+            reconcile_interval_secs: config.mcp_config.acemcp_reconcile_interval_secs,
+            max_chars_per_blob: config.mcp_config.acemcp_max_chars_per_blob,
+            follow_symlinks: config.mcp_config.acemcp_follow_symlinks,
+            search_result_max_length: config.mcp_config.acemcp_search_result_max_length,
+            merge_adjacent_hits: config.mcp_config.acemcp_merge_adjacent_hits,
+            max_concurrent_index_jobs: config.mcp_config.acemcp_max_concurrent_index_jobs,
+            circuit_breaker_enabled: config.mcp_config.acemcp_circuit_breaker_enabled,
+            webhook_url: config.mcp_config.acemcp_webhook_url,
+            webhook_headers: config.mcp_config.acemcp_webhook_headers,
+            skip_hidden: config.mcp_config.acemcp_skip_hidden,
+            secret_scan_policy: config.mcp_config.acemcp_secret_scan_policy,
+            upload_chunk_encoding: config.mcp_config.acemcp_upload_chunk_encoding,
+            preflight_probe: config.mcp_config.acemcp_preflight_probe,
+            project_overrides: config.mcp_config.acemcp_project_overrides,
+            audit_log_enabled: config.mcp_config.acemcp_audit_log,
+            query_enrichment_enabled: config.mcp_config.acemcp_query_enrichment,
+            query_enrichment_max_chars: config.mcp_config.acemcp_query_enrichment_max_chars,
+            index_only_changed_since: config.mcp_config.acemcp_index_only_changed_since,
+            user_agent: config.mcp_config.acemcp_user_agent,
+            custom_splitter_script: config.mcp_config.acemcp_custom_splitter_script,
         })
     }
 
@@ -268,6 +708,30 @@ impl AcemcpTool {
                     "type": "string",
                     "description": "项目根目录的绝对路径，使用正斜杠(/)作为分隔符。例如：C:/Users/username/projects/myproject"
                 },
+                "top_k": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "最多返回的结果条数（可选）。结果较多时可用较小的值节省上下文，返回内容会提示还有多少条未显示"
+                },
+                "max_output_length": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "返回文本的最大字符数（可选，默认 20000 或服务配置的上限，且不能超过该上限）。超出时在完整行末尾截断并追加 [...truncated] 标记，需要更精简的结果时可传入较小的值"
+                },
+                "local_rerank": {
+                    "type": "boolean",
+                    "description": "是否以查询词对各结果片段打分重排并在每个片段下方显示得分（可选，默认 false）"
+                },
+                "output_format": {
+                    "type": "string",
+                    "enum": ["plain", "markdown", "json", "citations"],
+                    "description": "结果输出格式（可选）：plain（默认，原始文本）、markdown（片段包裹在按扩展名标注语言的代码块中）、json（结构化结果 {hits, total, truncated}）、citations（只返回按相关度排序的 路径:起始行-结束行 引用，不含代码内容）"
+                },
+                "exclude_paths": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "之前搜索结果中已出现过的文件路径（可选，相对项目根目录）。追问时传入可排除这些文件，获得互补的结果"
+                },
                 "query": {
                     "type": "string",
                     "description": "用于查找相关代码上下文的自然语言搜索查询。此工具执行语义搜索并返回与查询匹配的代码片段。例如：'日志配置设置初始化logger'（查找日志设置代码）、'用户认证登录'（查找认证相关代码）、'数据库连接池'（查找数据库连接代码）、'错误处理异常'（查找错误处理模式）、'API端点路由'（查找API路由定义）。工具返回带有文件路径和行号的格式化文本片段，显示相关代码的位置。"
@@ -291,6 +755,13 @@ impl AcemcpTool {
             panic!("Schema creation failed");
         }
     }
+
+    /// 获取工具输入参数的 JSON Schema（格式化后的字符串）
+    /// 供测试、文档生成等不依赖 rmcp 模型类型的场景使用
+    pub fn get_tool_schema_json() -> String {
+        let tool = Self::get_tool_definition();
+        serde_json::to_string_pretty(tool.input_schema.as_ref()).unwrap_or_default()
+    }
 }
 
 // ---------------- 已移除 Python Web 服务依赖，完全使用 Rust 实现 ----------------
@@ -325,6 +796,128 @@ pub fn get_initial_index_state(project_root: &str) -> InitialIndexState {
     }
 }
 
+/// 校验 acemcp 配置各字段的合法性（不发起网络请求）
+fn validate_acemcp_config(config: &AcemcpConfig) -> std::result::Result<Vec<ConfigWarning>, Vec<ConfigError>> {
+    let mut warnings = Vec::new();
+    let mut errors = Vec::new();
+    let is_http_url = |url: &str| {
+        let url = url.trim();
+        (url.starts_with("http://") && url.len() > "http://".len())
+            || (url.starts_with("https://") && url.len() > "https://".len())
+    };
+
+    match config.base_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        None => errors.push(ConfigError::MissingBaseUrl),
+        Some(raw) => {
+            let url = normalize_base_url(raw);
+            if !is_http_url(&url) || url.contains(' ') {
+                errors.push(ConfigError::InvalidBaseUrl { value: raw.to_string() });
+            } else if url.starts_with("http://") {
+                warnings.push(ConfigWarning::InsecureBaseUrl { value: raw.to_string() });
+            }
+        }
+    }
+
+    if config.token.as_deref().map(str::trim).unwrap_or("").is_empty() {
+        errors.push(ConfigError::MissingToken);
+    }
+    if let Some(0) = config.batch_size {
+        errors.push(ConfigError::InvalidBatchSize { value: 0 });
+    }
+    if let Some(0) = config.max_lines_per_blob {
+        errors.push(ConfigError::InvalidMaxLinesPerBlob { value: 0 });
+    }
+    if let Some(0) = config.index_depth_limit {
+        errors.push(ConfigError::InvalidIndexDepthLimit { value: 0 });
+    }
+    if let Some(name) = &config.encoding_override {
+        if parse_encoding_override(name).is_err() {
+            errors.push(ConfigError::InvalidEncodingOverride { value: name.clone() });
+        }
+    }
+    if let Some(url) = &config.token_refresh_url {
+        if !is_http_url(url) {
+            errors.push(ConfigError::InvalidTokenRefreshUrl { value: url.clone() });
+        }
+    }
+    if let Some(0) = config.request_timeout_ms {
+        errors.push(ConfigError::InvalidRequestTimeout { value: 0 });
+    }
+    if let Some(0) = config.search_timeout_ms {
+        errors.push(ConfigError::InvalidSearchTimeout { value: 0 });
+    }
+    if let Some(path) = configured_ca_cert_path(config) {
+        if let Err(e) = load_ca_certificate(path) {
+            errors.push(ConfigError::InvalidCaCert { path: path.to_string(), message: e.to_string() });
+        }
+    }
+    if let Some(since) = config.index_only_changed_since.as_deref().filter(|s| !s.trim().is_empty()) {
+        if parse_changed_since(since).is_err() {
+            errors.push(ConfigError::InvalidChangedSince { value: since.to_string() });
+        }
+    }
+    if config.danger_accept_invalid_certs.unwrap_or(false) {
+        warnings.push(ConfigWarning::InvalidCertsAccepted);
+    }
+
+    let text_exts = config.text_extensions.clone().unwrap_or_default();
+    let has_exclude_exts = config.exclude_extensions.as_ref().map(|v| !v.is_empty()).unwrap_or(false);
+    if text_exts.is_empty() && !has_exclude_exts {
+        warnings.push(ConfigWarning::EmptyTextExtensions);
+    }
+    if !text_exts.is_empty() && has_exclude_exts {
+        warnings.push(ConfigWarning::ExcludeExtensionsIgnored);
+    }
+    for entry in text_exts.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+        if entry.starts_with('.') && !entry.contains('*') {
+            continue;
+        }
+        if GlobBuilder::new(entry).literal_separator(true).build().is_err() {
+            warnings.push(ConfigWarning::InvalidTextExtensionGlob { value: entry.to_string() });
+        } else if !entry.contains('*') && !entry.contains('/') && !entry.contains('.') {
+            warnings.push(ConfigWarning::TextExtensionMissingDot { value: entry.to_string() });
+        }
+    }
+
+    for pattern in config.exclude_patterns.clone().unwrap_or_default() {
+        if Glob::new(pattern.trim().trim_matches('/')).is_err() {
+            warnings.push(ConfigWarning::InvalidExcludePattern { value: pattern });
+        }
+    }
+
+    if let Some(naming) = config.chunk_naming.as_deref().map(|v| v.trim().to_lowercase()) {
+        if !naming.is_empty() && naming != "line_range" && naming != "legacy" {
+            warnings.push(ConfigWarning::UnknownChunkNaming { value: naming });
+        }
+    }
+
+    if let Some(encoding) = config.upload_chunk_encoding.as_deref().map(|v| v.trim().to_lowercase()) {
+        if !encoding.is_empty() && encoding != "plain" && encoding != "base64" {
+            warnings.push(ConfigWarning::UnknownUploadChunkEncoding { value: encoding });
+        }
+    }
+
+    if errors.is_empty() { Ok(warnings) } else { Err(errors) }
+}
+
+/// 智能等待时轮询索引状态的间隔
+const SMART_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 轮询项目索引状态，直到不再处于索引中或等待时间达到 `max_wait`
+/// 返回实际等待时长与最终状态；调用方丢弃该 future 即可中断等待
+async fn wait_for_index_settled(project_root: &str, max_wait: Duration) -> (Duration, InitialIndexState) {
+    let started_at = std::time::Instant::now();
+    loop {
+        let state = get_initial_index_state(project_root);
+        let waited = started_at.elapsed();
+        if state != InitialIndexState::Indexing || waited >= max_wait {
+            log_debug!("智能等待结束: 状态={:?}, 等待={}ms", state, waited.as_millis());
+            return (waited, state);
+        }
+        tokio::time::sleep(SMART_WAIT_POLL_INTERVAL.min(max_wait - waited)).await;
+    }
+}
+
 /// 确保后台索引已启动（非阻塞）
 /// 仅在项目未初始化或索引失败时启动后台索引任务
 pub async fn ensure_initial_index_background(config: &AcemcpConfig, project_root: &str) -> anyhow::Result<()> {
@@ -332,19 +925,10 @@ pub async fn ensure_initial_index_background(config: &AcemcpConfig, project_root
 
     match state {
         InitialIndexState::Missing | InitialIndexState::Idle | InitialIndexState::Failed => {
-            // 在后台启动索引任务
-            let config_clone = config.clone();
-            let project_root_clone = project_root.to_string();
-
-            tokio::spawn(async move {
-                log_important!(info, "后台索引任务启动: project_root={}", project_root_clone);
-                if let Err(e) = update_index(&config_clone, &project_root_clone).await {
-                    log_important!(info, "后台索引失败: project_root={}, error={}", project_root_clone, e);
-                } else {
-                    log_important!(info, "后台索引成功: project_root={}", project_root_clone);
-                }
-            });
-
+            // 加入后台索引队列，超过并发上限时排队等待；已在排队或运行的项目不重复提交
+            if !super::index_queue::enqueue_index(config, project_root) {
+                log_debug!("项目已在后台索引队列中: {}", project_root);
+            }
             Ok(())
         }
         InitialIndexState::Synced | InitialIndexState::Indexing => {
@@ -365,7 +949,7 @@ struct BlobItem {
 #[derive(Serialize, Deserialize, Default)]
 struct ProjectsFile(HashMap<String, Vec<String>>);
 
-fn normalize_base_url(input: &str) -> String {
+pub(crate) fn normalize_base_url(input: &str) -> String {
     let mut url = input.trim().to_string();
     if !(url.starts_with("http://") || url.starts_with("https://")) {
         url = format!("http://{}", url);
@@ -374,826 +958,5427 @@ fn normalize_base_url(input: &str) -> String {
     url
 }
 
-async fn retry_request<F, Fut, T>(mut f: F, max_retries: usize, base_delay_secs: f64) -> anyhow::Result<T>
+/// `req_id` 为调用方生成的请求 ID，用于在日志中关联同一次操作
+/// `breaker` 为熔断键（见 `circuit_breaker::breaker_key`），为 None 时不经过熔断器
+async fn retry_request<F, Fut, T>(mut f: F, max_retries: usize, base_delay_secs: f64, req_id: &str, breaker: Option<&str>) -> anyhow::Result<T>
 where
     F: FnMut() -> Fut,
     Fut: std::future::Future<Output = anyhow::Result<T>>,
 {
     let mut attempt = 0usize;
-    let mut last_error_str: Option<String> = None;
+    let mut last_error: Option<anyhow::Error> = None;
     
     while attempt < max_retries {
+        if let Some(host) = breaker {
+            if let Err(e) = circuit_breaker::global().check(host) {
+                log_debug!("[req_id={}] 熔断中，跳过请求: {}", req_id, e);
+                return Err(e.into());
+            }
+        }
         match f().await {
             Ok(v) => {
+                if let Some(host) = breaker {
+                    circuit_breaker::global().record_success(host);
+                }
                 if attempt > 0 {
-                    log_debug!("请求在第{}次尝试后成功", attempt + 1);
+                    log_debug!("[req_id={}] 请求在第{}次尝试后成功", req_id, attempt + 1);
                 }
                 return Ok(v);
             }
             Err(e) => {
-                last_error_str = Some(e.to_string());
                 attempt += 1;
+                // 只有服务端不可用类的错误计入熔断；4xx 等说明服务端仍在正常响应
+                if let Some(host) = breaker {
+                    if !acemcp_error::is_retryable(&e) {
+                        circuit_breaker::global().record_success(host);
+                    } else if circuit_breaker::global().record_failure(host) {
+                        log_important!(warn, "[req_id={}] 服务端 {} 连续请求失败，已熔断，暂停请求一段时间", req_id, host);
+                    }
+                }
                 
-                // 检查是否为可重试的错误
-                let error_str = e.to_string();
-                let is_retryable = error_str.contains("timeout") 
-                    || error_str.contains("connection") 
-                    || error_str.contains("network")
-                    || error_str.contains("temporary");
-                
-                if attempt >= max_retries || !is_retryable {
-                    log_debug!("请求失败，不再重试: {}", e);
+                // 仅网络错误、超时、被截断的响应与 429/5xx 响应可重试（见 AcemcpError::is_retryable）
+                if attempt >= max_retries || !acemcp_error::is_retryable(&e) {
+                    log_debug!("[req_id={}] 请求失败，不再重试: {}", req_id, e);
                     return Err(e);
                 }
                 
                 let delay = base_delay_secs * 2f64.powi((attempt as i32) - 1);
                 let ms = (delay * 1000.0) as u64;
-                log_debug!("请求失败，准备重试({}/{}), 等待 {}ms: {}", attempt, max_retries, ms, e);
+                log_debug!("[req_id={}] 请求失败，准备重试({}/{}), 等待 {}ms: {}", req_id, attempt, max_retries, ms, e);
+                last_error = Some(e);
                 tokio::time::sleep(Duration::from_millis(ms)).await;
             }
         }
     }
     
-    Err(last_error_str
-        .and_then(|s| anyhow::anyhow!(s).into())
-        .unwrap_or_else(|| anyhow::anyhow!("未知错误")))
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("未知错误")))
 }
 
-fn home_projects_file() -> PathBuf {
-    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-    let data_dir = home.join(".acemcp").join("data");
-    let _ = fs::create_dir_all(&data_dir);
-    data_dir.join("projects.json")
+/// 响应体默认大小上限（字节）
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 32 * 1024 * 1024;
+/// 错误响应体最多读取的字节数（仅用于日志与错误信息）
+const MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
+
+fn max_response_bytes(config: &AcemcpConfig) -> usize {
+    config.max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
 }
 
-/// 获取项目索引状态文件路径
-fn home_projects_status_file() -> PathBuf {
-    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-    let data_dir = home.join(".acemcp").join("data");
-    let _ = fs::create_dir_all(&data_dir);
-    data_dir.join("projects_status.json")
+/// 分段读取响应体，超过 `max_bytes` 时立即中止，避免异常服务端返回超大响应耗尽内存
+async fn read_body_limited(mut response: reqwest::Response, max_bytes: usize) -> anyhow::Result<Vec<u8>> {
+    if let Some(len) = response.content_length() {
+        if len > max_bytes as u64 {
+            anyhow::bail!(AcemcpError::server(format!("响应体过大: {} 字节，超过上限 {} 字节（可通过 max_response_bytes 调整）", len, max_bytes)));
+        }
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(AcemcpError::from)? {
+        if body.len() + chunk.len() > max_bytes {
+            anyhow::bail!(AcemcpError::server(format!("响应体超过上限 {} 字节（可通过 max_response_bytes 调整）", max_bytes)));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
 }
 
-/// 读取所有项目的索引状态
-fn load_projects_status() -> ProjectsIndexStatus {
-    let status_path = home_projects_status_file();
-    if status_path.exists() {
-        let data = fs::read_to_string(&status_path).unwrap_or_default();
-        serde_json::from_str(&data).unwrap_or_default()
-    } else {
-        ProjectsIndexStatus::default()
+/// 在大小上限内读取并解析 JSON 响应
+///
+/// 解析失败归为 `MalformedResponse`（可重试）：成功状态码下的无效 JSON 多半是代理截断了响应，重新请求通常即可恢复
+async fn read_json_limited(response: reqwest::Response, max_bytes: usize) -> anyhow::Result<serde_json::Value> {
+    let body = read_body_limited(response, max_bytes).await?;
+    serde_json::from_slice(&body).map_err(|e| {
+        AcemcpError::MalformedResponse(format!("响应不是有效的 JSON（{} 字节）: {}", body.len(), e)).into()
+    })
+}
+
+/// 读取错误响应体用于提示，超出限制或读取失败时返回空字符串
+async fn read_error_body(response: reqwest::Response, max_bytes: usize) -> String {
+    let request_id = response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = read_body_limited(response, max_bytes.min(MAX_ERROR_BODY_BYTES))
+        .await
+        .map(|body| String::from_utf8_lossy(&body).into_owned())
+        .unwrap_or_default();
+    match request_id {
+        Some(id) => format!("{} (X-Request-Id: {})", body, id).trim_start().to_string(),
+        None => body,
     }
 }
 
-/// 保存所有项目的索引状态
-fn save_projects_status(status: &ProjectsIndexStatus) -> Result<()> {
-    let status_path = home_projects_status_file();
-    let data = serde_json::to_string_pretty(status)?;
-    fs::write(status_path, data)?;
-    Ok(())
+/// 每个请求携带的唯一请求 ID 头，便于服务端日志与本地日志对照
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// 未配置 `user_agent` 时使用的 User-Agent
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// 请求使用的 User-Agent
+pub(crate) fn user_agent(config: &AcemcpConfig) -> String {
+    config
+        .user_agent
+        .as_deref()
+        .map(str::trim)
+        .filter(|ua| !ua.is_empty())
+        .unwrap_or(DEFAULT_USER_AGENT)
+        .to_string()
 }
 
-/// 更新指定项目的索引状态
-fn update_project_status<F>(project_root: &str, updater: F) -> Result<()>
-where
-    F: FnOnce(&mut ProjectIndexStatus),
-{
-    let mut all_status = load_projects_status();
-    let normalized_root = PathBuf::from(project_root)
-        .canonicalize()
-        .unwrap_or_else(|_| PathBuf::from(project_root))
-        .to_string_lossy()
-        .replace('\\', "/");
+/// 上传请求默认超时（毫秒）
+const DEFAULT_UPLOAD_TIMEOUT_MS: u64 = 120_000;
+/// 搜索请求默认超时（毫秒）
+const DEFAULT_SEARCH_TIMEOUT_MS: u64 = 30_000;
+
+/// 共享 HTTP 客户端的缓存键：超时时间与 TLS 配置相同的请求复用同一个客户端
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ClientKey {
+    timeout_ms: u64,
+    ca_cert_path: Option<String>,
+    accept_invalid_certs: bool,
+    user_agent: String,
+}
 
-    let project_status = all_status.projects
-        .entry(normalized_root.clone())
-        .or_insert_with(|| {
-            let mut status = ProjectIndexStatus::default();
-            status.project_root = normalized_root;
-            status
-        });
+/// 按超时时间与 TLS 配置缓存的 HTTP 客户端；`Client` 的克隆共享同一个连接池，避免每次请求重新建立连接
+static HTTP_CLIENTS: OnceLock<Mutex<HashMap<ClientKey, Client>>> = OnceLock::new();
 
-    updater(project_status);
-    save_projects_status(&all_status)?;
-    Ok(())
+/// 构建失败（如根证书无法读取）时返回错误且不写入缓存，避免静默退回到未应用 TLS 配置的默认客户端
+fn pooled_client(config: &AcemcpConfig, timeout_ms: u64) -> anyhow::Result<Client> {
+    let key = ClientKey {
+        timeout_ms,
+        ca_cert_path: configured_ca_cert_path(config).map(str::to_string),
+        accept_invalid_certs: config.danger_accept_invalid_certs.unwrap_or(false),
+        user_agent: user_agent(config),
+    };
+    let clients = HTTP_CLIENTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut clients = clients.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let builder = Client::builder().timeout(Duration::from_millis(timeout_ms)).user_agent(key.user_agent.clone());
+    let client = apply_tls_options(builder, config)?
+        .build()
+        .map_err(|e| AcemcpError::config(format!("创建 HTTP 客户端失败: {}", e)))?;
+    clients.insert(key, client.clone());
+    Ok(client)
 }
 
-/// 获取指定项目的索引状态
-fn get_project_status(project_root: &str) -> ProjectIndexStatus {
-    let all_status = load_projects_status();
-    let normalized_root = PathBuf::from(project_root)
-        .canonicalize()
-        .unwrap_or_else(|_| PathBuf::from(project_root))
-        .to_string_lossy()
-        .replace('\\', "/");
+/// 上传请求使用的 HTTP 客户端（`request_timeout_ms`）
+fn upload_client(config: &AcemcpConfig) -> anyhow::Result<Client> {
+    pooled_client(config, config.request_timeout_ms.unwrap_or(DEFAULT_UPLOAD_TIMEOUT_MS))
+}
 
-    all_status.projects.get(&normalized_root).cloned().unwrap_or_else(|| {
-        let mut status = ProjectIndexStatus::default();
-        status.project_root = normalized_root;
-        status
-    })
+/// 搜索请求使用的 HTTP 客户端（`search_timeout_ms`）
+fn search_client(config: &AcemcpConfig) -> anyhow::Result<Client> {
+    pooled_client(config, config.search_timeout_ms.unwrap_or(DEFAULT_SEARCH_TIMEOUT_MS))
 }
 
-/// 读取文件内容，支持多种编码检测
-/// 尝试的编码顺序：utf-8, gbk (包含 gb2312), windows-1252 (包含 latin-1)
-/// 如果都失败，则使用 utf-8 with errors='ignore'
-fn read_file_with_encoding(path: &Path) -> Option<String> {
-    let mut file = fs::File::open(path).ok()?;
-    let mut buf = Vec::new();
-    if file.read_to_end(&mut buf).is_err() {
-        return None;
-    }
+fn configured_ca_cert_path(config: &AcemcpConfig) -> Option<&str> {
+    config.ca_cert_path.as_deref().map(str::trim).filter(|p| !p.is_empty())
+}
 
-    // 尝试 utf-8
-    let (decoded, _, had_errors) = UTF_8.decode(&buf);
-    if !had_errors {
-        return Some(decoded.into_owned());
+/// 读取并解析 PEM 格式的根证书
+fn load_ca_certificate(path: &str) -> Result<reqwest::Certificate> {
+    let pem = fs::read(path).map_err(|e| AcemcpError::config(format!("无法读取根证书 {}: {}", path, e)))?;
+    if !String::from_utf8_lossy(&pem).contains("-----BEGIN CERTIFICATE-----") {
+        anyhow::bail!(AcemcpError::config(format!("根证书 {} 不是 PEM 格式（缺少 BEGIN CERTIFICATE）", path)));
     }
+    reqwest::Certificate::from_pem(&pem)
+        .map_err(|e| AcemcpError::config(format!("无法解析根证书 {}: {}", path, e)).into())
+}
 
-    // 尝试 gbk
-    let (decoded, _, had_errors) = GBK.decode(&buf);
-    if !had_errors {
-        log_debug!("成功使用 GBK 编码读取文件: {:?}", path);
-        return Some(decoded.into_owned());
+/// 按配置为客户端添加自定义根证书、关闭证书校验；未配置时使用系统信任库
+pub(crate) fn apply_tls_options(
+    mut builder: reqwest::ClientBuilder,
+    config: &AcemcpConfig,
+) -> Result<reqwest::ClientBuilder> {
+    if let Some(path) = configured_ca_cert_path(config) {
+        builder = builder.add_root_certificate(load_ca_certificate(path)?);
     }
+    if config.danger_accept_invalid_certs.unwrap_or(false) {
+        log_important!(warn, "⚠️ 已跳过 TLS 证书校验（tls_insecure_skip_verify / danger_accept_invalid_certs），任何证书都会被接受，切勿在生产环境中使用");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder)
+}
 
-    // 尝试 gb2312 (GBK 是 GB2312 的超集，可以处理 GB2312 编码)
-    // encoding_rs 中没有单独的 GB2312，使用 GBK 代替
-    // GBK 已经在上一步尝试过了，这里跳过
+/// 运行期间刷新得到的 token，记录刷新时所用配置的 base_url（规范化后）与 token
+struct RefreshedToken {
+    base_url: Option<String>,
+    replaced_token: Option<String>,
+    token: String,
+}
 
-    // 尝试 latin-1 (WINDOWS_1252 是 ISO-8859-1 的超集，可以处理大部分 latin-1 编码)
-    let (decoded, _, had_errors) = WINDOWS_1252.decode(&buf);
-    if !had_errors {
-        log_debug!("成功使用 WINDOWS_1252 编码读取文件: {:?}", path);
-        return Some(decoded.into_owned());
-    }
+/// 最近一次刷新得到的 token（多个请求共享）
+static REFRESHED_TOKEN: OnceLock<Mutex<Option<RefreshedToken>>> = OnceLock::new();
 
-    // 如果所有编码都失败，使用 utf-8 with errors='ignore' (lossy 解码)
-    let (decoded, _, _) = UTF_8.decode(&buf);
-    log_debug!("使用 UTF-8 (lossy) 读取文件，部分字符可能丢失: {:?}", path);
-    Some(decoded.into_owned())
+fn refreshed_token_slot() -> &'static Mutex<Option<RefreshedToken>> {
+    REFRESHED_TOKEN.get_or_init(|| Mutex::new(None))
 }
 
-fn sha256_hex(path: &str, content: &str) -> String {
-    let mut ctx = ShaContext::new(&SHA256);
-    // 先更新路径的哈希，再更新内容的哈希，与Python版本保持一致
-    ctx.update(path.as_bytes());
-    ctx.update(content.as_bytes());
-    let digest = ctx.finish();
-    hex::encode(digest.as_ref())
+/// 获取当前有效的 token：配置仍是刷新前的 base_url 与 token 时使用刷新得到的 token，否则使用配置中的 token
+///
+/// 保存配置或热加载后 token / base_url 发生变化时，刷新得到的 token 不再生效
+fn effective_token(config: &AcemcpConfig) -> anyhow::Result<String> {
+    refreshed_token_slot()
+        .lock()
+        .ok()
+        .and_then(|guard| {
+            guard
+                .as_ref()
+                .filter(|r| r.base_url == config.base_url.as_deref().map(normalize_base_url) && r.replaced_token == config.token)
+                .map(|r| r.token.clone())
+        })
+        .or_else(|| config.token.clone())
+        .ok_or_else(|| AcemcpError::config("未配置 token").into())
 }
 
-/// 分割文件内容为多个 blob（如果超过最大行数）
-/// 与 Python 版本保持一致：chunk 索引从 1 开始
-fn split_content(path: &str, content: &str, max_lines: usize) -> Vec<BlobItem> {
-    let lines: Vec<&str> = content.split_inclusive('\n').collect();
-    let total_lines = lines.len();
-    
-    // 如果文件在限制内，返回单个 blob
-    if total_lines <= max_lines {
-        return vec![BlobItem { path: path.to_string(), content: content.to_string() }];
+/// 请求 token_refresh_url 获取新 token，更新内存中的 token 并回写配置文件
+async fn refresh_token(config: &AcemcpConfig, req_id: &str) -> anyhow::Result<String> {
+    let refresh_url = config.token_refresh_url.clone().ok_or_else(|| AcemcpError::config("未配置 token_refresh_url"))?;
+    log_important!(info, "[req_id={}] token 已失效，正在刷新: url={}", req_id, refresh_url);
+
+    let payload = config.token_refresh_payload.clone().unwrap_or_default();
+    let r = search_client(config)?
+        .post(&refresh_url)
+        .header(CONTENT_TYPE, "application/json")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(AcemcpError::from)?;
+
+    let status = r.status();
+    if !status.is_success() {
+        let body = read_error_body(r, max_response_bytes(config)).await;
+        anyhow::bail!(AcemcpError::Auth(format!("刷新 token 失败: HTTP {} {}", status, body)));
     }
 
-    // 计算需要的 chunk 数量
-    let num_chunks = (total_lines + max_lines - 1) / max_lines;
-    let mut blobs = Vec::new();
+    let v = read_json_limited(r, max_response_bytes(config)).await?;
+    let new_token = ["access_token", "token"]
+        .iter()
+        .find_map(|key| v.get(*key).and_then(|t| t.as_str()))
+        .map(|t| t.to_string())
+        .ok_or_else(|| AcemcpError::server("刷新 token 响应中缺少 access_token/token 字段"))?;
+
+    if let Ok(mut guard) = refreshed_token_slot().lock() {
+        *guard = Some(RefreshedToken {
+            base_url: config.base_url.as_deref().map(normalize_base_url),
+            replaced_token: config.token.clone(),
+            token: new_token.clone(),
+        });
+    }
+
+    // 回写配置文件，保证重启后继续使用新 token
+    match crate::config::load_standalone_config() {
+        Ok(mut app_config) => {
+            app_config.mcp_config.acemcp_token = Some(new_token.clone());
+            if let Err(e) = crate::config::save_standalone_config(&app_config) {
+                log_important!(warn, "[req_id={}] 刷新后的 token 写入配置文件失败: {}", req_id, e);
+            }
+        }
+        Err(e) => log_important!(warn, "[req_id={}] 读取配置文件失败，刷新后的 token 未持久化: {}", req_id, e),
+    }
 
-    // 按 chunk 索引分割（从 0 开始，但显示时从 1 开始）
-    for chunk_idx in 0..num_chunks {
-        let start_line = chunk_idx * max_lines;
-        let end_line = usize::min(start_line + max_lines, total_lines);
-        let chunk_lines = &lines[start_line..end_line];
-        let chunk_content = chunk_lines.join("");
+    log_important!(info, "[req_id={}] token 刷新成功", req_id);
+    Ok(new_token)
+}
 
-        // chunk 编号从 1 开始（与 Python 版本保持一致）
-        let chunk_path = format!("{}#chunk{}of{}", path, chunk_idx + 1, num_chunks);
-        blobs.push(BlobItem { path: chunk_path, content: chunk_content });
+/// 发送带认证的请求；`build` 根据传入的 token 构造请求
+/// 响应为 401 且配置了 token_refresh_url 时，刷新 token 后重试一次
+/// 每次发送前都会从全局限速器获取令牌
+async fn send_with_token_refresh<F>(config: &AcemcpConfig, req_id: &str, build: F) -> anyhow::Result<reqwest::Response>
+where
+    F: Fn(&str) -> reqwest::RequestBuilder,
+{
+    let token = effective_token(config)?;
+    rate_limit::acquire(config).await;
+    let r = send_with_request_id(build(&token), req_id).await?;
+    if r.status() != reqwest::StatusCode::UNAUTHORIZED || config.token_refresh_url.is_none() {
+        return Ok(r);
     }
 
-    blobs
+    // 其他请求可能已完成刷新，此时直接使用新 token
+    let current = effective_token(config)?;
+    let new_token = if current != token { current } else { refresh_token(config, req_id).await? };
+    rate_limit::acquire(config).await;
+    Ok(send_with_request_id(build(&new_token), req_id).await?)
 }
 
-/// 构建排除模式的 GlobSet
-fn build_exclude_globset(exclude_patterns: &[String]) -> Result<GlobSet> {
-    let mut builder = GlobSetBuilder::new();
-    for pattern in exclude_patterns {
-        // 尝试将模式转换为 Glob
-        if let Ok(glob) = Glob::new(pattern) {
-            builder.add(glob);
-        } else {
-            log_debug!("无效的排除模式，跳过: {}", pattern);
+/// 为请求附加唯一的 `X-Request-Id` 并记录到本地日志后发送
+///
+/// 服务端未回传该头时写入响应头，使 `read_error_body` 能在错误信息中带上请求 ID；网络错误同样附带
+async fn send_with_request_id(builder: reqwest::RequestBuilder, req_id: &str) -> Result<reqwest::Response, AcemcpError> {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    log_debug!("[req_id={}] X-Request-Id: {}", req_id, request_id);
+    let mut r = builder
+        .header(REQUEST_ID_HEADER, &request_id)
+        .send()
+        .await
+        .map_err(|e| AcemcpError::from(e).with_request_id(&request_id))?;
+    if !r.headers().contains_key(REQUEST_ID_HEADER) {
+        if let Ok(value) = reqwest::header::HeaderValue::from_str(&request_id) {
+            r.headers_mut().insert(REQUEST_ID_HEADER, value);
         }
     }
-    builder.build().map_err(|e| anyhow::anyhow!("构建排除模式失败: {}", e))
+    Ok(r)
 }
 
-/// 检查路径是否应该被排除
-/// 使用 globset 进行完整的 fnmatch 模式匹配（与 Python 版本保持一致）
-/// Python 版本使用 fnmatch.fnmatch 检查路径的各个部分和完整路径
-fn should_exclude(path: &Path, root: &Path, exclude_globset: Option<&GlobSet>) -> bool {
-    if exclude_globset.is_none() {
-        return false;
-    }
-    let globset = exclude_globset.unwrap();
+/// 服务端存储用量的缓存时长
+const SERVER_STATS_TTL: Duration = Duration::from_secs(60);
 
-    // 获取相对路径
-    let rel = match path.strip_prefix(root) {
-        Ok(rel) => rel,
-        Err(_) => path,
-    };
+/// 最近一次获取的服务端存储用量及获取时间
+static SERVER_STATS: OnceLock<Mutex<Option<(std::time::Instant, ServerStats)>>> = OnceLock::new();
 
-    // 转换为使用正斜杠的字符串（用于匹配）
-    let rel_forward = rel.to_string_lossy().replace('\\', "/");
-    
-    // 检查完整相对路径（与 Python 版本的 fnmatch(path_str, pattern) 一致）
-    if globset.is_match(&rel_forward) {
-        return true;
+fn server_stats_slot() -> &'static Mutex<Option<(std::time::Instant, ServerStats)>> {
+    SERVER_STATS.get_or_init(|| Mutex::new(None))
+}
+
+/// 缓存未过期时返回缓存的服务端存储用量
+fn cached_server_stats() -> Option<ServerStats> {
+    let guard = server_stats_slot().lock().ok()?;
+    guard
+        .as_ref()
+        .filter(|(fetched_at, _)| fetched_at.elapsed() < SERVER_STATS_TTL)
+        .map(|(_, stats)| stats.clone())
+}
+
+/// 请求服务端的 `/stats` 接口
+async fn fetch_server_stats(config: &AcemcpConfig) -> anyhow::Result<ServerStats> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    let stats_url = format!("{}/stats", base_url);
+    log_debug!("[req_id={}] 获取服务端存储用量: url={}", req_id, stats_url);
+
+    let client = search_client(config)?;
+    let r = send_with_token_refresh(config, &req_id, |token| {
+        client.get(&stats_url).header(AUTHORIZATION, format!("Bearer {}", token))
+    })
+    .await?;
+
+    let status = r.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!(AcemcpError::server("服务端不支持 /stats 接口"));
+    }
+    if !status.is_success() {
+        let body = read_error_body(r, max_response_bytes(config)).await;
+        anyhow::bail!(AcemcpError::from_status(status, body));
     }
 
-    // 检查路径的各个部分（与 Python 版本的 fnmatch(part, pattern) 一致）
-    for part in rel.iter() {
-        if let Some(part_str) = part.to_str() {
-            if globset.is_match(part_str) {
-                return true;
-            }
+    let v = read_json_limited(r, max_response_bytes(config)).await?;
+    serde_json::from_value(v).map_err(|e| AcemcpError::server(format!("/stats 响应格式错误: {}", e)).into())
+}
+
+/// 调用服务端的 `/memories` 接口（记忆远程同步）：`payload` 为 Some 时 POST 写入，否则按项目标识 GET 读取
+///
+/// 与上传请求共用连接池、token 刷新、响应大小上限与重试（最多 3 次）
+pub(crate) async fn request_memories(config: &AcemcpConfig, project: &str, payload: Option<&serde_json::Value>) -> anyhow::Result<serde_json::Value> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.as_deref().map(normalize_base_url).ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    let url = format!("{}/memories", base_url);
+    log_debug!("[req_id={}] 记忆远程同步: url={}, project={}", req_id, url, project);
+
+    let client = upload_client(config)?;
+    retry_request(|| async {
+        let r = send_with_token_refresh(config, &req_id, |token| {
+            let builder = match payload {
+                Some(body) => client.post(&url).header(CONTENT_TYPE, "application/json").json(body),
+                None => client.get(&url).query(&[("project", project)]),
+            };
+            builder.header(AUTHORIZATION, format!("Bearer {}", token))
+        })
+        .await?;
+        let status = r.status();
+        if !status.is_success() {
+            let body = read_error_body(r, max_response_bytes(config)).await;
+            anyhow::bail!(AcemcpError::from_status(status, body));
         }
+        read_json_limited(r, max_response_bytes(config)).await
+    }, 3, 1.0, &req_id, circuit_breaker::breaker_key(config, &url).as_deref()).await
+}
+
+/// 从服务端 `/list-blobs` 接口获取项目已确认的 blob 名称，重建 projects.json 中该项目的记录（不上传任何内容），
+/// 并按本地当前文件重建 blob 路径映射，使下次增量索引基于服务端的实际内容比对
+///
+/// 服务端以命名空间（未配置时为规范化的项目根目录）区分项目；不提供该接口（HTTP 404）时保留原记录，
+/// 返回提示信息而不是错误，下次索引时会照常重新上传
+pub async fn rebuild_projects_json_with_config(config: &AcemcpConfig, project_root_path: &str) -> anyhow::Result<String> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    let normalized_root = normalize_project_root(project_root_path);
+    let project = project_namespace(config, &normalized_root).unwrap_or_else(|| normalized_root.clone());
+    let list_url = format!("{}/list-blobs", base_url);
+    log_important!(info, "[req_id={}] 从服务端重建 projects.json: url={}, project={}", req_id, list_url, project);
+
+    let client = search_client(config)?;
+    let r = send_with_token_refresh(config, &req_id, |token| {
+        client
+            .get(&list_url)
+            .query(&[("project", project.as_str())])
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+    })
+    .await?;
+
+    let status = r.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        log_important!(warn, "[req_id={}] 服务端不支持 /list-blobs 接口，保留现有 projects.json", req_id);
+        return Ok("服务端不支持 /list-blobs 接口，未修改 projects.json；下次索引时将重新上传所有文件".to_string());
+    }
+    if !status.is_success() {
+        let body = read_error_body(r, max_response_bytes(config)).await;
+        anyhow::bail!(AcemcpError::from_status(status, body));
     }
 
-    false
+    // 兼容 `{"blob_names": [...]}` 与直接返回数组两种格式
+    let v = read_json_limited(r, max_response_bytes(config)).await?;
+    let names = v
+        .get("blob_names")
+        .unwrap_or(&v)
+        .as_array()
+        .ok_or_else(|| AcemcpError::server("/list-blobs 响应格式错误：缺少 blob_names 数组"))?;
+    let mut blob_names: Vec<String> = names
+        .iter()
+        .filter_map(|n| n.as_str())
+        .filter(|n| !n.is_empty())
+        .map(str::to_string)
+        .collect();
+    blob_names.sort();
+    blob_names.dedup();
+
+    // 服务端只返回 blob 名称；与本地当前文件的 blob 比对得到路径，对应不到本地文件的 blob 不记录路径
+    let mut opts = CollectOptions::from_config(config);
+    opts.changed_since = None;
+    let (local_blobs, _) = collect_project_blobs_blocking(config, &normalized_root, opts).await?;
+    let server_names: HashSet<&str> = blob_names.iter().map(String::as_str).collect();
+    let blob_paths: HashMap<String, String> = local_blobs
+        .into_iter()
+        .map(|b| (sha256_hex(&b.path, &b.content), b.path))
+        .filter(|(hash, _)| server_names.contains(hash.as_str()))
+        .collect();
+
+    let count = blob_names.len();
+    let mut projects = load_projects_file();
+    projects.0.insert(normalized_root.clone(), blob_names);
+    save_projects_file(&projects)?;
+    save_blob_paths(&normalized_root, &blob_paths)?;
+    log_important!(info, "[req_id={}] 已从服务端恢复 {} 个 blob 记录（{} 个对应本地文件）: {}", req_id, count, blob_paths.len(), normalized_root);
+    Ok(format!("已从服务端恢复 {} 个 blob 记录，下次索引时只上传变更的文件", count))
 }
 
-fn build_gitignore(root: &Path) -> Option<Gitignore> {
-    let mut builder = GitignoreBuilder::new(root);
-    let gi_path = root.join(".gitignore");
-    if gi_path.exists() {
-        if builder.add(gi_path).is_some() { return None; }
-        return match builder.build() { Ok(gi) => Some(gi), Err(_) => None };
+/// 覆盖数据目录的环境变量
+const ACEMCP_DATA_DIR_ENV: &str = "ACEMCP_DATA_DIR";
+
+/// 解析 acemcp 数据目录
+///
+/// 优先级：环境变量 `ACEMCP_DATA_DIR` > `~/.acemcp/data` > 当前工作目录下的 `.acemcp/data`。
+/// 最后一种情况下不同工作目录启动的进程会看到不同的索引状态，因此会记录警告
+pub(crate) fn acemcp_data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(ACEMCP_DATA_DIR_ENV).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
     }
-    None
+    if let Some(home) = dirs::home_dir() {
+        return home.join(".acemcp").join("data");
+    }
+
+    let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")).join(".acemcp").join("data");
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        log_important!(warn, "无法获取用户主目录，acemcp 数据将保存在当前工作目录: {}（可通过 {} 指定固定位置）", dir.display(), ACEMCP_DATA_DIR_ENV);
+    });
+    dir
 }
 
-fn collect_blobs(root: &str, text_exts: &[String], exclude_patterns: &[String], max_lines_per_blob: usize) -> anyhow::Result<Vec<BlobItem>> {
-    let root_path = PathBuf::from(root);
-    if !root_path.exists() { anyhow::bail!("项目根目录不存在: {}", root); }
-    
-    log_important!(info, "开始收集代码文件: 根目录={}, 扩展名={:?}, 排除模式={:?}", root, text_exts, exclude_patterns);
-    
-    // 构建排除模式的 GlobSet
-    let exclude_globset = if exclude_patterns.is_empty() {
-        None
+/// 写入前确保文件所在目录存在；目录无法创建时返回错误，避免写到意料之外的位置
+fn ensure_parent_dir(path: PathBuf) -> Result<PathBuf> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| anyhow::anyhow!(
+            "无法创建 acemcp 数据目录: {}\n错误: {}\n可通过环境变量 {} 指定可写的数据目录",
+            parent.display(),
+            e,
+            ACEMCP_DATA_DIR_ENV
+        ))?;
+    }
+    Ok(path)
+}
+
+fn home_projects_file() -> PathBuf {
+    acemcp_data_dir().join("projects.json")
+}
+
+/// 读取 projects.json（项目路径 -> blob 名称列表）
+fn load_projects_file() -> ProjectsFile {
+    let projects_path = home_projects_file();
+    if projects_path.exists() {
+        let data = fs::read_to_string(&projects_path).unwrap_or_default();
+        serde_json::from_str(&data).unwrap_or_default()
     } else {
-        match build_exclude_globset(exclude_patterns) {
-            Ok(gs) => Some(gs),
-            Err(e) => {
-                log_debug!("构建排除模式失败，将使用简单匹配: {}", e);
-                None
+        ProjectsFile::default()
+    }
+}
+
+/// 配置字段改名表：(旧字段名, 当前字段名)；按 snake_case 形式匹配
+const CONFIG_FIELD_RENAMES: &[(&str, &str)] = &[
+    // 与 AcemcpConfig 的字段名混用
+    ("acemcp_search_audit_enabled", "acemcp_search_audit"),
+    ("search_audit_enabled", "acemcp_search_audit"),
+];
+
+/// 驼峰写法转为 snake_case（如 `acemcpBaseUrl` -> `acemcp_base_url`）
+fn camel_to_snake(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
             }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
         }
+    }
+    out
+}
+
+/// 未知字段对应的当前字段名：先查改名表，再尝试 snake_case 形式及补上 `acemcp_` 前缀
+fn config_migration_target(key: &str, known: &HashSet<String>) -> Option<String> {
+    let snake = camel_to_snake(key);
+    if let Some((_, to)) = CONFIG_FIELD_RENAMES.iter().find(|(from, _)| *from == snake) {
+        return Some(to.to_string());
+    }
+    [snake.clone(), format!("acemcp_{}", snake)].into_iter().find(|candidate| known.contains(candidate))
+}
+
+/// 检查配置文件 `mcp_config` 中当前版本不认识的字段，按改名表迁移后写回（没有需要迁移的字段时不写文件）
+fn migrate_config_file(config_path: &Path) -> Result<MigrationReport> {
+    let data = fs::read_to_string(config_path).map_err(|e| anyhow::anyhow!("读取配置文件失败: {}: {}", config_path.display(), e))?;
+    let mut root: serde_json::Value = serde_json::from_str(&data).map_err(|e| anyhow::anyhow!("配置文件格式错误: {}: {}", config_path.display(), e))?;
+    let known: HashSet<String> = match serde_json::to_value(crate::config::AppConfig::default().mcp_config)? {
+        serde_json::Value::Object(fields) => fields.keys().cloned().collect(),
+        _ => HashSet::new(),
     };
-    
-    let mut out = Vec::new();
-    let gitignore = build_gitignore(&root_path);
-    let mut dirs_stack = vec![root_path.clone()];
-    let mut scanned_files = 0;
-    let mut indexed_files = 0;
-    let mut excluded_count = 0;
-    
-    while let Some(dir) = dirs_stack.pop() {
-        let entries = match fs::read_dir(&dir) { Ok(e) => e, Err(_) => continue };
-        for entry in entries.flatten() {
-            let p = entry.path();
-            
-            // 检查 .gitignore
-            if let Some(gi) = &gitignore {
-                if gi.matched_path_or_any_parents(&p, p.is_dir()).is_ignore() { continue; }
+
+    let mut report = MigrationReport::default();
+    let Some(mcp_config) = root.get_mut("mcp_config").and_then(|v| v.as_object_mut()) else {
+        return Ok(report);
+    };
+    let unknown: Vec<String> = mcp_config.keys().filter(|k| !known.contains(*k)).cloned().collect();
+    for key in unknown {
+        match config_migration_target(&key, &known) {
+            Some(target) if mcp_config.get(&target).is_some_and(|v| !v.is_null()) => {
+                log_important!(warn, "配置字段 {} 已改名为 {}，但新字段已有值，保留旧字段未迁移", key, target);
+                report.conflicts.push(key);
             }
-            
-            // 检查排除模式
-            if p.is_dir() {
-                if should_exclude(&p, &root_path, exclude_globset.as_ref()) {
-                    excluded_count += 1;
-                    continue;
+            Some(target) => {
+                if let Some(value) = mcp_config.remove(&key) {
+                    mcp_config.insert(target.clone(), value);
                 }
-                dirs_stack.push(p);
-                continue;
-            }
-            
-            scanned_files += 1;
-            if should_exclude(&p, &root_path, exclude_globset.as_ref()) {
-                excluded_count += 1;
-                log_debug!("排除文件: {:?}", p);
-                continue;
+                log_important!(info, "已迁移配置字段: {} -> {}", key, target);
+                report.applied.push(ConfigMigration { from: key, to: target });
             }
-            
-            // 检查文件扩展名
-            let ext_ok = p.extension().and_then(|s| s.to_str()).map(|e| {
-                let dot = format!(".{}", e).to_lowercase();
-                text_exts.iter().any(|te| te.eq_ignore_ascii_case(&dot))
-            }).unwrap_or(false);
-            if !ext_ok { continue; }
-            
-            // 读取文件内容（使用多编码支持）
-            let rel = p.strip_prefix(&root_path).unwrap_or(&p).to_string_lossy().replace('\\', "/");
-            if let Some(content) = read_file_with_encoding(&p) {
-                let parts = split_content(&rel, &content, max_lines_per_blob);
-                let blob_count = parts.len();
-                indexed_files += 1;
-                out.extend(parts);
-                log_important!(info, "索引文件: path={}, content_length={}, blobs={}", rel, content.len(), blob_count);
-            } else {
-                log_debug!("无法读取文件: {:?}", p);
+            None => {
+                log_important!(warn, "配置文件中有无法识别的字段，将被忽略: {}", key);
+                report.unknown_fields.push(key);
             }
         }
     }
-    
-    log_important!(info, "文件收集完成: 扫描文件数={}, 索引文件数={}, 生成blobs数={}, 排除文件/目录数={}", scanned_files, indexed_files, out.len(), excluded_count);
-    Ok(out)
+
+    if !report.applied.is_empty() {
+        fs::write(config_path, serde_json::to_string_pretty(&root)?)?;
+    }
+    Ok(report)
 }
 
-/// 收集项目内所有可索引文件的索引状态
-///
-/// 为避免引入新的持久化结构，这里通过重新扫描文件并复用与索引阶段相同的
-/// 路径规范化与分块逻辑，基于现有的 blob 哈希集合判断文件是否“已完全索引”。
-fn collect_file_statuses(
-    root: &str,
-    text_exts: &[String],
-    exclude_patterns: &[String],
-    max_lines_per_blob: usize,
-    existing_blob_names: &HashSet<String>,
-) -> anyhow::Result<Vec<FileIndexStatus>> {
-    let root_path = PathBuf::from(root);
-    if !root_path.exists() {
-        anyhow::bail!("项目根目录不存在: {}", root);
+/// 读取一份 projects.json 快照，文件不存在或格式错误时返回错误
+fn load_projects_snapshot(path: &Path) -> Result<ProjectsFile> {
+    let data = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("读取索引快照失败: {}: {}", path.display(), e))?;
+    serde_json::from_str(&data).map_err(|e| anyhow::anyhow!("索引快照格式错误: {}: {}", path.display(), e))
+}
+
+fn compare_index_versions(project_root_path: &str, snapshot_a: &Path, snapshot_b: &Path) -> Result<IndexVersionDiff> {
+    let normalized_root = normalize_project_root(project_root_path);
+    let a = load_projects_snapshot(snapshot_a)?.0.remove(&normalized_root);
+    let b = load_projects_snapshot(snapshot_b)?.0.remove(&normalized_root);
+    if a.is_none() && b.is_none() {
+        anyhow::bail!("两份快照中都没有项目 {} 的索引记录", normalized_root);
     }
 
-    // 构建排除模式的 GlobSet
-    let exclude_globset = if exclude_patterns.is_empty() {
-        None
-    } else {
-        match build_exclude_globset(exclude_patterns) {
-            Ok(gs) => Some(gs),
-            Err(e) => {
-                log_debug!("构建排除模式失败，将使用简单匹配: {}", e);
-                None
-            }
+    let a: HashSet<String> = a.unwrap_or_default().into_iter().collect();
+    let b: HashSet<String> = b.unwrap_or_default().into_iter().collect();
+    fn sorted<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+        let mut names: Vec<String> = names.cloned().collect();
+        names.sort();
+        names
+    }
+    Ok(IndexVersionDiff {
+        added: sorted(b.difference(&a)),
+        removed: sorted(a.difference(&b)),
+        unchanged: sorted(a.intersection(&b)),
+    })
+}
+
+/// 重新收集项目文件并计算 blob 哈希，与 projects.json 中记录的 blob 集合比较
+pub fn validate_index_with_config(config: &AcemcpConfig, project_root_path: &str) -> Result<IndexValidation> {
+    let normalized_root = normalize_project_root(project_root_path);
+    let stored: HashSet<String> = load_projects_file()
+        .0
+        .remove(&normalized_root)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    let (blobs, collect_stats) = collect_project_blobs(config, project_root_path, CollectOptions::from_config(config))?;
+    let mut current: HashSet<String> = HashSet::with_capacity(blobs.len());
+    let mut unindexed: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
+    for blob in &blobs {
+        let hash = sha256_hex(&blob.path, &blob.content);
+        if !stored.contains(&hash) {
+            unindexed.insert(blob_source_path(&blob.path).to_string());
         }
-    };
+        current.insert(hash);
+    }
+    // 因修改时间早于下限而未读取的文件视为未变更，其已记录的 blob 与 update_index 一样计为最新
+    if !collect_stats.skipped_unchanged_files.is_empty() {
+        let blob_paths = load_blob_paths(&normalized_root);
+        let skipped_unchanged: HashSet<&str> = collect_stats.skipped_unchanged_files.iter().map(String::as_str).collect();
+        current.extend(
+            stored
+                .iter()
+                .filter(|h| blob_paths.get(*h).map(|p| skipped_unchanged.contains(blob_source_path(p))).unwrap_or(false))
+                .cloned(),
+        );
+    }
 
-    let gitignore = build_gitignore(&root_path);
-    let mut dirs_stack = vec![root_path.clone()];
-    let mut files_status = Vec::new();
+    let stale_blobs = stored.difference(&current).count();
+    let unindexed_paths: Vec<String> = unindexed.into_iter().collect();
+    Ok(IndexValidation {
+        project_root: normalized_root,
+        stored_blobs: stored.len(),
+        current_blobs: current.len(),
+        stale_blobs,
+        unindexed_files: unindexed_paths.len(),
+        reindex_recommended: stale_blobs > 0 || !unindexed_paths.is_empty(),
+        unindexed_paths,
+    })
+}
 
-    while let Some(dir) = dirs_stack.pop() {
-        let entries = match fs::read_dir(&dir) {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
+/// 索引概要中列出的 blob 数最多的文件数
+const SUMMARY_TOP_FILES: usize = 10;
+/// 索引概要中列出的排除文件最多的目录数
+const SUMMARY_TOP_EXCLUDED_DIRS: usize = 5;
+/// 统计被排除目录中的文件时最多计数的文件数，避免完整遍历 node_modules、target 等大目录
+const SUMMARY_EXCLUDED_COUNT_LIMIT: usize = 1000;
 
-        for entry in entries.flatten() {
-            let p = entry.path();
+/// 压缩比低于该值的文件视为内容高度重复
+const COMPRESSIBLE_RATIO_THRESHOLD: f64 = 0.1;
 
-            // .gitignore 过滤
-            if let Some(gi) = &gitignore {
-                if gi.matched_path_or_any_parents(&p, p.is_dir()).is_ignore() {
-                    continue;
-                }
-            }
+/// 生成最近一次成功索引的项目的文本概要
+///
+/// 全部由 projects_status.json、projects.json 与 blob 路径映射在查询时推导；排除目录需要重新遍历项目目录
+pub fn index_summary_with_config(config: &AcemcpConfig) -> Result<String> {
+    let status = load_projects_status()
+        .projects
+        .into_values()
+        .filter(|s| s.last_success_time.is_some())
+        .max_by_key(|s| s.last_success_time)
+        .ok_or_else(|| anyhow::anyhow!("还没有成功索引过的项目"))?;
+
+    let blob_names = load_projects_file().0.remove(&status.project_root).unwrap_or_default();
+    let blob_paths = load_blob_paths(&status.project_root);
+    let excluded_dirs = if Path::new(&status.project_root).exists() {
+        excluded_file_counts(&status.project_root, &CollectOptions::from_config(config))
+    } else {
+        Vec::new()
+    };
+    Ok(format_index_summary(&status, &blob_names, &blob_paths, &excluded_dirs))
+}
 
-            if p.is_dir() {
-                if should_exclude(&p, &root_path, exclude_globset.as_ref()) {
-                    continue;
-                }
-                dirs_stack.push(p);
-                continue;
-            }
+/// 遍历项目目录，统计各目录中被排除的文件数，按文件数从多到少排序
+///
+/// 遍历与 `collect_blobs` 相同，被排除的目录不会进入；这些目录中的文件只计数到 `SUMMARY_EXCLUDED_COUNT_LIMIT` 个为止，
+/// 被文件排除模式跳过的文件计入所在目录
+fn excluded_file_counts(root: &str, opts: &CollectOptions) -> Vec<(String, usize)> {
+    let root_path = PathBuf::from(root);
+    let filter = FileFilter::new(&root_path, opts);
+    let rel_path = |p: &Path| filter.rel_path(p);
 
-            if should_exclude(&p, &root_path, exclude_globset.as_ref()) {
-                continue;
-            }
+    let mut excluded_count = 0;
+    let mut excluded_dirs = Vec::new();
+    let files = walk_project_files(&filter, &mut excluded_count, Some(&mut excluded_dirs));
 
-            // 扩展名过滤
-            let ext_ok = p
-                .extension()
-                .and_then(|s| s.to_str())
-                .map(|e| {
-                    let dot = format!(".{}", e).to_lowercase();
-                    text_exts.iter().any(|te| te.eq_ignore_ascii_case(&dot))
-                })
-                .unwrap_or(false);
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for dir in &excluded_dirs {
+        let file_count = walkdir::WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .take(SUMMARY_EXCLUDED_COUNT_LIMIT)
+            .count();
+        *counts.entry(rel_path(dir)).or_default() += file_count;
+    }
+    for p in files.iter().filter(|p| filter.exclude_pattern(p, false).is_some()) {
+        let dir = p.parent().map(&rel_path).filter(|d| !d.is_empty()).unwrap_or_else(|| ".".to_string());
+        *counts.entry(dir).or_default() += 1;
+    }
 
-            if !ext_ok {
-                continue;
-            }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().filter(|(_, n)| *n > 0).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
 
-            let rel = p
-                .strip_prefix(&root_path)
-                .unwrap_or(&p)
-                .to_string_lossy()
-                .replace('\\', "/");
+/// 最近一次索引的耗时：状态历史中最后一次变为已同步的时间减去此前最近一次开始索引的时间
+fn last_index_duration(status: &ProjectIndexStatus) -> Option<chrono::Duration> {
+    let history: Vec<&StatusEvent> = status.status_history.iter().collect();
+    let synced = history.iter().rposition(|e| e.to_status == IndexStatus::Synced)?;
+    let started = history[..synced].iter().rposition(|e| e.to_status == IndexStatus::Indexing)?;
+    Some(history[synced].timestamp - history[started].timestamp)
+}
 
-            // 读取文件内容并根据分块结果计算 blob 哈希
-            if let Some(content) = read_file_with_encoding(&p) {
-                let blobs = split_content(&rel, &content, max_lines_per_blob);
-                if blobs.is_empty() {
-                    continue;
-                }
+fn format_index_summary(
+    status: &ProjectIndexStatus,
+    blob_names: &[String],
+    blob_paths: &HashMap<String, String>,
+    excluded_dirs: &[(String, usize)],
+) -> String {
+    let mut blobs_per_file: HashMap<&str, usize> = HashMap::new();
+    for name in blob_names {
+        if let Some(path) = blob_paths.get(name) {
+            *blobs_per_file.entry(blob_source_path(path)).or_default() += 1;
+        }
+    }
+    let mut largest: Vec<(&str, usize)> = blobs_per_file.iter().map(|(p, n)| (*p, *n)).collect();
+    largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut out = format!("索引概要: {}\n\n", status.project_root);
+    out.push_str("== 总览 ==\n");
+    let indexed_files = if status.indexed_files > 0 { status.indexed_files } else { blobs_per_file.len() };
+    out.push_str(&format!("已索引文件数: {}\n", indexed_files));
+    out.push_str(&format!("blob 总数: {}\n", blob_names.len()));
+    match status.last_success_time {
+        Some(time) => out.push_str(&format!("最近索引时间: {}\n", time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))),
+        None => out.push_str("最近索引时间: 未知\n"),
+    }
+    match last_index_duration(status) {
+        Some(duration) => out.push_str(&format!("索引耗时: {:.1} 秒\n", duration.num_milliseconds() as f64 / 1000.0)),
+        None => out.push_str("索引耗时: 未知（状态历史中没有完整的索引记录）\n"),
+    }
 
-                let mut all_indexed = true;
-                for blob in &blobs {
-                    let hash = sha256_hex(&blob.path, &blob.content);
-                    if !existing_blob_names.contains(&hash) {
-                        all_indexed = false;
-                        break;
-                    }
-                }
+    out.push_str(&format!("\n== blob 数最多的文件（前 {}）==\n", SUMMARY_TOP_FILES));
+    if largest.is_empty() {
+        out.push_str("（没有 blob 路径记录）\n");
+    }
+    for (i, (path, count)) in largest.iter().take(SUMMARY_TOP_FILES).enumerate() {
+        out.push_str(&format!("{:>2}. {}（{} 个 blob）\n", i + 1, path, count));
+    }
 
-                let status = if all_indexed {
-                    FileIndexStatusKind::Indexed
-                } else {
-                    FileIndexStatusKind::Pending
-                };
+    out.push_str(&format!("\n== 内容高度重复的文件（压缩比 < {}）==\n", COMPRESSIBLE_RATIO_THRESHOLD));
+    if status.compressible_files.is_empty() {
+        out.push_str("（没有内容高度重复的文件）\n");
+    }
+    for (i, file) in status.compressible_files.iter().take(SUMMARY_TOP_FILES).enumerate() {
+        out.push_str(&format!("{:>2}. {}（压缩比 {:.3}）\n", i + 1, file.path, file.ratio));
+    }
 
-                files_status.push(FileIndexStatus {
-                    path: rel.clone(),
-                    status,
-                });
-            } else {
-                // 无法读取内容时，保守地标记为 Pending，避免静默丢失
-                files_status.push(FileIndexStatus {
-                    path: rel.clone(),
-                    status: FileIndexStatusKind::Pending,
-                });
-            }
-        }
+    out.push_str(&format!("\n== 排除文件最多的目录（前 {}）==\n", SUMMARY_TOP_EXCLUDED_DIRS));
+    if excluded_dirs.is_empty() {
+        out.push_str("（没有被排除的文件）\n");
+    }
+    for (i, (dir, count)) in excluded_dirs.iter().take(SUMMARY_TOP_EXCLUDED_DIRS).enumerate() {
+        let count = if *count >= SUMMARY_EXCLUDED_COUNT_LIMIT { format!("{}+", SUMMARY_EXCLUDED_COUNT_LIMIT) } else { count.to_string() };
+        out.push_str(&format!("{:>2}. {}（{} 个文件）\n", i + 1, dir, count));
     }
+    out.trim_end().to_string()
+}
 
-    Ok(files_status)
+/// 保存 projects.json
+fn save_projects_file(projects: &ProjectsFile) -> Result<()> {
+    let data = serde_json::to_string_pretty(projects)?;
+    fs::write(ensure_parent_dir(home_projects_file())?, data)?;
+    Ok(())
 }
 
-/// 只执行索引更新，不进行搜索
+/// 获取项目索引状态文件路径
+fn home_projects_status_file() -> PathBuf {
+    acemcp_data_dir().join("projects_status.json")
+}
+
+/// 获取项目的数据目录（<数据目录>/projects/<项目路径哈希>）
+fn home_project_data_dir(normalized_root: &str) -> PathBuf {
+    let key = sha256_hex(normalized_root, "");
+    acemcp_data_dir().join("projects").join(&key[..16])
+}
+
+/// 读取项目的 blob 路径映射（blob 哈希 -> blob 路径）
+/// projects.json 只记录哈希，局部重建索引时依赖该映射定位属于某些路径的旧 blob
+fn load_blob_paths(normalized_root: &str) -> HashMap<String, String> {
+    let path = home_project_data_dir(normalized_root).join("blob_paths.json");
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// 保存项目的 blob 路径映射
+fn save_blob_paths(normalized_root: &str, blob_paths: &HashMap<String, String>) -> Result<()> {
+    let data = serde_json::to_string_pretty(blob_paths)?;
+    fs::write(ensure_parent_dir(home_project_data_dir(normalized_root).join("blob_paths.json"))?, data)?;
+    Ok(())
+}
+
+/// 上传断点：每个批次上传成功后写入，索引完成后删除
+/// 进程在上传中途退出时，下次索引据此跳过已上传的 blob
+#[derive(Serialize, Deserialize, Default)]
+struct UploadCheckpoint {
+    /// 最近一次成功的批次编号（从 1 开始）
+    last_batch: usize,
+    /// 已成功上传的 blob 名称
+    uploaded_names: Vec<String>,
+    /// 批次指纹 -> Idempotency-Key；进程中断后重新上传同一批次时沿用原来的 key
+    #[serde(default)]
+    idempotency_keys: HashMap<String, String>,
+}
+
+/// 批次指纹：批次内 blob 名称依次拼接后的 sha256
+fn batch_fingerprint(names: &[String]) -> String {
+    let mut ctx = ShaContext::new(&SHA256);
+    for name in names {
+        ctx.update(name.as_bytes());
+        ctx.update(b"\n");
+    }
+    hex::encode(ctx.finish().as_ref())
+}
+
+/// 为每个上传批次分配 Idempotency-Key：断点中已有的批次沿用原 key，其余新生成；
+/// 不再出现的批次的 key 会被丢弃
+fn assign_idempotency_keys(checkpoint: &mut UploadCheckpoint, names: &[String], batch_size: usize) -> Vec<String> {
+    let mut keys = HashMap::new();
+    let assigned = names
+        .chunks(batch_size.max(1))
+        .map(|batch| {
+            let fingerprint = batch_fingerprint(batch);
+            let key = checkpoint
+                .idempotency_keys
+                .get(&fingerprint)
+                .cloned()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            keys.insert(fingerprint, key.clone());
+            key
+        })
+        .collect();
+    checkpoint.idempotency_keys = keys;
+    assigned
+}
+
+fn upload_checkpoint_path(normalized_root: &str) -> PathBuf {
+    home_project_data_dir(normalized_root).join("checkpoint.json")
+}
+
+fn load_upload_checkpoint(normalized_root: &str) -> Option<UploadCheckpoint> {
+    let data = fs::read_to_string(upload_checkpoint_path(normalized_root)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+fn save_upload_checkpoint(normalized_root: &str, checkpoint: &UploadCheckpoint) -> Result<()> {
+    let data = serde_json::to_string(checkpoint)?;
+    fs::write(ensure_parent_dir(upload_checkpoint_path(normalized_root))?, data)?;
+    Ok(())
+}
+
+fn clear_upload_checkpoint(normalized_root: &str) {
+    let path = upload_checkpoint_path(normalized_root);
+    if path.exists() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// 从 blob 路径中去掉分块后缀（如 `src/a.rs#L1-800`、`src/a.rs#chunk1of3` -> `src/a.rs`）
+pub(crate) fn blob_source_path(blob_path: &str) -> &str {
+    match blob_path.rsplit_once('#') {
+        Some((p, suffix)) if ChunkNaming::is_chunk_suffix(suffix) => p,
+        _ => blob_path,
+    }
+}
+
+/// 依次压缩各片段，返回 zlib 压缩后长度与原始总长度之比，空内容返回 1.0
+fn compression_ratio(parts: &[&BlobItem]) -> f64 {
+    use std::io::Write;
+
+    let total: usize = parts.iter().map(|b| b.content.len()).sum();
+    if total == 0 {
+        return 1.0;
+    }
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    let written = parts.iter().try_for_each(|b| encoder.write_all(b.content.as_bytes()));
+    match written.and_then(|_| encoder.finish()) {
+        Ok(compressed) => compressed.len() as f64 / total as f64,
+        Err(_) => 1.0,
+    }
+}
+
+/// 按源文件分组计算 blob 内容的压缩比（不复制内容），找出压缩比低于阈值的文件（按压缩比从低到高排列）并记录警告
+fn find_compressible_files(blobs: &[BlobItem], req_id: &str) -> Vec<CompressibleFile> {
+    let mut by_file: HashMap<&str, Vec<&BlobItem>> = HashMap::new();
+    for blob in blobs {
+        by_file.entry(blob_source_path(&blob.path)).or_default().push(blob);
+    }
+
+    let mut files: Vec<CompressibleFile> = by_file
+        .into_iter()
+        .map(|(path, parts)| CompressibleFile { path: path.to_string(), ratio: compression_ratio(&parts) })
+        .filter(|f| f.ratio < COMPRESSIBLE_RATIO_THRESHOLD)
+        .collect();
+    files.sort_by(|a, b| a.ratio.total_cmp(&b.ratio).then_with(|| a.path.cmp(&b.path)));
+    for file in &files {
+        log_important!(warn, "[req_id={}] 文件内容高度重复（压缩比 {:.3}），可能是生成代码或模板，考虑排除: {}", req_id, file.ratio, file.path);
+    }
+    files
+}
+
+/// 合并压缩比检测结果：本次上传涉及的文件使用 `fresh` 中的结果，其余仍存在于项目中的文件保留上次的结果
+fn merge_compressible_files(previous: &mut Vec<CompressibleFile>, fresh: Vec<CompressibleFile>, uploaded: &HashSet<&str>, current: &HashSet<&str>) {
+    previous.retain(|f| current.contains(f.path.as_str()) && !uploaded.contains(f.path.as_str()));
+    previous.extend(fresh);
+    previous.sort_by(|a, b| a.ratio.total_cmp(&b.ratio).then_with(|| a.path.cmp(&b.path)));
+}
+
+/// 比较前后两份 blob 路径映射（哈希 -> blob 路径），按源文件汇总得到文件级变更
+/// 旧映射由上次索引写入的 blob_paths.json 提供；缺失时所有文件都视为新增
+fn compute_index_diff(previous: &HashMap<String, String>, current: &HashMap<String, String>) -> IndexDiff {
+    fn by_file(blob_paths: &HashMap<String, String>) -> std::collections::BTreeMap<&str, std::collections::BTreeSet<&str>> {
+        let mut files: std::collections::BTreeMap<&str, std::collections::BTreeSet<&str>> = std::collections::BTreeMap::new();
+        for (hash, path) in blob_paths {
+            files.entry(blob_source_path(path)).or_default().insert(hash.as_str());
+        }
+        files
+    }
+
+    let previous = by_file(previous);
+    let current = by_file(current);
+    let mut diff = IndexDiff::default();
+    for (path, hashes) in &current {
+        match previous.get(path) {
+            None => diff.added.push(path.to_string()),
+            Some(old) if old != hashes => diff.modified.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .map(|path| path.to_string())
+        .collect();
+    diff
+}
+
+/// 记录文件级变更摘要
+fn log_index_diff(req_id: &str, diff: &IndexDiff) {
+    if diff.is_empty() {
+        return;
+    }
+    log_important!(info, "[req_id={}] 文件变更: 新增 {}, 修改 {}, 删除 {}", req_id, diff.added.len(), diff.modified.len(), diff.removed.len());
+    for path in &diff.added {
+        log_debug!("[req_id={}]   + {}", req_id, path);
+    }
+    for path in &diff.modified {
+        log_debug!("[req_id={}]   ~ {}", req_id, path);
+    }
+    for path in &diff.removed {
+        log_debug!("[req_id={}]   - {}", req_id, path);
+    }
+}
+
+/// 追加一条搜索审计记录（JSON Lines），文件超过上限时轮转
+fn append_search_audit(normalized_root: &str, record: &serde_json::Value) {
+    let audit_path = home_project_data_dir(normalized_root).join("search_audit.log");
+    let result = serde_json::to_string(record)
+        .map_err(std::io::Error::from)
+        .and_then(|line| super::audit::append_line_rotating(&audit_path, &line, SEARCH_AUDIT_MAX_BYTES));
+    if let Err(e) = result {
+        log_debug!("写入搜索审计日志失败: {}", e);
+    }
+}
+
+/// 读取所有项目的索引状态
+fn load_projects_status() -> ProjectsIndexStatus {
+    with_projects_status(ProjectsIndexStatus::clone)
+}
+
+/// 已解析的 projects_status.json 及读取时文件的路径、修改时间与大小
+struct CachedProjectsStatus {
+    path: PathBuf,
+    modified: std::time::SystemTime,
+    len: u64,
+    status: ProjectsIndexStatus,
+}
+
+/// projects_status.json 的内存缓存：文件仍是唯一的数据来源，只要路径、修改时间与大小不变就复用解析结果；
+/// 本进程写入时同步更新，其他进程修改文件后按修改时间变化重新读取
+static PROJECTS_STATUS_CACHE: std::sync::RwLock<Option<CachedProjectsStatus>> = std::sync::RwLock::new(None);
+
+/// 读取文件的修改时间与大小，文件不存在或平台不支持修改时间时返回 None（不缓存）
+fn status_file_stamp(path: &Path) -> Option<(std::time::SystemTime, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// 在（必要时重新加载的）所有项目索引状态上执行 `f`，缓存命中时不读取文件也不解析 JSON
+fn with_projects_status<R>(f: impl FnOnce(&ProjectsIndexStatus) -> R) -> R {
+    let status_path = home_projects_status_file();
+    let stamp = status_file_stamp(&status_path);
+    {
+        let cached = PROJECTS_STATUS_CACHE.read().unwrap_or_else(|e| e.into_inner());
+        let hit = cached
+            .as_ref()
+            .filter(|c| c.path == status_path && Some((c.modified, c.len)) == stamp);
+        if let Some(c) = hit {
+            return f(&c.status);
+        }
+    }
+
+    let status: ProjectsIndexStatus = if status_path.exists() {
+        let data = fs::read_to_string(&status_path).unwrap_or_default();
+        serde_json::from_str(&data).unwrap_or_default()
+    } else {
+        ProjectsIndexStatus::default()
+    };
+    let result = f(&status);
+    if let Some((modified, len)) = stamp {
+        let mut cached = PROJECTS_STATUS_CACHE.write().unwrap_or_else(|e| e.into_inner());
+        *cached = Some(CachedProjectsStatus { path: status_path, modified, len, status });
+    }
+    result
+}
+
+/// 对比 projects.json 与 projects_status.json，返回按项目根路径排序的不一致列表
+/// 正在索引中的项目尚未写入 blob，不视为不一致
+fn find_index_inconsistencies(projects: &ProjectsFile, all_status: &ProjectsIndexStatus) -> Vec<IndexInconsistency> {
+    let mut issues = Vec::new();
+    for (root, status) in &all_status.projects {
+        match projects.0.get(root) {
+            Some(blobs) if !blobs.is_empty() => {}
+            _ if status.status == IndexStatus::Synced => {
+                issues.push(IndexInconsistency::SyncedButEmpty { project_root: root.clone() });
+            }
+            None if status.status != IndexStatus::Indexing && !Path::new(root).exists() => {
+                issues.push(IndexInconsistency::StatusWithoutBlobs { project_root: root.clone() });
+            }
+            _ => {}
+        }
+    }
+    for (root, blobs) in &projects.0 {
+        if !blobs.is_empty() && !all_status.projects.contains_key(root) {
+            issues.push(IndexInconsistency::BlobsWithoutStatus { project_root: root.clone(), blobs: blobs.len() });
+        }
+    }
+    issues.sort_by(|a, b| a.project_root().cmp(b.project_root()));
+    issues
+}
+
+/// 保存所有项目的索引状态
+fn save_projects_status(status: &ProjectsIndexStatus) -> Result<()> {
+    let status_path = ensure_parent_dir(home_projects_status_file())?;
+    let data = serde_json::to_string_pretty(status)?;
+    let mut cached = PROJECTS_STATUS_CACHE.write().unwrap_or_else(|e| e.into_inner());
+    let written = fs::write(&status_path, data);
+    // 写入后以新的修改时间与大小更新缓存；写入失败时清空，下次读取重新加载文件
+    *cached = match (&written, status_file_stamp(&status_path)) {
+        (Ok(()), Some((modified, len))) => Some(CachedProjectsStatus { path: status_path, modified, len, status: status.clone() }),
+        _ => None,
+    };
+    written?;
+    Ok(())
+}
+
+/// 更新指定项目的索引状态
+fn update_project_status<F>(project_root: &str, updater: F) -> Result<()>
+where
+    F: FnOnce(&mut ProjectIndexStatus),
+{
+    let mut all_status = load_projects_status();
+    let normalized_root = PathBuf::from(project_root)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(project_root))
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    let project_status = all_status.projects
+        .entry(normalized_root.clone())
+        .or_insert_with(|| {
+            let mut status = ProjectIndexStatus::default();
+            status.project_root = normalized_root;
+            status
+        });
+
+    let from_status = project_status.status.clone();
+    updater(project_status);
+    if project_status.status != from_status {
+        let detail = match project_status.status {
+            IndexStatus::Failed => project_status.last_error.clone(),
+            _ => None,
+        };
+        project_status.status_history.push_back(StatusEvent {
+            timestamp: chrono::Utc::now(),
+            from_status,
+            to_status: project_status.status.clone(),
+            detail,
+        });
+        while project_status.status_history.len() > STATUS_HISTORY_LIMIT {
+            project_status.status_history.pop_front();
+        }
+    }
+    save_projects_status(&all_status)?;
+    Ok(())
+}
+
+/// 获取指定项目的索引状态
+fn get_project_status(project_root: &str) -> ProjectIndexStatus {
+    let normalized_root = PathBuf::from(project_root)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(project_root))
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    with_projects_status(|all_status| all_status.projects.get(&normalized_root).cloned()).unwrap_or_else(|| {
+        let mut status = ProjectIndexStatus::default();
+        status.project_root = normalized_root;
+        status
+    })
+}
+
+/// 读取文件时实际采用的解码方式（用于统计编码分布、排查乱码）
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DecodedEncoding {
+    Utf8,
+    Gbk,
+    Windows1252,
+    /// 按 .editorconfig 声明或编码覆盖配置指定的编码无错解码
+    Declared(&'static Encoding),
+    /// 解码过程中存在无效字符，内容可能有损
+    Lossy,
+}
+
+impl DecodedEncoding {
+    /// 统计用的键名
+    fn key(&self) -> String {
+        match self {
+            Self::Utf8 => "utf8".to_string(),
+            Self::Gbk => "gbk".to_string(),
+            Self::Windows1252 => "windows1252".to_string(),
+            Self::Declared(encoding) => encoding.name().to_lowercase().replace('-', ""),
+            Self::Lossy => "lossy".to_string(),
+        }
+    }
+}
+
+/// 读取文件内容，支持多种编码检测，同时返回实际采用的解码方式
+/// 如果 .editorconfig 声明了 charset（`charset` 参数非空），则直接按该编码解码；
+/// 如果配置了编码覆盖（`override_encoding` 参数非空），先按该编码解码，出现无效字符时再回退自动检测；
+/// 自动检测尝试的编码顺序：utf-8, gbk (包含 gb2312), windows-1252 (包含 latin-1)
+/// 如果都失败，则使用 utf-8 with errors='ignore'
+fn read_file_with_encoding(
+    path: &Path,
+    charset: Option<&'static Encoding>,
+    override_encoding: Option<&'static Encoding>,
+) -> Option<(String, DecodedEncoding)> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut buf = Vec::new();
+    if file.read_to_end(&mut buf).is_err() {
+        return None;
+    }
+
+    if let Some(encoding) = charset {
+        let (decoded, _, had_errors) = encoding.decode(&buf);
+        if had_errors {
+            log_debug!("按 .editorconfig 声明的 {} 编码解码时存在无效字符: {:?}", encoding.name(), path);
+            return Some((decoded.into_owned(), DecodedEncoding::Lossy));
+        }
+        return Some((decoded.into_owned(), DecodedEncoding::Declared(encoding)));
+    }
+
+    if let Some(encoding) = override_encoding {
+        let (decoded, _, had_errors) = encoding.decode(&buf);
+        if !had_errors {
+            return Some((decoded.into_owned(), DecodedEncoding::Declared(encoding)));
+        }
+        log_debug!("按覆盖编码 {} 解码时存在无效字符，回退自动检测: {:?}", encoding.name(), path);
+    }
+
+    // 尝试 utf-8
+    let (decoded, _, had_errors) = UTF_8.decode(&buf);
+    if !had_errors {
+        return Some((decoded.into_owned(), DecodedEncoding::Utf8));
+    }
+
+    // 尝试 gbk
+    let (decoded, _, had_errors) = GBK.decode(&buf);
+    if !had_errors {
+        log_debug!("成功使用 GBK 编码读取文件: {:?}", path);
+        return Some((decoded.into_owned(), DecodedEncoding::Gbk));
+    }
+
+    // 尝试 gb2312 (GBK 是 GB2312 的超集，可以处理 GB2312 编码)
+    // encoding_rs 中没有单独的 GB2312，使用 GBK 代替
+    // GBK 已经在上一步尝试过了，这里跳过
+
+    // 尝试 latin-1 (WINDOWS_1252 是 ISO-8859-1 的超集，可以处理大部分 latin-1 编码)
+    let (decoded, _, had_errors) = WINDOWS_1252.decode(&buf);
+    if !had_errors {
+        log_debug!("成功使用 WINDOWS_1252 编码读取文件: {:?}", path);
+        return Some((decoded.into_owned(), DecodedEncoding::Windows1252));
+    }
+
+    // 如果所有编码都失败，使用 utf-8 with errors='ignore' (lossy 解码)
+    let (decoded, _, _) = UTF_8.decode(&buf);
+    log_debug!("使用 UTF-8 (lossy) 读取文件，部分字符可能丢失: {:?}", path);
+    Some((decoded.into_owned(), DecodedEncoding::Lossy))
+}
+
+// ---------------- .editorconfig 编码推断 ----------------
+
+/// 单个 .editorconfig 文件中声明了 charset 的节
+struct EditorConfigSection {
+    matcher: GlobMatcher,
+    charset: String,
+}
+
+/// 解析后的 .editorconfig 文件
+struct EditorConfigFile {
+    is_root: bool,
+    sections: Vec<EditorConfigSection>,
+}
+
+/// 按目录缓存 .editorconfig 解析结果，查找文件最近祖先目录中声明的 charset
+struct EditorConfigResolver {
+    root: PathBuf,
+    cache: HashMap<PathBuf, Option<EditorConfigFile>>,
+}
+
+impl EditorConfigResolver {
+    fn new(root: &Path) -> Self {
+        Self { root: root.to_path_buf(), cache: HashMap::new() }
+    }
+
+    /// 获取文件适用的编码；未声明 charset 或无法识别时返回 None
+    fn charset_for(&mut self, file: &Path) -> Option<&'static Encoding> {
+        let mut dir = file.parent();
+        while let Some(d) = dir {
+            if !self.cache.contains_key(d) {
+                let parsed = parse_editorconfig(&d.join(".editorconfig"));
+                self.cache.insert(d.to_path_buf(), parsed);
+            }
+
+            if let Some(Some(ec)) = self.cache.get(d) {
+                let rel = file.strip_prefix(d).unwrap_or(file).to_string_lossy().replace('\\', "/");
+                // 同一文件中后出现的节优先级更高
+                if let Some(section) = ec.sections.iter().rev().find(|s| s.matcher.is_match(&rel)) {
+                    return charset_to_encoding(&section.charset);
+                }
+                if ec.is_root {
+                    return None;
+                }
+            }
+
+            // 不越过项目根目录向上查找
+            if d == self.root.as_path() {
+                break;
+            }
+            dir = d.parent();
+        }
+        None
+    }
+}
+
+/// 最小化的 .editorconfig 解析器：仅关心 root 与各节的 charset
+fn parse_editorconfig(path: &Path) -> Option<EditorConfigFile> {
+    let data = fs::read_to_string(path).ok()?;
+    let mut is_root = false;
+    let mut sections = Vec::new();
+    let mut current_glob: Option<GlobMatcher> = None;
+
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let pattern = &line[1..line.len() - 1];
+            // 不含 "/" 的模式匹配任意层级的文件名；以 "/" 开头的模式相对于 .editorconfig 所在目录
+            let pattern = if pattern.contains('/') {
+                pattern.trim_start_matches('/').to_string()
+            } else {
+                format!("**/{}", pattern)
+            };
+            current_glob = Glob::new(&pattern).ok().map(|g| g.compile_matcher());
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim().to_lowercase();
+
+        match (&current_glob, key.as_str()) {
+            (None, "root") => is_root = value == "true",
+            (Some(matcher), "charset") => sections.push(EditorConfigSection {
+                matcher: matcher.clone(),
+                charset: value,
+            }),
+            _ => {}
+        }
+    }
+
+    Some(EditorConfigFile { is_root, sections })
+}
+
+/// 将 .editorconfig 的 charset 取值映射为 encoding_rs 编码
+fn charset_to_encoding(charset: &str) -> Option<&'static Encoding> {
+    match charset {
+        "utf-8" | "utf-8-bom" => Some(UTF_8),
+        "latin1" => Some(WINDOWS_1252),
+        "utf-16le" => Some(UTF_16LE),
+        "utf-16be" => Some(UTF_16BE),
+        other => Encoding::for_label(other.as_bytes()),
+    }
+}
+
+/// 解析修改时间下限：RFC3339 时间，或 `YYYY-MM-DD`（按 UTC 零点）
+fn parse_changed_since(value: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    let value = value.trim();
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+        .ok_or_else(|| anyhow::anyhow!("无法解析的时间: {}（应为 RFC3339 时间或 YYYY-MM-DD）", value))
+}
+
+/// 解析编码覆盖配置的名称（如 `utf-8`、`gbk`、`big5`、`shift_jis`、`euc-jp`、`windows-1252`）
+fn parse_encoding_override(name: &str) -> Result<&'static Encoding> {
+    let label = name.trim().to_lowercase();
+    charset_to_encoding(&label)
+        .ok_or_else(|| anyhow::anyhow!("不支持的编码覆盖配置: {}", name))
+}
+
+/// 项目级编码覆盖文件名（位于项目根目录，内容为编码名称）
+const PROJECT_ENCODING_OVERRIDE_FILE: &str = ".acemcp-encoding";
+
+/// 解析项目实际使用的编码覆盖：项目根目录的 `.acemcp-encoding` 优先于全局配置
+/// 无效的编码名称会被忽略并记录警告
+fn resolve_encoding_override(root: &Path, configured: Option<&str>) -> Option<&'static Encoding> {
+    let project_override = fs::read_to_string(root.join(PROJECT_ENCODING_OVERRIDE_FILE))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    let name = project_override.as_deref().or(configured)?;
+    match parse_encoding_override(name) {
+        Ok(encoding) => {
+            log_debug!("使用编码覆盖: {}", encoding.name());
+            Some(encoding)
+        }
+        Err(e) => {
+            log_important!(warn, "{}，将使用自动检测", e);
+            None
+        }
+    }
+}
+
+/// 将 `\r\n` 与单独的 `\r` 统一为 `\n`，使不同平台检出的同一文件得到相同的 blob 哈希
+fn normalize_line_endings(content: String) -> String {
+    if !content.contains('\r') {
+        return content;
+    }
+    content.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+fn sha256_hex(path: &str, content: &str) -> String {
+    let mut ctx = ShaContext::new(&SHA256);
+    // 先更新路径的哈希，再更新内容的哈希，与Python版本保持一致
+    ctx.update(path.as_bytes());
+    ctx.update(content.as_bytes());
+    let digest = ctx.finish();
+    hex::encode(digest.as_ref())
+}
+
+/// 智能分块配置
+/// 预定切分点落在多行字符串（`"""`、`'''`）、块注释（`/* */`）或代码围栏（```）内部时，
+/// 向后查找最近的闭合边界再切分；在 `max_lookahead_lines` 行内找不到则退回按行硬切分
+#[derive(Debug, Clone)]
+struct SmartSplitConfig {
+    max_lookahead_lines: usize,
+}
+
+/// 多行结构的定界符
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MultilineDelim {
+    TripleDouble,
+    TripleSingle,
+    BlockComment,
+    Fence,
+}
+
+impl MultilineDelim {
+    fn open(&self) -> &'static str {
+        match self {
+            MultilineDelim::TripleDouble => "\"\"\"",
+            MultilineDelim::TripleSingle => "'''",
+            MultilineDelim::BlockComment => "/*",
+            MultilineDelim::Fence => "```",
+        }
+    }
+
+    fn close(&self) -> &'static str {
+        match self {
+            MultilineDelim::BlockComment => "*/",
+            other => other.open(),
+        }
+    }
+}
+
+/// 计算每一行结束时是否仍处于多行结构内部
+fn multiline_states(lines: &[&str]) -> Vec<bool> {
+    let mut open: Option<MultilineDelim> = None;
+    let mut states = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        // 代码围栏只在行首识别，且不在其他多行结构内部时才生效
+        if line.trim_start().starts_with(MultilineDelim::Fence.open()) {
+            match open {
+                Some(MultilineDelim::Fence) => open = None,
+                None => open = Some(MultilineDelim::Fence),
+                _ => {}
+            }
+            states.push(open.is_some());
+            continue;
+        }
+
+        let mut rest: &str = line;
+        loop {
+            match open {
+                Some(MultilineDelim::Fence) => break,
+                Some(delim) => match rest.find(delim.close()) {
+                    Some(pos) => {
+                        rest = &rest[pos + delim.close().len()..];
+                        open = None;
+                    }
+                    None => break,
+                },
+                None => {
+                    let next = [MultilineDelim::TripleDouble, MultilineDelim::TripleSingle, MultilineDelim::BlockComment]
+                        .iter()
+                        .filter_map(|d| rest.find(d.open()).map(|pos| (pos, *d)))
+                        .min_by_key(|(pos, _)| *pos);
+                    match next {
+                        Some((pos, delim)) => {
+                            rest = &rest[pos + delim.open().len()..];
+                            open = Some(delim);
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+        states.push(open.is_some());
+    }
+
+    states
+}
+
+/// 计算各 chunk 的行区间 [start, end)
+/// 设置 `max_chars` 时，行数或字符数任一达到上限即切分；单行超过字符上限时该行单独成块
+fn chunk_ranges(lines: &[&str], max_lines: usize, max_chars: Option<usize>, smart: Option<&SmartSplitConfig>) -> Vec<(usize, usize)> {
+    let total_lines = lines.len();
+    let states = smart.map(|_| multiline_states(lines));
+    let line_chars: Option<Vec<usize>> = max_chars.map(|_| lines.iter().map(|l| l.chars().count()).collect());
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < total_lines {
+        let mut end = usize::min(start + max_lines.max(1), total_lines);
+        if let (Some(limit), Some(line_chars)) = (max_chars, line_chars.as_ref()) {
+            let mut chars = line_chars[start];
+            let mut char_end = start + 1;
+            while char_end < end && chars + line_chars[char_end] <= limit {
+                chars += line_chars[char_end];
+                char_end += 1;
+            }
+            end = char_end;
+        }
+        if let (Some(cfg), Some(states)) = (smart, states.as_ref()) {
+            if end < total_lines && states[end - 1] {
+                let limit = usize::min(end + cfg.max_lookahead_lines, total_lines);
+                if let Some(close_idx) = (end..limit).find(|&i| !states[i]) {
+                    end = close_idx + 1;
+                }
+            }
+        }
+        ranges.push((start, end));
+        start = end;
+    }
+
+    ranges
+}
+
+/// 大文件分块的命名方式
+///
+/// blob 哈希由路径与内容共同计算，分块名称变化会导致整个文件重新上传。
+/// 默认的 `LineRange` 只依赖分块自身的行区间，文件在末尾增长时前面未变的分块名称与哈希保持不变；
+/// `Legacy` 为旧格式 `#chunk<序号>of<总数>`，总块数变化会使所有分块改名。
+///
+/// 迁移说明：从旧格式切换到 `LineRange` 后，超过 `max_lines_per_blob` 的文件会在下一次索引时整体重新上传一次，
+/// 之后即恢复增量上传；如需保留旧哈希，可将 `chunk_naming` 设为 `legacy`。
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChunkNaming {
+    /// `#L<起始行>-<结束行>`（行号从 1 开始，闭区间）
+    LineRange,
+    /// `#chunk<序号>of<总数>`（与 Python 版本保持一致）
+    Legacy,
+}
+
+impl ChunkNaming {
+    fn from_config(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("line_range") => Self::LineRange,
+            Some("legacy") => Self::Legacy,
+            Some(other) => {
+                log_important!(warn, "未知的 chunk_naming 配置: {}，使用默认的 line_range", other);
+                Self::LineRange
+            }
+        }
+    }
+
+    /// 生成分块名称；`start`/`end` 为 [start, end) 行区间（从 0 开始），`idx` 为分块序号（从 0 开始）
+    fn chunk_path(&self, path: &str, start: usize, end: usize, idx: usize, total: usize) -> String {
+        match self {
+            Self::LineRange => format!("{}#L{}-{}", path, start + 1, end),
+            Self::Legacy => format!("{}#chunk{}of{}", path, idx + 1, total),
+        }
+    }
+
+    /// 判断 `#` 之后的部分是否为任一命名方式生成的分块后缀
+    fn is_chunk_suffix(suffix: &str) -> bool {
+        let is_num = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if let Some(range) = suffix.strip_prefix('L') {
+            return range.split_once('-').map(|(a, b)| is_num(a) && is_num(b)).unwrap_or(false);
+        }
+        if let Some(rest) = suffix.strip_prefix("chunk") {
+            return rest.split_once("of").map(|(a, b)| is_num(a) && is_num(b)).unwrap_or(false);
+        }
+        false
+    }
+}
+
+/// 分割文件内容为多个 blob（如果超过最大行数，或设置了 `max_chars` 且超过最大字符数）
+/// 分块名称由 `naming` 决定，见 [`ChunkNaming`]
+/// 传入 `smart` 时启用智能边界检测，避免切在多行字符串/注释内部
+fn split_content(
+    path: &str,
+    content: &str,
+    max_lines: usize,
+    max_chars: Option<usize>,
+    smart: Option<&SmartSplitConfig>,
+    naming: ChunkNaming,
+) -> Vec<BlobItem> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let total_lines = lines.len();
+    let max_chars = max_chars.filter(|n| *n > 0);
+    
+    // 如果文件在限制内，返回单个 blob
+    if total_lines <= max_lines && max_chars.map(|n| content.chars().count() <= n).unwrap_or(true) {
+        return vec![BlobItem { path: path.to_string(), content: content.to_string() }];
+    }
+
+    let ranges = chunk_ranges(&lines, max_lines, max_chars, smart);
+    let num_chunks = ranges.len();
+    let mut blobs = Vec::new();
+
+    if let Some(limit) = max_chars {
+        for (start_line, end_line) in &ranges {
+            if end_line - start_line == 1 && lines[*start_line].chars().count() > limit {
+                log_important!(warn, "第 {} 行超过 max_chars_per_blob（{} 字符），整行作为一个分块: {}", start_line + 1, limit, path);
+            }
+        }
+    }
+
+    for (chunk_idx, (start_line, end_line)) in ranges.into_iter().enumerate() {
+        let chunk_content = lines[start_line..end_line].join("");
+        let chunk_path = naming.chunk_path(path, start_line, end_line, chunk_idx, num_chunks);
+        blobs.push(BlobItem { path: chunk_path, content: chunk_content });
+    }
+
+    blobs
+}
+
+/// 上传时 blob 内容在 JSON 载荷中的编码方式（`upload_chunk_encoding`）
+///
+/// 部分反向代理会拒绝包含原始 Unicode 字符的 JSON，此时可改用 base64：`path` 与 `content` 均为 UTF-8 文本的
+/// 标准 base64 编码，并在该 blob 上附加 `"encoding": "base64"`，服务端需先解码再计算 blob 名称
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChunkEncoding {
+    /// 原样上传（默认）
+    Plain,
+    /// base64 编码后上传
+    Base64,
+}
+
+impl ChunkEncoding {
+    fn from_config(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("plain") => Self::Plain,
+            Some("base64") => Self::Base64,
+            Some(other) => {
+                log_important!(warn, "未知的 upload_chunk_encoding 配置: {}，使用默认的 plain", other);
+                Self::Plain
+            }
+        }
+    }
+}
+
+/// 生成上传载荷中的单个 blob；blob 名称始终按原始内容计算，与编码方式无关
+fn upload_blob_payload(blob: &BlobItem, encoding: ChunkEncoding) -> serde_json::Value {
+    match encoding {
+        ChunkEncoding::Plain => serde_json::json!({"path": blob.path, "content": blob.content}),
+        ChunkEncoding::Base64 => serde_json::json!({
+            "path": base64::engine::general_purpose::STANDARD.encode(blob.path.as_bytes()),
+            "content": base64::engine::general_purpose::STANDARD.encode(blob.content.as_bytes()),
+            "encoding": "base64",
+        }),
+    }
+}
+
+/// 过滤掉内容字符数低于 `min_chars` 或字节数低于 `min_bytes` 的 blob（作用于分块之后），
+/// 返回保留的 blob 与跳过数量
+fn filter_small_blobs(blobs: Vec<BlobItem>, min_chars: usize, min_bytes: usize) -> (Vec<BlobItem>, usize) {
+    if min_chars == 0 && min_bytes == 0 {
+        return (blobs, 0);
+    }
+    let before = blobs.len();
+    let kept: Vec<BlobItem> = blobs
+        .into_iter()
+        .filter(|b| {
+            let keep = b.content.len() >= min_bytes && b.content.chars().count() >= min_chars;
+            if !keep {
+                log_debug!("blob 内容过短，跳过: path={}, content_length={}", b.path, b.content.len());
+            }
+            keep
+        })
+        .collect();
+    let skipped = before - kept.len();
+    (kept, skipped)
+}
+
+/// 按 `secret_scan_policy` 处理内容包含密钥的 blob（作用于分块之后），
+/// 返回保留（或脱敏后）的 blob、跳过的 blob 数量与脱敏的密钥片段数量
+fn apply_secret_policy(blobs: Vec<BlobItem>, policy: SecretPolicy) -> (Vec<BlobItem>, usize, usize) {
+    if policy == SecretPolicy::Off {
+        return (blobs, 0, 0);
+    }
+    let mut kept = Vec::with_capacity(blobs.len());
+    let (mut skipped, mut redacted) = (0, 0);
+    for mut blob in blobs {
+        match scan_content(&blob.path, &blob.content, policy) {
+            SecretScanOutcome::Clean => kept.push(blob),
+            SecretScanOutcome::Skip => skipped += 1,
+            SecretScanOutcome::Redacted { content, count } => {
+                blob.content = content;
+                redacted += count;
+                kept.push(blob);
+            }
+        }
+    }
+    (kept, skipped, redacted)
+}
+
+/// text_extensions 匹配器
+///
+/// `text_extensions` 支持两种写法：
+/// - 纯扩展名：以 `.` 开头且不含 `*`，如 `.rs`、`.py`，按扩展名精确比较
+/// - 文件名 glob：包含 `*`，或不以 `.` 开头，如 `*.config.js`、`Dockerfile`、`src/**/*.sql`，
+///   通过 globset 匹配文件名（模式含 `/` 时匹配相对项目根目录的路径）
+///
+/// `text_extensions` 为空且配置了 `exclude_extensions` 时进入排除模式：
+/// 除文件名以这些扩展名结尾的文件外，其余文件均被索引
+///
+/// 以上比较是否忽略大小写由 `case_insensitive` 决定（见 `default_case_insensitive_paths`）
+#[derive(Clone)]
+struct TextExtMatcher {
+    exts: Vec<String>,
+    name_globs: Option<GlobSet>,
+    path_globs: Option<GlobSet>,
+    exclude_exts: Vec<String>,
+    case_insensitive: bool,
+}
+
+/// 文件系统默认是否大小写不敏感：Windows 与 macOS 为 true，其他平台为 false
+fn default_case_insensitive_paths() -> bool {
+    cfg!(any(target_os = "windows", target_os = "macos"))
+}
+
+impl TextExtMatcher {
+    fn new(text_exts: &[String], case_insensitive: bool) -> Self {
+        let mut exts = Vec::new();
+        let mut name_builder = GlobSetBuilder::new();
+        let mut path_builder = GlobSetBuilder::new();
+        let mut has_name = false;
+        let mut has_path = false;
+
+        for entry in text_exts {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            if entry.starts_with('.') && !entry.contains('*') {
+                exts.push(fold_case(entry, case_insensitive));
+                continue;
+            }
+            match GlobBuilder::new(entry).case_insensitive(case_insensitive).literal_separator(true).build() {
+                Ok(glob) if entry.contains('/') => {
+                    path_builder.add(glob);
+                    has_path = true;
+                }
+                Ok(glob) => {
+                    name_builder.add(glob);
+                    has_name = true;
+                }
+                Err(e) => log_debug!("无效的 text_extensions glob，跳过: {} ({})", entry, e),
+            }
+        }
+
+        let name_globs = if has_name { name_builder.build().ok() } else { None };
+        let path_globs = if has_path { path_builder.build().ok() } else { None };
+        Self { exts, name_globs, path_globs, exclude_exts: Vec::new(), case_insensitive }
+    }
+
+    /// 设置排除扩展名列表（缺少前导 `.` 时自动补全）
+    fn with_exclude_extensions(mut self, exclude_exts: &[String]) -> Self {
+        let case_insensitive = self.case_insensitive;
+        self.exclude_exts = exclude_exts
+            .iter()
+            .map(|e| fold_case(e.trim(), case_insensitive))
+            .filter(|e| !e.is_empty())
+            .map(|e| if e.starts_with('.') { e } else { format!(".{}", e) })
+            .collect();
+        self
+    }
+
+    /// 是否处于排除模式（未配置允许列表，仅配置了排除扩展名）
+    fn is_deny_mode(&self) -> bool {
+        self.exts.is_empty()
+            && self.name_globs.is_none()
+            && self.path_globs.is_none()
+            && !self.exclude_exts.is_empty()
+    }
+
+    /// 判断文件是否应被索引；`rel` 为相对项目根目录、使用正斜杠的路径
+    fn matches(&self, path: &Path, rel: &str) -> bool {
+        if self.is_deny_mode() {
+            let name = fold_case(path.file_name().and_then(|s| s.to_str()).unwrap_or_default(), self.case_insensitive);
+            return !self.exclude_exts.iter().any(|e| name.ends_with(e.as_str()));
+        }
+
+        let ext_ok = path.extension().and_then(|s| s.to_str()).map(|e| {
+            let dot = fold_case(&format!(".{}", e), self.case_insensitive);
+            self.exts.iter().any(|te| te == &dot)
+        }).unwrap_or(false);
+        if ext_ok {
+            return true;
+        }
+
+        if let Some(globs) = &self.name_globs {
+            if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
+                if globs.is_match(name) {
+                    return true;
+                }
+            }
+        }
+
+        self.path_globs.as_ref().map(|globs| globs.is_match(rel)).unwrap_or(false)
+    }
+}
+
+/// 大小写不敏感时统一转为小写，否则原样返回
+fn fold_case(value: &str, case_insensitive: bool) -> String {
+    if case_insensitive { value.to_lowercase() } else { value.to_string() }
+}
+
+/// 排除模式编译结果
+/// - 以 `/` 结尾的模式（如 `build/`）遵循 git 语义，仅匹配目录
+/// - 以 `/` 开头或包含 `/` 的模式（如 `/config.rs`、`src/config.rs`）为锚定模式，
+///   只与相对项目根目录的完整路径匹配，不再逐级匹配路径的各个部分
+/// - 不含 `/` 的模式（如 `config.rs`）同时匹配完整路径与任意层级的路径部分
+struct ExcludeGlobs {
+    /// 同时匹配文件与目录的非锚定模式
+    any: GlobSet,
+    /// 仅匹配目录的非锚定模式（已去掉末尾的 `/`）
+    dirs_only: GlobSet,
+    /// 同时匹配文件与目录的锚定模式（已去掉开头的 `/`）
+    anchored: GlobSet,
+    /// 仅匹配目录的锚定模式
+    anchored_dirs: GlobSet,
+}
+
+/// 构建排除模式的 GlobSet
+fn build_exclude_globset(exclude_patterns: &[String], case_insensitive: bool) -> Result<ExcludeGlobs> {
+    let mut any_builder = GlobSetBuilder::new();
+    let mut dirs_builder = GlobSetBuilder::new();
+    let mut anchored_builder = GlobSetBuilder::new();
+    let mut anchored_dirs_builder = GlobSetBuilder::new();
+    for pattern in exclude_patterns {
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(stripped) => (stripped, true),
+            None => (pattern.as_str(), false),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+
+        // 锚定模式中的 `*` 不跨越目录分隔符（与 git 一致）
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(anchored)
+            .case_insensitive(case_insensitive)
+            .build();
+
+        // 尝试将模式转换为 Glob
+        if let Ok(glob) = glob {
+            match (anchored, dir_only) {
+                (false, false) => any_builder.add(glob),
+                (false, true) => dirs_builder.add(glob),
+                (true, false) => anchored_builder.add(glob),
+                (true, true) => anchored_dirs_builder.add(glob),
+            };
+        } else {
+            log_debug!("无效的排除模式，跳过: {}", pattern);
+        }
+    }
+    let build = |builder: GlobSetBuilder| builder.build().map_err(|e| anyhow::anyhow!("构建排除模式失败: {}", e));
+    Ok(ExcludeGlobs {
+        any: build(any_builder)?,
+        dirs_only: build(dirs_builder)?,
+        anchored: build(anchored_builder)?,
+        anchored_dirs: build(anchored_dirs_builder)?,
+    })
+}
+
+/// 始终不索引的密钥类文件名（不区分大小写），不受 `skip_hidden` 与扩展名配置影响
+const SECRET_FILE_NAMES: &[&str] = &[".env", "id_rsa", "id_dsa", "id_ecdsa", "id_ed25519"];
+/// 始终不索引的密钥类文件扩展名
+const SECRET_FILE_EXTENSIONS: &[&str] = &[".pem", ".key", ".p12", ".pfx"];
+
+/// 是否是可能包含密钥的文件：`.env` 及 `.env.*`、SSH 私钥、证书与私钥文件
+fn is_secret_file_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SECRET_FILE_NAMES.contains(&name.as_str())
+        || name.starts_with(".env.")
+        || SECRET_FILE_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+/// 检查路径是否应该被排除
+/// 使用 globset 进行完整的 fnmatch 模式匹配（与 Python 版本保持一致）
+/// Python 版本使用 fnmatch.fnmatch 检查路径的各个部分和完整路径
+/// 仅匹配目录的模式（`build/`）只作用于目录路径本身以及文件的父级目录部分；
+/// 锚定模式只与完整相对路径（及其父级目录前缀）匹配
+fn should_exclude(path: &Path, root: &Path, exclude_globset: Option<&ExcludeGlobs>, is_dir: bool) -> bool {
+    let globs = match exclude_globset {
+        Some(g) => g,
+        None => return false,
+    };
+
+    // 获取相对路径
+    let rel = match path.strip_prefix(root) {
+        Ok(rel) => rel,
+        Err(_) => path,
+    };
+
+    // 转换为使用正斜杠的字符串（用于匹配）
+    let rel_forward = rel.to_string_lossy().replace('\\', "/");
+    
+    // 检查完整相对路径（与 Python 版本的 fnmatch(path_str, pattern) 一致）
+    if globs.any.is_match(&rel_forward) || globs.anchored.is_match(&rel_forward) {
+        return true;
+    }
+    if is_dir && (globs.dirs_only.is_match(&rel_forward) || globs.anchored_dirs.is_match(&rel_forward)) {
+        return true;
+    }
+
+    // 检查路径的各个部分（与 Python 版本的 fnmatch(part, pattern) 一致）
+    let parts: Vec<&str> = rel.iter().filter_map(|part| part.to_str()).collect();
+    for (idx, part_str) in parts.iter().enumerate() {
+        if globs.any.is_match(part_str) {
+            return true;
+        }
+        // 最后一个部分只有在路径本身是目录时才算目录
+        let part_is_dir = is_dir || idx + 1 < parts.len();
+        if part_is_dir && globs.dirs_only.is_match(part_str) {
+            return true;
+        }
+
+        // 父级目录前缀命中锚定模式时，其下所有路径同样被排除
+        if idx + 1 < parts.len() {
+            let prefix = parts[..=idx].join("/");
+            if globs.anchored.is_match(&prefix) || globs.anchored_dirs.is_match(&prefix) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// 目录允许列表（`include_dirs`）编译结果
+///
+/// 命中某个模式的目录及其所有子目录中的文件才会被索引；
+/// 尚未命中、但可能是某个模式前缀的目录（如 `packages` 之于 `packages/*/src`）只继续向下遍历，
+/// 其中的文件不索引；其余目录直接跳过
+struct IncludeDirs {
+    /// 与完整相对路径匹配的模式
+    full: GlobSet,
+    /// 按路径部分拆分的模式，`None` 表示 `**`
+    components: Vec<Vec<Option<GlobMatcher>>>,
+}
+
+impl IncludeDirs {
+    /// 未配置允许列表（或全部模式无效）时返回 None，表示不限制目录
+    fn new(patterns: &[String], case_insensitive: bool) -> Option<Self> {
+        let build_glob = |pattern: &str| GlobBuilder::new(pattern).literal_separator(true).case_insensitive(case_insensitive).build();
+        let mut full_builder = GlobSetBuilder::new();
+        let mut components = Vec::new();
+        for pattern in patterns {
+            let pattern = pattern.trim().trim_start_matches("./").trim_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+            let glob = match build_glob(pattern) {
+                Ok(glob) => glob,
+                Err(e) => {
+                    log_debug!("无效的 include_dirs 模式，跳过: {} ({})", pattern, e);
+                    continue;
+                }
+            };
+            full_builder.add(glob);
+            components.push(
+                pattern
+                    .split('/')
+                    .map(|part| if part == "**" { None } else { build_glob(part).ok().map(|g| g.compile_matcher()) })
+                    .collect(),
+            );
+        }
+        if components.is_empty() {
+            return None;
+        }
+        Some(Self { full: full_builder.build().ok()?, components })
+    }
+
+    /// 目录本身是否命中允许列表
+    fn matches(&self, rel_dir: &str) -> bool {
+        self.full.is_match(rel_dir)
+    }
+
+    /// 目录下是否可能存在命中允许列表的子目录
+    fn may_contain(&self, rel_dir: &str) -> bool {
+        let parts: Vec<&str> = rel_dir.split('/').collect();
+        self.components.iter().any(|pattern| {
+            for (idx, part) in parts.iter().enumerate() {
+                match pattern.get(idx) {
+                    Some(None) => return true,
+                    Some(Some(matcher)) if matcher.is_match(part) => continue,
+                    _ => return false,
+                }
+            }
+            pattern.len() > parts.len()
+        })
+    }
+}
+
+/// 通过 `git ls-files` 列出项目中 git 跟踪或未被忽略的文件（绝对路径）
+/// 项目不在 git 仓库中或 git 不可用时返回 None，调用方不做限制
+fn git_listed_files(root: &Path) -> Option<HashSet<PathBuf>> {
+    let output = std::process::Command::new("git")
+        .args(["ls-files", "-z", "--cached", "--others", "--exclude-standard"])
+        .current_dir(root)
+        .output();
+
+    match output {
+        Ok(out) if out.status.success() => Some(
+            out.stdout
+                .split(|b| *b == 0)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| root.join(String::from_utf8_lossy(entry).as_ref()))
+                .collect(),
+        ),
+        Ok(out) => {
+            log_important!(warn, "git ls-files 执行失败，将索引所有文件: {}", String::from_utf8_lossy(&out.stderr).trim());
+            None
+        }
+        Err(e) => {
+            log_important!(warn, "无法执行 git 命令，将索引所有文件: {}", e);
+            None
+        }
+    }
+}
+
+fn build_gitignore(root: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    let gi_path = root.join(".gitignore");
+    if gi_path.exists() {
+        if builder.add(gi_path).is_some() { return None; }
+        return match builder.build() { Ok(gi) => Some(gi), Err(_) => None };
+    }
+    None
+}
+
+/// 文件收集参数
+/// 由 AcemcpConfig 解析得到，索引与文件状态扫描共用，保证两者的过滤与分块结果一致
+struct CollectOptions {
+    text_exts: Vec<String>,
+    exclude_exts: Vec<String>,
+    exclude_patterns: Vec<String>,
+    include_dirs: Vec<String>,
+    max_lines_per_blob: usize,
+    /// 每个 blob 的最大字符数，None 表示只按行数分块
+    max_chars_per_blob: Option<usize>,
+    min_blob_chars: usize,
+    min_blob_content_length: usize,
+    /// 单个文件的大小上限（字节），超过时不读取，None 表示不限制
+    max_file_bytes: Option<u64>,
+    depth_limit: usize,
+    smart_split: Option<SmartSplitConfig>,
+    chunk_naming: ChunkNaming,
+    encoding_override: Option<String>,
+    only_committed_files: bool,
+    /// 是否将换行符统一为 `\n`
+    normalize_line_endings: bool,
+    /// 遍历目录时是否跟随符号链接
+    follow_symlinks: bool,
+    /// 是否跳过名称以 `.` 开头的文件与目录
+    skip_hidden: bool,
+    /// blob 内容包含密钥时的处理方式
+    secret_policy: SecretPolicy,
+    /// 扩展名与排除模式匹配是否忽略大小写
+    case_insensitive: bool,
+    /// 额外的路径过滤（用于按 glob 局部重建索引），为 None 时不过滤
+    only_paths: Option<TextExtMatcher>,
+    /// 只收集该时间之后修改过的文件（仅完整索引时按 `index_only_changed_since` 设置），为 None 时不过滤
+    changed_since: Option<std::time::SystemTime>,
+    /// 自定义分块脚本，为 None 时使用内置分块
+    splitter_script: Option<PathBuf>,
+}
+
+impl CollectOptions {
+    fn from_config(config: &AcemcpConfig) -> Self {
+        Self {
+            text_exts: config.text_extensions.clone().unwrap_or_default(),
+            exclude_exts: config.exclude_extensions.clone().unwrap_or_default(),
+            exclude_patterns: config.exclude_patterns.clone().unwrap_or_default(),
+            include_dirs: config.include_dirs.clone().unwrap_or_default(),
+            max_lines_per_blob: config.max_lines_per_blob.unwrap_or(800) as usize,
+            max_chars_per_blob: config.max_chars_per_blob.filter(|n| *n > 0),
+            min_blob_chars: config.min_blob_chars.unwrap_or(0) as usize,
+            min_blob_content_length: config.min_blob_content_length.unwrap_or(0),
+            max_file_bytes: config.max_file_bytes.filter(|n| *n > 0),
+            depth_limit: config.index_depth_limit.unwrap_or(DEFAULT_INDEX_DEPTH_LIMIT),
+            smart_split: config
+                .smart_split_lookahead_lines
+                .map(|n| SmartSplitConfig { max_lookahead_lines: n }),
+            chunk_naming: ChunkNaming::from_config(config.chunk_naming.as_deref()),
+            encoding_override: config.encoding_override.clone(),
+            only_committed_files: config.only_committed_files.unwrap_or(false),
+            normalize_line_endings: config.normalize_line_endings.unwrap_or(true),
+            follow_symlinks: config.follow_symlinks.unwrap_or(true),
+            skip_hidden: config.skip_hidden.unwrap_or(true),
+            secret_policy: SecretPolicy::from_config(config.secret_scan_policy.as_deref()),
+            case_insensitive: config.case_insensitive_paths.unwrap_or_else(default_case_insensitive_paths),
+            only_paths: None,
+            changed_since: None,
+            splitter_script: config
+                .custom_splitter_script
+                .as_deref()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+        }
+    }
+
+    /// 分割文件内容：配置了自定义分块脚本时优先使用脚本的结果，脚本失败时回退内置分块
+    ///
+    /// 脚本自行读取磁盘上的文件，输出的内容原样使用，不做换行符统一（`normalize_line_endings` 只作用于内置分块）
+    fn split(&self, root: &Path, rel: &str, content: &str) -> Vec<BlobItem> {
+        if let Some(script) = &self.splitter_script {
+            if let Some(blobs) = run_splitter_script(script, root, rel, SPLITTER_SCRIPT_TIMEOUT) {
+                return blobs;
+            }
+        }
+        split_content(rel, content, self.max_lines_per_blob, self.max_chars_per_blob, self.smart_split.as_ref(), self.chunk_naming)
+    }
+}
+
+/// 自定义分块脚本的最长运行时间，超时后终止脚本并回退内置分块
+const SPLITTER_SCRIPT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 读取自定义分块脚本标准输出的上限，超过时终止脚本并回退内置分块
+const SPLITTER_SCRIPT_MAX_OUTPUT_BYTES: usize = 16 * 1024 * 1024;
+/// 日志中保留的脚本标准错误的最大字节数
+const SPLITTER_SCRIPT_MAX_STDERR_BYTES: usize = 4 * 1024;
+
+/// 在后台线程中读取子进程的管道，最多保留 `limit + 1` 个字节（多出的 1 字节用于判断是否超限）；
+/// `stop_at_limit` 为 false 时继续读取并丢弃多余内容，避免子进程因管道写满而阻塞
+fn spawn_pipe_reader<R: Read + Send + 'static>(pipe: Option<R>, limit: usize, stop_at_limit: bool) -> std::sync::mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut kept = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let mut chunk = [0u8; 8192];
+            while let Ok(n) = pipe.read(&mut chunk) {
+                if n == 0 {
+                    break;
+                }
+                let room = (limit + 1).saturating_sub(kept.len());
+                kept.extend_from_slice(&chunk[..n.min(room)]);
+                if stop_at_limit && kept.len() > limit {
+                    break;
+                }
+            }
+        }
+        let _ = tx.send(kept);
+    });
+    rx
+}
+
+/// 终止子进程并回收，避免留下僵尸进程
+fn kill_child(child: &mut std::process::Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// 执行自定义分块脚本：工作目录为项目根目录，参数为文件的相对路径，标准输出为 `[{"path": ..., "content": ...}]`
+/// 脚本无法执行、超过 `timeout` 未退出、输出超过上限、退出码非零、输出无法解析或 blob 路径不属于该文件时
+/// 返回 None，由调用方回退内置分块
+fn run_splitter_script(script: &Path, root: &Path, rel: &str, timeout: Duration) -> Option<Vec<BlobItem>> {
+    use std::process::{Command, Stdio};
+
+    let mut child = match Command::new(script)
+        .arg(rel)
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            log_important!(warn, "无法执行自定义分块脚本 {}，使用内置分块: {}", script.display(), e);
+            return None;
+        }
+    };
+    let stdout_rx = spawn_pipe_reader(child.stdout.take(), SPLITTER_SCRIPT_MAX_OUTPUT_BYTES, true);
+    let stderr_rx = spawn_pipe_reader(child.stderr.take(), SPLITTER_SCRIPT_MAX_STDERR_BYTES, false);
+    let deadline = std::time::Instant::now() + timeout;
+    let too_large = |stdout: &[u8]| stdout.len() > SPLITTER_SCRIPT_MAX_OUTPUT_BYTES;
+
+    let mut stdout: Option<Vec<u8>> = None;
+    let status = loop {
+        if stdout.is_none() {
+            stdout = stdout_rx.try_recv().ok();
+            if stdout.as_deref().map(too_large).unwrap_or(false) {
+                kill_child(&mut child);
+                log_important!(warn, "自定义分块脚本处理 {} 的输出超过 {} 字节，使用内置分块", rel, SPLITTER_SCRIPT_MAX_OUTPUT_BYTES);
+                return None;
+            }
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if std::time::Instant::now() >= deadline => {
+                kill_child(&mut child);
+                log_important!(warn, "自定义分块脚本处理 {} 超过 {} 毫秒未退出，已终止并使用内置分块", rel, timeout.as_millis());
+                return None;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            Err(e) => {
+                kill_child(&mut child);
+                log_important!(warn, "等待自定义分块脚本处理 {} 失败，使用内置分块: {}", rel, e);
+                return None;
+            }
+        }
+    };
+
+    // 脚本已退出；其派生的后台进程仍持有管道时不再等待
+    let grace = deadline.saturating_duration_since(std::time::Instant::now()).max(Duration::from_millis(100));
+    let stdout = match stdout.or_else(|| stdout_rx.recv_timeout(grace).ok()) {
+        Some(stdout) if !too_large(stdout.as_slice()) => stdout,
+        Some(_) => {
+            log_important!(warn, "自定义分块脚本处理 {} 的输出超过 {} 字节，使用内置分块", rel, SPLITTER_SCRIPT_MAX_OUTPUT_BYTES);
+            return None;
+        }
+        None => {
+            log_important!(warn, "读取自定义分块脚本处理 {} 的输出超时，使用内置分块", rel);
+            return None;
+        }
+    };
+    if !status.success() {
+        let stderr = stderr_rx.recv_timeout(Duration::from_millis(100)).unwrap_or_default();
+        log_important!(
+            warn,
+            "自定义分块脚本处理 {} 失败（{}），使用内置分块: {}",
+            rel,
+            status,
+            String::from_utf8_lossy(&stderr[..stderr.len().min(SPLITTER_SCRIPT_MAX_STDERR_BYTES)]).trim()
+        );
+        return None;
+    }
+
+    let blobs: Vec<BlobItem> = match serde_json::from_slice(&stdout) {
+        Ok(blobs) => blobs,
+        Err(e) => {
+            log_important!(warn, "自定义分块脚本处理 {} 的输出不是有效的 JSON，使用内置分块: {}", rel, e);
+            return None;
+        }
+    };
+    // 增量索引与文件状态依赖 blob 路径还原出源文件
+    if let Some(bad) = blobs.iter().find(|b| blob_source_path(&b.path) != rel) {
+        log_important!(warn, "自定义分块脚本为 {} 输出了不属于该文件的 blob 路径 {}，使用内置分块", rel, bad.path);
+        return None;
+    }
+    if blobs.is_empty() {
+        log_important!(warn, "自定义分块脚本处理 {} 没有输出任何 blob，使用内置分块", rel);
+        return None;
+    }
+    Some(blobs)
+}
+
+/// 文件收集阶段的统计信息
+#[derive(Debug, Default)]
+struct CollectStats {
+    /// 因内容过短而跳过的 blob 数量
+    skipped_small: usize,
+    /// 因内容包含密钥而跳过的 blob 数量
+    secrets_skipped: usize,
+    /// 脱敏替换的密钥片段数量
+    secrets_redacted: usize,
+    /// 各解码方式对应的文件数（键见 `DecodedEncoding::key`）
+    encodings: HashMap<String, usize>,
+    /// 修改时间早于 `changed_since` 而跳过的文件（相对路径）
+    skipped_unchanged_files: Vec<String>,
+}
+
+impl CollectStats {
+    /// 累加另一个根目录的统计
+    fn merge(&mut self, other: CollectStats) {
+        self.skipped_small += other.skipped_small;
+        self.secrets_skipped += other.secrets_skipped;
+        self.secrets_redacted += other.secrets_redacted;
+        for (key, count) in other.encodings {
+            *self.encodings.entry(key).or_default() += count;
+        }
+        self.skipped_unchanged_files.extend(other.skipped_unchanged_files);
+    }
+}
+
+/// 根据目录允许列表判断子目录的遍历方式：
+/// 返回 `Some(true)` 表示其中的文件需要索引，`Some(false)` 表示只继续向下遍历，`None` 表示跳过该目录
+fn include_dir_state(include_dirs: Option<&IncludeDirs>, dir: &Path, root: &Path, parent_included: bool) -> Option<bool> {
+    let include_dirs = match include_dirs {
+        Some(dirs) if !parent_included => dirs,
+        _ => return Some(true),
+    };
+    let rel = dir.strip_prefix(root).unwrap_or(dir).to_string_lossy().replace('\\', "/");
+    if include_dirs.matches(&rel) {
+        Some(true)
+    } else if include_dirs.may_contain(&rel) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// 索引时的文件过滤规则，由项目根目录与 `CollectOptions` 构建
+///
+/// 遍历目录、`collect_blobs`、文件状态扫描与 `explain_file_exclusion` 都通过它判断文件是否被索引，
+/// 各处对同一文件的结论一致：依次为条目规则（`entry_exclusion`）、目录规则（`dir_exclusion`）、
+/// 读取前的文件规则（`file_exclusion`）与读取后的内容规则（`read_blobs`）
+struct FileFilter<'a> {
+    root: PathBuf,
+    opts: &'a CollectOptions,
+    gitignore: Option<Gitignore>,
+    exclude_globset: Option<ExcludeGlobs>,
+    /// 逐条构建的排除模式，用于找出具体命中的那一条
+    exclude_each: Vec<(String, ExcludeGlobs)>,
+    include_dirs: Option<IncludeDirs>,
+    text_matcher: TextExtMatcher,
+    git_files: Option<HashSet<PathBuf>>,
+    editorconfig: EditorConfigResolver,
+    override_encoding: Option<&'static Encoding>,
+}
+
+impl<'a> FileFilter<'a> {
+    fn new(root: &Path, opts: &'a CollectOptions) -> Self {
+        // 构建排除模式的 GlobSet
+        let exclude_globset = if opts.exclude_patterns.is_empty() {
+            None
+        } else {
+            match build_exclude_globset(&opts.exclude_patterns, opts.case_insensitive) {
+                Ok(gs) => Some(gs),
+                Err(e) => {
+                    log_debug!("构建排除模式失败，将使用简单匹配: {}", e);
+                    None
+                }
+            }
+        };
+        let exclude_each = opts
+            .exclude_patterns
+            .iter()
+            .filter_map(|pattern| {
+                build_exclude_globset(std::slice::from_ref(pattern), opts.case_insensitive)
+                    .ok()
+                    .map(|globs| (pattern.clone(), globs))
+            })
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            opts,
+            gitignore: build_gitignore(root),
+            exclude_globset,
+            exclude_each,
+            include_dirs: IncludeDirs::new(&opts.include_dirs, opts.case_insensitive),
+            text_matcher: TextExtMatcher::new(&opts.text_exts, opts.case_insensitive).with_exclude_extensions(&opts.exclude_exts),
+            git_files: if opts.only_committed_files { git_listed_files(root) } else { None },
+            editorconfig: EditorConfigResolver::new(root),
+            override_encoding: resolve_encoding_override(root, opts.encoding_override.as_deref()),
+        }
+    }
+
+    fn rel_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+    }
+
+    /// 路径命中排除模式时返回命中的模式
+    fn exclude_pattern(&self, path: &Path, is_dir: bool) -> Option<String> {
+        if !should_exclude(path, &self.root, self.exclude_globset.as_ref(), is_dir) {
+            return None;
+        }
+        let hit = self
+            .exclude_each
+            .iter()
+            .find(|(_, globs)| should_exclude(path, &self.root, Some(globs), is_dir))
+            .map(|(pattern, _)| pattern.clone());
+        Some(hit.unwrap_or_default())
+    }
+
+    /// 对文件与目录都生效的规则：.gitignore、隐藏条目与密钥类文件名
+    fn entry_exclusion(&self, path: &Path, is_dir: bool) -> Option<ExclusionReason> {
+        if let Some(gi) = &self.gitignore {
+            if gi.matched_path_or_any_parents(path, is_dir).is_ignore() {
+                return Some(ExclusionReason::GitIgnored);
+            }
+        }
+
+        // 隐藏文件与目录（.idea/、.vscode/ 等）；.gitignore 由 build_gitignore 直接读取，不受影响
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if self.opts.skip_hidden && name.starts_with('.') {
+            return Some(ExclusionReason::Hidden);
+        }
+        if !is_dir && is_secret_file_name(&name) {
+            log_debug!("疑似密钥文件，跳过: {:?}", path);
+            return Some(ExclusionReason::SecretFile);
+        }
+        None
+    }
+
+    /// 目录规则：排除模式、目录允许列表与深度限制（`depth` 为相对项目根目录的层数）
+    /// 返回 `Ok(目录内的文件是否在允许列表内)`；`Ok(false)` 表示只继续向下遍历
+    fn dir_exclusion(&self, dir: &Path, depth: usize, parent_included: bool) -> Result<bool, ExclusionReason> {
+        if let Some(pattern) = self.exclude_pattern(dir, true) {
+            return Err(ExclusionReason::ExcludePattern(pattern));
+        }
+        let included = include_dir_state(self.include_dirs.as_ref(), dir, &self.root, parent_included)
+            .ok_or(ExclusionReason::OutsideIncludeDirs)?;
+        if depth > self.opts.depth_limit {
+            return Err(ExclusionReason::TooDeep);
+        }
+        Ok(included)
+    }
+
+    /// 读取内容前的文件规则：排除模式、扩展名、git 跟踪、修改时间下限与文件大小上限
+    fn file_exclusion(&self, path: &Path, rel: &str) -> Option<ExclusionReason> {
+        if let Some(pattern) = self.exclude_pattern(path, false) {
+            log_debug!("排除文件: {:?}", path);
+            return Some(ExclusionReason::ExcludePattern(pattern));
+        }
+        // 检查文件扩展名 / 文件名 glob
+        if !self.text_matcher.matches(path, rel) {
+            return Some(ExclusionReason::WrongExtension);
+        }
+        if let Some(files) = &self.git_files {
+            if !files.contains(path) {
+                return Some(ExclusionReason::NotCommitted);
+            }
+        }
+        // 修改时间早于下限的文件不读取，读取不到修改时间时照常收集
+        if let Some(since) = self.opts.changed_since {
+            if fs::metadata(path).and_then(|m| m.modified()).map(|t| t < since).unwrap_or(false) {
+                log_debug!("文件修改时间早于下限，跳过: {}", rel);
+                return Some(ExclusionReason::NotChangedSince);
+            }
+        }
+        if let Some(max_bytes) = self.opts.max_file_bytes {
+            if fs::metadata(path).map(|m| m.len() > max_bytes).unwrap_or(false) {
+                log_debug!("文件超过大小上限 {} 字节，跳过: {}", max_bytes, rel);
+                return Some(ExclusionReason::FileTooLarge);
+            }
+        }
+        None
+    }
+
+    /// 读取文件（使用多编码支持）并分块，依次应用二进制检测、过短过滤与密钥策略，统计计入 `stats`；
+    /// 没有可索引的 blob 时返回原因
+    fn read_blobs(&mut self, path: &Path, rel: &str, stats: &mut CollectStats) -> Result<Vec<BlobItem>, ExclusionReason> {
+        let Some((content, encoding)) = read_file_with_encoding(path, self.editorconfig.charset_for(path), self.override_encoding) else {
+            log_debug!("无法读取文件: {:?}", path);
+            return Err(ExclusionReason::Unreadable);
+        };
+        let content = if self.opts.normalize_line_endings { normalize_line_endings(content) } else { content };
+        if encoding == DecodedEncoding::Lossy {
+            log_important!(warn, "文件编码识别失败，已按 UTF-8 有损解码: {}", rel);
+        }
+        *stats.encodings.entry(encoding.key()).or_insert(0) += 1;
+        // 排除模式下没有扩展名白名单兜底，跳过含 NUL 字符的二进制文件
+        if self.text_matcher.is_deny_mode() && content.contains('\0') {
+            log_debug!("疑似二进制文件，跳过: {}", rel);
+            return Err(ExclusionReason::Binary);
+        }
+        let (parts, skipped_small) = filter_small_blobs(
+            self.opts.split(&self.root, rel, &content),
+            self.opts.min_blob_chars,
+            self.opts.min_blob_content_length,
+        );
+        stats.skipped_small += skipped_small;
+        if parts.is_empty() {
+            log_debug!("文件内容过短，跳过: {}", rel);
+            return Err(ExclusionReason::TooShort);
+        }
+        let (parts, secrets_skipped, secrets_redacted) = apply_secret_policy(parts, self.opts.secret_policy);
+        stats.secrets_skipped += secrets_skipped;
+        stats.secrets_redacted += secrets_redacted;
+        if parts.is_empty() {
+            log_debug!("文件内容包含密钥，跳过: {}", rel);
+            return Err(ExclusionReason::SecretContent);
+        }
+        Ok(parts)
+    }
+}
+
+/// 遍历项目目录，返回通过条目规则与目录规则（见 `FileFilter`）的文件
+///
+/// 同一目录下的条目按文件名排序、深度优先，结果顺序与文件系统无关；文件级的规则由调用方通过 `FileFilter::file_exclusion` 处理。
+/// `excluded_count` 累计因排除模式或允许列表被跳过的目录数，传入 `excluded_dirs` 时同时记录这些目录
+fn walk_project_files(
+    filter: &FileFilter,
+    excluded_count: &mut usize,
+    mut excluded_dirs: Option<&mut Vec<PathBuf>>,
+) -> Vec<PathBuf> {
+    let root_path = filter.root.as_path();
+    let opts = filter.opts;
+    // 已进入的目录 -> 目录内文件是否在允许列表内
+    let mut dir_included: HashMap<PathBuf, bool> = HashMap::new();
+    dir_included.insert(root_path.to_path_buf(), filter.include_dirs.is_none());
+
+    let walker = walkdir::WalkDir::new(root_path)
+        .follow_links(opts.follow_symlinks)
+        // 深度为 depth_limit 的目录内的文件仍需读取
+        .max_depth(opts.depth_limit.saturating_add(1))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            let p = entry.path();
+            let is_dir = entry.file_type().is_dir();
+            if filter.entry_exclusion(p, is_dir).is_some() {
+                return false;
+            }
+
+            let parent_included = p.parent().and_then(|d| dir_included.get(d)).copied().unwrap_or(false);
+            if !is_dir {
+                // 不跟随符号链接时，指向目录的链接在这里被跳过
+                return parent_included && p.is_file();
+            }
+
+            match filter.dir_exclusion(p, entry.depth(), parent_included) {
+                Ok(child_included) => {
+                    dir_included.insert(p.to_path_buf(), child_included);
+                    true
+                }
+                Err(ExclusionReason::TooDeep) => {
+                    log_important!(warn, "目录超过最大遍历深度 {}，跳过: {:?}", opts.depth_limit, p);
+                    false
+                }
+                Err(_) => {
+                    *excluded_count += 1;
+                    if let Some(dirs) = excluded_dirs.as_deref_mut() {
+                        dirs.push(p.to_path_buf());
+                    }
+                    false
+                }
+            }
+        });
+
+    let mut files = Vec::new();
+    for entry in walker {
+        match entry {
+            Ok(entry) if !entry.file_type().is_dir() => files.push(entry.into_path()),
+            Ok(_) => {}
+            // 无权限读取的目录、成环的符号链接等
+            Err(e) => log_debug!("遍历目录出错，已跳过: {}", e),
+        }
+    }
+    files
+}
+
+/// 判断单个文件被排除的原因：按遍历顺序从最外层目录检查到文件本身，再读取文件检查内容规则，
+/// 使用与 `walk_project_files` / `collect_blobs` 相同的 `FileFilter`
+fn explain_file_exclusion(root: &Path, file: &Path, opts: &CollectOptions) -> anyhow::Result<ExclusionReason> {
+    let root = root.canonicalize().map_err(|e| anyhow::anyhow!("项目根目录不存在: {}: {}", root.display(), e))?;
+    let file = if file.is_absolute() { file.to_path_buf() } else { root.join(file) };
+    let file = file.canonicalize().map_err(|e| anyhow::anyhow!("文件不存在: {}: {}", file.display(), e))?;
+    let rel = file
+        .strip_prefix(&root)
+        .map_err(|_| anyhow::anyhow!("文件不在项目根目录内: {}", file.display()))?
+        .to_path_buf();
+
+    let mut filter = FileFilter::new(&root, opts);
+    let mut parent_included = filter.include_dirs.is_none();
+    let mut dir = root.clone();
+    let parents: Vec<_> = rel.parent().map(|p| p.iter().collect()).unwrap_or_default();
+    for (idx, part) in parents.into_iter().enumerate() {
+        dir.push(part);
+        if let Some(reason) = filter.entry_exclusion(&dir, true) {
+            return Ok(reason);
+        }
+        match filter.dir_exclusion(&dir, idx + 1, parent_included) {
+            Ok(included) => parent_included = included,
+            Err(reason) => return Ok(reason),
+        }
+    }
+
+    if let Some(reason) = filter.entry_exclusion(&file, false) {
+        return Ok(reason);
+    }
+    if !parent_included {
+        return Ok(ExclusionReason::OutsideIncludeDirs);
+    }
+    let rel_forward = filter.rel_path(&file);
+    if let Some(reason) = filter.file_exclusion(&file, &rel_forward) {
+        return Ok(reason);
+    }
+    Ok(match filter.read_blobs(&file, &rel_forward, &mut CollectStats::default()) {
+        Ok(_) => ExclusionReason::NotExcluded,
+        Err(reason) => reason,
+    })
+}
+
+fn collect_blobs(root: &str, opts: &CollectOptions) -> anyhow::Result<(Vec<BlobItem>, CollectStats)> {
+    let root_path = PathBuf::from(root);
+    if !root_path.exists() { anyhow::bail!("项目根目录不存在: {}", root); }
+    
+    log_important!(info, "开始收集代码文件: 根目录={}, 扩展名={:?}, 排除模式={:?}", root, opts.text_exts, opts.exclude_patterns);
+    
+    let mut filter = FileFilter::new(&root_path, opts);
+    let mut out = Vec::new();
+    let mut stats = CollectStats::default();
+    let mut scanned_files = 0;
+    let mut indexed_files = 0;
+    let mut excluded_count = 0;
+    let files = walk_project_files(&filter, &mut excluded_count, None);
+    
+    for p in files {
+        scanned_files += 1;
+        let rel = filter.rel_path(&p);
+        if let Some(only) = &opts.only_paths {
+            if !only.matches(&p, &rel) { continue; }
+        }
+        match filter.file_exclusion(&p, &rel) {
+            None => {}
+            Some(ExclusionReason::ExcludePattern(_)) => {
+                excluded_count += 1;
+                continue;
+            }
+            Some(ExclusionReason::NotChangedSince) => {
+                stats.skipped_unchanged_files.push(rel);
+                continue;
+            }
+            Some(_) => continue,
+        }
+        
+        if let Ok(parts) = filter.read_blobs(&p, &rel, &mut stats) {
+            let blob_count = parts.len();
+            indexed_files += 1;
+            out.extend(parts);
+            log_important!(info, "索引文件: path={}, blobs={}", rel, blob_count);
+        }
+    }
+    
+    log_important!(info, "文件收集完成: 扫描文件数={}, 索引文件数={}, 生成blobs数={}, 排除文件/目录数={}, 过短跳过blobs数={}, 含密钥跳过blobs数={}, 脱敏密钥数={}", scanned_files, indexed_files, out.len(), excluded_count, stats.skipped_small, stats.secrets_skipped, stats.secrets_redacted);
+    Ok((out, stats))
+}
+
+/// 项目配置的附加根目录：(blob 路径前缀, 目录路径)
+///
+/// 相对路径相对项目根目录解析；前缀为目录名，多个根目录同名时依次追加 `-2`、`-3`
+fn additional_roots(config: &AcemcpConfig, root: &str) -> Vec<(String, PathBuf)> {
+    let roots = match config.project_override(root).and_then(|o| o.additional_roots.as_ref()) {
+        Some(roots) => roots,
+        None => return Vec::new(),
+    };
+    let mut used: HashSet<String> = HashSet::new();
+    roots
+        .iter()
+        .map(|extra| {
+            let path = Path::new(root).join(extra.trim());
+            let path = path.canonicalize().unwrap_or(path);
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "root".to_string());
+            let mut prefix = name.clone();
+            let mut n = 2;
+            while !used.insert(prefix.clone()) {
+                prefix = format!("{}-{}", name, n);
+                n += 1;
+            }
+            (prefix, path)
+        })
+        .collect()
+}
+
+/// 收集项目的 blob：项目配置了 `additional_roots` 时一并收集这些根目录（各自应用 .gitignore），
+/// 其 blob 路径以根目录前缀开头，全部记录在主项目根目录下；未配置时与 `collect_blobs` 相同
+///
+/// `only_paths` 对附加根目录按带前缀的路径匹配，与局部重建索引识别旧 blob 的方式一致
+fn collect_project_blobs(config: &AcemcpConfig, root: &str, mut opts: CollectOptions) -> anyhow::Result<(Vec<BlobItem>, CollectStats)> {
+    let (mut blobs, mut stats) = collect_blobs(root, &opts)?;
+    let extra_roots = additional_roots(config, root);
+    if extra_roots.is_empty() {
+        return Ok((blobs, stats));
+    }
+
+    let only_paths = opts.only_paths.take();
+    for (prefix, extra_root) in extra_roots {
+        let extra_root = extra_root.to_string_lossy().to_string();
+        let (extra_blobs, mut extra_stats) = match collect_blobs(&extra_root, &opts) {
+            Ok(collected) => collected,
+            Err(e) => {
+                log_important!(warn, "收集附加根目录失败，已跳过: {}: {}", extra_root, e);
+                continue;
+            }
+        };
+        log_important!(info, "附加根目录 {} 收集到 {} 个 blobs，路径前缀: {}/", extra_root, extra_blobs.len(), prefix);
+        blobs.extend(
+            extra_blobs
+                .into_iter()
+                .map(|b| BlobItem { path: format!("{}/{}", prefix, b.path), content: b.content })
+                .filter(|b| {
+                    let src = blob_source_path(&b.path);
+                    only_paths.as_ref().map(|m| m.matches(Path::new(src), src)).unwrap_or(true)
+                }),
+        );
+        for path in &mut extra_stats.skipped_unchanged_files {
+            *path = format!("{}/{}", prefix, path);
+        }
+        stats.merge(extra_stats);
+    }
+    Ok((blobs, stats))
+}
+
+/// 在阻塞线程池中执行 `collect_project_blobs`：遍历目录、读取文件与等待自定义分块脚本都会阻塞，
+/// 异步的索引流程通过它收集文件，避免长时间占用 tokio 工作线程
+async fn collect_project_blobs_blocking(config: &AcemcpConfig, root: &str, opts: CollectOptions) -> anyhow::Result<(Vec<BlobItem>, CollectStats)> {
+    let (config, root) = (config.clone(), root.to_string());
+    tokio::task::spawn_blocking(move || collect_project_blobs(&config, &root, opts))
+        .await
+        .map_err(|e| anyhow::anyhow!("收集项目文件的任务异常退出: {}", e))?
+}
+
+/// 收集项目内所有可索引文件的索引状态
+///
+/// 为避免引入新的持久化结构，这里通过重新扫描文件并复用与索引阶段相同的
+/// 路径规范化与分块逻辑，基于现有的 blob 哈希集合判断文件是否“已完全索引”。
+fn collect_file_statuses(
+    root: &str,
+    opts: &CollectOptions,
+    existing_blob_names: &HashSet<String>,
+) -> anyhow::Result<Vec<FileIndexStatus>> {
+    let root_path = PathBuf::from(root);
+    if !root_path.exists() {
+        anyhow::bail!("项目根目录不存在: {}", root);
+    }
+
+    let mut filter = FileFilter::new(&root_path, opts);
+    let mut files_status = Vec::new();
+    let mut excluded_count = 0;
+
+    for p in walk_project_files(&filter, &mut excluded_count, None) {
+        let rel = filter.rel_path(&p);
+        if filter.file_exclusion(&p, &rel).is_some() {
+            continue;
+        }
+
+        // 读取文件内容并根据分块结果计算 blob 哈希
+        match filter.read_blobs(&p, &rel, &mut CollectStats::default()) {
+            Ok(blobs) => {
+                let all_indexed = blobs
+                    .iter()
+                    .all(|blob| existing_blob_names.contains(&sha256_hex(&blob.path, &blob.content)));
+                let status = if all_indexed {
+                    FileIndexStatusKind::Indexed
+                } else {
+                    FileIndexStatusKind::Pending
+                };
+                files_status.push(FileIndexStatus { path: rel, status });
+            }
+            // 无法读取内容时，保守地标记为 Pending，避免静默丢失
+            Err(ExclusionReason::Unreadable) => files_status.push(FileIndexStatus {
+                path: rel,
+                status: FileIndexStatusKind::Pending,
+            }),
+            Err(_) => {}
+        }
+    }
+
+    Ok(files_status)
+}
+
+/// 索引进度事件通道容量，订阅者处理不及时时丢弃最旧的事件
+const INDEX_PROGRESS_CHANNEL_CAPACITY: usize = 256;
+
+static INDEX_PROGRESS: OnceLock<tokio::sync::broadcast::Sender<IndexProgressEvent>> = OnceLock::new();
+
+fn index_progress_sender() -> &'static tokio::sync::broadcast::Sender<IndexProgressEvent> {
+    INDEX_PROGRESS.get_or_init(|| tokio::sync::broadcast::channel(INDEX_PROGRESS_CHANNEL_CAPACITY).0)
+}
+
+/// 订阅索引进度事件（状态文件仍会同步写入，订阅只用于实时展示）
+pub fn subscribe_index_progress() -> tokio::sync::broadcast::Receiver<IndexProgressEvent> {
+    index_progress_sender().subscribe()
+}
+
+/// 推送索引进度事件，没有订阅者时直接丢弃
+///
+/// 事件中的 `project_root` 一律使用规范化的项目根目录（见 `normalize_project_root`），与 projects_status.json 的键一致
+fn emit_index_progress(event: IndexProgressEvent) {
+    let _ = index_progress_sender().send(event);
+}
+
+/// 规范化项目根目录：解析为绝对路径并统一使用 `/` 分隔，作为 projects.json 与 projects_status.json 的键
+pub(crate) fn normalize_project_root(project_root_path: &str) -> String {
+    PathBuf::from(project_root_path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(project_root_path))
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// 只执行索引更新，不进行搜索
 /// 返回值：成功上传的 blob 名称列表
-pub(crate) async fn update_index(config: &AcemcpConfig, project_root_path: &str) -> anyhow::Result<Vec<String>> {
-    let base_url = config.base_url.clone().ok_or_else(|| anyhow::anyhow!("未配置 base_url"))?;
+pub async fn update_index(config: &AcemcpConfig, project_root_path: &str) -> anyhow::Result<Vec<String>> {
+    update_index_with_outcome(config, project_root_path)
+        .await
+        .map(|(blob_names, _)| blob_names)
+}
+
+/// 计算项目命名空间，随上传与检索请求发送，供共享服务端隔离不同用户的索引
+/// 优先使用配置的 `project_namespace`；否则配置了 `project_namespace_salt` 时取规范化根路径加盐的哈希；
+/// 两者都未配置时返回 None，请求中不携带命名空间
+fn project_namespace(config: &AcemcpConfig, normalized_root: &str) -> Option<String> {
+    if let Some(ns) = config.project_namespace.as_deref().map(str::trim).filter(|ns| !ns.is_empty()) {
+        return Some(ns.to_string());
+    }
+    let salt = config.project_namespace_salt.as_deref().filter(|salt| !salt.is_empty())?;
+    Some(sha256_hex(salt, normalized_root)[..16].to_string())
+}
+
+/// 在请求载荷中附加命名空间字段
+fn with_namespace(mut payload: serde_json::Value, namespace: Option<&str>) -> serde_json::Value {
+    if let (Some(ns), Some(map)) = (namespace, payload.as_object_mut()) {
+        map.insert("namespace".to_string(), serde_json::Value::String(ns.to_string()));
+    }
+    payload
+}
+
+/// 校验服务端为一个批次返回的 blob_names：提取其中的字符串名称（去重），
+/// 同时列出与上传批次不一致之处（数量不符、非字符串或空字符串、重复名称）
+fn check_batch_blob_names(returned: &[serde_json::Value], batch_len: usize) -> (Vec<String>, Vec<String>) {
+    let mut names: Vec<String> = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut invalid = 0;
+    let mut duplicated = 0;
+    for value in returned {
+        match value.as_str().filter(|s| !s.trim().is_empty()) {
+            Some(name) if seen.insert(name) => names.push(name.to_string()),
+            Some(_) => duplicated += 1,
+            None => invalid += 1,
+        }
+    }
+
+    let mut problems = Vec::new();
+    if returned.len() != batch_len {
+        problems.push(format!("上传 {} 个blob，返回 {} 个名称", batch_len, returned.len()));
+    }
+    if invalid > 0 {
+        problems.push(format!("{} 个名称不是有效字符串", invalid));
+    }
+    if duplicated > 0 {
+        problems.push(format!("{} 个名称重复", duplicated));
+    }
+    (names, problems)
+}
+
+/// 上传批次的幂等键请求头
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// 每个上传批次的 blob 数量
+fn upload_batch_size(config: &AcemcpConfig) -> usize {
+    (config.batch_size.unwrap_or(10) as usize).max(1)
+}
+
+/// 批量上传 blob，返回成功上传的 blob 名称与失败的批次编号（从 1 开始）
+/// 每个批次结束后以 (批次编号, 总批次数, 本批成功上传的 blob 名称) 调用 `on_batch_done`，
+/// 失败的批次名称列表为空；用于写入断点与推送进度
+/// 启用 `use_idempotency_keys` 时第 i 个批次使用 `idempotency_keys[i]`，未提供时为该批次新生成一个
+#[allow(clippy::too_many_arguments)]
+async fn upload_blobs(
+    client: &Client,
+    config: &AcemcpConfig,
+    base_url: &str,
+    new_blobs: &[BlobItem],
+    idempotency_keys: &[String],
+    namespace: Option<&str>,
+    req_id: &str,
+    on_batch_done: &mut (dyn FnMut(usize, usize, &[String]) + Send),
+) -> (Vec<String>, Vec<usize>) {
+    let mut uploaded_names: Vec<String> = Vec::new();
+    let mut failed_batches: Vec<usize> = Vec::new();
+
+    let batch_size = upload_batch_size(config);
+    let use_idempotency_keys = config.use_idempotency_keys.unwrap_or(false);
+    let encoding = ChunkEncoding::from_config(config.upload_chunk_encoding.as_deref());
+    let total_batches = (new_blobs.len() + batch_size - 1) / batch_size;
+    log_important!(info,
+        "[req_id={}] === 开始批量上传代码索引 ===", req_id
+    );
+    log_important!(info,
+        "[req_id={}] 目标端点: {}/batch-upload, 总批次: {}, 每批上限: {}, 总blobs: {}", req_id,
+        base_url,
+        total_batches,
+        batch_size,
+        new_blobs.len()
+    );
+    
+    for i in 0..total_batches {
+        let start = i * batch_size;
+        let end = usize::min(start + batch_size, new_blobs.len());
+        let batch = &new_blobs[start..end];
+        let url = format!("{}/batch-upload", base_url);
+        
+        log_important!(info,
+            "[req_id={}] 上传批次 {}/{}: url={}, blobs={}", req_id,
+            i + 1,
+            total_batches,
+            url,
+            batch.len()
+        );
+        
+        // 详细记录每个 blob 的信息
+        for (idx, blob) in batch.iter().enumerate() {
+            log_important!(info,
+                "[req_id={}]   批次 {} - Blob {}/{}: path={}, content_length={}", req_id,
+                i + 1,
+                idx + 1,
+                batch.len(),
+                blob.path,
+                blob.content.len()
+            );
+        }
+        
+        let blobs: Vec<serde_json::Value> = batch.iter().map(|b| upload_blob_payload(b, encoding)).collect();
+        let payload = with_namespace(serde_json::json!({"blobs": blobs}), namespace);
+        log_important!(info, "[req_id={}] 批次载荷大小: {} 字节", req_id, payload.to_string().len());
+
+        // 在重试循环之外确定 key，同一批次的每次重试都携带同一个 key
+        let idempotency_key = use_idempotency_keys.then(|| {
+            idempotency_keys.get(i).cloned().unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+        });
+        if let Some(key) = &idempotency_key {
+            log_debug!("[req_id={}] 批次 {} Idempotency-Key: {}", req_id, i + 1, key);
+        }
+        
+        match retry_request(|| async {
+            let r = send_with_token_refresh(config, req_id, |token| {
+                let builder = client
+                    .post(&url)
+                    .header(AUTHORIZATION, format!("Bearer {}", token))
+                    .header(CONTENT_TYPE, "application/json");
+                let builder = match &idempotency_key {
+                    Some(key) => builder.header(IDEMPOTENCY_KEY_HEADER, key),
+                    None => builder,
+                };
+                builder.json(&payload)
+            })
+            .await?;
+            
+            let status = r.status();
+            log_important!(info, "[req_id={}] HTTP响应状态: {}", req_id, status);
+            
+            if !status.is_success() {
+                let body = read_error_body(r, max_response_bytes(config)).await;
+                anyhow::bail!(AcemcpError::from_status(status, body));
+            }
+            
+            let v = read_json_limited(r, max_response_bytes(config)).await?;
+            log_important!(info, "[req_id={}] 响应数据: {}", req_id, serde_json::to_string_pretty(&v).unwrap_or_default());
+            Ok(v)
+        }, 3, 1.0, req_id, circuit_breaker::breaker_key(config, &url).as_deref()).await {
+            Ok(value) => {
+                record_endpoint_success(base_url);
+                if let Some(arr) = value.get("blob_names").and_then(|v| v.as_array()) {
+                    let (batch_names, problems) = check_batch_blob_names(arr, batch.len());
+                    if !problems.is_empty() {
+                        log_important!(warn, "[req_id={}] 批次 {} 返回的blob名称与上传内容不一致: {}", req_id, i + 1, problems.join("；"));
+                    }
+
+                    if !problems.is_empty() && config.strict_blob_names.unwrap_or(false) {
+                        log_important!(warn, "[req_id={}] 已启用 strict_blob_names，批次 {} 按失败处理", req_id, i + 1);
+                        failed_batches.push(i + 1);
+                        on_batch_done(i + 1, total_batches, &[]);
+                    } else if batch_names.is_empty() {
+                        log_important!(info, "[req_id={}] 批次 {} 返回了空的blob名称列表", req_id, i + 1);
+                        failed_batches.push(i + 1);
+                        on_batch_done(i + 1, total_batches, &[]);
+                    } else {
+                        uploaded_names.extend(batch_names.clone());
+                        on_batch_done(i + 1, total_batches, &batch_names);
+                        log_important!(info, "[req_id={}] 批次 {} 上传成功，获得 {} 个blob名称", req_id, i + 1, batch_names.len());
+                        // 详细记录每个上传成功的 blob 名称
+                        for (idx, name) in batch_names.iter().enumerate() {
+                            log_important!(info, "[req_id={}]   批次 {} - 上传成功 Blob {}/{}: name={}", req_id, i + 1, idx + 1, batch_names.len(), name);
+                        }
+                    }
+                } else {
+                    log_important!(info, "[req_id={}] 批次 {} 响应中缺少blob_names字段", req_id, i + 1);
+                    failed_batches.push(i + 1);
+                    on_batch_done(i + 1, total_batches, &[]);
+                }
+            }
+            Err(e) => {
+                log_important!(info, "[req_id={}] 批次 {} 上传失败: {}", req_id, i + 1, e);
+                failed_batches.push(i + 1);
+                on_batch_done(i + 1, total_batches, &[]);
+            }
+        }
+    }
+    
+    // 上传结果总结
+    log_important!(info,
+        "[req_id={}] === 上传结果总结 ===", req_id
+    );
+    if !failed_batches.is_empty() {
+        log_important!(info, "[req_id={}] 上传完成，但有失败的批次: {:?}, 成功上传blobs: {}", req_id, failed_batches, uploaded_names.len());
+    } else {
+        log_important!(info, "[req_id={}] 所有批次上传成功，共上传 {} 个blobs", req_id, uploaded_names.len());
+    }
+
+    (uploaded_names, failed_batches)
+}
+
+/// 执行索引更新，同时返回 blob 名称列表与本次索引的统计结果
+pub(crate) async fn update_index_with_outcome(config: &AcemcpConfig, project_root_path: &str) -> anyhow::Result<(Vec<String>, IndexOutcome)> {
+    let result = run_index_update(config, project_root_path).await;
+    let project_root = normalize_project_root(project_root_path);
+    match &result {
+        Ok((_, outcome)) => {
+            notify_index_webhook(config, project_root_path, outcome);
+            emit_index_progress(IndexProgressEvent::Completed { project_root, outcome: outcome.clone() })
+        }
+        Err(e) => emit_index_progress(IndexProgressEvent::Failed { project_root, error: e.to_string() }),
+    }
+    result
+}
+
+/// 配置了 `webhook_url` 时，在后台向其 POST 本次索引的结果；不重试、不等待，失败只记录日志
+fn notify_index_webhook(config: &AcemcpConfig, project_root_path: &str, outcome: &IndexOutcome) {
+    let Some(url) = config.webhook_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) else {
+        return;
+    };
+    let status = get_project_status(project_root_path);
+    let payload = serde_json::json!({
+        "project_root": status.project_root,
+        "indexed_files": status.indexed_files,
+        "uploaded_blobs": outcome.uploaded,
+        "duration_ms": outcome.duration_ms,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    let client = match upload_client(config) {
+        Ok(client) => client,
+        Err(e) => {
+            log_important!(warn, "索引完成通知发送失败: url={}, error={}", url, e);
+            return;
+        }
+    };
+    let mut request = client.post(url).header(CONTENT_TYPE, "application/json");
+    for (name, value) in config.webhook_headers.iter().flatten() {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let url = url.to_string();
+    tokio::spawn(async move {
+        match request.json(&payload).send().await {
+            Ok(r) if r.status().is_success() => log_debug!("索引完成通知已发送: {}", url),
+            Ok(r) => log_important!(warn, "索引完成通知被拒绝: url={}, status={}", url, r.status()),
+            Err(e) => log_important!(warn, "索引完成通知发送失败: url={}, error={}", url, e),
+        }
+    });
+}
+
+/// 最近一次成功请求服务端的时间在该时长内时，跳过上传前的连通性探测
+const PROBE_CACHE_TTL: Duration = Duration::from_secs(300);
+/// 连通性探测的请求超时
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 规范化的 base_url -> 最近一次成功请求的时间
+static ENDPOINT_SUCCESS: OnceLock<Mutex<HashMap<String, std::time::Instant>>> = OnceLock::new();
+
+/// 记录一次对服务端的成功请求
+fn record_endpoint_success(base_url: &str) {
+    let slot = ENDPOINT_SUCCESS.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Ok(mut map) = slot.lock() {
+        map.insert(normalize_base_url(base_url), std::time::Instant::now());
+    }
+}
+
+/// 服务端最近（`PROBE_CACHE_TTL` 内）是否有成功的请求
+fn endpoint_recently_ok(base_url: &str) -> bool {
+    ENDPOINT_SUCCESS
+        .get()
+        .and_then(|slot| slot.lock().ok()?.get(&normalize_base_url(base_url)).copied())
+        .map(|at| at.elapsed() < PROBE_CACHE_TTL)
+        .unwrap_or(false)
+}
+
+/// 健康检查不可用时使用的最小检索请求（不返回任何内容），与设置页的“测试连接”一致
+pub(crate) fn probe_retrieval_payload() -> serde_json::Value {
+    serde_json::json!({
+        "information_request": "test",
+        "blobs": {"checkpoint_id": null, "added_blobs": [], "deleted_blobs": []},
+        "dialog": [],
+        "max_output_length": 0,
+        "disable_codebase_retrieval": false,
+        "enable_commit_retrieval": false,
+    })
+}
+
+/// 上传前的连通性探测（`preflight_probe`，默认开启）：先请求 `/health`，不可用时改用最小检索请求。
+/// token 无效、服务端不可达或 base_url 路径错误时返回明确的错误；
+/// 服务端暂时异常（429 / 5xx）不属于配置错误，交由上传阶段的重试处理
+async fn preflight_probe(config: &AcemcpConfig, base_url: &str, req_id: &str) -> Result<(), AcemcpError> {
+    if !config.preflight_probe.unwrap_or(true) {
+        return Ok(());
+    }
+    if endpoint_recently_ok(base_url) {
+        log_debug!("[req_id={}] 服务端最近请求成功，跳过连通性探测", req_id);
+        return Ok(());
+    }
+
+    let client = upload_client(config).map_err(|e| AcemcpError::config(e.to_string()))?;
+    let health_url = format!("{}/health", base_url);
+    let search_url = format!("{}/agents/codebase-retrieval", base_url);
+    let send = |url: &str, health: bool| {
+        let url = url.to_string();
+        let client = client.clone();
+        async move {
+            send_with_token_refresh(config, req_id, |token| {
+                let builder = if health {
+                    client.get(&url)
+                } else {
+                    client.post(&url).header(CONTENT_TYPE, "application/json").json(&probe_retrieval_payload())
+                };
+                builder.header(AUTHORIZATION, format!("Bearer {}", token)).timeout(PROBE_TIMEOUT)
+            })
+            .await
+        }
+    };
+    let unreachable = |e: anyhow::Error| match e.downcast::<AcemcpError>() {
+        Ok(AcemcpError::Network(msg)) | Ok(AcemcpError::Timeout(msg)) => {
+            AcemcpError::Network(format!("无法连接服务端 {}，请检查 base_url 与网络: {}", base_url, msg))
+        }
+        Ok(other) => other,
+        Err(e) => AcemcpError::Network(format!("无法连接服务端 {}: {}", base_url, e)),
+    };
+
+    let mut status = send(&health_url, true).await.map_err(unreachable)?.status();
+    if !status.is_success() && !matches!(status.as_u16(), 401 | 403) {
+        // 健康检查接口可能不存在，改用检索接口
+        status = send(&search_url, false).await.map_err(unreachable)?.status();
+    }
+
+    match status.as_u16() {
+        _ if status.is_success() => {
+            log_debug!("[req_id={}] 连通性探测成功: HTTP {}", req_id, status);
+            record_endpoint_success(base_url);
+            Ok(())
+        }
+        401 | 403 => Err(AcemcpError::Auth(format!("服务端拒绝了当前 token（HTTP {}），请检查 token 是否正确或已过期", status.as_u16()))),
+        404 | 405 => Err(AcemcpError::config(format!("服务端 {} 上未找到检索接口（HTTP {}），请检查 base_url 是否正确", base_url, status.as_u16()))),
+        _ => {
+            log_important!(warn, "[req_id={}] 连通性探测返回 HTTP {}，继续索引", req_id, status);
+            Ok(())
+        }
+    }
+}
+
+async fn run_index_update(config: &AcemcpConfig, project_root_path: &str) -> anyhow::Result<(Vec<String>, IndexOutcome)> {
+    let started_at = std::time::Instant::now();
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
     // 严格校验 base_url
     let has_scheme = base_url.starts_with("http://") || base_url.starts_with("https://");
     let has_host = base_url.trim().len() > "https://".len();
-    if !has_scheme || !has_host { anyhow::bail!("无效的 base_url，请填写完整的 http(s)://host[:port] 格式"); }
-    let token = config.token.clone().ok_or_else(|| anyhow::anyhow!("未配置 token"))?;
+    if !has_scheme || !has_host { anyhow::bail!(AcemcpError::config("无效的 base_url，请填写完整的 http(s)://host[:port] 格式")); }
+    // 提前校验 token 已配置（实际请求时使用 effective_token，以便 401 后自动刷新）
+    effective_token(config)?;
+    // 收集文件与上传之前先确认 token 与 base_url 可用，配置错误时立即失败
+    if let Err(e) = preflight_probe(config, &base_url, &req_id).await {
+        log_important!(warn, "[req_id={}] 连通性探测失败，取消索引: {}", req_id, e);
+        let _ = update_project_status(project_root_path, |status| {
+            status.status = IndexStatus::Failed;
+            status.last_error = Some(e.to_string());
+            status.last_failure_time = Some(chrono::Utc::now());
+        });
+        anyhow::bail!(e);
+    }
     let batch_size = config.batch_size.unwrap_or(10) as usize;
     let max_lines = config.max_lines_per_blob.unwrap_or(800) as usize;
     let text_exts = config.text_extensions.clone().unwrap_or_default();
     let exclude_patterns = config.exclude_patterns.clone().unwrap_or_default();
 
-    // 更新状态：开始索引
-    let _ = update_project_status(project_root_path, |status| {
-        status.status = IndexStatus::Indexing;
-        status.progress = 0;
-    });
+    // 更新状态：开始索引
+    let _ = update_project_status(project_root_path, |status| {
+        status.status = IndexStatus::Indexing;
+        status.progress = 0;
+    });
+
+    // 日志：基础配置
+    log_important!(info,
+        "[req_id={}] === 开始索引代码库 ===", req_id
+    );
+    log_important!(info,
+        "[req_id={}] Acemcp配置: base_url={}, batch_size={}, max_lines_per_blob={}, text_exts数量={}, exclude_patterns数量={}", req_id,
+        base_url,
+        batch_size,
+        max_lines,
+        text_exts.len(),
+        exclude_patterns.len()
+    );
+    log_important!(info,
+        "[req_id={}] 项目路径: {}", req_id, project_root_path
+    );
+
+    // 收集 blob（根据扩展名与排除规则，简化版 .gitignore 支持）
+    log_important!(info, "[req_id={}] 开始收集代码文件...", req_id);
+    let event_root = normalize_project_root(project_root_path);
+    emit_index_progress(IndexProgressEvent::CollectionStarted { project_root: event_root.clone() });
+    let mut opts = CollectOptions::from_config(config);
+    if let Some(since) = config.index_only_changed_since.as_deref().filter(|s| !s.trim().is_empty()) {
+        let since = parse_changed_since(since).map_err(|e| AcemcpError::config(format!("index_only_changed_since 配置无效: {}", e)))?;
+        log_important!(info, "[req_id={}] 只收集 {} 之后修改过的文件", req_id, since.to_rfc3339());
+        opts.changed_since = Some(since.into());
+    }
+    let (blobs, collect_stats) = collect_project_blobs_blocking(config, project_root_path, opts).await?;
+    emit_index_progress(IndexProgressEvent::CollectionDone { project_root: event_root, files: blobs.len() });
+    // 所有文件都早于修改时间下限时不算失败，沿用已有索引记录
+    if blobs.is_empty() && collect_stats.skipped_unchanged_files.is_empty() {
+        // 更新状态：失败
+        let _ = update_project_status(project_root_path, |status| {
+            status.status = IndexStatus::Failed;
+            status.last_error = Some("未在项目中找到可索引的文本文件".to_string());
+            status.last_failure_time = Some(chrono::Utc::now());
+        });
+        anyhow::bail!(AcemcpError::NoBlobs("未在项目中找到可索引的文本文件".to_string()));
+    }
+
+    // 更新状态：文件收集完成
+    let _ = update_project_status(project_root_path, |status| {
+        status.total_files = blobs.len();
+        status.progress = 20;
+    });
+
+    // 加载 projects.json
+    let projects_path = home_projects_file();
+    let mut projects: ProjectsFile = if projects_path.exists() {
+        let data = fs::read_to_string(&projects_path).unwrap_or_default();
+        serde_json::from_str(&data).unwrap_or_default()
+    } else { ProjectsFile::default() };
+
+    let normalized_root = normalize_project_root(project_root_path);
+    let existing_blob_names: std::collections::HashSet<String> = projects.0.get(&normalized_root).cloned().unwrap_or_default().into_iter().collect();
+
+    // 计算所有 blob 的哈希值，建立哈希到 blob 的映射
+    let mut blob_hash_map: std::collections::HashMap<String, BlobItem> = std::collections::HashMap::new();
+    for blob in &blobs {
+        let hash = sha256_hex(&blob.path, &blob.content);
+        blob_hash_map.insert(hash.clone(), blob.clone());
+    }
+
+    // 因修改时间早于下限而未收集的文件，保留其已有的 blob 记录
+    let previous_blob_paths = load_blob_paths(&normalized_root);
+    let skipped_unchanged: HashSet<&str> = collect_stats.skipped_unchanged_files.iter().map(String::as_str).collect();
+    let retained: Vec<String> = existing_blob_names
+        .iter()
+        .filter(|h| !blob_hash_map.contains_key(*h))
+        .filter(|h| previous_blob_paths.get(*h).map(|p| skipped_unchanged.contains(blob_source_path(p))).unwrap_or(false))
+        .cloned()
+        .collect();
+    if !retained.is_empty() {
+        log_important!(info, "[req_id={}] {} 个文件修改时间早于下限，保留其 {} 个已有 blobs", req_id, skipped_unchanged.len(), retained.len());
+    }
+
+    // 分离已存在和新增加的 blob（与 Python 版本保持一致）
+    let mut all_blob_hashes: std::collections::HashSet<String> = blob_hash_map.keys().cloned().collect();
+    all_blob_hashes.extend(retained);
+    let existing_hashes: std::collections::HashSet<String> = all_blob_hashes.intersection(&existing_blob_names).cloned().collect();
+    let new_hashes: std::collections::HashSet<String> = all_blob_hashes.difference(&existing_blob_names).cloned().collect();
+
+    // 与已记录的哈希集合完全一致：无需上传、无需重写 projects.json，仅刷新状态
+    if all_blob_hashes == existing_blob_names {
+        log_important!(info, "[req_id={}] 项目无变化，跳过上传: 共 {} 个 blobs", req_id, all_blob_hashes.len());
+        let indexed_bytes: u64 = blobs.iter().map(|b| b.content.len() as u64).sum();
+        let _ = update_project_status(project_root_path, |status| {
+            status.status = IndexStatus::Synced;
+            status.progress = 100;
+            status.indexed_files = blobs.len();
+            status.pending_files = 0;
+            status.last_success_time = Some(chrono::Utc::now());
+            status.last_error = None;
+            status.encoding_breakdown = collect_stats.encodings.clone();
+            status.indexed_bytes = indexed_bytes;
+            status.uploaded_bytes = 0;
+            let current: HashSet<&str> = blobs.iter().map(|b| blob_source_path(&b.path)).collect();
+            merge_compressible_files(&mut status.compressible_files, Vec::new(), &HashSet::new(), &current);
+        });
+        clear_upload_checkpoint(&normalized_root);
+
+        let blob_names = projects.0.get(&normalized_root).cloned().unwrap_or_default();
+        let outcome = IndexOutcome {
+            uploaded: 0,
+            skipped_existing: blob_names.len(),
+            skipped_small: collect_stats.skipped_small,
+            secrets_skipped: collect_stats.secrets_skipped,
+            secrets_redacted: collect_stats.secrets_redacted,
+            total_blobs: blob_names.len(),
+            failed_batches: Vec::new(),
+            duration_ms: started_at.elapsed().as_millis() as u64,
+            diff: IndexDiff::default(),
+        };
+        return Ok((blob_names, outcome));
+    }
+
+    // 读取上次中断留下的断点，跳过其中已上传的 blob
+    let mut checkpoint = load_upload_checkpoint(&normalized_root).unwrap_or_default();
+    checkpoint.uploaded_names.retain(|name| new_hashes.contains(name));
+    let resumed: HashSet<String> = checkpoint.uploaded_names.iter().cloned().collect();
+    if !resumed.is_empty() {
+        log_important!(info, "[req_id={}] 检测到上传断点（上次完成到批次 {}），跳过已上传的 {} 个blobs", req_id, checkpoint.last_batch, resumed.len());
+    }
+
+    // 需要上传的新 blob；按名称排序，使中断后重新索引时的批次划分保持一致
+    let mut pending_names: Vec<String> = new_hashes
+        .iter()
+        .filter(|h| !resumed.contains(*h) && blob_hash_map.contains_key(*h))
+        .cloned()
+        .collect();
+    pending_names.sort();
+    let new_blobs: Vec<BlobItem> = pending_names.iter().filter_map(|h| blob_hash_map.get(h).cloned()).collect();
+    // 压缩比只对本次需要上传的 blob 计算，未变更的文件沿用上次的结果
+    let fresh_compressible = find_compressible_files(&new_blobs, &req_id);
+
+    // 在上传前写入断点，进程在请求发出后、收到响应前退出时下次仍使用相同的 key
+    let idempotency_keys = if config.use_idempotency_keys.unwrap_or(false) && !new_blobs.is_empty() {
+        let keys = assign_idempotency_keys(&mut checkpoint, &pending_names, upload_batch_size(config));
+        if let Err(e) = save_upload_checkpoint(&normalized_root, &checkpoint) {
+            log_debug!("[req_id={}] 写入上传断点失败: {}", req_id, e);
+        }
+        keys
+    } else {
+        Vec::new()
+    };
+
+    log_important!(info,
+        "[req_id={}] === 索引统计 ===", req_id
+    );
+    log_important!(info,
+        "[req_id={}] 收集到blobs总数: {}, 既有blobs: {}, 新增blobs: {}, 需要上传: {}", req_id,
+        blobs.len(),
+        existing_hashes.len(),
+        new_hashes.len(),
+        new_blobs.len()
+    );
+
+    let client = upload_client(config)?;
+
+    // 批量上传新增 blobs
+    let (mut uploaded_names, failed_batches) = if !new_blobs.is_empty() {
+        let mut on_batch_done = |batch: usize, total: usize, names: &[String]| {
+            if !names.is_empty() {
+                checkpoint.last_batch = batch;
+                checkpoint.uploaded_names.extend_from_slice(names);
+                if let Err(e) = save_upload_checkpoint(&normalized_root, &checkpoint) {
+                    log_debug!("[req_id={}] 写入上传断点失败: {}", req_id, e);
+                }
+            }
+            // 上传阶段占 20% ~ 95% 的进度
+            let _ = update_project_status(project_root_path, |status| {
+                status.progress = (20 + batch * 75 / total.max(1)) as u8;
+            });
+            emit_index_progress(IndexProgressEvent::BatchDone {
+                project_root: normalized_root.clone(),
+                index: batch,
+                total,
+                success: !names.is_empty(),
+            });
+        };
+        let namespace = project_namespace(config, &normalized_root);
+        upload_blobs(&client, config, &base_url, &new_blobs, &idempotency_keys, namespace.as_deref(), &req_id, &mut on_batch_done).await
+    } else {
+        log_important!(info, "[req_id={}] 没有新的blob需要上传，使用已有索引", req_id);
+        (Vec::new(), Vec::new())
+    };
+    // 断点中记录的 blob 已在之前的运行中上传成功，一并计入
+    uploaded_names.extend(resumed);
+
+    let uploaded_count = uploaded_names.len();
+    let skipped_existing = existing_hashes.len();
+    let indexed_bytes: u64 = blobs.iter().map(|b| b.content.len() as u64).sum();
+    let uploaded_bytes: u64 = uploaded_names
+        .iter()
+        .filter_map(|name| blob_hash_map.get(name))
+        .map(|b| b.content.len() as u64)
+        .sum();
+
+    // 合并并保存 projects.json（与 Python 版本保持一致）
+    // 只保留当前项目中仍然存在的 blob 的哈希值（自动删除已删除的 blob）
+    let all_blob_names: Vec<String> = existing_hashes.into_iter().chain(uploaded_names.into_iter()).collect();
+    projects.0.insert(normalized_root.clone(), all_blob_names.clone());
+    save_projects_file(&projects)?;
+    clear_upload_checkpoint(&normalized_root);
+
+    // 同步保存 blob 路径映射，供局部重建索引时识别旧 blob
+    let blob_paths: HashMap<String, String> = all_blob_names
+        .iter()
+        .filter_map(|h| {
+            let path = blob_hash_map.get(h).map(|b| b.path.clone()).or_else(|| previous_blob_paths.get(h).cloned())?;
+            Some((h.clone(), path))
+        })
+        .collect();
+    let diff = compute_index_diff(&previous_blob_paths, &blob_paths);
+    log_index_diff(&req_id, &diff);
+    if let Err(e) = save_blob_paths(&normalized_root, &blob_paths) {
+        log_debug!("[req_id={}] 保存 blob 路径映射失败: {}", req_id, e);
+    }
+
+    // 使用合并后的 blob_names（与 Python 版本保持一致）
+    let blob_names = all_blob_names;
+    if blob_names.is_empty() {
+        log_important!(info, "[req_id={}] 索引后未找到 blobs，项目路径: {}", req_id, normalized_root);
+        // 更新状态：失败
+        let _ = update_project_status(project_root_path, |status| {
+            status.status = IndexStatus::Failed;
+            status.last_error = Some("索引后未找到 blobs".to_string());
+            status.last_failure_time = Some(chrono::Utc::now());
+        });
+        anyhow::bail!(AcemcpError::NoBlobs("索引后未找到 blobs".to_string()));
+    }
+
+    // 检查是否是首次成功索引（用于 ji 集成）
+    let is_first_success = {
+        let status = get_project_status(project_root_path);
+        status.last_success_time.is_none()
+    };
+
+    // 更新状态：索引成功完成
+    let _ = update_project_status(project_root_path, |status| {
+        status.status = IndexStatus::Synced;
+        status.progress = 100;
+        status.indexed_files = blobs.len();
+        status.pending_files = 0;
+        status.last_success_time = Some(chrono::Utc::now());
+        status.last_error = None;
+        status.encoding_breakdown = collect_stats.encodings.clone();
+        status.indexed_bytes = indexed_bytes;
+        status.uploaded_bytes = uploaded_bytes;
+        let current: HashSet<&str> = blobs.iter().map(|b| blob_source_path(&b.path)).collect();
+        let uploaded: HashSet<&str> = new_blobs.iter().map(|b| blob_source_path(&b.path)).collect();
+        merge_compressible_files(&mut status.compressible_files, fresh_compressible, &uploaded, &current);
+    });
+
+    // 首次成功索引时，写入 ji 记忆
+    if is_first_success {
+        write_index_memory_to_ji(project_root_path, config).await;
+    }
+
+    let outcome = IndexOutcome {
+        uploaded: uploaded_count,
+        skipped_existing,
+        skipped_small: collect_stats.skipped_small,
+        secrets_skipped: collect_stats.secrets_skipped,
+        secrets_redacted: collect_stats.secrets_redacted,
+        total_blobs: blob_names.len(),
+        failed_batches,
+        duration_ms: started_at.elapsed().as_millis() as u64,
+        diff,
+    };
+
+    log_important!(info, "[req_id={}] 索引更新完成，共 {} 个 blobs，耗时 {}ms", req_id, blob_names.len(), outcome.duration_ms);
+    Ok((blob_names, outcome))
+}
+
+/// 仅对匹配 `glob` 的文件重建索引
+///
+/// 仍遵循排除规则与 .gitignore；与 projects.json 中已记录的哈希比对后只上传差异部分。
+/// 匹配路径下已失效的旧 blob（文件修改或删除）会从记录中移除，其他路径的记录保持不变。
+pub(crate) async fn reindex_glob(config: &AcemcpConfig, project_root_path: &str, glob: &str) -> anyhow::Result<IndexOutcome> {
+    let started_at = std::time::Instant::now();
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    effective_token(config)?;
+
+    log_important!(info, "[req_id={}] === 开始局部重建索引 === 项目路径: {}, glob: {}", req_id, project_root_path, glob);
+
+    let mut opts = CollectOptions::from_config(config);
+    let glob_matcher = TextExtMatcher::new(&[glob.to_string()], opts.case_insensitive);
+    opts.only_paths = Some(glob_matcher.clone());
+    let (blobs, collect_stats) = collect_project_blobs_blocking(config, project_root_path, opts).await?;
+
+    let _ = update_project_status(project_root_path, |status| {
+        status.status = IndexStatus::Indexing;
+        status.progress = 20;
+    });
+
+    let normalized_root = normalize_project_root(project_root_path);
+    let mut projects = load_projects_file();
+    let stored: Vec<String> = projects.0.get(&normalized_root).cloned().unwrap_or_default();
+    let stored_set: HashSet<String> = stored.iter().cloned().collect();
+    let mut blob_paths = load_blob_paths(&normalized_root);
+
+    let blob_hash_map: HashMap<String, BlobItem> = blobs
+        .iter()
+        .map(|b| (sha256_hex(&b.path, &b.content), b.clone()))
+        .collect();
+
+    // 已记录但不再是当前内容、且属于匹配路径的旧 blob
+    let stale: HashSet<String> = stored
+        .iter()
+        .filter(|h| !blob_hash_map.contains_key(*h))
+        .filter(|h| {
+            blob_paths.get(*h).map(|p| {
+                let src = blob_source_path(p);
+                glob_matcher.matches(Path::new(src), src)
+            }).unwrap_or(false)
+        })
+        .cloned()
+        .collect();
+
+    let new_blobs: Vec<BlobItem> = blob_hash_map
+        .iter()
+        .filter(|(h, _)| !stored_set.contains(*h))
+        .map(|(_, b)| b.clone())
+        .collect();
+    let skipped_existing = blob_hash_map.len() - new_blobs.len();
+
+    log_important!(info,
+        "[req_id={}] 匹配blobs: {}, 既有blobs: {}, 需要上传: {}, 失效旧blobs: {}", req_id,
+        blob_hash_map.len(), skipped_existing, new_blobs.len(), stale.len()
+    );
+
+    let (uploaded_names, failed_batches) = if new_blobs.is_empty() {
+        (Vec::new(), Vec::new())
+    } else {
+        let client = upload_client(config)?;
+        let namespace = project_namespace(config, &normalized_root);
+        upload_blobs(&client, config, &base_url, &new_blobs, &[], namespace.as_deref(), &req_id, &mut |_, _, _| {}).await
+    };
+
+    let mut blob_names: Vec<String> = stored.into_iter().filter(|h| !stale.contains(h)).collect();
+    let mut seen: HashSet<String> = blob_names.iter().cloned().collect();
+    for name in &uploaded_names {
+        if seen.insert(name.clone()) {
+            blob_names.push(name.clone());
+        }
+    }
+    projects.0.insert(normalized_root.clone(), blob_names.clone());
+    save_projects_file(&projects)?;
+
+    let previous_blob_paths = blob_paths.clone();
+    for h in &stale {
+        blob_paths.remove(h);
+    }
+    for name in &uploaded_names {
+        if let Some(b) = blob_hash_map.get(name) {
+            blob_paths.insert(name.clone(), b.path.clone());
+        }
+    }
+    if let Err(e) = save_blob_paths(&normalized_root, &blob_paths) {
+        log_debug!("[req_id={}] 保存 blob 路径映射失败: {}", req_id, e);
+    }
+    let diff = compute_index_diff(&previous_blob_paths, &blob_paths);
+    log_index_diff(&req_id, &diff);
+
+    // 与全量索引一致地写入索引状态；文件数只统计匹配的文件，不覆盖全量索引记录的 indexed_files
+    let _ = update_project_status(project_root_path, |status| {
+        status.status = IndexStatus::Synced;
+        status.progress = 100;
+        status.last_success_time = Some(chrono::Utc::now());
+        status.last_error = None;
+    });
+
+    let outcome = IndexOutcome {
+        uploaded: uploaded_names.len(),
+        skipped_existing,
+        skipped_small: collect_stats.skipped_small,
+        secrets_skipped: collect_stats.secrets_skipped,
+        secrets_redacted: collect_stats.secrets_redacted,
+        total_blobs: blob_names.len(),
+        failed_batches,
+        duration_ms: started_at.elapsed().as_millis() as u64,
+        diff,
+    };
+
+    log_important!(info, "[req_id={}] 局部重建索引完成，上传 {} 个 blobs，项目共 {} 个 blobs，耗时 {}ms", req_id, outcome.uploaded, outcome.total_blobs, outcome.duration_ms);
+    Ok(outcome)
+}
+
+/// 每个删除请求携带的 blob 名称数量上限
+const DELETE_BATCH_SIZE: usize = 500;
+
+/// 删除项目在服务端的所有 blob，并清除本地的 projects.json 记录、索引状态与 blob 路径映射
+///
+/// 返回服务端确认删除的 blob 数量。本地没有该项目的记录时直接返回 0，不发起请求，因此重复执行是安全的；
+/// 有批次在重试后仍失败时保留本地记录并返回错误，重新执行即可继续删除
+pub async fn delete_remote_index_with_config(config: &AcemcpConfig, project_root_path: &str) -> anyhow::Result<usize> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let normalized_root = normalize_project_root(project_root_path);
+    let mut projects = load_projects_file();
+    let blob_names: Vec<String> = projects.0.get(&normalized_root).cloned().unwrap_or_default();
+
+    let mut confirmed = 0;
+    if blob_names.is_empty() {
+        log_important!(info, "[req_id={}] 项目在本地没有索引记录，无需删除: {}", req_id, normalized_root);
+    } else {
+        let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+        effective_token(config)?;
+        let url = format!("{}/batch-delete", base_url);
+        let namespace = project_namespace(config, &normalized_root);
+        let client = upload_client(config)?;
+        let total_batches = blob_names.len().div_ceil(DELETE_BATCH_SIZE);
+        log_important!(info, "[req_id={}] === 开始删除服务端索引 === 项目: {}, blobs: {}, 批次: {}", req_id, normalized_root, blob_names.len(), total_batches);
+
+        let mut failed_batches: Vec<usize> = Vec::new();
+        for (i, batch) in blob_names.chunks(DELETE_BATCH_SIZE).enumerate() {
+            let payload = with_namespace(serde_json::json!({"blob_names": batch}), namespace.as_deref());
+            let result = retry_request(|| async {
+                let r = send_with_token_refresh(config, &req_id, |token| {
+                    client
+                        .post(&url)
+                        .header(AUTHORIZATION, format!("Bearer {}", token))
+                        .header(CONTENT_TYPE, "application/json")
+                        .json(&payload)
+                })
+                .await?;
+                let status = r.status();
+                if !status.is_success() {
+                    let body = read_error_body(r, max_response_bytes(config)).await;
+                    anyhow::bail!(AcemcpError::from_status(status, body));
+                }
+                read_json_limited(r, max_response_bytes(config)).await
+            }, 3, 1.0, &req_id, circuit_breaker::breaker_key(config, &url).as_deref()).await;
+
+            match result {
+                Ok(value) => {
+                    // 服务端在 blob_names 中返回确认删除的名称，已不存在的 blob 可能不在其中
+                    let batch_confirmed = value.get("blob_names").and_then(|v| v.as_array()).map(Vec::len).unwrap_or(0);
+                    confirmed += batch_confirmed;
+                    log_important!(info, "[req_id={}] 删除批次 {}/{} 完成: 请求 {} 个，确认 {} 个", req_id, i + 1, total_batches, batch.len(), batch_confirmed);
+                }
+                Err(e) => {
+                    log_important!(warn, "[req_id={}] 删除批次 {}/{} 失败: {}", req_id, i + 1, total_batches, e);
+                    failed_batches.push(i + 1);
+                }
+            }
+        }
+
+        log_important!(info, "[req_id={}] 服务端删除结束: 请求删除 {} 个 blobs，服务端确认 {} 个", req_id, blob_names.len(), confirmed);
+        if !failed_batches.is_empty() {
+            anyhow::bail!("删除服务端索引时批次 {:?} 失败，本地记录已保留，可重新执行删除", failed_batches);
+        }
+    }
+
+    if projects.0.remove(&normalized_root).is_some() {
+        save_projects_file(&projects)?;
+    }
+    let mut all_status = load_projects_status();
+    if all_status.projects.remove(&normalized_root).is_some() {
+        save_projects_status(&all_status)?;
+    }
+    let _ = fs::remove_file(home_project_data_dir(&normalized_root).join("blob_paths.json"));
+    clear_upload_checkpoint(&normalized_root);
+
+    Ok(confirmed)
+}
+
+/// 将索引配置信息写入 ji（记忆）工具
+async fn write_index_memory_to_ji(project_root_path: &str, config: &AcemcpConfig) {
+    use super::super::memory::shared_memory_store;
+    use super::super::memory::MemoryCategory;
+
+    // 获取共享的记忆存储（与 ji 工具共用同一把锁）
+    let store = match shared_memory_store(project_root_path).await {
+        Ok(m) => m,
+        Err(e) => {
+            log_debug!("创建记忆管理器失败（不影响索引）: {}", e);
+            return;
+        }
+    };
+
+    // 构建记忆内容
+    let text_exts = config.text_extensions.clone().unwrap_or_default();
+    let exclude_patterns = config.exclude_patterns.clone().unwrap_or_default();
+    let batch_size = config.batch_size.unwrap_or(10);
+    let max_lines = config.max_lines_per_blob.unwrap_or(800);
+
+    let memory_content = format!(
+        "acemcp 代码索引已启用 - 配置摘要: 文件扩展名={:?}, 排除模式={:?}, 批次大小={}, 最大行数/块={}",
+        text_exts, exclude_patterns, batch_size, max_lines
+    );
+
+    // 写入记忆
+    let manager = store.write().await;
+    match manager.add_memory(&memory_content, MemoryCategory::Context) {
+        Ok(id) => {
+            log_important!(info, "已将索引配置写入 ji 记忆: id={}", id);
+        }
+        Err(e) => {
+            log_debug!("写入 ji 记忆失败（不影响索引）: {}", e);
+        }
+    }
+}
+
+/// 规范化调用方传入的文件路径，便于与 blob 路径比较
+fn normalize_rel_path(path: &str) -> String {
+    let path = path.trim().replace('\\', "/");
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    blob_source_path(path).to_string()
+}
+
+/// 去掉源文件属于 `exclude_paths` 的 blob；路径映射中没有记录的 blob 保留
+fn exclude_blobs_by_path(blob_names: Vec<String>, blob_paths: &HashMap<String, String>, exclude_paths: &[String]) -> Vec<String> {
+    let excluded: HashSet<String> = exclude_paths.iter().map(|p| normalize_rel_path(p)).collect();
+    blob_names
+        .into_iter()
+        .filter(|hash| {
+            blob_paths
+                .get(hash)
+                .map(|p| !excluded.contains(blob_source_path(p)))
+                .unwrap_or(true)
+        })
+        .collect()
+}
+
+/// 搜索结果的数量与长度限制、是否在本地重排、是否合并相邻结果，以及输出格式
+#[derive(Debug, Clone, Copy)]
+struct SearchLimits {
+    top_k: Option<usize>,
+    max_output_length: usize,
+    local_rerank: bool,
+    output_format: OutputFormat,
+    /// 合并同一文件中行区间相邻或重叠的结构化结果
+    merge_hits: bool,
+}
+
+impl SearchLimits {
+    /// 请求未指定 `max_output_length` 时使用配置的 `search_result_max_length`（再缺省为 20000）；
+    /// 配置了 `search_result_max_length` 时它同时是上限，请求只能要求更短的结果
+    fn from_request(request: &AcemcpRequest, config: &AcemcpConfig) -> Self {
+        let configured = config.search_result_max_length.filter(|n| *n > 0);
+        let requested = request.max_output_length.filter(|n| *n > 0);
+        let max_output_length = match (requested, configured) {
+            (Some(requested), Some(configured)) => requested.min(configured),
+            (requested, configured) => requested.or(configured).unwrap_or(DEFAULT_SEARCH_MAX_OUTPUT_LENGTH),
+        };
+        Self {
+            top_k: request.top_k.filter(|k| *k > 0),
+            local_rerank: request.local_rerank,
+            output_format: request.output_format,
+            merge_hits: config.merge_adjacent_hits.unwrap_or(false),
+            max_output_length,
+        }
+    }
+}
+
+/// 将检索响应整理为返回给调用方的文本
+/// 服务端返回结构化的 `hits` 数组且设置了 `top_k` 时只保留前 k 条并提示剩余数量，
+/// 否则使用 `formatted_retrieval`；最终文本按 `max_output_length` 截断。
+/// markdown 格式在截断后将各片段包裹为代码块，json 格式见 `format_retrieval_json`
+fn format_retrieval(value: &serde_json::Value, limits: SearchLimits) -> String {
+    match limits.output_format {
+        OutputFormat::Json => return format_retrieval_json(value, limits),
+        OutputFormat::Citations => return format_retrieval_citations(value, limits),
+        OutputFormat::Plain | OutputFormat::Markdown => {}
+    }
+    let hits = structured_hits(value, limits);
+    let mut text = match (limits.top_k, hits.as_deref()) {
+        (Some(k), Some(hits)) => {
+            let mut parts: Vec<&str> = hits
+                .iter()
+                .take(k)
+                .filter_map(|h| h.get("formatted").or_else(|| h.get("text")).and_then(|t| t.as_str()))
+                .collect();
+            let remaining = hits.len().saturating_sub(k);
+            let note = format!("（另有 {} 条结果未显示，可增大 top_k 获取）", remaining);
+            if remaining > 0 {
+                parts.push(&note);
+            }
+            parts.join("\n\n")
+        }
+        _ => value
+            .get("formatted_retrieval")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+    };
+
+    if text.is_empty() {
+        return NO_RETRIEVAL_RESULT.to_string();
+    }
+
+    let truncated = text.chars().count() > limits.max_output_length;
+    if truncated {
+        text = truncate_at_line_boundary(&text, limits.max_output_length).to_string();
+    }
+    if limits.output_format == OutputFormat::Markdown {
+        text = markdown_retrieval(&text);
+    }
+    if truncated {
+        text.push_str(&truncation_note(limits.max_output_length));
+    }
+    text
+}
+
+/// 截取不超过 `max_chars` 个字符的前缀，在最后一个完整行的末尾截断；第一行就超过上限时按字符截断
+fn truncate_at_line_boundary(text: &str, max_chars: usize) -> &str {
+    let end = match text.char_indices().nth(max_chars) {
+        Some((i, _)) => i,
+        None => return text,
+    };
+    let head = &text[..end];
+    // 截断点恰好落在换行符上时，前缀本身已是完整的行
+    if text[end..].starts_with('\n') {
+        return head;
+    }
+    match head.rfind('\n') {
+        Some(pos) if pos > 0 => &head[..pos],
+        _ => head,
+    }
+}
+
+fn truncation_note(max_output_length: usize) -> String {
+    format!("\n\n{}（结果已截断至 {} 字符以内，可增大 max_output_length 获取更多）", TRUNCATION_MARKER, max_output_length)
+}
+
+/// 服务端返回的结构化 `hits`；启用 `merge_adjacent_hits` 时先合并同一文件的相邻结果
+fn structured_hits(value: &serde_json::Value, limits: SearchLimits) -> Option<Cow<'_, [serde_json::Value]>> {
+    let hits = value.get("hits").and_then(|v| v.as_array())?;
+    if limits.merge_hits {
+        Some(Cow::Owned(merge_adjacent_hits(hits)))
+    } else {
+        Some(Cow::Borrowed(hits.as_slice()))
+    }
+}
+
+/// 解析 `#L<起始行>-<结束行>` 命名的 blob 路径，返回 (源文件路径, 起始行, 结束行)
+fn blob_line_range(blob_path: &str) -> Option<(&str, usize, usize)> {
+    let (source, suffix) = blob_path.rsplit_once('#')?;
+    let (start, end) = suffix.strip_prefix('L')?.split_once('-')?;
+    let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+    if start == 0 || end < start {
+        return None;
+    }
+    Some((source, start, end))
+}
+
+/// 结构化结果中可合并的片段：来自 `#L<起始行>-<结束行>` 命名的分块
+struct HitSpan {
+    /// 在服务端结果中的位置，合并后取组内最靠前的位置
+    index: usize,
+    source: String,
+    start: usize,
+    end: usize,
+    body: String,
+    score: Option<f64>,
+}
+
+/// 解析结果对应的 blob 路径与内容：优先使用 `path` 字段，否则取文本首行的 `Path: `；
+/// 只有按行区间命名的分块可以合并，其它结果返回 None
+fn hit_span(index: usize, hit: &serde_json::Value) -> Option<HitSpan> {
+    let text = hit.get("formatted").or_else(|| hit.get("text")).and_then(|t| t.as_str())?;
+    let (first_line, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let (blob_path, body) = match hit.get("path").and_then(|p| p.as_str()) {
+        Some(path) if first_line.strip_prefix("Path: ").map(str::trim) == Some(path) => (path, rest),
+        Some(path) => (path, text),
+        None => (first_line.strip_prefix("Path: ")?.trim(), rest),
+    };
+    let (source, start, end) = blob_line_range(blob_path)?;
+    Some(HitSpan {
+        index,
+        source: source.to_string(),
+        start,
+        end,
+        body: body.trim_end_matches('\n').to_string(),
+        score: hit.get("score").and_then(|s| s.as_f64()),
+    })
+}
+
+/// 合并同一文件中行区间相邻或重叠的结果：内容按行拼接（重叠的行只保留一次），得分取最高值，
+/// 合并后的结果位于组内排名最靠前的结果处；无法解析行区间的结果原样保留
+fn merge_adjacent_hits(hits: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    let mut passthrough: Vec<(usize, serde_json::Value)> = Vec::new();
+    let mut by_file: std::collections::BTreeMap<String, Vec<HitSpan>> = std::collections::BTreeMap::new();
+    for (index, hit) in hits.iter().enumerate() {
+        match hit_span(index, hit) {
+            Some(span) => by_file.entry(span.source.clone()).or_default().push(span),
+            None => passthrough.push((index, hit.clone())),
+        }
+    }
+
+    let mut merged: Vec<(usize, serde_json::Value)> = passthrough;
+    for (_, mut spans) in by_file {
+        spans.sort_by_key(|s| (s.start, s.end));
+        let mut groups: Vec<(HitSpan, usize)> = Vec::new();
+        for span in spans {
+            match groups.last_mut() {
+                Some((current, count)) if span.start <= current.end + 1 => {
+                    // 跳过与当前片段重叠的行；片段行数与区间不符时直接拼接
+                    let overlap = (current.end + 1).saturating_sub(span.start);
+                    let lines: Vec<&str> = span.body.lines().collect();
+                    if span.end > current.end {
+                        let tail = if lines.len() == span.end - span.start + 1 { lines[overlap.min(lines.len())..].join("\n") } else { span.body.clone() };
+                        if !tail.is_empty() {
+                            current.body.push('\n');
+                            current.body.push_str(&tail);
+                        }
+                        current.end = span.end;
+                    }
+                    current.index = current.index.min(span.index);
+                    current.score = match (current.score, span.score) {
+                        (Some(a), Some(b)) => Some(a.max(b)),
+                        (a, b) => a.or(b),
+                    };
+                    *count += 1;
+                }
+                _ => groups.push((span, 1)),
+            }
+        }
+        for (span, count) in groups {
+            let base = &hits[span.index];
+            if count == 1 {
+                merged.push((span.index, base.clone()));
+                continue;
+            }
+            let path = format!("{}#L{}-{}", span.source, span.start, span.end);
+            let mut hit = base.clone();
+            if let Some(obj) = hit.as_object_mut() {
+                obj.remove("text");
+                obj.insert("formatted".to_string(), serde_json::Value::String(format!("Path: {}\n{}", path, span.body)));
+                obj.insert("path".to_string(), serde_json::Value::String(path));
+                if let Some(score) = span.score {
+                    obj.insert("score".to_string(), serde_json::json!(score));
+                }
+            }
+            merged.push((span.index, hit));
+        }
+    }
+
+    merged.sort_by_key(|(index, _)| *index);
+    merged.into_iter().map(|(_, hit)| hit).collect()
+}
+
+/// 按文件扩展名推断代码块的语言标注，无法识别时为空
+fn code_fence_language(path: &str) -> &'static str {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" => "typescript",
+        "tsx" => "tsx",
+        "vue" => "vue",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        "sh" | "bash" | "zsh" => "bash",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" => "markdown",
+        _ => "",
+    }
+}
+
+/// 将按 `Path: ` 行分隔的检索文本转换为 markdown：路径行保留在代码块之外（本地重排得分行同样保留），
+/// 片段内容包裹在按扩展名标注语言的代码块中；没有可识别的片段时原样返回
+fn markdown_retrieval(text: &str) -> String {
+    let (header, snippets) = super::rerank::split_snippets(text);
+    if snippets.is_empty() {
+        return text.to_string();
+    }
+
+    let mut out = header;
+    for snippet in &snippets {
+        let (path_line, mut body) = snippet.split_once('\n').unwrap_or((snippet.as_str(), ""));
+        out.push_str(path_line);
+        out.push('\n');
+        if let Some((score_line, rest)) = body.split_once('\n').filter(|(line, _)| line.starts_with("（本地重排得分")) {
+            out.push_str(score_line);
+            out.push('\n');
+            body = rest;
+        }
+        // 片段本身含有 ``` 时使用更长的围栏，避免提前闭合
+        let fence = if body.contains("```") { "````" } else { "```" };
+        let lang = super::rerank::snippet_path(snippet).map(code_fence_language).unwrap_or("");
+        out.push_str(&format!("{}{}\n{}\n{}\n\n", fence, lang, body.trim_end_matches('\n'), fence));
+    }
+    out.trim_end().to_string()
+}
+
+/// json 格式：服务端返回 `hits` 时直接使用，否则将 `formatted_retrieval` 按片段拆分为 `{path, content}`；
+/// 设置 `top_k` 时只保留前 k 条，序列化后超过 `max_output_length` 时从末尾逐条移除并标记 `truncated`
+fn format_retrieval_json(value: &serde_json::Value, limits: SearchLimits) -> String {
+    let all_hits: Vec<serde_json::Value> = match structured_hits(value, limits) {
+        Some(hits) => hits.into_owned(),
+        None => {
+            let formatted = value.get("formatted_retrieval").and_then(|v| v.as_str()).unwrap_or("");
+            super::rerank::split_snippets(formatted)
+                .1
+                .iter()
+                .map(|snippet| {
+                    let content = snippet.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+                    serde_json::json!({
+                        "path": super::rerank::snippet_path(snippet).unwrap_or(""),
+                        "content": content.trim_end_matches('\n'),
+                    })
+                })
+                .collect()
+        }
+    };
+
+    let total = all_hits.len();
+    let mut hits: Vec<serde_json::Value> = all_hits.into_iter().take(limits.top_k.unwrap_or(usize::MAX)).collect();
+    let mut truncated = false;
+    loop {
+        let text = serde_json::json!({ "hits": hits, "total": total, "truncated": truncated }).to_string();
+        if text.chars().count() <= limits.max_output_length || hits.is_empty() {
+            return text;
+        }
+        hits.pop();
+        truncated = true;
+    }
+}
+
+/// citations 格式：按结果顺序（即相关度）列出去重后的 `路径:起始行-结束行`，每行一条，不含片段内容；
+/// 不是按行区间命名的分块（如 `chunk_naming: legacy`）只列出文件路径。`top_k` 限制条数，超长时按行截断
+fn format_retrieval_citations(value: &serde_json::Value, limits: SearchLimits) -> String {
+    let blob_paths: Vec<String> = match structured_hits(value, limits) {
+        Some(hits) => hits
+            .iter()
+            .filter_map(|hit| {
+                let path = hit.get("path").and_then(|p| p.as_str()).map(str::to_string);
+                path.or_else(|| {
+                    let text = hit.get("formatted").or_else(|| hit.get("text")).and_then(|t| t.as_str())?;
+                    Some(text.lines().next()?.strip_prefix("Path: ")?.trim().to_string())
+                })
+            })
+            .collect(),
+        None => {
+            let formatted = value.get("formatted_retrieval").and_then(|v| v.as_str()).unwrap_or("");
+            super::rerank::split_snippets(formatted)
+                .1
+                .iter()
+                .filter_map(|snippet| Some(snippet.lines().next()?.strip_prefix("Path: ")?.trim().to_string()))
+                .collect()
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let citations: Vec<String> = blob_paths
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| match blob_line_range(p) {
+            Some((source, start, end)) => format!("{}:{}-{}", source, start, end),
+            None => blob_source_path(p).to_string(),
+        })
+        .filter(|c| seen.insert(c.clone()))
+        .take(limits.top_k.unwrap_or(usize::MAX))
+        .collect();
+    if citations.is_empty() {
+        return NO_RETRIEVAL_RESULT.to_string();
+    }
+
+    let text = citations.join("\n");
+    if text.chars().count() > limits.max_output_length {
+        format!("{}{}", truncate_at_line_boundary(&text, limits.max_output_length), truncation_note(limits.max_output_length))
+    } else {
+        text
+    }
+}
+
+/// 只执行搜索，不触发索引
+/// 使用已有的索引数据进行搜索
+/// `exclude_paths` 中的文件对应的 blob 不参与本次检索
+async fn search_only(config: &AcemcpConfig, project_root_path: &str, query: &str, limits: SearchLimits, exclude_paths: &[String]) -> anyhow::Result<String> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    effective_token(config)?;
+
+    let (normalized_root, blob_names, payload) = build_search_payload(config, project_root_path, query, limits, exclude_paths, &req_id)?;
+
+    // 发起检索
+    log_important!(info,
+        "[req_id={}] === 开始代码检索（仅搜索模式） ===", req_id
+    );
+    let search_url = format!("{}/agents/codebase-retrieval", base_url);
+    log_important!(info, "[req_id={}] 检索请求: url={}, 使用blobs数量={}, 查询内容={}", req_id, search_url, blob_names.len(), query);
+
+    let payload_bytes = payload.to_string().len();
+    log_important!(info, "[req_id={}] 检索载荷大小: {} 字节", req_id, payload_bytes);
+
+    let client = search_client(config)?;
+    let response = retry_request(|| async {
+        let r = send_with_token_refresh(config, &req_id, |token| {
+            client
+                .post(&search_url)
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .header(CONTENT_TYPE, "application/json")
+                .json(&payload)
+        })
+        .await?;
+
+        let status = r.status();
+        log_important!(info, "[req_id={}] 检索请求HTTP响应状态: {}", req_id, status);
+
+        if !status.is_success() {
+            let body = read_error_body(r, max_response_bytes(config)).await;
+            anyhow::bail!(AcemcpError::from_status(status, body));
+        }
+
+        let v = read_json_limited(r, max_response_bytes(config)).await?;
+        log_important!(info, "[req_id={}] 检索响应数据: {}", req_id, serde_json::to_string_pretty(&v).unwrap_or_default());
+        Ok(v)
+    }, 3, 2.0, &req_id, circuit_breaker::breaker_key(config, &search_url).as_deref()).await;
+
+    audit_search(config, &normalized_root, query, &blob_names, payload_bytes, response.as_ref());
+
+    let value = response?;
+    record_endpoint_success(&base_url);
+    Ok(finish_retrieval(value, query, &normalized_root, limits, &req_id))
+}
+
+/// 启用 `search_audit_enabled` 时记录一次检索：查询、参与检索的 blob 集合及其哈希、载荷大小与响应长度或错误
+fn audit_search(
+    config: &AcemcpConfig,
+    normalized_root: &str,
+    query: &str,
+    blob_names: &[String],
+    payload_bytes: usize,
+    response: Result<&serde_json::Value, &anyhow::Error>,
+) {
+    if !config.search_audit_enabled.unwrap_or(false) {
+        return;
+    }
+    let mut sorted_names = blob_names.to_vec();
+    sorted_names.sort();
+    let (response_len, error) = match response {
+        Ok(v) => (v.get("formatted_retrieval").and_then(|t| t.as_str()).map(|t| t.len()).unwrap_or(0), None),
+        Err(e) => (0, Some(e.to_string())),
+    };
+    append_search_audit(normalized_root, &serde_json::json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "project_root": normalized_root,
+        "query": query,
+        "blob_count": sorted_names.len(),
+        "blob_set_hash": sha256_hex("", &sorted_names.join("\n")),
+        "blob_names": sorted_names,
+        "payload_bytes": payload_bytes,
+        "response_length": response_len,
+        "error": error,
+    }));
+}
+
+/// 按 `limits` 整理一条检索响应：需要时先在本地重排，再按输出格式与长度限制生成返回文本
+fn finish_retrieval(mut value: serde_json::Value, query: &str, normalized_root: &str, limits: SearchLimits, req_id: &str) -> String {
+    // 在截断之前重排，使本地得分最高的片段优先保留
+    if limits.local_rerank {
+        if let Some(formatted) = value.get("formatted_retrieval").and_then(|v| v.as_str()) {
+            let reranked = super::rerank::rerank_retrieval(formatted, query, Path::new(normalized_root));
+            log_important!(info, "[req_id={}] 已按片段得分重排检索结果", req_id);
+            value["formatted_retrieval"] = serde_json::Value::String(reranked);
+        }
+    }
+    let text = format_retrieval(&value, limits);
+    log_important!(info, "[req_id={}] 搜索完成，返回文本长度: {}", req_id, text.len());
+    text
+}
+
+/// 项目级同义词表文件名（位于项目根目录），格式为 `{"txn": ["transaction"], "cfg": ["config"]}`
+const PROJECT_SYNONYMS_FILE: &str = ".acemcp-synonyms.json";
+
+/// 读取项目的同义词表，键统一为小写；文件不存在或格式错误时返回空表
+fn load_project_synonyms(root: &Path) -> HashMap<String, Vec<String>> {
+    let path = root.join(PROJECT_SYNONYMS_FILE);
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(_) => return HashMap::new(),
+    };
+    match serde_json::from_str::<HashMap<String, Vec<String>>>(&data) {
+        Ok(map) => map.into_iter().map(|(k, v)| (k.trim().to_lowercase(), v)).collect(),
+        Err(e) => {
+            log_important!(warn, "同义词表格式错误，已忽略: {}: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+/// 按同义词表扩展查询：查询中出现的缩写（按单词匹配，不区分大小写）对应的扩展词追加到查询末尾，
+/// 已出现在查询中的扩展词不再重复追加；没有可追加的扩展词时返回 None
+fn expand_query(query: &str, synonyms: &HashMap<String, Vec<String>>) -> Option<String> {
+    let lowered = query.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut expansions: Vec<&str> = Vec::new();
+    for word in &words {
+        for expansion in synonyms.get(*word).into_iter().flatten() {
+            let expansion = expansion.trim();
+            let lowered_expansion = expansion.to_lowercase();
+            let present = lowered_expansion
+                .split_whitespace()
+                .all(|part| words.contains(&part))
+                || expansions.iter().any(|e| e.eq_ignore_ascii_case(expansion));
+            if !expansion.is_empty() && !present {
+                expansions.push(expansion);
+            }
+        }
+    }
+
+    if expansions.is_empty() {
+        None
+    } else {
+        Some(format!("{} {}", query.trim_end(), expansions.join(" ")))
+    }
+}
+
+/// 读取项目未过期的规则与模式记忆，置顶与优先级高的在前；读取失败时返回空列表
+async fn load_enrichment_memories(project_root_path: &str) -> Vec<String> {
+    use super::super::memory::shared_memory_store;
+    use super::super::memory::MemoryCategory;
+
+    let store = match shared_memory_store(project_root_path).await {
+        Ok(s) => s,
+        Err(e) => {
+            log_debug!("读取记忆失败，查询不追加记忆: {}", e);
+            return Vec::new();
+        }
+    };
+    let manager = store.read().await;
+    let now = chrono::Utc::now();
+    let mut memories: Vec<_> = [MemoryCategory::Rule, MemoryCategory::Pattern]
+        .into_iter()
+        .filter_map(|category| manager.get_memories_by_category(category).ok())
+        .flatten()
+        .filter(|m| !m.is_expired(now))
+        .collect();
+    memories.sort_by_key(|m| std::cmp::Reverse((m.pinned, m.priority)));
+    memories.into_iter().map(|m| m.content).collect()
+}
+
+/// 将记忆作为上下文追加到查询末尾，追加部分不超过 `max_chars` 个字符（按整条记忆截取）；
+/// 没有可追加的记忆时返回 None
+fn enrich_query(query: &str, memories: &[String], max_chars: usize) -> Option<String> {
+    let mut context = String::new();
+    let mut used = 0;
+    for memory in memories.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        let line = format!("\n- {}", memory);
+        let len = line.chars().count();
+        if used + len > max_chars {
+            break;
+        }
+        context.push_str(&line);
+        used += len;
+    }
+
+    if context.is_empty() {
+        None
+    } else {
+        Some(format!("{}\n\n项目约定:{}", query.trim_end(), context))
+    }
+}
+
+/// 读取项目已索引的 blob（按 `exclude_paths` 排除已出现过的文件）并构造检索载荷
+/// 返回 (规范化项目根路径, 参与检索的 blob 名称, 载荷)
+fn build_search_payload(
+    config: &AcemcpConfig,
+    project_root_path: &str,
+    query: &str,
+    limits: SearchLimits,
+    exclude_paths: &[String],
+    req_id: &str,
+) -> anyhow::Result<(String, Vec<String>, serde_json::Value)> {
+    // 从 projects.json 读取已有的 blob 名称
+    let projects_path = home_projects_file();
+    let projects: ProjectsFile = if projects_path.exists() {
+        let data = fs::read_to_string(&projects_path).unwrap_or_default();
+        serde_json::from_str(&data).unwrap_or_default()
+    } else {
+        ProjectsFile::default()
+    };
+
+    let normalized_root = normalize_project_root(project_root_path);
+
+    let mut blob_names = projects.0.get(&normalized_root).cloned().unwrap_or_default();
+
+    if blob_names.is_empty() {
+        if get_project_status(&normalized_root).status == IndexStatus::Synced {
+            log_important!(warn, "[req_id={}] 项目状态为已同步，但 projects.json 中没有 blob 记录，可运行索引诊断（doctor）修复: {}", req_id, normalized_root);
+        }
+        anyhow::bail!(AcemcpError::NotIndexed("项目尚未索引或索引为空，请先执行索引操作".to_string()));
+    }
+
+    if !exclude_paths.is_empty() {
+        let before = blob_names.len();
+        blob_names = exclude_blobs_by_path(blob_names, &load_blob_paths(&normalized_root), exclude_paths);
+        log_important!(info, "[req_id={}] 排除已出现过的文件: 路径数={}, 排除blobs数={}", req_id, exclude_paths.len(), before - blob_names.len());
+        if blob_names.is_empty() {
+            anyhow::bail!(AcemcpError::NoBlobs("排除已出现过的文件后没有可检索的代码，请减少 exclude_paths".to_string()));
+        }
+    }
+
+    let mut query = Cow::Borrowed(query);
+    if config.query_expansion.unwrap_or(false) {
+        if let Some(expanded) = expand_query(&query, &load_project_synonyms(Path::new(&normalized_root))) {
+            log_important!(info, "[req_id={}] 查询已按同义词表扩展: {} -> {}", req_id, query, expanded);
+            query = Cow::Owned(expanded);
+        }
+    }
+
+    let payload = with_namespace(serde_json::json!({
+        "information_request": query,
+        "blobs": {"checkpoint_id": serde_json::Value::Null, "added_blobs": blob_names, "deleted_blobs": []},
+        "dialog": [],
+        "max_output_length": limits.max_output_length,
+        "disable_codebase_retrieval": false,
+        "enable_commit_retrieval": false,
+    }), project_namespace(config, &normalized_root).as_deref());
+    Ok((normalized_root, blob_names, payload))
+}
+
+/// 使用给定配置执行检索（不读取配置文件、不触发索引、不启动文件监听）
+/// 供集成测试等需要直接指定服务端地址的调用方使用
+pub async fn search_with_config(config: &AcemcpConfig, request: &AcemcpRequest) -> anyhow::Result<String> {
+    let exclude_paths = request.exclude_paths.clone().unwrap_or_default();
+    search_only(config, &request.project_root_path, &request.query, SearchLimits::from_request(request, config), &exclude_paths).await
+}
+
+/// 批量检索接口路径（相对 base_url）
+const BATCH_SEARCH_PATH: &str = "/agents/codebase-retrieval/batch";
+/// 批量检索回退为逐个检索时的最大并发数
+const BATCH_SEARCH_CONCURRENCY: usize = 4;
+
+/// 批量检索接口返回 404/405 的服务端（规范化的 base_url），之后的批量检索直接逐个检索，不再探测
+static BATCH_SEARCH_UNSUPPORTED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn batch_search_unsupported(base_url: &str) -> bool {
+    BATCH_SEARCH_UNSUPPORTED
+        .get()
+        .and_then(|slot| slot.lock().ok().map(|set| set.contains(&normalize_base_url(base_url))))
+        .unwrap_or(false)
+}
+
+fn mark_batch_search_unsupported(base_url: &str) {
+    let slot = BATCH_SEARCH_UNSUPPORTED.get_or_init(|| Mutex::new(HashSet::new()));
+    if let Ok(mut set) = slot.lock() {
+        set.insert(normalize_base_url(base_url));
+    }
+}
+
+/// 使用给定配置批量检索，结果顺序与请求顺序一致，每个查询各自成功或失败（不触发索引、不启动文件监听）
+///
+/// 同一项目中参与检索的 blob（已应用 `exclude_paths`）与输出长度都相同的查询合并为一组，
+/// 每组通过一次批量检索请求完成，blob 列表只发送一份；不同项目的 blob 不会合并到同一请求。
+/// 服务端不支持批量接口或批量请求失败时，该组回退为逐个检索，最多同时进行 `BATCH_SEARCH_CONCURRENCY` 个
+pub async fn search_batch_with_config(config: &AcemcpConfig, requests: &[AcemcpRequest]) -> Vec<anyhow::Result<String>> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let mut results: Vec<Option<anyhow::Result<String>>> = requests.iter().map(|_| None).collect();
+
+    // 分组键为项目根目录加上去掉查询后的载荷（blob 列表、命名空间、输出长度）
+    let mut groups: Vec<(String, serde_json::Value, Vec<(usize, serde_json::Value)>)> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+    for (i, request) in requests.iter().enumerate() {
+        let limits = SearchLimits::from_request(request, config);
+        let exclude_paths = request.exclude_paths.clone().unwrap_or_default();
+        match build_search_payload(config, &request.project_root_path, &request.query, limits, &exclude_paths, &req_id) {
+            Ok((normalized_root, _, mut payload)) => {
+                let query = payload
+                    .as_object_mut()
+                    .and_then(|map| map.remove("information_request"))
+                    .unwrap_or_default();
+                let key = format!("{}\n{}", normalized_root, payload);
+                let slot = *group_index.entry(key).or_insert_with(|| {
+                    groups.push((normalized_root, payload, Vec::new()));
+                    groups.len() - 1
+                });
+                groups[slot].2.push((i, query));
+            }
+            Err(e) => results[i] = Some(Err(e)),
+        }
+    }
+
+    let mut fallback: Vec<usize> = Vec::new();
+    for (normalized_root, shared, members) in groups {
+        let batch_available = config.base_url.as_deref().map(|base| !batch_search_unsupported(base)).unwrap_or(false);
+        if members.len() < 2 || !batch_available {
+            fallback.extend(members.iter().map(|(i, _)| *i));
+            continue;
+        }
+
+        let queries: Vec<serde_json::Value> = members.iter().map(|(_, query)| query.clone()).collect();
+        match search_only_batch(config, &normalized_root, &shared, queries, &req_id).await {
+            Ok(values) => {
+                for ((i, _), value) in members.iter().zip(values) {
+                    let request = &requests[*i];
+                    let limits = SearchLimits::from_request(request, config);
+                    results[*i] = Some(Ok(finish_retrieval(value, &request.query, &normalized_root, limits, &req_id)));
+                }
+            }
+            Err(e) => {
+                log_important!(info, "[req_id={}] 批量检索不可用，回退为逐个检索: {}", req_id, e);
+                fallback.extend(members.iter().map(|(i, _)| *i));
+            }
+        }
+    }
+
+    if !fallback.is_empty() {
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(BATCH_SEARCH_CONCURRENCY));
+        let handles: Vec<_> = fallback
+            .into_iter()
+            .map(|i| {
+                let (config, request, semaphore) = (config.clone(), requests[i].clone(), semaphore.clone());
+                (i, tokio::spawn(async move {
+                    let _permit = semaphore.acquire_owned().await?;
+                    search_with_config(&config, &request).await
+                }))
+            })
+            .collect();
+        for (i, handle) in handles {
+            results[i] = Some(handle.await.unwrap_or_else(|e| Err(anyhow::anyhow!("检索任务异常退出: {}", e))));
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("未返回检索结果"))))
+        .collect()
+}
+
+/// 通过一次批量检索请求执行同一组查询：`shared` 为去掉查询后的检索载荷，查询放入 `information_requests`，
+/// 服务端按相同顺序在 `results` 中返回每个查询的检索响应。
+/// 接口返回 404/405 时记录该服务端不支持批量检索；结果数量与查询数量不一致时返回错误
+async fn search_only_batch(
+    config: &AcemcpConfig,
+    normalized_root: &str,
+    shared: &serde_json::Value,
+    queries: Vec<serde_json::Value>,
+    req_id: &str,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    effective_token(config)?;
+
+    let query_count = queries.len();
+    let mut payload = shared.clone();
+    payload["information_requests"] = serde_json::Value::Array(queries.clone());
+    let blob_names: Vec<String> = shared
+        .pointer("/blobs/added_blobs")
+        .and_then(|v| v.as_array())
+        .map(|names| names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    let payload_bytes = payload.to_string().len();
+
+    let search_url = format!("{}{}", base_url, BATCH_SEARCH_PATH);
+    log_important!(info, "[req_id={}] 批量检索请求: url={}, 使用blobs数量={}, 查询数={}, 载荷大小={} 字节", req_id, search_url, blob_names.len(), query_count, payload_bytes);
+
+    let client = search_client(config)?;
+    let response = retry_request(|| async {
+        let r = send_with_token_refresh(config, req_id, |token| {
+            client
+                .post(&search_url)
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .header(CONTENT_TYPE, "application/json")
+                .json(&payload)
+        })
+        .await?;
+
+        let status = r.status();
+        log_important!(info, "[req_id={}] 批量检索HTTP响应状态: {}", req_id, status);
+
+        if !status.is_success() {
+            let body = read_error_body(r, max_response_bytes(config)).await;
+            anyhow::bail!(AcemcpError::from_status(status, body));
+        }
+        read_json_limited(r, max_response_bytes(config)).await
+    }, 3, 2.0, req_id, circuit_breaker::breaker_key(config, &search_url).as_deref()).await;
+
+    let value = match response {
+        Ok(value) => value,
+        Err(e) => {
+            let unsupported = e.chain().any(|cause| {
+                matches!(cause.downcast_ref::<AcemcpError>(), Some(AcemcpError::Http { status: 404 | 405, .. }))
+            });
+            if unsupported {
+                log_important!(info, "[req_id={}] 服务端不支持批量检索，之后的批量检索将逐个进行: {}", req_id, base_url);
+                mark_batch_search_unsupported(&base_url);
+            }
+            return Err(e);
+        }
+    };
+
+    let results = value
+        .get("results")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| AcemcpError::server("批量检索响应缺少 results 字段"))?;
+    if results.len() != query_count {
+        anyhow::bail!(AcemcpError::server(format!("批量检索结果数量不匹配: 期望 {}，实际 {}", query_count, results.len())));
+    }
+    record_endpoint_success(&base_url);
+
+    for (query, result) in queries.iter().zip(results) {
+        audit_search(config, normalized_root, query.as_str().unwrap_or_default(), &blob_names, payload_bytes, Ok(result));
+    }
+    log_important!(info, "[req_id={}] 批量检索成功，返回 {} 条结果", req_id, query_count);
+    Ok(results.clone())
+}
+
+/// 检索结果为空时返回的提示文本
+const NO_RETRIEVAL_RESULT: &str = "No relevant code context found for your query.";
+
+/// 流式检索：边接收服务端响应边输出 `formatted_retrieval` 文本，适合结果很大、需要尽快展示开头的场景
+///
+/// 返回的 channel 依次产出文本片段，发送端关闭即表示结束；与 `search_with_config` 相同地按
+/// `max_output_length` 截断，但不支持 `top_k`（`hits` 数组需要完整读取后才能截取）。
+/// 只有建立连接与读取响应状态阶段会重试，开始输出后读取失败会以 `Err` 片段结束
+pub async fn search_stream_with_config(config: &AcemcpConfig, request: &AcemcpRequest) -> anyhow::Result<tokio::sync::mpsc::Receiver<anyhow::Result<String>>> {
+    let req_id = uuid::Uuid::new_v4().to_string();
+    let base_url = config.base_url.clone().ok_or_else(|| AcemcpError::config("未配置 base_url"))?;
+    effective_token(config)?;
+
+    let limits = SearchLimits::from_request(request, config);
+    let exclude_paths = request.exclude_paths.clone().unwrap_or_default();
+    let (_, blob_names, payload) = build_search_payload(config, &request.project_root_path, &request.query, limits, &exclude_paths, &req_id)?;
+    let search_url = format!("{}/agents/codebase-retrieval", base_url);
+    log_important!(info, "[req_id={}] 流式检索请求: url={}, 使用blobs数量={}, 查询内容={}", req_id, search_url, blob_names.len(), request.query);
+
+    let client = search_client(config)?;
+    let response = retry_request(|| async {
+        let r = send_with_token_refresh(config, &req_id, |token| {
+            client
+                .post(&search_url)
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .header(CONTENT_TYPE, "application/json")
+                .json(&payload)
+        })
+        .await?;
+
+        let status = r.status();
+        log_important!(info, "[req_id={}] 流式检索HTTP响应状态: {}", req_id, status);
+
+        if !status.is_success() {
+            let body = read_error_body(r, max_response_bytes(config)).await;
+            anyhow::bail!(AcemcpError::from_status(status, body));
+        }
+        Ok(r)
+    }, 3, 2.0, &req_id, circuit_breaker::breaker_key(config, &search_url).as_deref()).await?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    tokio::spawn(forward_retrieval_stream(response, limits.max_output_length, max_response_bytes(config), tx, req_id));
+    Ok(rx)
+}
+
+/// 逐块读取检索响应，将 `formatted_retrieval` 的内容转发到 channel；接收端关闭后停止读取
+async fn forward_retrieval_stream(
+    mut response: reqwest::Response,
+    max_output_length: usize,
+    max_bytes: usize,
+    tx: tokio::sync::mpsc::Sender<anyhow::Result<String>>,
+    req_id: String,
+) {
+    let mut extractor = RetrievalExtractor::default();
+    let mut received = 0usize;
+    let mut emitted_chars = 0usize;
+
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = tx.send(Err(AcemcpError::from(e).into())).await;
+                return;
+            }
+        };
+        received += chunk.len();
+        if received > max_bytes {
+            let _ = tx.send(Err(AcemcpError::server(format!("响应体超过上限 {} 字节（可通过 max_response_bytes 调整）", max_bytes)).into())).await;
+            return;
+        }
+
+        let text = match extractor.feed(&chunk) {
+            Ok(text) => text,
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        let remaining = max_output_length - emitted_chars;
+        let count = text.chars().count();
+        if count > remaining {
+            // 之前的片段已经发出，只能在本片段内回退到行尾
+            let head = truncate_at_line_boundary(&text, remaining);
+            let _ = tx.send(Ok(format!("{}{}", head, truncation_note(max_output_length)))).await;
+            log_important!(info, "[req_id={}] 流式检索结果已截断至 {} 字符", req_id, max_output_length);
+            return;
+        }
+        emitted_chars += count;
+        if tx.send(Ok(text)).await.is_err() {
+            log_debug!("[req_id={}] 流式检索接收端已关闭，停止读取", req_id);
+            return;
+        }
+    }
+
+    if emitted_chars == 0 {
+        let _ = tx.send(Ok(NO_RETRIEVAL_RESULT.to_string())).await;
+    }
+    log_important!(info, "[req_id={}] 流式检索完成，返回文本长度: {} 字符", req_id, emitted_chars);
+}
+
+/// 从 JSON 响应体中增量提取顶层 `formatted_retrieval` 字段的字符串值
+///
+/// 只跟踪字符串与嵌套层级，不解析其余内容；目标字符串按块解码，
+/// 不会在 UTF-8 多字节字符或转义序列（包括 `\ud83d\ude00` 这类代理对）中间截断
+#[derive(Default)]
+struct RetrievalExtractor {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// 当前字符串是目标字段的值
+    in_target: bool,
+    /// 顶层对象中最近读取的字符串（可能是键）
+    last_string: Vec<u8>,
+    /// 刚读取到 `"formatted_retrieval":`，下一个值即为目标
+    value_expected: bool,
+    /// 目标字符串中尚未解码的原始字节（仍包含转义）
+    raw: Vec<u8>,
+    done: bool,
+}
+
+impl RetrievalExtractor {
+    /// 输入一块响应数据，返回其中可以解码的目标文本（可能为空）
+    fn feed(&mut self, chunk: &[u8]) -> anyhow::Result<String> {
+        for &b in chunk {
+            if self.done {
+                break;
+            }
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                    if self.in_target {
+                        self.in_target = false;
+                        self.done = true;
+                    }
+                    continue;
+                }
+                if self.in_target {
+                    self.raw.push(b);
+                } else if self.depth == 1 {
+                    self.last_string.push(b);
+                }
+                continue;
+            }
+            match b {
+                b'"' => {
+                    self.in_string = true;
+                    self.in_target = self.depth == 1 && self.value_expected;
+                    self.value_expected = false;
+                    self.last_string.clear();
+                }
+                b':' if self.depth == 1 => self.value_expected = self.last_string == b"formatted_retrieval",
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    self.value_expected = false;
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.value_expected = false;
+                }
+                b if b.is_ascii_whitespace() => {}
+                _ => self.value_expected = false,
+            }
+        }
+        self.decode_ready()
+    }
+
+    /// 解码 `raw` 中完整的部分，剩余字节留待下一块
+    fn decode_ready(&mut self) -> anyhow::Result<String> {
+        let mut cut = match std::str::from_utf8(&self.raw) {
+            Ok(_) => self.raw.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        let mut i = 0;
+        while i < cut {
+            if self.raw[i] != b'\\' {
+                i += 1;
+                continue;
+            }
+            let len = match self.raw.get(i + 1) {
+                Some(b'u') => {
+                    let high_surrogate = self
+                        .raw
+                        .get(i + 2..i + 6)
+                        .and_then(|hex| std::str::from_utf8(hex).ok())
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                        .map(|code| (0xD800..=0xDBFF).contains(&code));
+                    match high_surrogate {
+                        Some(true) => 12,
+                        Some(false) => 6,
+                        None => usize::MAX,
+                    }
+                }
+                Some(_) => 2,
+                None => usize::MAX,
+            };
+            if len > cut - i {
+                cut = i;
+                break;
+            }
+            i += len;
+        }
+        if cut == 0 {
+            return Ok(String::new());
+        }
+
+        let mut quoted = Vec::with_capacity(cut + 2);
+        quoted.push(b'"');
+        quoted.extend_from_slice(&self.raw[..cut]);
+        quoted.push(b'"');
+        let text: String = serde_json::from_slice(&quoted)
+            .map_err(|e| AcemcpError::server(format!("检索结果解码失败: {}", e)))?;
+        self.raw.drain(..cut);
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(patterns: &[&str]) -> ExcludeGlobs {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        build_exclude_globset(&patterns, false).unwrap()
+    }
+
+    #[test]
+    fn test_trailing_slash_pattern_excludes_directory() {
+        let root = Path::new("/project");
+        let gs = globs(&["build/"]);
+
+        assert!(should_exclude(&root.join("build"), root, Some(&gs), true));
+        assert!(should_exclude(&root.join("src/build"), root, Some(&gs), true));
+        assert!(should_exclude(&root.join("build/main.rs"), root, Some(&gs), false));
+    }
+
+    #[test]
+    fn test_trailing_slash_pattern_keeps_same_named_file() {
+        let root = Path::new("/project");
+        let gs = globs(&["build/"]);
+
+        assert!(!should_exclude(&root.join("build"), root, Some(&gs), false));
+        assert!(!should_exclude(&root.join("scripts/build"), root, Some(&gs), false));
+    }
+
+    #[test]
+    fn test_leading_slash_pattern_is_anchored_to_root() {
+        let root = Path::new("/project");
+        let gs = globs(&["/config.rs"]);
+
+        assert!(should_exclude(&root.join("config.rs"), root, Some(&gs), false));
+        assert!(!should_exclude(&root.join("src/config.rs"), root, Some(&gs), false));
+    }
+
+    #[test]
+    fn test_slash_free_pattern_matches_anywhere() {
+        let root = Path::new("/project");
+        let gs = globs(&["config.rs"]);
+
+        assert!(should_exclude(&root.join("config.rs"), root, Some(&gs), false));
+        assert!(should_exclude(&root.join("src/config.rs"), root, Some(&gs), false));
+    }
+
+    #[test]
+    fn test_pattern_with_inner_slash_matches_full_path_only() {
+        let root = Path::new("/project");
+        let gs = globs(&["src/config.rs"]);
+
+        assert!(should_exclude(&root.join("src/config.rs"), root, Some(&gs), false));
+        assert!(!should_exclude(&root.join("lib/src/config.rs"), root, Some(&gs), false));
+    }
+
+    #[test]
+    fn test_smart_split_extends_chunk_to_close_multiline_string() {
+        let content = "a = 1\ns = \"\"\"\nline\nline\n\"\"\"\nb = 2\n";
+        let smart = SmartSplitConfig { max_lookahead_lines: 10 };
+        let blobs = split_content("x.py", content, 2, None, Some(&smart), ChunkNaming::LineRange);
+
+        assert_eq!(blobs[0].content, "a = 1\ns = \"\"\"\nline\nline\n\"\"\"\n");
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs[1].path, "x.py#L6-6");
+    }
+
+    #[test]
+    fn test_char_limit_splits_before_line_limit() {
+        let content = "aaaa\nbb\ncc\nlong-line-over-limit\nd\n";
+        let blobs = split_content("x.txt", content, 10, Some(6), None, ChunkNaming::LineRange);
+        let paths: Vec<&str> = blobs.iter().map(|b| b.path.as_str()).collect();
+        assert_eq!(paths, vec!["x.txt#L1-1", "x.txt#L2-3", "x.txt#L4-4", "x.txt#L5-5"]);
+        assert_eq!(blobs[2].content, "long-line-over-limit\n");
+
+        // 未设置字符上限时保持按行分块
+        assert_eq!(split_content("x.txt", content, 10, None, None, ChunkNaming::LineRange).len(), 1);
+    }
+
+    #[test]
+    fn test_smart_split_falls_back_to_hard_split_without_close() {
+        let content = "/*\n1\n2\n3\n4\n";
+        let smart = SmartSplitConfig { max_lookahead_lines: 1 };
+        let smart_blobs = split_content("x.c", content, 2, None, Some(&smart), ChunkNaming::LineRange);
+        let hard_blobs = split_content("x.c", content, 2, None, None, ChunkNaming::LineRange);
+
+        assert_eq!(smart_blobs.len(), hard_blobs.len());
+        assert_eq!(smart_blobs[0].content, hard_blobs[0].content);
+    }
+
+    #[test]
+    fn test_exclude_extensions_apply_when_allow_list_empty() {
+        let matcher = TextExtMatcher::new(&[], true)
+            .with_exclude_extensions(&[".min.js".to_string(), "lock".to_string()]);
+        assert!(matcher.matches(Path::new("src/app.js"), "src/app.js"));
+        assert!(!matcher.matches(Path::new("dist/app.MIN.js"), "dist/app.MIN.js"));
+        assert!(!matcher.matches(Path::new("Cargo.lock"), "Cargo.lock"));
+
+        let matcher = TextExtMatcher::new(&[".rs".to_string()], true)
+            .with_exclude_extensions(&[".lock".to_string()]);
+        assert!(!matcher.matches(Path::new("src/app.js"), "src/app.js"));
+    }
+
+    #[test]
+    fn test_case_sensitivity_applies_to_extensions_and_excludes() {
+        let root = Path::new("/project");
+        let file = root.join("assets/Foo.PNG");
+        let exts = [".png".to_string(), "*.svg".to_string()];
+        let patterns = ["*.png".to_string()];
+
+        let insensitive = TextExtMatcher::new(&exts, true);
+        assert!(insensitive.matches(&file, "assets/Foo.PNG"));
+        assert!(insensitive.matches(Path::new("Logo.SVG"), "Logo.SVG"));
+        let gs = build_exclude_globset(&patterns, true).unwrap();
+        assert!(should_exclude(&file, root, Some(&gs), false));
+
+        let sensitive = TextExtMatcher::new(&exts, false);
+        assert!(!sensitive.matches(&file, "assets/Foo.PNG"));
+        assert!(!sensitive.matches(Path::new("Logo.SVG"), "Logo.SVG"));
+        let gs = build_exclude_globset(&patterns, false).unwrap();
+        assert!(!should_exclude(&file, root, Some(&gs), false));
+    }
 
-    // 日志：基础配置
-    log_important!(info,
-        "=== 开始索引代码库 ==="
-    );
-    log_important!(info,
-        "Acemcp配置: base_url={}, batch_size={}, max_lines_per_blob={}, text_exts数量={}, exclude_patterns数量={}",
-        base_url,
-        batch_size,
-        max_lines,
-        text_exts.len(),
-        exclude_patterns.len()
-    );
-    log_important!(info,
-        "项目路径: {}", project_root_path
-    );
+    #[test]
+    fn test_parse_encoding_override_accepts_common_names() {
+        for name in ["utf-8", "GBK", "big5", "shift_jis", "euc-jp", "windows-1252"] {
+            assert!(parse_encoding_override(name).is_ok(), "{}", name);
+        }
+        assert_eq!(parse_encoding_override(" gbk ").unwrap(), GBK);
+        assert!(parse_encoding_override("klingon").is_err());
+    }
 
-    // 收集 blob（根据扩展名与排除规则，简化版 .gitignore 支持）
-    log_important!(info, "开始收集代码文件...");
-    let blobs = collect_blobs(project_root_path, &text_exts, &exclude_patterns, max_lines)?;
-    if blobs.is_empty() {
-        // 更新状态：失败
-        let _ = update_project_status(project_root_path, |status| {
-            status.status = IndexStatus::Failed;
-            status.last_error = Some("未在项目中找到可索引的文本文件".to_string());
-            status.last_failure_time = Some(chrono::Utc::now());
+    #[test]
+    fn test_format_retrieval_applies_top_k_and_length_cap() {
+        let value = serde_json::json!({
+            "formatted_retrieval": "all",
+            "hits": [{"formatted": "a"}, {"formatted": "b"}, {"formatted": "c"}],
         });
-        anyhow::bail!("未在项目中找到可索引的文本文件");
-    }
 
-    // 更新状态：文件收集完成
-    let _ = update_project_status(project_root_path, |status| {
-        status.total_files = blobs.len();
-        status.progress = 20;
-    });
+        let text = format_retrieval(&value, SearchLimits { top_k: Some(2), max_output_length: 100, local_rerank: false, output_format: OutputFormat::Plain, merge_hits: false });
+        assert!(text.starts_with("a\n\nb\n\n"));
+        assert!(text.contains("另有 1 条结果"));
 
-    // 加载 projects.json
-    let projects_path = home_projects_file();
-    let mut projects: ProjectsFile = if projects_path.exists() {
-        let data = fs::read_to_string(&projects_path).unwrap_or_default();
-        serde_json::from_str(&data).unwrap_or_default()
-    } else { ProjectsFile::default() };
+        let text = format_retrieval(&value, SearchLimits { top_k: None, max_output_length: 100, local_rerank: false, output_format: OutputFormat::Plain, merge_hits: false });
+        assert_eq!(text, "all");
 
-    let normalized_root = PathBuf::from(project_root_path).canonicalize().unwrap_or_else(|_| PathBuf::from(project_root_path)).to_string_lossy().replace('\\', "/");
-    let existing_blob_names: std::collections::HashSet<String> = projects.0.get(&normalized_root).cloned().unwrap_or_default().into_iter().collect();
+        let lines = serde_json::json!({"formatted_retrieval": "first line\nsecond line\nthird line"});
+        let text = format_retrieval(&lines, SearchLimits { top_k: None, max_output_length: 15, local_rerank: false, output_format: OutputFormat::Plain, merge_hits: false });
+        assert!(text.starts_with("first line\n\n[...truncated]"), "{}", text);
 
-    // 计算所有 blob 的哈希值，建立哈希到 blob 的映射
-    let mut blob_hash_map: std::collections::HashMap<String, BlobItem> = std::collections::HashMap::new();
-    for blob in &blobs {
-        let hash = sha256_hex(&blob.path, &blob.content);
-        blob_hash_map.insert(hash.clone(), blob.clone());
+        let long = serde_json::json!({"formatted_retrieval": "x".repeat(50)});
+        let text = format_retrieval(&long, SearchLimits { top_k: None, max_output_length: 10, local_rerank: false, output_format: OutputFormat::Plain, merge_hits: false });
+        assert!(text.starts_with(&"x".repeat(10)));
+        assert!(text.contains("已截断"));
     }
 
-    // 分离已存在和新增加的 blob（与 Python 版本保持一致）
-    let all_blob_hashes: std::collections::HashSet<String> = blob_hash_map.keys().cloned().collect();
-    let existing_hashes: std::collections::HashSet<String> = all_blob_hashes.intersection(&existing_blob_names).cloned().collect();
-    let new_hashes: std::collections::HashSet<String> = all_blob_hashes.difference(&existing_blob_names).cloned().collect();
+    #[test]
+    fn test_format_retrieval_citations() {
+        let limits = SearchLimits { top_k: None, max_output_length: 1000, local_rerank: false, output_format: OutputFormat::Citations, merge_hits: false };
+        let value = serde_json::json!({
+            "formatted_retrieval": "Retrieved:\nPath: src/lib.rs#L801-1600\nfn a() {}\nPath: web/app.ts\nconst a = 1;\nPath: src/lib.rs#L801-1600\nfn a() {}\n",
+        });
+        assert_eq!(format_retrieval(&value, limits), "src/lib.rs:801-1600\nweb/app.ts");
 
-    // 需要上传的新 blob
-    let new_blobs: Vec<BlobItem> = new_hashes.iter().filter_map(|h| blob_hash_map.get(h).cloned()).collect();
+        let hits = serde_json::json!({
+            "hits": [{"path": "src/b.rs#L1-20", "formatted": "Path: src/b.rs#L1-20\nfn b() {}"}, {"formatted": "Path: src/a.rs#L5-9\nfn a() {}"}],
+        });
+        assert_eq!(format_retrieval(&hits, SearchLimits { top_k: Some(1), ..limits }), "src/b.rs:1-20");
+        assert_eq!(format_retrieval(&serde_json::json!({"formatted_retrieval": ""}), limits), NO_RETRIEVAL_RESULT);
+    }
 
-    log_important!(info,
-        "=== 索引统计 ==="
-    );
-    log_important!(info,
-        "收集到blobs总数: {}, 既有blobs: {}, 新增blobs: {}, 需要上传: {}",
-        blobs.len(),
-        existing_hashes.len(),
-        new_hashes.len(),
-        new_blobs.len()
-    );
+    #[test]
+    fn test_merge_adjacent_hits_from_same_file() {
+        let value = serde_json::json!({
+            "hits": [
+                {"formatted": "Path: src/a.rs#L4-6\nline4\nline5\nline6", "score": 0.9},
+                {"formatted": "Path: src/b.rs\nfn b() {}", "score": 0.8},
+                {"formatted": "Path: src/a.rs#L1-3\nline1\nline2\nline3", "score": 0.5},
+            ],
+        });
+        let limits = SearchLimits { top_k: Some(10), max_output_length: 1000, local_rerank: false, output_format: OutputFormat::Json, merge_hits: true };
+        let parsed: serde_json::Value = serde_json::from_str(&format_retrieval(&value, limits)).unwrap();
+        assert_eq!(parsed["total"], 2);
+        let merged = &parsed["hits"][0];
+        assert_eq!(merged["path"], "src/a.rs#L1-6");
+        assert_eq!(merged["formatted"], "Path: src/a.rs#L1-6\nline1\nline2\nline3\nline4\nline5\nline6");
+        assert_eq!(merged["score"], 0.9);
+        assert_eq!(parsed["hits"][1]["formatted"], "Path: src/b.rs\nfn b() {}");
+
+        let limits = SearchLimits { merge_hits: false, ..limits };
+        let parsed: serde_json::Value = serde_json::from_str(&format_retrieval(&value, limits)).unwrap();
+        assert_eq!(parsed["total"], 3);
+    }
 
-    let client = Client::new();
+    #[test]
+    fn test_format_retrieval_markdown_and_json() {
+        let value = serde_json::json!({
+            "formatted_retrieval": "Retrieved:\nPath: src/lib.rs#L1-800\nfn main() {}\nPath: web/app.ts\nconst a = 1;\n",
+        });
+        let limits = SearchLimits { top_k: None, max_output_length: 1000, local_rerank: false, output_format: OutputFormat::Markdown, merge_hits: false };
+        let text = format_retrieval(&value, limits);
+        assert_eq!(text, "Retrieved:\nPath: src/lib.rs#L1-800\n```rust\nfn main() {}\n```\n\nPath: web/app.ts\n```typescript\nconst a = 1;\n```");
+
+        let limits = SearchLimits { output_format: OutputFormat::Json, ..limits };
+        let parsed: serde_json::Value = serde_json::from_str(&format_retrieval(&value, limits)).unwrap();
+        assert_eq!(parsed["total"], 2);
+        assert_eq!(parsed["hits"][0], serde_json::json!({"path": "src/lib.rs", "content": "fn main() {}"}));
+
+        let limits = SearchLimits { max_output_length: 100, ..limits };
+        let parsed: serde_json::Value = serde_json::from_str(&format_retrieval(&value, limits)).unwrap();
+        assert_eq!(parsed["hits"].as_array().unwrap().len(), 1);
+        assert_eq!(parsed["truncated"], true);
+    }
 
-    // 批量上传新增 blobs
-    let mut uploaded_names: Vec<String> = Vec::new();
-    let mut failed_batches: Vec<usize> = Vec::new();
-    
-    if !new_blobs.is_empty() {
-        let total_batches = (new_blobs.len() + batch_size - 1) / batch_size;
-        log_important!(info,
-            "=== 开始批量上传代码索引 ==="
-        );
-        log_important!(info,
-            "目标端点: {}/batch-upload, 总批次: {}, 每批上限: {}, 总blobs: {}",
-            base_url,
-            total_batches,
-            batch_size,
-            new_blobs.len()
-        );
-        
-        for i in 0..total_batches {
-            let start = i * batch_size;
-            let end = usize::min(start + batch_size, new_blobs.len());
-            let batch = &new_blobs[start..end];
-            let url = format!("{}/batch-upload", base_url);
-            
-            log_important!(info,
-                "上传批次 {}/{}: url={}, blobs={}",
-                i + 1,
-                total_batches,
-                url,
-                batch.len()
-            );
-            
-            // 详细记录每个 blob 的信息
-            for (idx, blob) in batch.iter().enumerate() {
-                log_important!(info,
-                    "  批次 {} - Blob {}/{}: path={}, content_length={}",
-                    i + 1,
-                    idx + 1,
-                    batch.len(),
-                    blob.path,
-                    blob.content.len()
-                );
-            }
-            
-            let payload = serde_json::json!({"blobs": batch});
-            log_important!(info, "批次载荷大小: {} 字节", payload.to_string().len());
-            
-            match retry_request(|| async {
-                let r = client
-                    .post(&url)
-                    .header(AUTHORIZATION, format!("Bearer {}", token))
-                    .header(CONTENT_TYPE, "application/json")
-                    .json(&payload)
-                    .send()
-                    .await?;
-                
-                let status = r.status();
-                log_important!(info, "HTTP响应状态: {}", status);
-                
-                if !status.is_success() {
-                    let body = r.text().await.unwrap_or_default();
-                    anyhow::bail!("HTTP {} {}", status, body);
-                }
-                
-                let v: serde_json::Value = r.json().await?;
-                log_important!(info, "响应数据: {}", serde_json::to_string_pretty(&v).unwrap_or_default());
-                Ok(v)
-            }, 3, 1.0).await {
-                Ok(value) => {
-                    if let Some(arr) = value.get("blob_names").and_then(|v| v.as_array()) {
-                        let mut batch_names: Vec<String> = Vec::new();
-                        for v in arr { 
-                            if let Some(s) = v.as_str() { 
-                                batch_names.push(s.to_string()); 
-                            }
-                        }
-                        
-                        if batch_names.is_empty() {
-                            log_important!(info, "批次 {} 返回了空的blob名称列表", i + 1);
-                            failed_batches.push(i + 1);
-                        } else {
-                            uploaded_names.extend(batch_names.clone());
-                            log_important!(info, "批次 {} 上传成功，获得 {} 个blob名称", i + 1, batch_names.len());
-                            // 详细记录每个上传成功的 blob 名称
-                            for (idx, name) in batch_names.iter().enumerate() {
-                                log_important!(info, "  批次 {} - 上传成功 Blob {}/{}: name={}", i + 1, idx + 1, batch_names.len(), name);
-                            }
-                        }
-                    } else {
-                        log_important!(info, "批次 {} 响应中缺少blob_names字段", i + 1);
-                        failed_batches.push(i + 1);
-                    }
-                }
-                Err(e) => {
-                    log_important!(info, "批次 {} 上传失败: {}", i + 1, e);
-                    failed_batches.push(i + 1);
-                }
-            }
+    #[test]
+    fn test_format_index_summary_sections() {
+        let started = chrono::Utc::now();
+        let mut status = ProjectIndexStatus::default();
+        status.project_root = "/work/app".to_string();
+        status.indexed_files = 2;
+        status.last_success_time = Some(started + chrono::Duration::seconds(3));
+        for (offset, from, to) in [(0, IndexStatus::Idle, IndexStatus::Indexing), (3, IndexStatus::Indexing, IndexStatus::Synced)] {
+            status.status_history.push_back(StatusEvent {
+                timestamp: started + chrono::Duration::seconds(offset),
+                from_status: from,
+                to_status: to,
+                detail: None,
+            });
         }
-        
-        // 上传结果总结
-        log_important!(info,
-            "=== 上传结果总结 ==="
+        let blob_paths: HashMap<String, String> = [("h1", "src/big.rs#L1-800"), ("h2", "src/big.rs#L801-1600"), ("h3", "README.md")]
+            .into_iter()
+            .map(|(h, p)| (h.to_string(), p.to_string()))
+            .collect();
+        let blob_names: Vec<String> = vec!["h1".into(), "h2".into(), "h3".into()];
+        let excluded = vec![("target".to_string(), SUMMARY_EXCLUDED_COUNT_LIMIT), ("node_modules".to_string(), 120), ("dist".to_string(), 4)];
+
+        status.compressible_files = find_compressible_files(
+            &[
+                BlobItem { path: "src/big.rs#L1-800".to_string(), content: "let x = 1;\n".repeat(500) },
+                BlobItem { path: "README.md".to_string(), content: "# demo\nA small project.\n".to_string() },
+            ],
+            "test",
         );
-        if !failed_batches.is_empty() {
-            log_important!(info, "上传完成，但有失败的批次: {:?}, 成功上传blobs: {}", failed_batches, uploaded_names.len());
-        } else {
-            log_important!(info, "所有批次上传成功，共上传 {} 个blobs", uploaded_names.len());
-        }
-    } else {
-        log_important!(info, "没有新的blob需要上传，使用已有索引");
-    }
 
-    // 合并并保存 projects.json（与 Python 版本保持一致）
-    // 只保留当前项目中仍然存在的 blob 的哈希值（自动删除已删除的 blob）
-    let all_blob_names: Vec<String> = existing_hashes.into_iter().chain(uploaded_names.into_iter()).collect();
-    projects.0.insert(normalized_root.clone(), all_blob_names.clone());
-    if let Ok(s) = serde_json::to_string_pretty(&projects) { let _ = fs::write(projects_path, s); }
+        let text = format_index_summary(&status, &blob_names, &blob_paths, &excluded);
+        assert!(text.contains("已索引文件数: 2"), "{}", text);
+        assert_eq!(status.compressible_files.len(), 1);
+        assert!(text.contains(" 1. src/big.rs（压缩比 0.0"), "{}", text);
+        assert!(text.contains("blob 总数: 3"));
+        assert!(text.contains("索引耗时: 3.0 秒"));
+        assert!(text.find(" 1. src/big.rs（2 个 blob）").unwrap() < text.find(" 2. README.md（1 个 blob）").unwrap());
+        assert!(text.contains(" 1. target（1000+ 个文件）"), "{}", text);
+        assert!(text.contains(" 2. node_modules（120 个文件）"));
+    }
 
-    // 使用合并后的 blob_names（与 Python 版本保持一致）
-    let blob_names = all_blob_names;
-    if blob_names.is_empty() {
-        log_important!(info, "索引后未找到 blobs，项目路径: {}", normalized_root);
-        // 更新状态：失败
-        let _ = update_project_status(project_root_path, |status| {
-            status.status = IndexStatus::Failed;
-            status.last_error = Some("索引后未找到 blobs".to_string());
-            status.last_failure_time = Some(chrono::Utc::now());
+    #[test]
+    fn test_migrate_config_schema_renames_old_fields() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.json");
+        let original = serde_json::json!({
+            "mcp_config": {
+                "base_url": "https://api.example.com",
+                "acemcpBatchSize": 20,
+                "acemcp_search_audit_enabled": true,
+                "token": "old",
+                "acemcp_token": "current",
+                "acemcp_no_such_option": 1,
+            },
         });
-        anyhow::bail!("索引后未找到 blobs");
+        fs::write(&path, original.to_string()).unwrap();
+
+        let report = AcemcpTool::migrate_config_schema(&path).unwrap();
+        let mut froms: Vec<&str> = report.applied.iter().map(|m| m.from.as_str()).collect();
+        froms.sort();
+        assert_eq!(froms, vec!["acemcpBatchSize", "acemcp_search_audit_enabled", "base_url"]);
+        assert_eq!(report.conflicts, vec!["token"]);
+        assert_eq!(report.unknown_fields, vec!["acemcp_no_such_option"]);
+
+        let migrated: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(migrated["mcp_config"]["acemcp_base_url"], "https://api.example.com");
+        assert_eq!(migrated["mcp_config"]["acemcp_batch_size"], 20);
+        assert_eq!(migrated["mcp_config"]["acemcp_search_audit"], true);
+        assert_eq!(migrated["mcp_config"]["acemcp_token"], "current");
+        assert!(migrated["mcp_config"].get("base_url").is_none());
+
+        // 再次迁移没有可应用的改名
+        assert!(AcemcpTool::migrate_config_schema(&path).unwrap().applied.is_empty());
     }
 
-    // 检查是否是首次成功索引（用于 ji 集成）
-    let is_first_success = {
-        let status = get_project_status(project_root_path);
-        status.last_success_time.is_none()
-    };
+    #[test]
+    fn test_validate_config_reports_errors_and_warnings() {
+        let config = AcemcpConfig {
+            base_url: Some("http://localhost:8080".to_string()),
+            token: Some("t".to_string()),
+            text_extensions: Some(vec![".rs".to_string(), "py".to_string()]),
+            ..Default::default()
+        };
+        let warnings = validate_acemcp_config(&config).unwrap();
+        assert!(warnings.contains(&ConfigWarning::InsecureBaseUrl { value: "http://localhost:8080".to_string() }));
+        assert!(warnings.contains(&ConfigWarning::TextExtensionMissingDot { value: "py".to_string() }));
+
+        let config = AcemcpConfig {
+            batch_size: Some(0),
+            encoding_override: Some("klingon".to_string()),
+            ..Default::default()
+        };
+        let errors = validate_acemcp_config(&config).unwrap_err();
+        assert!(errors.contains(&ConfigError::MissingBaseUrl));
+        assert!(errors.contains(&ConfigError::MissingToken));
+        assert!(errors.contains(&ConfigError::InvalidBatchSize { value: 0 }));
+        assert!(errors.contains(&ConfigError::InvalidEncodingOverride { value: "klingon".to_string() }));
+    }
 
-    // 更新状态：索引成功完成
-    let _ = update_project_status(project_root_path, |status| {
-        status.status = IndexStatus::Synced;
-        status.progress = 100;
-        status.indexed_files = blobs.len();
-        status.pending_files = 0;
-        status.last_success_time = Some(chrono::Utc::now());
-        status.last_error = None;
-    });
+    #[test]
+    fn test_enrich_query_appends_memories_within_budget() {
+        let memories = vec!["使用 anyhow 处理错误".to_string(), "  ".to_string(), "x".repeat(50)];
+        assert_eq!(
+            enrich_query("错误处理 ", &memories, 20).as_deref(),
+            Some("错误处理\n\n项目约定:\n- 使用 anyhow 处理错误")
+        );
+        assert_eq!(enrich_query("错误处理", &memories, 5), None);
+        assert_eq!(enrich_query("错误处理", &[], 1000), None);
+    }
 
-    // 首次成功索引时，写入 ji 记忆
-    if is_first_success {
-        let _ = write_index_memory_to_ji(project_root_path, config);
+    #[test]
+    fn test_expand_query_appends_missing_synonyms() {
+        let synonyms: HashMap<String, Vec<String>> = [
+            ("txn".to_string(), vec!["transaction".to_string()]),
+            ("cfg".to_string(), vec!["config".to_string(), "configuration".to_string()]),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(expand_query("rollback TXN on error", &synonyms).as_deref(), Some("rollback TXN on error transaction"));
+        assert_eq!(expand_query("load cfg config", &synonyms).as_deref(), Some("load cfg config configuration"));
+        assert_eq!(expand_query("txn transaction", &synonyms), None);
+        assert_eq!(expand_query("txnid parser", &synonyms), None);
     }
 
-    log_important!(info, "索引更新完成，共 {} 个 blobs", blob_names.len());
-    Ok(blob_names)
-}
+    #[test]
+    fn test_invalid_ca_cert_fails_validation() {
+        let path = std::env::temp_dir().join(format!("acemcp-ca-{}.pem", uuid::Uuid::new_v4()));
+        fs::write(&path, "not a certificate").unwrap();
+        let path_str = path.to_string_lossy().to_string();
+        let config = AcemcpConfig {
+            base_url: Some("https://acemcp.internal".to_string()),
+            token: Some("t".to_string()),
+            ca_cert_path: Some(path_str.clone()),
+            danger_accept_invalid_certs: Some(true),
+            ..Default::default()
+        };
+        let errors = validate_acemcp_config(&config).unwrap_err();
+        assert!(errors.iter().any(|e| matches!(e, ConfigError::InvalidCaCert { path, .. } if path == &path_str)));
+        assert!(load_ca_certificate(&path_str).unwrap_err().to_string().contains("PEM"));
 
-/// 将索引配置信息写入 ji（记忆）工具
-fn write_index_memory_to_ji(project_root_path: &str, config: &AcemcpConfig) {
-    use super::super::memory::MemoryManager;
-    use super::super::memory::MemoryCategory;
+        let _ = fs::remove_file(&path);
+        assert!(load_ca_certificate(&path_str).unwrap_err().to_string().contains("无法读取根证书"));
+    }
 
-    // 创建记忆管理器
-    let manager = match MemoryManager::new(project_root_path) {
-        Ok(m) => m,
-        Err(e) => {
-            log_debug!("创建记忆管理器失败（不影响索引）: {}", e);
-            return;
-        }
-    };
+    #[test]
+    fn test_compute_index_diff_groups_chunks_by_file() {
+        let map = |entries: &[(&str, &str)]| -> HashMap<String, String> {
+            entries.iter().map(|(h, p)| (h.to_string(), p.to_string())).collect()
+        };
+        let previous = map(&[("h1", "a.rs"), ("h2", "big.rs#L1-800"), ("h3", "big.rs#L801-900"), ("h4", "gone.rs")]);
+        let current = map(&[("h1", "a.rs"), ("h2", "big.rs#L1-800"), ("h5", "big.rs#L801-950"), ("h6", "new.rs")]);
 
-    // 构建记忆内容
-    let text_exts = config.text_extensions.clone().unwrap_or_default();
-    let exclude_patterns = config.exclude_patterns.clone().unwrap_or_default();
-    let batch_size = config.batch_size.unwrap_or(10);
-    let max_lines = config.max_lines_per_blob.unwrap_or(800);
+        let diff = compute_index_diff(&previous, &current);
+        assert_eq!(diff.added, vec!["new.rs".to_string()]);
+        assert_eq!(diff.modified, vec!["big.rs".to_string()]);
+        assert_eq!(diff.removed, vec!["gone.rs".to_string()]);
+    }
 
-    let memory_content = format!(
-        "acemcp 代码索引已启用 - 配置摘要: 文件扩展名={:?}, 排除模式={:?}, 批次大小={}, 最大行数/块={}",
-        text_exts, exclude_patterns, batch_size, max_lines
-    );
+    #[test]
+    fn test_base64_upload_payload_round_trips() {
+        let blob = BlobItem { path: "src/中文.rs".to_string(), content: "// 注释 ✓\nfn main() {}\n".to_string() };
+
+        let plain = upload_blob_payload(&blob, ChunkEncoding::Plain);
+        assert_eq!(plain["content"], blob.content.as_str());
+        assert!(plain.get("encoding").is_none());
+
+        let encoded = upload_blob_payload(&blob, ChunkEncoding::Base64);
+        assert_eq!(encoded["encoding"], "base64");
+        let content = encoded["content"].as_str().unwrap();
+        assert!(content.is_ascii());
+        let decoded = base64::engine::general_purpose::STANDARD.decode(content).unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), blob.content);
+        let path = encoded["path"].as_str().unwrap();
+        assert!(path.is_ascii());
+        let decoded = base64::engine::general_purpose::STANDARD.decode(path).unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), blob.path);
+
+        assert_eq!(ChunkEncoding::from_config(Some(" Base64 ")), ChunkEncoding::Base64);
+        assert_eq!(ChunkEncoding::from_config(Some("gzip")), ChunkEncoding::Plain);
+    }
 
-    // 写入记忆
-    match manager.add_memory(&memory_content, MemoryCategory::Context) {
-        Ok(id) => {
-            log_important!(info, "已将索引配置写入 ji 记忆: id={}", id);
+    #[test]
+    fn test_exclude_blobs_by_path_drops_all_chunks_of_seen_files() {
+        let blob_paths: HashMap<String, String> = [
+            ("h1", "src/big.rs#L1-800"),
+            ("h2", "src/big.rs#L801-900"),
+            ("h3", "src/lib.rs"),
+        ]
+        .iter()
+        .map(|(h, p)| (h.to_string(), p.to_string()))
+        .collect();
+        let names = vec!["h1".to_string(), "h2".to_string(), "h3".to_string(), "unknown".to_string()];
+
+        let kept = exclude_blobs_by_path(names, &blob_paths, &["./src\\big.rs".to_string()]);
+        assert_eq!(kept, vec!["h3".to_string(), "unknown".to_string()]);
+    }
+
+    #[test]
+    fn test_blob_source_path_strips_chunk_suffix() {
+        assert_eq!(blob_source_path("db/schema.sql#chunk2of3"), "db/schema.sql");
+        assert_eq!(blob_source_path("db/schema.sql#L801-1600"), "db/schema.sql");
+        assert_eq!(blob_source_path("db/schema.sql"), "db/schema.sql");
+        assert_eq!(blob_source_path("docs/c#notes.md"), "docs/c#notes.md");
+    }
+
+    #[test]
+    fn test_line_range_naming_keeps_leading_chunks_when_file_grows() {
+        let before: String = (1..=5).map(|i| format!("line {}\n", i)).collect();
+        let after: String = (1..=7).map(|i| format!("line {}\n", i)).collect();
+
+        let hashes = |content: &str, naming: ChunkNaming| -> Vec<String> {
+            split_content("a.txt", content, 2, None, None, naming)
+                .iter()
+                .map(|b| sha256_hex(&b.path, &b.content))
+                .collect()
+        };
+
+        let old = hashes(&before, ChunkNaming::LineRange);
+        let new = hashes(&after, ChunkNaming::LineRange);
+        assert_eq!(old.len(), 3);
+        assert_eq!(new.len(), 4);
+        assert_eq!(old[..2], new[..2]);
+        assert_ne!(old[2], new[2]);
+
+        // 旧格式下总块数变化会使所有分块改名
+        let old = hashes(&before, ChunkNaming::Legacy);
+        let new = hashes(&after, ChunkNaming::Legacy);
+        assert!(old.iter().all(|h| !new.contains(h)));
+    }
+
+    #[test]
+    fn test_plain_pattern_matches_files_and_directories() {
+        let root = Path::new("/project");
+        let gs = globs(&["build"]);
+
+        assert!(should_exclude(&root.join("build"), root, Some(&gs), true));
+        assert!(should_exclude(&root.join("build"), root, Some(&gs), false));
+    }
+
+    #[test]
+    fn test_normalized_line_endings_hash_equally() {
+        let unix = normalize_line_endings("fn main() {\n}\n".to_string());
+        let windows = normalize_line_endings("fn main() {\r\n}\r\n".to_string());
+        let classic_mac = normalize_line_endings("fn main() {\r}\r".to_string());
+        assert_eq!(windows, unix);
+        assert_eq!(classic_mac, unix);
+        assert_eq!(sha256_hex("a.rs", &windows), sha256_hex("a.rs", &unix));
+    }
+
+    #[test]
+    fn test_retry_decision_uses_typed_errors() {
+        let http = |status| anyhow::Error::from(AcemcpError::Http { status, body: String::new() });
+        assert!(acemcp_error::is_retryable(&http(503)));
+        assert!(acemcp_error::is_retryable(&http(429)));
+        assert!(!acemcp_error::is_retryable(&http(400)));
+        assert!(!acemcp_error::is_retryable(&anyhow::Error::from(AcemcpError::Auth("HTTP 401".to_string()))));
+        assert!(acemcp_error::is_retryable(&anyhow::Error::from(AcemcpError::Timeout("30s".to_string())).context("检索失败")));
+        assert!(acemcp_error::is_retryable(&anyhow::Error::from(AcemcpError::MalformedResponse("EOF".to_string()))));
+
+        // 只看错误类型，不再根据错误文本猜测
+        assert!(!acemcp_error::is_retryable(&anyhow::anyhow!("connection timeout")));
+    }
+
+    #[test]
+    fn test_traversal_order_and_depth_limit() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for rel in ["b.rs", "a.rs", "sub/c.rs", "sub/deeper/d.rs", "aa/e.rs"] {
+            let path = dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "fn main() {}\n").unwrap();
         }
-        Err(e) => {
-            log_debug!("写入 ji 记忆失败（不影响索引）: {}", e);
+
+        let mut config = AcemcpConfig { text_extensions: Some(vec![".rs".to_string()]), ..Default::default() };
+        let root = dir.path().to_string_lossy().to_string();
+        let paths = |config: &AcemcpConfig| -> Vec<String> {
+            collect_blobs(&root, &CollectOptions::from_config(config)).unwrap().0.into_iter().map(|b| b.path).collect()
+        };
+        // 同一目录内按文件名排序，深度优先
+        assert_eq!(paths(&config), vec!["a.rs", "aa/e.rs", "b.rs", "sub/c.rs", "sub/deeper/d.rs"]);
+
+        config.index_depth_limit = Some(1);
+        assert_eq!(paths(&config), vec!["a.rs", "aa/e.rs", "b.rs", "sub/c.rs"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_option() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(outside.path().join("shared.rs"), "fn shared() {}\n").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("linked")).unwrap();
+        // 指向祖先目录的链接成环，跟随时应被跳过而不是无限遍历
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("loop")).unwrap();
+
+        let mut config = AcemcpConfig { text_extensions: Some(vec![".rs".to_string()]), ..Default::default() };
+        let root = dir.path().to_string_lossy().to_string();
+        let paths = |config: &AcemcpConfig| -> Vec<String> {
+            collect_blobs(&root, &CollectOptions::from_config(config)).unwrap().0.into_iter().map(|b| b.path).collect()
+        };
+        assert_eq!(paths(&config), vec!["linked/shared.rs", "main.rs"]);
+
+        config.follow_symlinks = Some(false);
+        assert_eq!(paths(&config), vec!["main.rs"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_custom_splitter_script_with_fallback() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        for rel in ["a.rs", "b.rs", "c.rs"] {
+            fs::write(dir.path().join(rel), "fn a() {}\nfn b() {}\n").unwrap();
         }
+        let scripts = tempfile::TempDir::new().unwrap();
+        let script = scripts.path().join("split.sh");
+        fs::write(&script, r##"#!/bin/sh
+case "$1" in
+  a.rs) printf '[{"path":"a.rs#L1-1","content":"fn a() {}\\n"},{"path":"a.rs#L2-2","content":"fn b() {}\\n"}]' ;;
+  c.rs) printf '[{"path":"other.rs","content":"fn a() {}\\n"}]' ;;
+  *) exit 1 ;;
+esac
+"##).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = AcemcpConfig {
+            text_extensions: Some(vec![".rs".to_string()]),
+            custom_splitter_script: Some(script.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let (blobs, _) = collect_blobs(&dir.path().to_string_lossy(), &CollectOptions::from_config(&config)).unwrap();
+        let paths: Vec<&str> = blobs.iter().map(|b| b.path.as_str()).collect();
+        // 退出码非零（b.rs）与路径不属于该文件（c.rs）时回退内置分块
+        assert_eq!(paths, vec!["a.rs#L1-1", "a.rs#L2-2", "b.rs", "c.rs"]);
+        assert_eq!(blobs[0].content, "fn a() {}\n");
+
+        // 超时未退出的脚本被终止并回退
+        let hanging = scripts.path().join("hang.sh");
+        fs::write(&hanging, "#!/bin/sh\nsleep 30\n").unwrap();
+        fs::set_permissions(&hanging, fs::Permissions::from_mode(0o755)).unwrap();
+        let started = std::time::Instant::now();
+        assert!(run_splitter_script(&hanging, dir.path(), "a.rs", Duration::from_millis(200)).is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
     }
-}
 
-/// 只执行搜索，不触发索引
-/// 使用已有的索引数据进行搜索
-async fn search_only(config: &AcemcpConfig, project_root_path: &str, query: &str) -> anyhow::Result<String> {
-    let base_url = config.base_url.clone().ok_or_else(|| anyhow::anyhow!("未配置 base_url"))?;
-    let token = config.token.clone().ok_or_else(|| anyhow::anyhow!("未配置 token"))?;
+    #[test]
+    fn test_hidden_and_secret_files_are_skipped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for rel in ["main.rs", ".env", ".env.local", ".idea/workspace.rs", ".vscode/settings.rs", "certs/server.pem", "src/.hidden.rs"] {
+            let path = dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "fn main() {}\n").unwrap();
+        }
 
-    // 从 projects.json 读取已有的 blob 名称
-    let projects_path = home_projects_file();
-    let projects: ProjectsFile = if projects_path.exists() {
-        let data = fs::read_to_string(&projects_path).unwrap_or_default();
-        serde_json::from_str(&data).unwrap_or_default()
-    } else {
-        ProjectsFile::default()
-    };
+        // 排除扩展名模式下所有文本文件都会被收集，用于确认 .env 与 .pem 是被名单排除的
+        let mut config = AcemcpConfig { exclude_extensions: Some(vec![".md".to_string()]), ..Default::default() };
+        let root = dir.path().to_string_lossy().to_string();
+        let paths = |config: &AcemcpConfig| -> Vec<String> {
+            collect_blobs(&root, &CollectOptions::from_config(config)).unwrap().0.into_iter().map(|b| b.path).collect()
+        };
+        assert_eq!(paths(&config), vec!["main.rs"]);
 
-    let normalized_root = PathBuf::from(project_root_path)
-        .canonicalize()
-        .unwrap_or_else(|_| PathBuf::from(project_root_path))
-        .to_string_lossy()
-        .replace('\\', "/");
+        // 关闭 skip_hidden 后隐藏目录可以被索引，密钥类文件仍然跳过
+        config.skip_hidden = Some(false);
+        assert_eq!(paths(&config), vec![".idea/workspace.rs", ".vscode/settings.rs", "main.rs", "src/.hidden.rs"]);
+    }
 
-    let blob_names = projects.0.get(&normalized_root).cloned().unwrap_or_default();
+    #[test]
+    fn test_explain_exclusion_reports_first_matching_rule() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for rel in [".gitignore", "main.rs", "README.txt", "target/out.rs", "build/gen.rs", ".idea/workspace.rs", "certs/server.key", "src/lib.rs"] {
+            let path = dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "target/\n").unwrap();
+        }
+        fs::write(dir.path().join("big.rs"), "// generated\n".repeat(100)).unwrap();
 
-    if blob_names.is_empty() {
-        anyhow::bail!("项目尚未索引或索引为空，请先执行索引操作");
+        let mut config = AcemcpConfig {
+            text_extensions: Some(vec![".rs".to_string()]),
+            exclude_patterns: Some(vec!["node_modules".to_string(), "build/".to_string()]),
+            max_file_bytes: Some(1024),
+            ..Default::default()
+        };
+        let opts = CollectOptions::from_config(&config);
+        let explain = |rel: &str| explain_file_exclusion(dir.path(), Path::new(rel), &opts).unwrap();
+
+        assert_eq!(explain("main.rs"), ExclusionReason::NotExcluded);
+        assert_eq!(explain("README.txt"), ExclusionReason::WrongExtension);
+        assert_eq!(explain("target/out.rs"), ExclusionReason::GitIgnored);
+        assert_eq!(explain("build/gen.rs"), ExclusionReason::ExcludePattern("build/".to_string()));
+        assert_eq!(explain(".idea/workspace.rs"), ExclusionReason::Hidden);
+        assert_eq!(explain("certs/server.key"), ExclusionReason::SecretFile);
+        assert_eq!(explain("big.rs"), ExclusionReason::FileTooLarge);
+
+        // 与 collect_blobs 使用相同的规则：报告为未排除的文件正是被收集的文件
+        let root = dir.path().to_string_lossy().to_string();
+        let collected: Vec<String> = collect_blobs(&root, &opts).unwrap().0.into_iter().map(|b| b.path).collect();
+        assert_eq!(collected, vec!["main.rs", "src/lib.rs"]);
+
+        config.include_dirs = Some(vec!["src".to_string()]);
+        let include_opts = CollectOptions::from_config(&config);
+        assert_eq!(explain_file_exclusion(dir.path(), Path::new("main.rs"), &include_opts).unwrap(), ExclusionReason::OutsideIncludeDirs);
+        assert_eq!(explain_file_exclusion(dir.path(), Path::new("src/lib.rs"), &include_opts).unwrap(), ExclusionReason::NotExcluded);
+        // 绝对路径与相对路径等价，项目外的文件返回错误
+        assert_eq!(
+            explain_file_exclusion(dir.path(), &dir.path().join("main.rs"), &opts).unwrap(),
+            ExclusionReason::NotExcluded
+        );
+        assert!(explain_file_exclusion(&dir.path().join("build"), &dir.path().join("main.rs"), &opts).is_err());
     }
 
-    // 发起检索
-    log_important!(info,
-        "=== 开始代码检索（仅搜索模式） ==="
-    );
-    let search_url = format!("{}/agents/codebase-retrieval", base_url);
-    log_important!(info, "检索请求: url={}, 使用blobs数量={}, 查询内容={}", search_url, blob_names.len(), query);
+    #[test]
+    fn test_include_dirs_composes_with_exclude_patterns() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for rel in ["main.rs", "src/lib.rs", "src/generated/api.rs", "lib/util.rs", "packages/web/src/app.rs", "packages/web/test.rs", "docs/guide.rs"] {
+            let path = dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "fn main() {}\n").unwrap();
+        }
 
-    let payload = serde_json::json!({
-        "information_request": query,
-        "blobs": {"checkpoint_id": serde_json::Value::Null, "added_blobs": blob_names, "deleted_blobs": []},
-        "dialog": [],
-        "max_output_length": 0,
-        "disable_codebase_retrieval": false,
-        "enable_commit_retrieval": false,
-    });
+        let config = AcemcpConfig {
+            text_extensions: Some(vec![".rs".to_string()]),
+            include_dirs: Some(vec!["src".to_string(), "lib/".to_string(), "packages/*/src".to_string()]),
+            exclude_patterns: Some(vec!["generated".to_string()]),
+            ..Default::default()
+        };
+        let (blobs, _) = collect_blobs(&dir.path().to_string_lossy(), &CollectOptions::from_config(&config)).unwrap();
 
-    log_important!(info, "检索载荷大小: {} 字节", payload.to_string().len());
+        let mut paths: Vec<&str> = blobs.iter().map(|b| b.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["lib/util.rs", "packages/web/src/app.rs", "src/lib.rs"]);
+    }
 
-    let client = Client::new();
-    let value: serde_json::Value = retry_request(|| async {
-        let r = client
-            .post(&search_url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .header(CONTENT_TYPE, "application/json")
-            .json(&payload)
-            .send()
-            .await?;
+    #[test]
+    fn test_compare_index_versions_diffs_project_blobs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (a, b) = (dir.path().join("a.json"), dir.path().join("b.json"));
+        fs::write(&a, r#"{"/missing/project": ["h1", "h2", "h3"], "/other": ["x"]}"#).unwrap();
+        fs::write(&b, r#"{"/missing/project": ["h4", "h2", "h1"]}"#).unwrap();
 
-        let status = r.status();
-        log_important!(info, "检索请求HTTP响应状态: {}", status);
+        let diff = compare_index_versions("/missing/project", &a, &b).unwrap();
+        assert!(compare_index_versions("/not/indexed", &a, &b).is_err());
 
-        if !status.is_success() {
-            let body = r.text().await.unwrap_or_default();
-            anyhow::bail!("HTTP {} {}", status, body);
-        }
+        assert_eq!(diff.added, vec!["h4".to_string()]);
+        assert_eq!(diff.removed, vec!["h3".to_string()]);
+        assert_eq!(diff.unchanged, vec!["h1".to_string(), "h2".to_string()]);
+    }
 
-        let v: serde_json::Value = r.json().await?;
-        log_important!(info, "检索响应数据: {}", serde_json::to_string_pretty(&v).unwrap_or_default());
-        Ok(v)
-    }, 3, 2.0).await?;
+    #[test]
+    fn test_find_index_inconsistencies() {
+        let status = |root: &str, status: IndexStatus| {
+            let mut s = ProjectIndexStatus::default();
+            s.project_root = root.to_string();
+            s.status = status;
+            (root.to_string(), s)
+        };
+        let projects = ProjectsFile(
+            [("/p/ok", vec!["h1".to_string()]), ("/p/empty", vec![]), ("/p/orphan", vec!["h2".to_string()])]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        );
+        let all_status = ProjectsIndexStatus {
+            projects: [
+                status("/p/ok", IndexStatus::Synced),
+                status("/p/empty", IndexStatus::Synced),
+                status("/p/gone", IndexStatus::Failed),
+                status("/p/running", IndexStatus::Indexing),
+            ]
+            .into_iter()
+            .collect(),
+        };
 
-    let text = value
-        .get("formatted_retrieval")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
+        assert_eq!(find_index_inconsistencies(&projects, &all_status), vec![
+            IndexInconsistency::SyncedButEmpty { project_root: "/p/empty".to_string() },
+            IndexInconsistency::StatusWithoutBlobs { project_root: "/p/gone".to_string() },
+            IndexInconsistency::BlobsWithoutStatus { project_root: "/p/orphan".to_string(), blobs: 1 },
+        ]);
+    }
 
-    if text.is_empty() {
-        log_important!(info, "搜索返回空结果");
-        Ok("No relevant code context found for your query.".to_string())
-    } else {
-        log_important!(info, "搜索成功，返回文本长度: {}", text.len());
-        Ok(text)
+    #[test]
+    fn test_retrieval_extractor_decodes_across_chunk_boundaries() {
+        let body = r#"{"hits": [{"formatted_retrieval": "nested"}], "formatted_retrieval": "Path: a.rs\n中文 \"q\" \u4e2d \ud83d\ude00", "other": "formatted_retrieval"}"#;
+        // 逐字节输入，覆盖在多字节字符与转义序列中间分块的情况
+        let mut extractor = RetrievalExtractor::default();
+        let mut text = String::new();
+        for byte in body.as_bytes() {
+            text.push_str(&extractor.feed(std::slice::from_ref(byte)).unwrap());
+        }
+        assert_eq!(text, "Path: a.rs\n中文 \"q\" 中 😀");
+
+        let mut extractor = RetrievalExtractor::default();
+        assert_eq!(extractor.feed(body.as_bytes()).unwrap(), text);
     }
 }
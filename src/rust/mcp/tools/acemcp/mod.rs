@@ -3,8 +3,16 @@
 
 pub mod mcp;
 pub mod types;
+pub mod error;
 pub mod commands;
 pub mod watcher;
+pub mod rate_limit;
+pub mod config_reload;
+pub mod rerank;
+pub mod index_queue;
+pub mod circuit_breaker;
+pub mod secret_scan;
+pub mod audit;
 
 // 重新导出工具以便访问
 pub use mcp::AcemcpTool;
@@ -0,0 +1,107 @@
+// Acemcp 请求限速
+// 所有项目的上传与检索请求共用一个令牌桶，多个项目同时索引时对服务端的总请求速率仍受限
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::types::AcemcpConfig;
+
+/// 令牌桶限速器：每秒补充 `rate` 个令牌，最多积攒 `rate` 个（允许 1 秒的突发）
+///
+/// 令牌不足时先预留（令牌数变为负数）再等待，并发的请求按到达顺序依次放行
+pub struct RateLimiter {
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        let rate = requests_per_second.max(1) as f64;
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket { tokens: rate, refilled_at: Instant::now() }),
+        }
+    }
+
+    /// 每秒允许的请求数
+    pub fn requests_per_second(&self) -> u32 {
+        self.rate as u32
+    }
+
+    /// 获取一个令牌，令牌不足时等待到可用为止
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+            bucket.refilled_at = now;
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// 全局共享的限速器；配置中的速率变化后重新创建
+static GLOBAL_LIMITER: OnceLock<Mutex<Option<Arc<RateLimiter>>>> = OnceLock::new();
+
+fn global_limiter(requests_per_second: u32) -> Arc<RateLimiter> {
+    let slot = GLOBAL_LIMITER.get_or_init(|| Mutex::new(None));
+    let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+    match slot.as_ref() {
+        Some(limiter) if limiter.requests_per_second() == requests_per_second => limiter.clone(),
+        _ => {
+            let limiter = Arc::new(RateLimiter::new(requests_per_second));
+            *slot = Some(limiter.clone());
+            limiter
+        }
+    }
+}
+
+/// 发送请求前调用：配置了 `requests_per_second` 时从全局令牌桶获取令牌，未配置或为 0 时不限速
+pub(crate) async fn acquire(config: &AcemcpConfig) {
+    if let Some(rps) = config.requests_per_second.filter(|r| *r > 0) {
+        global_limiter(rps).acquire().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_requests_respect_rate() {
+        // 20 次/秒：前 20 个请求消耗初始令牌，其余 10 个至少需要等待 0.5 秒
+        let limiter = Arc::new(RateLimiter::new(20));
+        let started = Instant::now();
+        let handles: Vec<_> = (0..30)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(450), "elapsed: {:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_burst_within_rate_is_not_delayed() {
+        let limiter = RateLimiter::new(50);
+        let started = Instant::now();
+        for _ in 0..10 {
+            limiter.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+}
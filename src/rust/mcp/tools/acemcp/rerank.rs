@@ -0,0 +1,203 @@
+// Acemcp 本地重排
+// 将服务端返回的 formatted_retrieval 按 `Path: ` 行拆分为片段，以查询词对各片段的文本做 BM25 打分后重新排序
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// BM25 词频饱和参数
+const K1: f64 = 1.2;
+/// BM25 文档长度归一化参数
+const B: f64 = 0.75;
+/// 超过该大小的本地文件不读取，使用服务端返回的片段文本
+const MAX_LOCAL_FILE_BYTES: u64 = 1024 * 1024;
+/// 片段起始行的前缀
+const PATH_PREFIX: &str = "Path: ";
+
+/// 切分为小写的检索词：字母、数字与下划线组成的连续串为一个词，每个中日韩字符单独成词
+fn tokenize(text: &str) -> Vec<String> {
+    let is_cjk = |c: char| matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF);
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            tokens.push(c.to_string());
+        } else if c.is_alphanumeric() || c == '_' {
+            current.extend(c.to_lowercase());
+        } else if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// 最小的 BM25 打分器，语料为本次检索返回的片段
+struct Bm25 {
+    term_freqs: Vec<HashMap<String, usize>>,
+    doc_lens: Vec<usize>,
+    doc_freqs: HashMap<String, usize>,
+    avg_len: f64,
+}
+
+impl Bm25 {
+    fn new(documents: &[String]) -> Self {
+        let mut term_freqs = Vec::with_capacity(documents.len());
+        let mut doc_lens = Vec::with_capacity(documents.len());
+        let mut doc_freqs: HashMap<String, usize> = HashMap::new();
+        for doc in documents {
+            let tokens = tokenize(doc);
+            doc_lens.push(tokens.len());
+            let mut freqs: HashMap<String, usize> = HashMap::new();
+            for token in tokens {
+                *freqs.entry(token).or_default() += 1;
+            }
+            for term in freqs.keys() {
+                *doc_freqs.entry(term.clone()).or_default() += 1;
+            }
+            term_freqs.push(freqs);
+        }
+        let avg_len = if doc_lens.is_empty() {
+            0.0
+        } else {
+            doc_lens.iter().sum::<usize>() as f64 / doc_lens.len() as f64
+        };
+        Self { term_freqs, doc_lens, doc_freqs, avg_len }
+    }
+
+    fn score(&self, terms: &[String], doc: usize) -> f64 {
+        let n = self.term_freqs.len() as f64;
+        let len_norm = if self.avg_len > 0.0 { self.doc_lens[doc] as f64 / self.avg_len } else { 0.0 };
+        terms
+            .iter()
+            .map(|term| {
+                let tf = self.term_freqs[doc].get(term).copied().unwrap_or(0) as f64;
+                if tf == 0.0 {
+                    return 0.0;
+                }
+                let df = self.doc_freqs.get(term).copied().unwrap_or(0) as f64;
+                let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len_norm))
+            })
+            .sum()
+    }
+}
+
+/// 按 `Path: ` 行拆分检索文本，返回 (首个片段之前的说明文字, 各片段)
+pub(crate) fn split_snippets(formatted: &str) -> (String, Vec<String>) {
+    let mut header = String::new();
+    let mut snippets: Vec<String> = Vec::new();
+    for line in formatted.split_inclusive('\n') {
+        if line.starts_with(PATH_PREFIX) {
+            snippets.push(String::new());
+        }
+        match snippets.last_mut() {
+            Some(snippet) => snippet.push_str(line),
+            None => header.push_str(line),
+        }
+    }
+    (header, snippets)
+}
+
+/// 片段对应的源文件路径（去掉分块后缀）
+pub(crate) fn snippet_path(snippet: &str) -> Option<&str> {
+    let first_line = snippet.lines().next()?;
+    let path = first_line.strip_prefix(PATH_PREFIX)?.trim();
+    Some(super::mcp::blob_source_path(path)).filter(|p| !p.is_empty())
+}
+
+/// 将服务端返回的相对路径解析到项目根目录下；绝对路径、含 `..` 或规范化后不在根目录内的路径返回 None
+fn resolve_under_root(root: &Path, rel: &str) -> Option<PathBuf> {
+    let rel = Path::new(rel);
+    if rel.is_absolute() || rel.components().any(|c| !matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir)) {
+        return None;
+    }
+    let root = root.canonicalize().ok()?;
+    let path = root.join(rel).canonicalize().ok()?;
+    path.starts_with(&root).then_some(path)
+}
+
+/// 片段的打分文本：片段带有 `#L起始-结束` 行号后缀且本地文件可读时取本地文件中对应的行（反映未上传的修改），
+/// 否则使用片段本身；同一文件的不同分块各自打分
+fn snippet_document(root: &Path, snippet: &str) -> String {
+    let local_lines = || -> Option<String> {
+        let blob_path = snippet.lines().next()?.strip_prefix(PATH_PREFIX)?.trim();
+        let (rel, range) = blob_path.rsplit_once("#L")?;
+        let (start, end) = range.split_once('-')?;
+        let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+        let path = resolve_under_root(root, rel)?;
+        if !fs::metadata(&path).map(|m| m.is_file() && m.len() <= MAX_LOCAL_FILE_BYTES).unwrap_or(false) {
+            return None;
+        }
+        let content = fs::read_to_string(path).ok()?;
+        let lines: Vec<&str> = content.lines().skip(start.saturating_sub(1)).take(end.saturating_sub(start) + 1).collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    };
+    local_lines().unwrap_or_else(|| snippet.to_string())
+}
+
+/// 以 `query` 的检索词对各片段打分，按得分从高到低重排片段，
+/// 并在每个片段的 `Path: ` 行之后注明得分；得分相同时保持服务端原有顺序。
+/// 没有可识别的片段时原样返回
+pub(crate) fn rerank_retrieval(formatted: &str, query: &str, root: &Path) -> String {
+    let (header, snippets) = split_snippets(formatted);
+    if snippets.is_empty() {
+        return formatted.to_string();
+    }
+
+    let documents: Vec<String> = snippets.iter().map(|s| snippet_document(root, s)).collect();
+    let bm25 = Bm25::new(&documents);
+    let mut seen = HashSet::new();
+    let terms: Vec<String> = tokenize(query).into_iter().filter(|t| seen.insert(t.clone())).collect();
+
+    let mut scored: Vec<(f64, &String)> = snippets
+        .iter()
+        .enumerate()
+        .map(|(i, snippet)| (bm25.score(&terms, i), snippet))
+        .collect();
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut out = header;
+    for (score, snippet) in scored {
+        let (path_line, rest) = snippet.split_once('\n').unwrap_or((snippet.as_str(), ""));
+        out.push_str(&format!("{}\n（本地重排得分: {:.3}）\n{}", path_line, score, rest));
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rerank_scores_each_snippet() {
+        let root = std::env::temp_dir().join(format!("acemcp-rerank-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/ledger.rs"), "fn render_header() {}\nfn commit_transaction() { transaction.commit(); rollback(); }\n").unwrap();
+
+        // 同一文件的两个分块按各自的文本打分，不会因为属于同一文件而得分相同
+        let formatted = "The following code sections were retrieved:\nPath: src/ledger.rs#L1-1\nfn render_header() {}\nPath: src/ledger.rs#L2-2\nfn commit_transaction()";
+        let text = rerank_retrieval(formatted, "transaction rollback", &root);
+
+        let commit = text.find("Path: src/ledger.rs#L2-2").unwrap();
+        let header = text.find("Path: src/ledger.rs#L1-1").unwrap();
+        assert!(text.starts_with("The following code sections were retrieved:\n"));
+        assert!(commit < header, "{}", text);
+        assert!(text.contains("（本地重排得分: 0.000）"));
+
+        // 指向项目外的路径不读取本地文件
+        assert!(resolve_under_root(&root, "../outside.rs").is_none());
+        assert!(resolve_under_root(&root, "/etc/hosts").is_none());
+        assert!(resolve_under_root(&root, "src/ledger.rs").is_some());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 /// Acemcp搜索请求参数
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,10 +9,40 @@ pub struct AcemcpRequest {
     pub project_root_path: String,
     /// 用于查找相关代码上下文的自然语言搜索查询
     pub query: String,
+    /// 最多返回的结果条数（服务端返回结构化结果时生效）
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// 返回文本的最大字符数，为空时使用默认上限
+    #[serde(default)]
+    pub max_output_length: Option<usize>,
+    /// 之前的搜索结果中已出现过的文件路径（相对项目根目录），检索时排除这些文件的 blob
+    #[serde(default)]
+    pub exclude_paths: Option<Vec<String>>,
+    /// 是否以查询词对返回的各片段做 BM25 打分并重排（默认关闭）
+    #[serde(default)]
+    pub local_rerank: bool,
+    /// 检索结果的输出格式（默认纯文本）
+    #[serde(default)]
+    pub output_format: OutputFormat,
+}
+
+/// 检索结果的输出格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// 服务端返回的原始文本
+    #[default]
+    Plain,
+    /// 每个片段包裹在带语言标注的代码块中
+    Markdown,
+    /// 结构化的检索结果（JSON）
+    Json,
+    /// 只返回按相关度排序、去重后的 `路径:起始行-结束行` 引用，不含片段内容
+    Citations,
 }
 
 /// Acemcp配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AcemcpConfig {
     /// API端点URL
     pub base_url: Option<String>,
@@ -22,14 +52,285 @@ pub struct AcemcpConfig {
     pub batch_size: Option<u32>,
     /// 大文件分割前的最大行数
     pub max_lines_per_blob: Option<u32>,
+    /// 每个 blob 的最大字符数，与 `max_lines_per_blob` 任一达到即切分；未配置或为 0 时只按行数分块
+    pub max_chars_per_blob: Option<usize>,
     /// 要索引的文件扩展名列表
     pub text_extensions: Option<Vec<String>>,
     /// 要排除的模式列表
     pub exclude_patterns: Option<Vec<String>>,
+    /// 只索引这些目录（相对项目根目录的 glob，如 `src`、`packages/*/lib`）及其子目录
+    /// 未配置时不限制；与 `exclude_patterns` 同时生效
+    pub include_dirs: Option<Vec<String>>,
+    /// 要排除的文件扩展名列表（如 `.min.js`、`.map`、`.lock`）
+    /// 仅在 `text_extensions` 为空时生效，此时索引除这些扩展名外的所有文本文件
+    pub exclude_extensions: Option<Vec<String>>,
     /// 搜索时的智能等待配置（秒）
-    /// 当检测到索引正在进行时，随机等待 [min, max] 秒后再执行搜索
+    /// 当检测到索引正在进行时，轮询索引状态，索引完成即开始搜索，最多等待 max 秒（min 保留以兼容旧配置，不再使用）
     /// 默认值：Some((1, 5))，设为 None 则禁用智能等待
     pub smart_wait_range: Option<(u64, u64)>,
+    /// 最小 blob 字符数，分块后内容短于该值的 blob 会被跳过（默认 0，不过滤）
+    pub min_blob_chars: Option<u32>,
+    /// 最小 blob 内容字节数（默认 0，不过滤），用于跳过 `__init__.py` 之类几乎为空的文件
+    pub min_blob_content_length: Option<usize>,
+    /// 单个文件的大小上限（字节），超过时不读取也不索引（默认不限制，大文件按 `max_lines_per_blob` 拆分）
+    pub max_file_bytes: Option<u64>,
+    /// 目录遍历的最大深度（相对项目根目录，默认 20），超过该深度的目录不再进入
+    pub index_depth_limit: Option<usize>,
+    /// 是否启用搜索审计日志（默认关闭）
+    /// 启用后每次搜索的查询、blob 集合、载荷大小与响应摘要会以 JSON Lines 追加到项目的 search_audit.log
+    pub search_audit_enabled: Option<bool>,
+    /// 智能分块的最大向后查找行数
+    /// 设置后分块时会避免切在多行字符串、块注释或代码围栏内部；为 None 时保持按行硬切分
+    pub smart_split_lookahead_lines: Option<usize>,
+    /// 强制优先使用的文件编码（如 `gbk`、`big5`、`shift_jis`）
+    /// 设置后读取文件先按该编码解码，出现无效字符时才回退自动检测；项目根目录的 `.acemcp-encoding` 文件优先于该配置
+    pub encoding_override: Option<String>,
+    /// 大文件分块的命名方式：`line_range`（默认，如 `#L1-800`）或 `legacy`（旧格式 `#chunk1of3`）
+    pub chunk_naming: Option<String>,
+    /// token 刷新端点，请求返回 401 时自动 POST 该地址获取新 token 并重试一次
+    pub token_refresh_url: Option<String>,
+    /// token 刷新请求的 JSON 请求体（如 client_id、refresh_token）
+    pub token_refresh_payload: Option<HashMap<String, String>>,
+    /// 是否只索引 git 已跟踪或未被忽略的文件（`git ls-files --cached --others --exclude-standard`）
+    pub only_committed_files: Option<bool>,
+    /// 上传请求超时（毫秒，默认 120000）；批量上传的载荷可达数百 KB，应明显长于搜索超时
+    pub request_timeout_ms: Option<u64>,
+    /// 搜索请求超时（毫秒，默认 30000）
+    pub search_timeout_ms: Option<u64>,
+    /// 扩展名与排除模式匹配是否忽略大小写
+    /// 默认跟随平台：Windows/macOS 忽略大小写，Linux 等区分大小写
+    pub case_insensitive_paths: Option<bool>,
+    /// 单个响应体的大小上限（字节，默认 32 MB），超过时中止读取并报错
+    pub max_response_bytes: Option<usize>,
+    /// 项目命名空间，随上传与检索请求发送，多人共用服务端时用于隔离索引
+    pub project_namespace: Option<String>,
+    /// 未配置 `project_namespace` 时，用该盐值与项目根路径的哈希作为命名空间；两者都为空时不发送
+    pub project_namespace_salt: Option<String>,
+    /// 是否在计算哈希与上传前将 `\r\n` / `\r` 统一为 `\n`（默认开启）
+    /// 避免同一文件在 Windows 与 Linux 检出后哈希不同而重复上传
+    pub normalize_line_endings: Option<bool>,
+    /// 文件监听的防抖窗口（毫秒，默认 2000）：最后一次文件变更后超过该时间没有新的变更才触发索引
+    pub index_on_save_debounce_ms: Option<u64>,
+    /// 服务端返回的 blob_names 与上传批次不一致（数量不符、非字符串或重复）时是否判定该批次失败（默认关闭，仅记录警告）
+    pub strict_blob_names: Option<bool>,
+    /// 所有项目共享的请求速率上限（次/秒），上传与检索请求都计入；未配置或为 0 时不限速
+    pub requests_per_second: Option<u32>,
+    /// 额外信任的根证书（PEM 文件路径），用于内部 CA 签发的服务端证书；系统信任库仍然生效
+    pub ca_cert_path: Option<String>,
+    /// 跳过 TLS 证书校验（危险：连接可被中间人劫持，仅在无法配置 ca_cert_path 时临时使用）
+    /// 也可写作 `tls_insecure_skip_verify`
+    #[serde(alias = "tls_insecure_skip_verify")]
+    pub danger_accept_invalid_certs: Option<bool>,
+    /// 上传批次是否携带 `Idempotency-Key` 请求头（默认关闭）；同一批次重试时使用同一个 key，避免响应丢失后重复处理
+    pub use_idempotency_keys: Option<bool>,
+    /// 是否按项目根目录的 `.acemcp-synonyms.json` 扩展检索查询（默认关闭）
+    pub query_expansion: Option<bool>,
+    /// 最多同时监听的项目数（默认 20），超出时停止最久未活跃项目的监听
+    pub max_watched_projects: Option<usize>,
+    /// 监听中的项目定期全量对账的间隔（秒），弥补文件监听漏掉的变更；未配置或为 0 时关闭
+    pub reconcile_interval_secs: Option<u64>,
+    /// 遍历项目目录时是否跟随符号链接（默认跟随，符号链接成环时自动跳过）
+    pub follow_symlinks: Option<bool>,
+    /// 检索结果的最大字符数，超出时在行尾截断并追加 `[...truncated]`；请求中的 `max_output_length` 只能更小
+    pub search_result_max_length: Option<usize>,
+    /// 是否合并结构化检索结果中同一文件行区间相邻或重叠的分块（需 `#L起始-结束` 分块命名，默认关闭）
+    pub merge_adjacent_hits: Option<bool>,
+    /// 同时运行的后台首次索引数（默认 2），其余项目排队等待
+    pub max_concurrent_index_jobs: Option<usize>,
+    /// 对同一主机连续失败 5 次后熔断 60 秒，期间请求直接失败（默认关闭）
+    pub circuit_breaker_enabled: Option<bool>,
+    /// 索引成功后 POST 通知的地址（如触发 CI、发送群消息）
+    pub webhook_url: Option<String>,
+    /// 发送索引完成通知时附加的请求头（如 `Authorization`）
+    pub webhook_headers: Option<HashMap<String, String>>,
+    /// 是否跳过名称以 `.` 开头的文件与目录（默认 true）；`.env`、`*.pem`、`id_rsa` 等密钥文件总是跳过
+    pub skip_hidden: Option<bool>,
+    /// blob 内容包含密钥（AWS Access Key ID、PEM 私钥、Slack / GitHub 令牌）时的处理方式：
+    /// skip（默认，跳过该 blob 并记录警告）、redact（替换为 `[REDACTED]` 后上传）、off（不扫描）
+    pub secret_scan_policy: Option<String>,
+    /// 上传时 blob 路径与内容的编码：plain（默认）或 base64（附加 `"encoding": "base64"`，用于拒绝原始 Unicode JSON 的反向代理）
+    pub upload_chunk_encoding: Option<String>,
+    /// 索引前是否先探测 token 与 base_url 是否可用（默认 true），配置错误时在收集文件之前立即失败；
+    /// 5 分钟内对同一服务端有过成功请求时跳过探测
+    pub preflight_probe: Option<bool>,
+    /// 按项目根目录覆盖的配置（如 `auto_index`），键按规范化后的路径匹配
+    pub project_overrides: Option<HashMap<String, crate::config::AcemcpProjectOverride>>,
+    /// 启用后每次 sou / jiyi 工具调用（工具名、项目、查询长度、耗时与结果）以 JSON Lines 追加到 acemcp 数据目录下的 `audit.jsonl`
+    pub audit_log_enabled: Option<bool>,
+    /// 启用后 `search_context_with_rewrite` 会将项目的规则（Rule）与模式（Pattern）记忆作为上下文追加到查询
+    pub query_enrichment_enabled: Option<bool>,
+    /// 追加到查询的记忆最大字符数（默认 1000），超出时按优先级保留靠前的整条记忆
+    pub query_enrichment_max_chars: Option<usize>,
+    /// 完整索引时只收集该时间之后修改过的文件（RFC3339 时间或 `YYYY-MM-DD`，按 UTC 零点），更早的文件保留已有索引记录
+    pub index_only_changed_since: Option<String>,
+    /// 请求携带的 User-Agent，未配置时为 `<crate 名>/<版本>`，便于服务端区分客户端
+    pub user_agent: Option<String>,
+    /// 自定义分块脚本：以项目根目录为工作目录、文件的相对路径为参数执行，输出 `[{"path": ..., "content": ...}]`；
+    /// 退出码非零或输出无效时回退内置的按行分块
+    pub custom_splitter_script: Option<String>,
+}
+
+impl AcemcpConfig {
+    /// 推荐的默认文件扩展名列表（常见源码、标记与配置文件），设置界面据此预填
+    pub fn default_text_extensions() -> Vec<String> {
+        [
+            ".py", ".js", ".ts", ".jsx", ".tsx", ".java", ".go", ".rs", ".cpp", ".c", ".h", ".hpp",
+            ".cs", ".rb", ".php", ".md", ".txt", ".json", ".yaml", ".yml", ".toml", ".xml", ".html",
+            ".css", ".scss", ".sql", ".sh", ".bash",
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+    }
+
+    /// 未配置文件扩展名（且未使用 exclude_extensions 排除模式）时填充默认列表，返回是否使用了默认值
+    pub fn apply_default_text_extensions(&mut self) -> bool {
+        let no_text_exts = self.text_extensions.as_ref().map(|v| v.is_empty()).unwrap_or(true);
+        let no_exclude_exts = self.exclude_extensions.as_ref().map(|v| v.is_empty()).unwrap_or(true);
+        if no_text_exts && no_exclude_exts {
+            self.text_extensions = Some(Self::default_text_extensions());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 项目的覆盖配置：键与项目根目录都按规范化路径（解析符号链接、统一为 `/`、去掉末尾 `/`）比较
+    pub fn project_override(&self, project_root: &str) -> Option<&crate::config::AcemcpProjectOverride> {
+        let normalize = |path: &str| {
+            std::path::PathBuf::from(path)
+                .canonicalize()
+                .unwrap_or_else(|_| std::path::PathBuf::from(path))
+                .to_string_lossy()
+                .replace('\\', "/")
+                .trim_end_matches('/')
+                .to_string()
+        };
+        let overrides = self.project_overrides.as_ref().filter(|o| !o.is_empty())?;
+        let root = normalize(project_root);
+        overrides.iter().find(|(key, _)| normalize(key) == root).map(|(_, o)| o)
+    }
+
+    /// 搜索时是否为项目自动后台索引并监听文件变更（`project_overrides` 中的 `auto_index`，默认 true）
+    pub fn auto_index_enabled(&self, project_root: &str) -> bool {
+        self.project_override(project_root).and_then(|o| o.auto_index).unwrap_or(true)
+    }
+}
+
+/// 索引进度事件，由 `update_index` 推送给订阅者（前端通过 `acemcp-index-progress` 事件接收）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IndexProgressEvent {
+    /// 开始收集文件
+    CollectionStarted { project_root: String },
+    /// 文件收集完成，`files` 为收集到的 blob 数
+    CollectionDone { project_root: String, files: usize },
+    /// 上传批次结束（成功或失败），`index` 从 1 开始
+    BatchDone { project_root: String, index: usize, total: usize, success: bool },
+    /// 索引完成
+    Completed { project_root: String, outcome: IndexOutcome },
+    /// 索引失败
+    Failed { project_root: String, error: String },
+}
+
+/// 配置校验发现的错误（会导致索引或搜索失败），`field` 为对应的配置字段名
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfigError {
+    /// 无法读取配置文件
+    Unreadable { message: String },
+    /// 未配置 base_url
+    MissingBaseUrl,
+    /// base_url 格式无效
+    InvalidBaseUrl { value: String },
+    /// 未配置 token
+    MissingToken,
+    /// 批次大小必须大于 0
+    InvalidBatchSize { value: u32 },
+    /// 每个 blob 的最大行数必须大于 0
+    InvalidMaxLinesPerBlob { value: u32 },
+    /// 目录遍历深度必须大于 0
+    InvalidIndexDepthLimit { value: usize },
+    /// 不支持的编码覆盖名称
+    InvalidEncodingOverride { value: String },
+    /// token 刷新地址格式无效
+    InvalidTokenRefreshUrl { value: String },
+    /// 上传超时必须大于 0
+    InvalidRequestTimeout { value: u64 },
+    /// 搜索超时必须大于 0
+    InvalidSearchTimeout { value: u64 },
+    /// 无法读取或解析的根证书文件
+    InvalidCaCert { path: String, message: String },
+    /// 无法解析的修改时间下限
+    InvalidChangedSince { value: String },
+}
+
+impl ConfigError {
+    /// 对应的配置字段名
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::Unreadable { .. } => "config",
+            Self::MissingBaseUrl | Self::InvalidBaseUrl { .. } => "base_url",
+            Self::MissingToken => "token",
+            Self::InvalidBatchSize { .. } => "batch_size",
+            Self::InvalidMaxLinesPerBlob { .. } => "max_lines_per_blob",
+            Self::InvalidIndexDepthLimit { .. } => "index_depth_limit",
+            Self::InvalidEncodingOverride { .. } => "encoding_override",
+            Self::InvalidTokenRefreshUrl { .. } => "token_refresh_url",
+            Self::InvalidRequestTimeout { .. } => "request_timeout_ms",
+            Self::InvalidSearchTimeout { .. } => "search_timeout_ms",
+            Self::InvalidCaCert { .. } => "ca_cert_path",
+            Self::InvalidChangedSince { .. } => "index_only_changed_since",
+        }
+    }
+}
+
+/// 配置校验发现的警告（不影响运行，但可能与预期不符），`field` 为对应的配置字段名
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfigWarning {
+    /// 未配置文件扩展名，将使用默认列表
+    EmptyTextExtensions,
+    /// 扩展名缺少前导 `.`，会被当作完整文件名匹配（如 `rs` 只匹配名为 `rs` 的文件）
+    TextExtensionMissingDot { value: String },
+    /// 无法编译的文件名 glob，会被忽略
+    InvalidTextExtensionGlob { value: String },
+    /// 无法编译的排除模式
+    InvalidExcludePattern { value: String },
+    /// 未配置允许列表时 exclude_extensions 才生效，当前配置会被忽略
+    ExcludeExtensionsIgnored,
+    /// 未知的分块命名方式，将使用默认的 line_range
+    UnknownChunkNaming { value: String },
+    /// 未知的上传编码方式，将使用默认的 plain
+    UnknownUploadChunkEncoding { value: String },
+    /// base_url 使用明文 http，token 可能被窃听
+    InsecureBaseUrl { value: String },
+    /// 已关闭 TLS 证书校验
+    InvalidCertsAccepted,
+}
+
+impl ConfigWarning {
+    /// 对应的配置字段名
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::EmptyTextExtensions
+            | Self::TextExtensionMissingDot { .. }
+            | Self::InvalidTextExtensionGlob { .. } => "text_extensions",
+            Self::InvalidExcludePattern { .. } => "exclude_patterns",
+            Self::ExcludeExtensionsIgnored => "exclude_extensions",
+            Self::UnknownChunkNaming { .. } => "chunk_naming",
+            Self::UnknownUploadChunkEncoding { .. } => "upload_chunk_encoding",
+            Self::InsecureBaseUrl { .. } => "base_url",
+            Self::InvalidCertsAccepted => "danger_accept_invalid_certs",
+        }
+    }
+}
+
+/// 配置校验结果（供前端按字段高亮）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ConfigValidation {
+    pub warnings: Vec<ConfigWarning>,
+    pub errors: Vec<ConfigError>,
 }
 
 /// 索引状态枚举
@@ -71,6 +372,43 @@ pub struct ProjectIndexStatus {
     pub last_error: Option<String>,
     /// 按目录聚合的统计信息（目录路径 -> (已索引, 待处理)）
     pub directory_stats: HashMap<String, (usize, usize)>,
+    /// 最近一次完整索引中各解码方式的文件数（如 `utf8`、`gbk`、`lossy`）
+    /// `lossy` 非零说明有文件未能正确识别编码，可能出现乱码
+    #[serde(default)]
+    pub encoding_breakdown: HashMap<String, usize>,
+    /// 最近一次完整索引中所有 blob 内容的总字节数
+    #[serde(default)]
+    pub indexed_bytes: u64,
+    /// 最近一次索引中新上传 blob 内容的字节数
+    #[serde(default)]
+    pub uploaded_bytes: u64,
+    /// 最近的状态变迁记录（按时间先后，最多保留 `STATUS_HISTORY_LIMIT` 条）
+    #[serde(default)]
+    pub status_history: VecDeque<StatusEvent>,
+    /// 内容高度重复（压缩比低于 0.1）的文件，按压缩比从低到高排列；每次索引只重新计算本次上传的文件
+    #[serde(default)]
+    pub compressible_files: Vec<CompressibleFile>,
+}
+
+/// 内容高度重复的文件（如自动生成的代码、大型配置模板），通常不需要拆成很多 blob
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompressibleFile {
+    pub path: String,
+    /// zlib 压缩后长度 / 原始长度
+    pub ratio: f64,
+}
+
+/// 每个项目最多保留的状态变迁记录数
+pub const STATUS_HISTORY_LIMIT: usize = 50;
+
+/// 一次索引状态变迁
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatusEvent {
+    pub timestamp: DateTime<Utc>,
+    pub from_status: IndexStatus,
+    pub to_status: IndexStatus,
+    /// 变迁说明（如失败时的错误信息）
+    pub detail: Option<String>,
 }
 
 impl Default for ProjectIndexStatus {
@@ -87,6 +425,11 @@ impl Default for ProjectIndexStatus {
             last_failure_time: None,
             last_error: None,
             directory_stats: HashMap::new(),
+            encoding_breakdown: HashMap::new(),
+            indexed_bytes: 0,
+            uploaded_bytes: 0,
+            status_history: VecDeque::new(),
+            compressible_files: Vec::new(),
         }
     }
 }
@@ -98,6 +441,138 @@ pub struct ProjectsIndexStatus {
     pub projects: HashMap<String, ProjectIndexStatus>,
 }
 
+/// projects.json 与 projects_status.json 之间的不一致（`AcemcpTool::doctor` 的检查结果）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IndexInconsistency {
+    /// 状态为已同步，但 projects.json 中没有任何 blob，检索时会提示“项目尚未索引或索引为空”
+    SyncedButEmpty { project_root: String },
+    /// projects.json 中有 blob 记录，但没有对应的索引状态
+    BlobsWithoutStatus { project_root: String, blobs: usize },
+    /// 有索引状态，但 projects.json 中没有该项目的记录且项目目录已不存在
+    StatusWithoutBlobs { project_root: String },
+}
+
+impl IndexInconsistency {
+    /// 涉及的项目根路径
+    pub fn project_root(&self) -> &str {
+        match self {
+            Self::SyncedButEmpty { project_root }
+            | Self::BlobsWithoutStatus { project_root, .. }
+            | Self::StatusWithoutBlobs { project_root } => project_root,
+        }
+    }
+
+    /// 建议的修复方式
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            Self::SyncedButEmpty { .. } => "重新索引该项目",
+            Self::BlobsWithoutStatus { .. } => "重新索引该项目以刷新状态",
+            Self::StatusWithoutBlobs { .. } => "清理已删除项目的索引记录",
+        }
+    }
+}
+
+/// 索引记录一致性检查结果
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DoctorReport {
+    /// 发现的不一致
+    pub issues: Vec<IndexInconsistency>,
+    /// 已自动修复的不一致（`issues` 的子集）
+    pub repaired: Vec<IndexInconsistency>,
+}
+
+/// 单次索引更新的结构化结果（供前端直接读取各项计数）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct IndexOutcome {
+    /// 本次新上传的 blob 数量
+    pub uploaded: usize,
+    /// 已存在于索引中而跳过上传的 blob 数量
+    pub skipped_existing: usize,
+    /// 因内容过短（低于 min_blob_chars 或 min_blob_content_length）而跳过的 blob 数量
+    pub skipped_small: usize,
+    /// 因内容包含密钥而跳过上传的 blob 数量（`secret_scan_policy` 为 skip 时）
+    #[serde(default)]
+    pub secrets_skipped: usize,
+    /// 上传前脱敏替换的密钥片段数量（`secret_scan_policy` 为 redact 时）
+    #[serde(default)]
+    pub secrets_redacted: usize,
+    /// 索引完成后项目的 blob 总数
+    pub total_blobs: usize,
+    /// 上传失败的批次编号（从 1 开始）
+    pub failed_batches: Vec<usize>,
+    /// 本次索引耗时（毫秒）
+    pub duration_ms: u64,
+    /// 与上次索引相比的文件级变更
+    #[serde(default)]
+    pub diff: IndexDiff,
+}
+
+/// 配置文件迁移时的一次字段改名
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfigMigration {
+    pub from: String,
+    pub to: String,
+}
+
+/// 配置文件迁移结果（`AcemcpTool::migrate_config_schema`）
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct MigrationReport {
+    /// 已改名的字段
+    pub applied: Vec<ConfigMigration>,
+    /// 无法识别、原样保留的字段
+    pub unknown_fields: Vec<String>,
+    /// 对应的当前字段已有值、因而未迁移的旧字段
+    pub conflicts: Vec<String>,
+}
+
+/// 两份 projects.json 快照中同一项目的 blob 差异（blob 名称按字典序排列）
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct IndexVersionDiff {
+    /// 只出现在快照 B 中的 blob
+    pub added: Vec<String>,
+    /// 只出现在快照 A 中的 blob
+    pub removed: Vec<String>,
+    /// 两份快照中都存在的 blob
+    pub unchanged: Vec<String>,
+}
+
+/// 索引校验结果：重新遍历项目并计算哈希，与 projects.json 中记录的 blob 比较（只读，不访问服务端）
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct IndexValidation {
+    /// 规范化后的项目根目录
+    pub project_root: String,
+    /// projects.json 中记录的 blob 数
+    pub stored_blobs: usize,
+    /// 按当前文件内容计算出的 blob 数（修改时间早于 `index_only_changed_since` 而未读取的文件沿用已记录的 blob）
+    pub current_blobs: usize,
+    /// 已记录但不再对应任何当前文件内容的 blob 数
+    pub stale_blobs: usize,
+    /// 存在未索引 blob 的当前文件数
+    pub unindexed_files: usize,
+    /// 存在未索引 blob 的文件路径（相对项目根目录，按字典序排列）
+    pub unindexed_paths: Vec<String>,
+    /// 是否建议重新索引（存在过期 blob 或未索引文件）
+    pub reindex_recommended: bool,
+}
+
+/// 两次索引之间的文件级变更（路径相对项目根目录，按字典序排列）
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct IndexDiff {
+    /// 新增的文件
+    pub added: Vec<String>,
+    /// 路径相同但 blob 哈希发生变化的文件
+    pub modified: Vec<String>,
+    /// 已删除的文件
+    pub removed: Vec<String>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
 /// 单个文件的索引状态
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -124,4 +599,78 @@ pub struct ProjectFilesStatus {
     pub project_root: String,
     /// 文件状态列表
     pub files: Vec<FileIndexStatus>,
-}
\ No newline at end of file
+}
+/// 文件未被索引的原因（`AcemcpTool::explain_exclusion` 的结果），按索引时的过滤顺序判断
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", content = "pattern", rename_all = "snake_case")]
+pub enum ExclusionReason {
+    /// 文件或其所在目录被 .gitignore 忽略
+    GitIgnored,
+    /// 文件或其所在目录是隐藏的（以 `.` 开头）
+    Hidden,
+    /// 可能包含密钥的文件（`.env`、SSH 私钥、证书与私钥文件），始终不索引
+    SecretFile,
+    /// 文件或其所在目录命中排除模式，附带命中的模式
+    ExcludePattern(String),
+    /// 文件所在目录不在 `include_dirs` 允许列表内
+    OutsideIncludeDirs,
+    /// 文件所在目录超过 `index_depth_limit`
+    TooDeep,
+    /// 扩展名（或文件名）不在可索引范围内
+    WrongExtension,
+    /// 开启了 `only_committed_files`，文件未被 git 跟踪
+    NotCommitted,
+    /// 修改时间早于 `index_only_changed_since`
+    NotChangedSince,
+    /// 文件超过 `max_file_bytes`
+    FileTooLarge,
+    /// 文件无法读取
+    Unreadable,
+    /// 排除扩展名模式下内容含 NUL 字符，视为二进制文件
+    Binary,
+    /// 分块后的内容都短于 `min_blob_chars` / `min_blob_content_length`
+    TooShort,
+    /// 按 `secret_scan_policy` 跳过了全部内容
+    SecretContent,
+    /// 未被上述规则排除
+    NotExcluded,
+}
+
+/// 文件监听数量统计
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct WatcherStats {
+    /// 使用文件系统事件监听的项目数
+    pub event_watchers: usize,
+    /// 因系统监听数耗尽而改为定期重新索引的项目数
+    pub polling_projects: usize,
+    /// 最多同时监听的项目数
+    pub max_watched_projects: usize,
+}
+
+/// 后台索引队列状态
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct IndexQueueStats {
+    /// 排队等待的项目数
+    pub queued: usize,
+    /// 正在索引的项目数
+    pub running: usize,
+    /// 同时运行的后台索引上限
+    pub max_concurrent_jobs: usize,
+}
+
+/// 服务端存储用量（`/stats` 接口），与本地的 `ProjectIndexStatus` 对照查看
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ServerStats {
+    /// 服务端保存的 blob 总数
+    #[serde(default)]
+    pub total_blobs: u64,
+    /// 服务端保存的内容总字节数
+    #[serde(default)]
+    pub total_bytes: u64,
+    /// 服务端记录的项目（命名空间）数量
+    #[serde(default)]
+    pub project_count: u64,
+    /// 服务端版本号
+    #[serde(default)]
+    pub server_version: String,
+}
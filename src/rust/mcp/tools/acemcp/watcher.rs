@@ -1,27 +1,174 @@
 use anyhow::Result;
 use notify_debouncer_full::{
     new_debouncer,
-    notify::{RecommendedWatcher, RecursiveMode, Watcher},
+    notify::{self, RecommendedWatcher, RecursiveMode, Watcher},
     DebounceEventResult, Debouncer, FileIdMap,
 };
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
-use super::types::AcemcpConfig;
-use super::mcp::update_index;
+use super::types::{AcemcpConfig, WatcherStats};
+use super::mcp::{normalize_project_root, update_index, update_index_with_outcome, AcemcpTool};
 use crate::log_important;
 use crate::log_debug;
 
+/// 默认防抖窗口（毫秒）
+const DEFAULT_INDEX_ON_SAVE_DEBOUNCE_MS: u64 = 2000;
+/// notify 层的事件合并间隔，只用于合并操作系统对同一次写入产生的重复事件
+const NOTIFY_COALESCE_MS: u64 = 500;
+/// 默认最多同时监听的项目数
+const DEFAULT_MAX_WATCHED_PROJECTS: usize = 20;
+/// 操作系统拒绝新增监听时，改为按该间隔（秒）定期重新索引
+const FALLBACK_RESCAN_INTERVAL_SECS: u64 = 300;
+
+/// 后台任务每次触发时读取最新配置，配置热加载后无需重启监听即可生效；读取失败时沿用启动监听时的配置
+async fn latest_config(fallback: &AcemcpConfig) -> AcemcpConfig {
+    match AcemcpTool::get_acemcp_config().await {
+        Ok(config) => config,
+        Err(e) => {
+            log_debug!("读取最新配置失败，沿用启动监听时的配置: {}", e);
+            fallback.clone()
+        }
+    }
+}
+
+/// 按文件防抖：记录每个文件最近一次变更的时间，文件在防抖窗口内没有新的变更后才算就绪
+struct FileDebouncer {
+    window: Duration,
+    last_change: HashMap<PathBuf, Instant>,
+}
+
+impl FileDebouncer {
+    fn new(window: Duration) -> Self {
+        Self { window, last_change: HashMap::new() }
+    }
+
+    /// 记录一批变更的文件，已在等待中的文件重新计时
+    fn record(&mut self, paths: Vec<PathBuf>, now: Instant) {
+        for path in paths {
+            self.last_change.insert(path, now);
+        }
+    }
+
+    /// 最早就绪的文件的就绪时间，没有等待中的文件时返回 None
+    fn next_deadline(&self) -> Option<Instant> {
+        self.last_change.values().min().map(|t| *t + self.window)
+    }
+
+    /// 取出已就绪的文件（按路径排序）
+    fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let window = self.window;
+        let mut ready: Vec<PathBuf> = self
+            .last_change
+            .iter()
+            .filter(|(_, t)| now.duration_since(**t) >= window)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &ready {
+            self.last_change.remove(path);
+        }
+        ready.sort();
+        ready
+    }
+}
+
+/// 监听 `path` 并合并 `coalesce` 内的重复事件（操作系统对同一次写入常产生多个事件），
+/// 以本轮变更的路径调用 `on_change`；项目文件监听与配置文件热加载共用
+pub(crate) fn watch_path<F>(path: &Path, mode: RecursiveMode, coalesce: Duration, mut on_change: F) -> notify::Result<Debouncer<RecommendedWatcher, FileIdMap>>
+where
+    F: FnMut(Vec<PathBuf>) + Send + 'static,
+{
+    let mut debouncer = new_debouncer(coalesce, None, move |result: DebounceEventResult| match result {
+        Ok(events) => {
+            let paths: Vec<PathBuf> = events.into_iter().flat_map(|e| e.event.paths).collect();
+            if !paths.is_empty() {
+                on_change(paths);
+            }
+        }
+        Err(errors) => log_debug!("文件监听错误: {:?}", errors),
+    })?;
+    debouncer.watcher().watch(path, mode)?;
+    Ok(debouncer)
+}
+
+/// 正在监听的项目列表文件（按最近活跃时间从新到旧），应用重启后据此恢复监听
+fn watched_projects_file() -> PathBuf {
+    super::mcp::acemcp_data_dir().join("watched_projects.json")
+}
+
+/// 读取上次运行时正在监听的项目列表
+fn load_watched_projects() -> Vec<String> {
+    std::fs::read_to_string(watched_projects_file())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// 保存当前正在监听的项目列表；写入失败不影响监听
+fn save_watched_projects(watchers: &HashMap<String, WatchedProject>) {
+    let mut projects: Vec<(&String, Instant)> = watchers.iter().map(|(root, p)| (root, p.last_active)).collect();
+    projects.sort_by(|a, b| b.1.cmp(&a.1));
+    let roots: Vec<&String> = projects.into_iter().map(|(root, _)| root).collect();
+    let path = watched_projects_file();
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, serde_json::to_string_pretty(&roots).unwrap_or_default()));
+    if let Err(e) = result {
+        log_debug!("保存监听项目列表失败: {}", e);
+    }
+}
+
+/// 项目的监听方式
+enum WatchMode {
+    /// 文件系统事件监听
+    Events(Debouncer<RecommendedWatcher, FileIdMap>),
+    /// 监听数达到系统上限后的降级：后台任务定期重新索引
+    Polling(tokio::task::JoinHandle<()>),
+}
+
+/// 已监听的项目
+struct WatchedProject {
+    mode: WatchMode,
+    /// 最近一次搜索或文件变更的时间，超出数量上限时淘汰最久未活跃的项目
+    last_active: Instant,
+    /// 定期全量对账任务（`reconcile_interval_secs` 大于 0 时启动）
+    reconcile: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for WatchedProject {
+    fn drop(&mut self) {
+        if let WatchMode::Polling(handle) = &self.mode {
+            handle.abort();
+        }
+        if let Some(handle) = &self.reconcile {
+            handle.abort();
+        }
+    }
+}
+
+/// 是否是操作系统监听数量/句柄耗尽导致的失败（如 inotify 的 max_user_watches、max_user_instances）
+fn is_watch_limit_error(error: &notify::Error) -> bool {
+    match &error.kind {
+        notify::ErrorKind::MaxFilesWatch => true,
+        // ENOSPC（inotify watch 数耗尽）、EMFILE（inotify 实例或文件句柄耗尽）
+        notify::ErrorKind::Io(e) => matches!(e.raw_os_error(), Some(28) | Some(24)),
+        _ => false,
+    }
+}
+
 /// 文件监听器管理器
 /// 负责管理多个项目的文件监听器
 pub struct WatcherManager {
     /// 项目路径 -> 监听器句柄
-    watchers: Arc<Mutex<HashMap<String, Debouncer<RecommendedWatcher, FileIdMap>>>>,
+    watchers: Arc<Mutex<HashMap<String, WatchedProject>>>,
     /// 是否启用自动索引（全局开关）
     auto_index_enabled: Arc<Mutex<bool>>,
+    /// 最多同时监听的项目数（取最近一次启动监听时的配置）
+    max_watched_projects: Arc<Mutex<usize>>,
 }
 
 impl WatcherManager {
@@ -30,6 +177,7 @@ impl WatcherManager {
         Self {
             watchers: Arc::new(Mutex::new(HashMap::new())),
             auto_index_enabled: Arc::new(Mutex::new(true)), // 默认启用
+            max_watched_projects: Arc::new(Mutex::new(DEFAULT_MAX_WATCHED_PROJECTS)),
         }
     }
 
@@ -60,60 +208,77 @@ impl WatcherManager {
             .replace('\\', "/");
 
         // 检查是否已经在监听
-        {
-            let watchers = self.watchers.lock().unwrap();
-            if watchers.contains_key(&normalized_root) {
-                log_debug!("项目 {} 已在监听中，跳过重复启动", normalized_root);
-                return Ok(());
-            }
+        if self.mark_active(&normalized_root) {
+            log_debug!("项目 {} 已在监听中，跳过重复启动", normalized_root);
+            return Ok(());
         }
 
+        let max_watched = config.max_watched_projects.filter(|n| *n > 0).unwrap_or(DEFAULT_MAX_WATCHED_PROJECTS);
+        *self.max_watched_projects.lock().unwrap() = max_watched;
+        self.evict_least_recently_active(max_watched - 1);
+
         log_important!(info, "启动文件监听: project_root={}", normalized_root);
 
-        // 创建异步通道用于接收文件变更事件
-        let (tx, mut rx) = mpsc::channel::<()>(100);
-
-        // 创建 debouncer（1.5 秒延迟）
-        let mut debouncer = new_debouncer(
-            Duration::from_millis(1500),
-            None,
-            move |result: DebounceEventResult| {
-                match result {
-                    Ok(events) => {
-                        if !events.is_empty() {
-                            log_debug!("检测到文件变更事件，共 {} 个", events.len());
-                            // 发送信号触发索引更新
-                            let _ = tx.try_send(());
-                        }
-                    }
-                    Err(errors) => {
-                        log_debug!("文件监听错误: {:?}", errors);
-                    }
-                }
-            },
-        )?;
+        // 创建异步通道用于接收文件变更事件（变更的文件路径）
+        let (tx, mut rx) = mpsc::channel::<Vec<PathBuf>>(100);
 
-        // 添加监听路径
-        debouncer
-            .watcher()
-            .watch(Path::new(&project_root), RecursiveMode::Recursive)?;
+        let debouncer = match Self::create_debouncer(Path::new(&project_root), tx) {
+            Ok(debouncer) => debouncer,
+            Err(e) if is_watch_limit_error(&e) => {
+                log_important!(warn,
+                    "操作系统拒绝新增文件监听（{}），项目 {} 改为每 {} 秒定期重新索引；可调小 max_watched_projects 或调大系统监听上限",
+                    e, normalized_root, FALLBACK_RESCAN_INTERVAL_SECS
+                );
+                self.start_polling(normalized_root, config);
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         log_important!(info, "文件监听已启动: {}", normalized_root);
 
         // 保存 debouncer 到管理器
         {
             let mut watchers = self.watchers.lock().unwrap();
-            watchers.insert(normalized_root.clone(), debouncer);
+            let reconcile = Self::spawn_reconcile(normalized_root.clone(), config.clone());
+            watchers.insert(normalized_root.clone(), WatchedProject { mode: WatchMode::Events(debouncer), last_active: Instant::now(), reconcile });
+            save_watched_projects(&watchers);
         }
 
         // 启动后台任务处理索引更新
+        // 连续保存（如保存时自动格式化）会对同一文件产生多轮事件：每个文件的新事件都会重新计时，
+        // 某个文件在防抖窗口内没有新的变更后才为其触发索引，其他文件的持续变更不会推迟它
         let project_root_clone = normalized_root.clone();
         let config_clone = config.clone();
+        let watchers = self.watchers.clone();
+        let debounce = Duration::from_millis(config.index_on_save_debounce_ms.unwrap_or(DEFAULT_INDEX_ON_SAVE_DEBOUNCE_MS));
         tokio::spawn(async move {
-            while let Some(_) = rx.recv().await {
-                log_important!(info, "触发自动索引更新: project_root={}", project_root_clone);
+            let mut pending = FileDebouncer::new(debounce);
+            loop {
+                match pending.next_deadline() {
+                    None => match rx.recv().await {
+                        Some(paths) => pending.record(paths, Instant::now()),
+                        None => return,
+                    },
+                    Some(deadline) => tokio::select! {
+                        next = rx.recv() => match next {
+                            Some(paths) => pending.record(paths, Instant::now()),
+                            None => return,
+                        },
+                        _ = tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)) => {}
+                    },
+                }
+                let changed = pending.take_ready(Instant::now());
+                if changed.is_empty() {
+                    continue;
+                }
+                log_important!(info, "触发自动索引更新: project_root={}, 变更文件数={}", project_root_clone, changed.len());
+                if let Some(project) = watchers.lock().unwrap().get_mut(&project_root_clone) {
+                    project.last_active = Instant::now();
+                }
                 
-                match update_index(&config_clone, &project_root_clone).await {
+                let config = latest_config(&config_clone).await;
+                match update_index(&config, &project_root_clone).await {
                     Ok(blob_names) => {
                         log_important!(info, "自动索引更新成功: project_root={}, blobs={}", project_root_clone, blob_names.len());
                     }
@@ -127,6 +292,145 @@ impl WatcherManager {
         Ok(())
     }
 
+    /// 创建事件监听器，递归监听 `root`
+    fn create_debouncer(root: &Path, tx: mpsc::Sender<Vec<PathBuf>>) -> notify::Result<Debouncer<RecommendedWatcher, FileIdMap>> {
+        watch_path(root, RecursiveMode::Recursive, Duration::from_millis(NOTIFY_COALESCE_MS), move |paths| {
+            log_debug!("检测到文件变更事件，共 {} 个路径", paths.len());
+            let _ = tx.try_send(paths);
+        })
+    }
+
+    /// 无法使用事件监听时的降级：定期重新索引（未变化的项目不会重复上传）
+    fn start_polling(&self, normalized_root: String, config: AcemcpConfig) {
+        let project_root = normalized_root.clone();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(FALLBACK_RESCAN_INTERVAL_SECS));
+            // 第一次 tick 立即完成，跳过（启动监听时项目刚索引过）
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                log_debug!("定期重新索引: project_root={}", project_root);
+                let config = latest_config(&config).await;
+                if let Err(e) = update_index(&config, &project_root).await {
+                    log_important!(info, "定期重新索引失败: project_root={}, error={}", project_root, e);
+                }
+            }
+        });
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.insert(normalized_root, WatchedProject { mode: WatchMode::Polling(handle), last_active: Instant::now(), reconcile: None });
+        save_watched_projects(&watchers);
+    }
+
+    /// 应用启动时恢复上次运行时正在监听的项目，并为每个项目做一次对账（只上传/移除差异部分），
+    /// 补上应用未运行期间的文件修改；最多恢复 `max_watched_projects` 个最近活跃的项目，返回恢复的项目数
+    pub async fn restore_watching(&self, config: AcemcpConfig) -> usize {
+        if !self.is_auto_index_enabled() {
+            return 0;
+        }
+        let max_watched = config.max_watched_projects.filter(|n| *n > 0).unwrap_or(DEFAULT_MAX_WATCHED_PROJECTS);
+        let roots: Vec<String> = load_watched_projects()
+            .into_iter()
+            .filter(|root| Path::new(root).is_dir())
+            .take(max_watched)
+            .collect();
+
+        // 从最久未活跃的项目开始启动，恢复后的活跃时间先后与上次运行一致
+        let mut restored = 0;
+        for root in roots.iter().rev() {
+            match self.start_watching(root.clone(), config.clone()).await {
+                Ok(()) => {
+                    restored += 1;
+                    super::index_queue::enqueue_index(&config, root);
+                }
+                Err(e) => log_important!(warn, "恢复文件监听失败: project_root={}, error={}", root, e),
+            }
+        }
+        if restored > 0 {
+            log_important!(info, "已恢复 {} 个项目的文件监听", restored);
+        }
+        restored
+    }
+
+    /// 定期全量对账：重新收集项目文件并与已记录的 blob 集合比对，只上传/移除差异部分，
+    /// 修正文件监听漏掉的变更（网络盘、大量文件同时变化时常见）；`reconcile_interval_secs` 为 0 或未配置时不启动。
+    /// 每次对账前重新读取配置：间隔被修改时按新间隔重新计时，被关闭时跳过对账
+    fn spawn_reconcile(normalized_root: String, config: AcemcpConfig) -> Option<tokio::task::JoinHandle<()>> {
+        let mut interval_secs = config.reconcile_interval_secs.filter(|s| *s > 0)?;
+        log_debug!("启动定期对账: project_root={}, 间隔={} 秒", normalized_root, interval_secs);
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let config = latest_config(&config).await;
+                let Some(latest_secs) = config.reconcile_interval_secs.filter(|s| *s > 0) else {
+                    log_debug!("定期对账已在配置中关闭，跳过: project_root={}", normalized_root);
+                    continue;
+                };
+                if latest_secs != interval_secs {
+                    log_debug!("定期对账间隔已修改为 {} 秒: project_root={}", latest_secs, normalized_root);
+                    interval_secs = latest_secs;
+                    interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                    interval.tick().await;
+                }
+                match update_index_with_outcome(&config, &normalized_root).await {
+                    Ok((_, outcome)) if !outcome.diff.is_empty() || outcome.uploaded > 0 => {
+                        log_important!(info,
+                            "定期对账发现索引与磁盘不一致并已修正: project_root={}, 新增={}, 修改={}, 删除={}, 上传blobs={}",
+                            normalized_root, outcome.diff.added.len(), outcome.diff.modified.len(), outcome.diff.removed.len(), outcome.uploaded
+                        );
+                    }
+                    Ok(_) => log_debug!("定期对账完成，索引与磁盘一致: project_root={}", normalized_root),
+                    Err(e) => log_important!(info, "定期对账失败: project_root={}, error={}", normalized_root, e),
+                }
+            }
+        }))
+    }
+
+    /// 记录项目最近一次活跃（如被搜索）的时间，返回该项目是否正在监听
+    pub fn mark_active(&self, project_root: &str) -> bool {
+        let normalized_root = normalize_project_root(project_root);
+
+        let mut watchers = self.watchers.lock().unwrap();
+        match watchers.get_mut(&normalized_root) {
+            Some(project) => {
+                project.last_active = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 淘汰最久未活跃的项目，直到监听数不超过 `keep`
+    fn evict_least_recently_active(&self, keep: usize) {
+        let mut watchers = self.watchers.lock().unwrap();
+        while watchers.len() > keep {
+            let oldest = watchers
+                .iter()
+                .min_by_key(|(_, project)| project.last_active)
+                .map(|(root, _)| root.clone());
+            match oldest {
+                Some(root) => {
+                    watchers.remove(&root);
+                    log_important!(info, "监听项目数达到上限，已停止最久未活跃项目的文件监听: {}", root);
+                }
+                None => break,
+            }
+        }
+        save_watched_projects(&watchers);
+    }
+
+    /// 当前监听数量统计（供诊断）
+    pub fn stats(&self) -> WatcherStats {
+        let watchers = self.watchers.lock().unwrap();
+        let polling = watchers.values().filter(|p| matches!(p.mode, WatchMode::Polling(_))).count();
+        WatcherStats {
+            event_watchers: watchers.len() - polling,
+            polling_projects: polling,
+            max_watched_projects: *self.max_watched_projects.lock().unwrap(),
+        }
+    }
+
     /// 停止监听指定项目
     pub fn stop_watching(&self, project_root: &str) -> Result<()> {
         let normalized_root = PathBuf::from(project_root)
@@ -137,6 +441,7 @@ impl WatcherManager {
 
         let mut watchers = self.watchers.lock().unwrap();
         if watchers.remove(&normalized_root).is_some() {
+            save_watched_projects(&watchers);
             log_important!(info, "已停止文件监听: {}", normalized_root);
             Ok(())
         } else {
@@ -150,6 +455,7 @@ impl WatcherManager {
         let mut watchers = self.watchers.lock().unwrap();
         let count = watchers.len();
         watchers.clear();
+        save_watched_projects(&watchers);
         log_important!(info, "已停止所有文件监听，共 {} 个项目", count);
     }
 
@@ -181,3 +487,27 @@ pub fn get_watcher_manager() -> &'static WatcherManager {
     &WATCHER_MANAGER
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debounce_is_per_file() {
+        let start = Instant::now();
+        let ms = |n: u64| start + Duration::from_millis(n);
+        let mut debouncer = FileDebouncer::new(Duration::from_millis(100));
+
+        debouncer.record(vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")], ms(0));
+        // a.rs 连续保存会重新计时，但不推迟 b.rs
+        debouncer.record(vec![PathBuf::from("a.rs")], ms(80));
+        assert_eq!(debouncer.next_deadline(), Some(ms(100)));
+        assert!(debouncer.take_ready(ms(99)).is_empty());
+        assert_eq!(debouncer.take_ready(ms(100)), vec![PathBuf::from("b.rs")]);
+
+        assert_eq!(debouncer.next_deadline(), Some(ms(180)));
+        assert!(debouncer.take_ready(ms(150)).is_empty());
+        assert_eq!(debouncer.take_ready(ms(180)), vec![PathBuf::from("a.rs")]);
+        assert_eq!(debouncer.next_deadline(), None);
+    }
+}
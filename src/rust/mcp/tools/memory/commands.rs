@@ -0,0 +1,78 @@
+//! 记忆管理的 Tauri 命令
+//!
+//! MCP 的 `jiyi` 工具返回面向模型的文本；这里的命令返回结构化的记忆条目，供前端直接使用
+
+use chrono::{DateTime, Utc};
+
+use super::{shared_memory_store, MemoryCategory, MemoryEntry, MemoryStore, SyncReport};
+use crate::mcp::tools::acemcp::config_reload::current_config;
+use crate::mcp::utils::validate_project_path;
+
+/// 添加记忆，返回新增的记忆条目（含分配的 ID）
+#[tauri::command]
+pub async fn add_memory_cmd(project_path: String, content: String, category: String) -> Result<MemoryEntry, String> {
+    if content.trim().is_empty() {
+        return Err("缺少记忆内容".to_string());
+    }
+    with_store(&project_path, |store| {
+        let id = store.add_memory(&content, MemoryCategory::from_key(&category))?;
+        find_memory(store, &id)
+    })
+    .await
+}
+
+/// 设置记忆的回忆优先级（0-255），返回更新后的记忆条目
+#[tauri::command]
+pub async fn set_memory_priority_cmd(project_path: String, memory_id: String, priority: u8) -> Result<MemoryEntry, String> {
+    with_store(&project_path, |store| {
+        store.set_priority(&memory_id, priority)?;
+        find_memory(store, &memory_id)
+    })
+    .await
+}
+
+/// 设置记忆的过期时间（RFC3339），返回更新后的记忆条目
+#[tauri::command]
+pub async fn set_memory_expiry_cmd(project_path: String, memory_id: String, expires_at: String) -> Result<MemoryEntry, String> {
+    let expiry = DateTime::parse_from_rfc3339(expires_at.trim())
+        .map_err(|e| format!("过期时间格式错误（应为 RFC3339）: {}", e))?
+        .with_timezone(&Utc);
+    with_store(&project_path, |store| {
+        store.set_expiry(&memory_id, expiry)?;
+        find_memory(store, &memory_id)
+    })
+    .await
+}
+
+/// 将项目记忆备份到 acemcp 服务端（使用 acemcp 的 base_url 与 token）
+#[tauri::command]
+pub async fn sync_memories_to_remote_cmd(project_path: String) -> Result<SyncReport, String> {
+    validate_project_path(&project_path).map_err(|e| format!("路径验证失败: {}", e))?;
+    let config = current_config().map_err(|e| format!("获取配置失败: {}", e))?;
+    super::sync_to_remote(&project_path, &config).await.map_err(|e| e.to_string())
+}
+
+/// 从 acemcp 服务端恢复项目记忆，返回新增的记忆数量
+#[tauri::command]
+pub async fn restore_memories_from_remote_cmd(project_path: String) -> Result<usize, String> {
+    validate_project_path(&project_path).map_err(|e| format!("路径验证失败: {}", e))?;
+    let config = current_config().map_err(|e| format!("获取配置失败: {}", e))?;
+    super::restore_from_remote(&project_path, &config).await.map_err(|e| e.to_string())
+}
+
+/// 校验项目路径后在共享存储的写锁内执行操作
+async fn with_store<T>(
+    project_path: &str,
+    action: impl FnOnce(&dyn MemoryStore) -> anyhow::Result<T>,
+) -> Result<T, String> {
+    validate_project_path(project_path).map_err(|e| format!("路径验证失败: {}", e))?;
+    let store = shared_memory_store(project_path)
+        .await
+        .map_err(|e| format!("创建记忆管理器失败: {}", e))?;
+    let manager = store.write().await;
+    action(&**manager).map_err(|e| e.to_string())
+}
+
+fn find_memory(store: &dyn MemoryStore, id: &str) -> anyhow::Result<MemoryEntry> {
+    store.get_memory(id)?.ok_or_else(|| anyhow::anyhow!("未找到记忆: {}", id))
+}
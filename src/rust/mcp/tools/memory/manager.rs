@@ -1,9 +1,21 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use super::types::{MemoryEntry, MemoryCategory, MemoryMetadata};
+use super::store::MemoryStore;
+use super::types::{MemoryAnchor, MemoryEntry, MemoryCategory, MemoryLink, MemoryMetadata, DEFAULT_MEMORY_PRIORITY};
+use crate::constants::mcp::{DEFAULT_MEMORY_DIR_NAME, MEMORY_STORE_DIR_ENV};
+
+/// 记忆过期时间文件（记忆 ID -> 过期时间），与分类文件放在同一目录
+const EXPIRY_FILE_NAME: &str = "expiry.json";
+/// 记忆代码标注文件（记忆 ID -> 文件/行），与分类文件放在同一目录
+const ANCHOR_FILE_NAME: &str = "anchors.json";
+/// 记忆优先级文件（记忆 ID -> 优先级），只记录非默认优先级
+const PRIORITY_FILE_NAME: &str = "priority.json";
+/// 记忆关联文件（记忆 ID -> 关联列表），双向记录
+const LINKS_FILE_NAME: &str = "links.json";
 
 /// 记忆管理器
 pub struct MemoryManager {
@@ -16,12 +28,12 @@ impl MemoryManager {
     pub fn new(project_path: &str) -> Result<Self> {
         // 规范化项目路径
         let normalized_path = Self::normalize_project_path(project_path)?;
-        let memory_dir = normalized_path.join(".sanshu-memory");
+        let memory_dir = Self::resolve_memory_dir(&normalized_path);
 
         // 创建记忆目录，如果失败则说明项目不适合使用记忆功能
         fs::create_dir_all(&memory_dir)
             .map_err(|e| anyhow::anyhow!(
-                "无法在git项目中创建记忆目录: {}\n错误: {}\n这可能是因为项目目录没有写入权限。",
+                "无法创建记忆目录: {}\n错误: {}\n这可能是因为目录没有写入权限。",
                 memory_dir.display(),
                 e
             ))?;
@@ -37,8 +49,42 @@ impl MemoryManager {
         Ok(manager)
     }
 
+    /// 获取当前使用的记忆目录
+    pub fn memory_dir(&self) -> &Path {
+        &self.memory_dir
+    }
+
+    /// 解析记忆存储目录
+    ///
+    /// 优先级：环境变量 `SANSHU_MEMORY_DIR` > 配置项 `memory_store_dir` > 项目内 `.sanshu-memory`。
+    /// 使用外部存储根目录时，按规范化后的项目完整路径的哈希划分子目录（与 acemcp 的项目数据目录一致），
+    /// 不同位置的同名项目不会共用同一份记忆；存储根目录可放在同步盘或 git 仓库中共享。
+    ///
+    /// 迁移已有的项目内记忆：将 `<项目>/.sanshu-memory/` 下的文件整体移动到
+    /// `<存储根目录>/<项目路径哈希>/` 即可（项目规范化完整路径 SHA-256 的前 16 位十六进制），文件格式无需转换。
+    pub fn resolve_memory_dir(project_root: &Path) -> PathBuf {
+        let store_root = std::env::var(MEMORY_STORE_DIR_ENV)
+            .ok()
+            .or_else(|| {
+                crate::config::load_standalone_config()
+                    .ok()
+                    .and_then(|config| config.mcp_config.memory_store_dir)
+            })
+            .map(|dir| dir.trim().to_string())
+            .filter(|dir| !dir.is_empty());
+
+        match store_root {
+            Some(root) => {
+                let normalized = project_root.to_string_lossy().replace('\\', "/");
+                let digest = ring::digest::digest(&ring::digest::SHA256, normalized.as_bytes());
+                PathBuf::from(root).join(&hex::encode(digest.as_ref())[..16])
+            }
+            None => project_root.join(DEFAULT_MEMORY_DIR_NAME),
+        }
+    }
+
     /// 规范化项目路径
-    fn normalize_project_path(project_path: &str) -> Result<PathBuf> {
+    pub(crate) fn normalize_project_path(project_path: &str) -> Result<PathBuf> {
         // 使用增强的路径解码和规范化功能
         let normalized_path_str = crate::mcp::utils::decode_and_normalize_path(project_path)
             .map_err(|e| anyhow::anyhow!("路径格式错误: {}", e))?;
@@ -137,6 +183,15 @@ impl MemoryManager {
         None
     }
 
+    /// 读取已有记忆目录中的全部记忆（不创建任何文件），用于迁移到其他存储后端
+    pub(crate) fn load_legacy_entries(memory_dir: &Path) -> Result<Vec<MemoryEntry>> {
+        let manager = Self {
+            memory_dir: memory_dir.to_path_buf(),
+            project_path: String::new(),
+        };
+        manager.get_all_memories()
+    }
+
     /// 初始化记忆文件结构
     fn initialize_memory_structure(&self) -> Result<()> {
         // 创建各类记忆文件，使用新的结构化格式
@@ -168,9 +223,20 @@ impl MemoryManager {
         Ok(())
     }
 
+    /// 根据分类与内容生成稳定的记忆 ID
+    ///
+    /// 记忆文件中不保存 ID，因此使用内容哈希保证同一条记忆在每次解析、
+    /// 不同机器之间得到相同的 ID（远程同步按 ID 幂等写入）
+    pub(crate) fn memory_id(category: MemoryCategory, content: &str) -> String {
+        let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+        ctx.update(format!("{:?}", category).as_bytes());
+        ctx.update(content.trim().as_bytes());
+        hex::encode(&ctx.finish().as_ref()[..8])
+    }
+
     /// 添加记忆条目
     pub fn add_memory(&self, content: &str, category: MemoryCategory) -> Result<String> {
-        let id = uuid::Uuid::new_v4().to_string();
+        let id = Self::memory_id(category, content);
         let now = Utc::now();
 
         let entry = MemoryEntry {
@@ -179,6 +245,13 @@ impl MemoryManager {
             category,
             created_at: now,
             updated_at: now,
+            expires_at: None,
+            access_count: 0,
+            pinned: false,
+            tags: Vec::new(),
+            file: None,
+            line: None,
+            priority: DEFAULT_MEMORY_PRIORITY,
         };
 
         // 将记忆添加到对应的文件中
@@ -190,6 +263,191 @@ impl MemoryManager {
         Ok(id)
     }
 
+    /// 为记忆设置过期时间，过期后在下次回忆时自动删除
+    pub fn set_expiry(&self, id: &str, expiry: DateTime<Utc>) -> Result<()> {
+        if !self.get_all_memories()?.iter().any(|m| m.id == id) {
+            anyhow::bail!("未找到记忆: {}", id);
+        }
+
+        let mut expiry_map = self.load_expiry();
+        expiry_map.insert(id.to_string(), expiry);
+        self.save_expiry(&expiry_map)
+    }
+
+    /// 将记忆关联到文件/行，作为代码标注
+    pub fn set_anchor(&self, id: &str, anchor: &MemoryAnchor) -> Result<()> {
+        if !self.get_all_memories()?.iter().any(|m| m.id == id) {
+            anyhow::bail!("未找到记忆: {}", id);
+        }
+
+        let mut anchor_map = self.load_anchors();
+        anchor_map.insert(id.to_string(), anchor.clone());
+        self.save_anchors(&anchor_map)
+    }
+
+    /// 设置记忆的回忆优先级（越大越靠前）
+    pub fn set_priority(&self, id: &str, priority: u8) -> Result<()> {
+        if !self.get_all_memories()?.iter().any(|m| m.id == id) {
+            anyhow::bail!("未找到记忆: {}", id);
+        }
+
+        let mut priority_map = self.load_priorities();
+        if priority == DEFAULT_MEMORY_PRIORITY {
+            priority_map.remove(id);
+        } else {
+            priority_map.insert(id.to_string(), priority);
+        }
+        self.save_priorities(&priority_map)
+    }
+
+    /// 在两条记忆之间建立关联（双向），已存在关联时更新关联关系
+    pub fn link(&self, id_a: &str, id_b: &str, relation: &str) -> Result<()> {
+        if id_a == id_b {
+            anyhow::bail!("不能将记忆关联到自身: {}", id_a);
+        }
+        let memories = self.get_all_memories()?;
+        for id in [id_a, id_b] {
+            if !memories.iter().any(|m| m.id == id) {
+                anyhow::bail!("未找到记忆: {}", id);
+            }
+        }
+
+        let mut link_map = self.load_links();
+        for (from, to) in [(id_a, id_b), (id_b, id_a)] {
+            let links = link_map.entry(from.to_string()).or_default();
+            links.retain(|l| l.id != to);
+            links.push(MemoryLink { id: to.to_string(), relation: relation.to_string() });
+            links.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        self.save_links(&link_map)
+    }
+
+    /// 删除两条记忆之间的关联，返回关联此前是否存在
+    pub fn unlink(&self, id_a: &str, id_b: &str) -> Result<bool> {
+        let mut link_map = self.load_links();
+        let mut removed = false;
+        for (from, to) in [(id_a, id_b), (id_b, id_a)] {
+            if let Some(links) = link_map.get_mut(from) {
+                let before = links.len();
+                links.retain(|l| l.id != to);
+                removed |= links.len() != before;
+                if links.is_empty() {
+                    link_map.remove(from);
+                }
+            }
+        }
+        if removed {
+            self.save_links(&link_map)?;
+        }
+        Ok(removed)
+    }
+
+    /// 指定记忆的全部关联（按关联的记忆 ID 排序）
+    pub fn links(&self, id: &str) -> Result<Vec<MemoryLink>> {
+        Ok(self.load_links().remove(id).unwrap_or_default())
+    }
+
+    /// 读取记忆关联表
+    fn load_links(&self) -> HashMap<String, Vec<MemoryLink>> {
+        fs::read_to_string(self.memory_dir.join(LINKS_FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// 保存记忆关联表
+    fn save_links(&self, link_map: &HashMap<String, Vec<MemoryLink>>) -> Result<()> {
+        let data = serde_json::to_string_pretty(link_map)?;
+        fs::write(self.memory_dir.join(LINKS_FILE_NAME), data)?;
+        Ok(())
+    }
+
+    /// 读取记忆优先级表
+    fn load_priorities(&self) -> HashMap<String, u8> {
+        fs::read_to_string(self.memory_dir.join(PRIORITY_FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// 保存记忆优先级表
+    fn save_priorities(&self, priority_map: &HashMap<String, u8>) -> Result<()> {
+        let data = serde_json::to_string_pretty(priority_map)?;
+        fs::write(self.memory_dir.join(PRIORITY_FILE_NAME), data)?;
+        Ok(())
+    }
+
+    /// 读取记忆代码标注表
+    fn load_anchors(&self) -> HashMap<String, MemoryAnchor> {
+        fs::read_to_string(self.memory_dir.join(ANCHOR_FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// 保存记忆代码标注表
+    fn save_anchors(&self, anchor_map: &HashMap<String, MemoryAnchor>) -> Result<()> {
+        let data = serde_json::to_string_pretty(anchor_map)?;
+        fs::write(self.memory_dir.join(ANCHOR_FILE_NAME), data)?;
+        Ok(())
+    }
+
+    /// 读取记忆过期时间表
+    fn load_expiry(&self) -> HashMap<String, DateTime<Utc>> {
+        fs::read_to_string(self.memory_dir.join(EXPIRY_FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// 保存记忆过期时间表
+    fn save_expiry(&self, expiry_map: &HashMap<String, DateTime<Utc>>) -> Result<()> {
+        let data = serde_json::to_string_pretty(expiry_map)?;
+        fs::write(self.memory_dir.join(EXPIRY_FILE_NAME), data)?;
+        Ok(())
+    }
+
+    /// 删除已过期的记忆，返回删除数量
+    fn purge_expired(&self) -> Result<usize> {
+        let mut expiry_map = self.load_expiry();
+        if expiry_map.is_empty() {
+            return Ok(0);
+        }
+
+        let now = Utc::now();
+        let expired: Vec<MemoryEntry> = self
+            .get_all_memories()?
+            .into_iter()
+            .filter(|m| m.is_expired(now))
+            .collect();
+
+        let mut anchor_map = self.load_anchors();
+        let mut priority_map = self.load_priorities();
+        let mut link_map = self.load_links();
+        for entry in &expired {
+            self.remove_from_category_file(entry)?;
+            expiry_map.remove(&entry.id);
+            anchor_map.remove(&entry.id);
+            priority_map.remove(&entry.id);
+            link_map.remove(&entry.id);
+        }
+        // 删除其它记忆指向已过期记忆的关联
+        for links in link_map.values_mut() {
+            links.retain(|l| !expired.iter().any(|e| e.id == l.id));
+        }
+        link_map.retain(|_, links| !links.is_empty());
+
+        if !expired.is_empty() {
+            self.save_expiry(&expiry_map)?;
+            self.save_anchors(&anchor_map)?;
+            self.save_priorities(&priority_map)?;
+            self.save_links(&link_map)?;
+            self.update_metadata()?;
+        }
+
+        Ok(expired.len())
+    }
+
     /// 获取所有记忆
     pub fn get_all_memories(&self) -> Result<Vec<MemoryEntry>> {
         let mut memories = Vec::new();
@@ -257,9 +515,39 @@ impl MemoryManager {
         Ok(())
     }
 
+    /// 从分类文件中删除指定记忆所在的行
+    fn remove_from_category_file(&self, entry: &MemoryEntry) -> Result<()> {
+        let filename = match entry.category {
+            MemoryCategory::Rule => "rules.md",
+            MemoryCategory::Preference => "preferences.md",
+            MemoryCategory::Pattern => "patterns.md",
+            MemoryCategory::Context => "context.md",
+        };
+
+        let file_path = self.memory_dir.join(filename);
+        if !file_path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&file_path)?;
+        let kept: String = content
+            .split_inclusive('\n')
+            .filter(|line| {
+                let line = line.trim();
+                !(line.starts_with("- ") && Self::memory_id(entry.category, line[2..].trim()) == entry.id)
+            })
+            .collect();
+
+        fs::write(&file_path, kept)?;
+        Ok(())
+    }
+
     /// 解析记忆文件内容 - 简化版本
     fn parse_memory_file(&self, content: &str, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
         let mut memories = Vec::new();
+        let expiry_map = self.load_expiry();
+        let anchor_map = self.load_anchors();
+        let priority_map = self.load_priorities();
 
         // 按列表项解析，每个 "- " 开头的行是一个记忆条目
         for line in content.lines() {
@@ -267,12 +555,21 @@ impl MemoryManager {
             if line.starts_with("- ") && line.len() > 2 {
                 let content = line[2..].trim(); // 去掉 "- " 前缀
                 if !content.is_empty() {
+                    let id = Self::memory_id(category, content);
+                    let anchor = anchor_map.get(&id);
                     let entry = MemoryEntry {
-                        id: uuid::Uuid::new_v4().to_string(),
+                        expires_at: expiry_map.get(&id).copied(),
+                        file: anchor.map(|a| a.file.clone()),
+                        line: anchor.and_then(|a| a.line),
+                        priority: priority_map.get(&id).copied().unwrap_or(DEFAULT_MEMORY_PRIORITY),
+                        id,
                         content: content.to_string(),
                         category,
                         created_at: Utc::now(),
                         updated_at: Utc::now(),
+                        access_count: 0,
+                        pinned: false,
+                        tags: Vec::new(),
                     };
 
                     memories.push(entry);
@@ -313,50 +610,226 @@ impl MemoryManager {
 
         Ok(())
     }
+}
 
-    /// 获取项目信息供MCP调用方分析 - 压缩简化版本
-    pub fn get_project_info(&self) -> Result<String> {
-        // 汇总所有记忆规则并压缩
-        let all_memories = self.get_all_memories()?;
-        if all_memories.is_empty() {
-            return Ok("📭 暂无项目记忆".to_string());
-        }
+impl MemoryStore for MemoryManager {
+    fn add_memory(&self, content: &str, category: MemoryCategory) -> Result<String> {
+        MemoryManager::add_memory(self, content, category)
+    }
 
-        let mut compressed_info = Vec::new();
+    fn set_expiry(&self, id: &str, expiry: DateTime<Utc>) -> Result<()> {
+        MemoryManager::set_expiry(self, id, expiry)
+    }
 
-        // 按分类压缩汇总
-        let categories = [
-            (MemoryCategory::Rule, "规范"),
-            (MemoryCategory::Preference, "偏好"),
-            (MemoryCategory::Pattern, "模式"),
-            (MemoryCategory::Context, "背景"),
-        ];
+    fn set_anchor(&self, id: &str, anchor: &MemoryAnchor) -> Result<()> {
+        MemoryManager::set_anchor(self, id, anchor)
+    }
 
-        for (category, title) in categories.iter() {
-            let memories = self.get_memories_by_category(*category)?;
-            if !memories.is_empty() {
-                let mut items = Vec::new();
-                for memory in memories {
-                    let content = memory.content.trim();
-                    if !content.is_empty() {
-                        // 去除多余空格和换行，压缩内容
-                        let compressed_content = content
-                            .split_whitespace()
-                            .collect::<Vec<&str>>()
-                            .join(" ");
-                        items.push(compressed_content);
-                    }
-                }
-                if !items.is_empty() {
-                    compressed_info.push(format!("**{}**: {}", title, items.join("; ")));
-                }
-            }
-        }
+    fn set_priority(&self, id: &str, priority: u8) -> Result<()> {
+        MemoryManager::set_priority(self, id, priority)
+    }
 
-        if compressed_info.is_empty() {
-            Ok("📭 暂无有效项目记忆".to_string())
-        } else {
-            Ok(format!("📚 项目记忆总览: {}", compressed_info.join(" | ")))
-        }
+    fn link(&self, id_a: &str, id_b: &str, relation: &str) -> Result<()> {
+        MemoryManager::link(self, id_a, id_b, relation)
+    }
+
+    fn unlink(&self, id_a: &str, id_b: &str) -> Result<bool> {
+        MemoryManager::unlink(self, id_a, id_b)
+    }
+
+    fn links(&self, id: &str) -> Result<Vec<MemoryLink>> {
+        MemoryManager::links(self, id)
+    }
+
+    fn get_all_memories(&self) -> Result<Vec<MemoryEntry>> {
+        MemoryManager::get_all_memories(self)
+    }
+
+    fn get_memories_by_category(&self, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
+        MemoryManager::get_memories_by_category(self, category)
+    }
+
+    fn purge_expired(&self) -> Result<usize> {
+        MemoryManager::purge_expired(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时目录中创建 Markdown 记忆存储（不依赖 git 仓库与存储目录配置）
+    fn open_temp_manager(dir: &tempfile::TempDir) -> MemoryManager {
+        let manager = MemoryManager {
+            memory_dir: dir.path().to_path_buf(),
+            project_path: dir.path().to_string_lossy().to_string(),
+        };
+        manager.initialize_memory_structure().unwrap();
+        manager
+    }
+
+    fn read_sidecar<T: serde::de::DeserializeOwned + Default>(dir: &tempfile::TempDir, name: &str) -> T {
+        fs::read_to_string(dir.path().join(name))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_expiry_persists_and_purge_removes_expired_memories() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = open_temp_manager(&dir);
+
+        let expired = manager.add_memory("临时约定", MemoryCategory::Context).unwrap();
+        let later = manager.add_memory("发布前冻结依赖版本", MemoryCategory::Rule).unwrap();
+        let expiry = Utc::now() + chrono::Duration::days(1);
+        manager.set_expiry(&expired, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+        manager.set_expiry(&later, expiry).unwrap();
+        assert!(manager.set_expiry("missing", expiry).is_err());
+
+        // 重新打开后过期时间从 expiry.json 读回
+        let manager = open_temp_manager(&dir);
+        assert_eq!(manager.get_memory(&later).unwrap().unwrap().expires_at, Some(expiry));
+
+        assert_eq!(manager.purge_expired().unwrap(), 1);
+        assert!(manager.get_memory(&expired).unwrap().is_none());
+        assert!(!fs::read_to_string(dir.path().join("context.md")).unwrap().contains("临时约定"));
+        let expiry_map: HashMap<String, DateTime<Utc>> = read_sidecar(&dir, EXPIRY_FILE_NAME);
+        assert_eq!(expiry_map.keys().collect::<Vec<_>>(), vec![&later]);
+        assert_eq!(manager.purge_expired().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_anchors_persist_and_are_removed_with_expired_memories() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = open_temp_manager(&dir);
+
+        let late = manager.add_memory("这里刻意使用 O(n^2)，不要优化", MemoryCategory::Rule).unwrap();
+        manager.set_anchor(&late, &MemoryAnchor::new("./src/sort.rs", Some(40))).unwrap();
+        let early = manager.add_memory("输入已保证有序", MemoryCategory::Context).unwrap();
+        manager.set_anchor(&early, &MemoryAnchor::new("src\\sort.rs", Some(12))).unwrap();
+        assert!(manager.set_anchor("missing", &MemoryAnchor::new("src/sort.rs", None)).is_err());
+
+        // 重新打开后代码标注从 anchors.json 读回，路径已规范化
+        let manager = open_temp_manager(&dir);
+        let anchored = manager.memories_for_file("src/sort.rs").unwrap();
+        assert_eq!(anchored.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec![early.as_str(), late.as_str()]);
+        assert_eq!(anchored[0].line, Some(12));
+
+        manager.set_expiry(&early, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+        assert_eq!(manager.purge_expired().unwrap(), 1);
+        let anchor_map: HashMap<String, MemoryAnchor> = read_sidecar(&dir, ANCHOR_FILE_NAME);
+        assert_eq!(anchor_map.keys().collect::<Vec<_>>(), vec![&late]);
+    }
+
+    #[test]
+    fn test_priority_persists_and_orders_recall() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = open_temp_manager(&dir);
+
+        manager.add_memory("使用 4 空格缩进", MemoryCategory::Rule).unwrap();
+        let important = manager.add_memory("禁止直接修改生成的代码", MemoryCategory::Rule).unwrap();
+        manager.set_priority(&important, 200).unwrap();
+        assert!(manager.set_priority("missing", 1).is_err());
+
+        // 重新打开后优先级从 priority.json 读回
+        let manager = open_temp_manager(&dir);
+        assert_eq!(manager.get_memory(&important).unwrap().unwrap().priority, 200);
+        assert!(manager.get_project_info().unwrap().contains("**规范**: 禁止直接修改生成的代码; 使用 4 空格缩进"));
+
+        // 恢复默认优先级时不再记录
+        manager.set_priority(&important, DEFAULT_MEMORY_PRIORITY).unwrap();
+        assert!(read_sidecar::<HashMap<String, u8>>(&dir, PRIORITY_FILE_NAME).is_empty());
+
+        manager.set_priority(&important, 200).unwrap();
+        manager.set_expiry(&important, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+        assert_eq!(manager.purge_expired().unwrap(), 1);
+        assert!(read_sidecar::<HashMap<String, u8>>(&dir, PRIORITY_FILE_NAME).is_empty());
+    }
+
+    #[test]
+    fn test_import_markdown_writes_category_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = open_temp_manager(&dir);
+        let doc = dir.path().join("CONVENTIONS.md");
+        fs::write(&doc, "# Team Conventions\n\n- 错误处理统一使用 anyhow\n\n## Preferences\n\n* 提交信息使用中文\n").unwrap();
+        manager.add_memory("提交信息使用中文", MemoryCategory::Preference).unwrap();
+
+        let report = manager.import_markdown(&doc).unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+
+        // 导入的记忆写入分类文件，重新打开后仍然存在且不会重复导入
+        let manager = open_temp_manager(&dir);
+        let rules: Vec<String> = manager.get_memories_by_category(MemoryCategory::Rule).unwrap().into_iter().map(|m| m.content).collect();
+        assert_eq!(rules, vec!["错误处理统一使用 anyhow"]);
+        assert_eq!(manager.get_memories_by_category(MemoryCategory::Preference).unwrap().len(), 1);
+        assert_eq!(manager.import_markdown(&doc).unwrap().imported, 0);
+    }
+
+    #[test]
+    fn test_word_and_char_counts_skip_expired_memories() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = open_temp_manager(&dir);
+
+        manager.add_memory("错误处理使用 anyhow", MemoryCategory::Rule).unwrap();
+        manager.add_memory("prefer small commits", MemoryCategory::Preference).unwrap();
+        let expired = manager.add_memory("临时约定", MemoryCategory::Context).unwrap();
+        manager.set_expiry(&expired, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+
+        // 过期时间来自 expiry.json，未清理前同样不计入
+        let manager = open_temp_manager(&dir);
+        assert_eq!(manager.word_count().unwrap(), 10);
+        assert_eq!(manager.char_count().unwrap(), "错误处理使用 anyhow".chars().count() + "prefer small commits".len());
+    }
+
+    #[test]
+    fn test_merge_from_copies_sidecar_metadata() {
+        let (source_dir, target_dir) = (tempfile::TempDir::new().unwrap(), tempfile::TempDir::new().unwrap());
+        let source = open_temp_manager(&source_dir);
+        let target = open_temp_manager(&target_dir);
+
+        source.add_memory("错误处理统一使用 anyhow", MemoryCategory::Rule).unwrap();
+        let anchored = source.add_memory("输入已保证有序", MemoryCategory::Context).unwrap();
+        source.set_anchor(&anchored, &MemoryAnchor::new("src/sort.rs", Some(12))).unwrap();
+        source.set_priority(&anchored, 200).unwrap();
+        target.add_memory("错误处理统一使用 anyhow", MemoryCategory::Rule).unwrap();
+
+        let report = target.merge_from(&source.get_all_memories().unwrap()).unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+
+        // 代码标注与优先级写入目标项目的 anchors.json / priority.json
+        let target = open_temp_manager(&target_dir);
+        let copied = target.get_memory(&anchored).unwrap().unwrap();
+        assert_eq!((copied.file.as_deref(), copied.line, copied.priority), (Some("src/sort.rs"), Some(12), 200));
+        assert_eq!(target.get_all_memories().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_links_persist_and_are_removed_with_expired_memories() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let manager = open_temp_manager(&dir);
+
+        let decision = manager.add_memory("索引状态统一写入 projects_status.json", MemoryCategory::Rule).unwrap();
+        let limitation = manager.add_memory("状态文件不支持多进程并发写入", MemoryCategory::Context).unwrap();
+        let other = manager.add_memory("提交信息使用中文", MemoryCategory::Preference).unwrap();
+        manager.link(&decision, &limitation, "限制").unwrap();
+        manager.link(&decision, &other, "相关").unwrap();
+        assert!(manager.link(&decision, &decision, "自身").is_err());
+        assert!(manager.link(&decision, "missing", "相关").is_err());
+
+        // 重新打开后关联从 links.json 读回，双向可见
+        let manager = open_temp_manager(&dir);
+        let linked = manager.get_linked(&limitation).unwrap();
+        assert_eq!(linked.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec![decision.as_str()]);
+        assert_eq!(manager.links(&decision).unwrap().len(), 2);
+
+        assert!(manager.unlink(&other, &decision).unwrap());
+        assert!(!manager.unlink(&other, &decision).unwrap());
+
+        // 过期记忆被清理时，指向它的关联一并删除
+        manager.set_expiry(&limitation, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+        assert_eq!(manager.purge_expired().unwrap(), 1);
+        assert!(manager.links(&decision).unwrap().is_empty());
+        assert!(read_sidecar::<HashMap<String, Vec<MemoryLink>>>(&dir, LINKS_FILE_NAME).is_empty());
     }
 }
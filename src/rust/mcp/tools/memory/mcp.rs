@@ -1,7 +1,7 @@
 use anyhow::Result;
 use rmcp::model::{ErrorData as McpError, CallToolResult, Content};
 
-use super::{MemoryManager, MemoryCategory};
+use super::{shared_memory_store, MemoryAnchor, MemoryCategory, MemoryManager, MemoryStore, SharedMemoryStore, DEFAULT_MEMORY_RELATION};
 use crate::mcp::{JiyiRequest, utils::{validate_project_path, project_path_error}};
 use crate::log_debug;
 
@@ -24,7 +24,8 @@ impl MemoryTool {
             )).into());
         }
 
-        let manager = MemoryManager::new(&request.project_path)
+        let store = shared_memory_store(&request.project_path)
+            .await
             .map_err(|e| McpError::internal_error(format!("创建记忆管理器失败: {}", e), None))?;
 
         // 检查 sou 工具是否启用，如果启用则尝试触发后台索引
@@ -37,30 +38,172 @@ impl MemoryTool {
             }
         }
 
+        // 统计只读取记忆，取读锁；复制读取当前项目、写入目标项目；
+        // 其余操作会写入存储（回忆时会清理已过期的记忆），取写锁
+        let result = if request.action == "复制" {
+            Self::copy_memories(&request, &store, &index_hint).await?
+        } else if request.action == "统计" {
+            let manager = store.read().await;
+            Self::run_action(&request, &**manager, &index_hint)?
+        } else {
+            let manager = store.write().await;
+            Self::run_action(&request, &**manager, &index_hint)?
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
+    /// 将当前项目的全部记忆复制到 `content` 指定的目标项目
+    ///
+    /// 先在读锁内取出当前项目的记忆并释放，再获取目标项目的写锁，两个项目互相复制时不会死锁
+    async fn copy_memories(request: &JiyiRequest, store: &SharedMemoryStore, index_hint: &str) -> Result<String, McpError> {
+        let target = request.content.trim();
+        if target.is_empty() {
+            return Err(McpError::invalid_params("缺少目标项目路径".to_string(), None));
+        }
+        if let Err(e) = validate_project_path(target) {
+            return Err(project_path_error(format!("目标项目路径验证失败: {}\n原始路径: {}", e, target)).into());
+        }
+
+        let target_store = shared_memory_store(target)
+            .await
+            .map_err(|e| McpError::internal_error(format!("创建目标项目记忆管理器失败: {}", e), None))?;
+        if std::sync::Arc::ptr_eq(store, &target_store) {
+            return Err(McpError::invalid_params("目标项目与当前项目使用同一个记忆存储".to_string(), None));
+        }
+
+        let memories = store.read().await.get_all_memories()
+            .map_err(|e| McpError::internal_error(format!("获取记忆失败: {}", e), None))?;
+        let report = target_store.write().await.merge_from(&memories)
+            .map_err(|e| McpError::internal_error(format!("复制记忆失败: {}", e), None))?;
+
+        Ok(format!("✅ 已将 {} 条记忆复制到 {}，跳过重复 {} 条{}", report.imported, target, report.skipped, index_hint))
+    }
+
+    /// 在已持有锁的存储上执行记忆操作，返回结果文本
+    fn run_action(request: &JiyiRequest, manager: &dyn MemoryStore, index_hint: &str) -> Result<String, McpError> {
         let result = match request.action.as_str() {
             "记忆" => {
                 if request.content.trim().is_empty() {
                     return Err(McpError::invalid_params("缺少记忆内容".to_string(), None));
                 }
 
-                let category = match request.category.as_str() {
-                    "rule" => MemoryCategory::Rule,
-                    "preference" => MemoryCategory::Preference,
-                    "pattern" => MemoryCategory::Pattern,
-                    "context" => MemoryCategory::Context,
-                    _ => MemoryCategory::Context,
+                let category = MemoryCategory::from_key(&request.category);
+
+                let anchor = match request.file.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+                    Some(file) => Some(MemoryAnchor::new(&relative_to_project(&request.project_path, file), request.line)),
+                    None if request.line.is_some() => {
+                        return Err(McpError::invalid_params("设置行号时必须同时提供 file".to_string(), None));
+                    }
+                    None => None,
                 };
 
                 let id = manager.add_memory(&request.content, category)
                     .map_err(|e| McpError::internal_error(format!("添加记忆失败: {}", e), None))?;
 
-                format!("✅ 记忆已添加，ID: {}\n📝 内容: {}\n📂 分类: {:?}{}", id, request.content, category, index_hint)
+                let anchor_hint = match &anchor {
+                    Some(anchor) => {
+                        manager.set_anchor(&id, anchor)
+                            .map_err(|e| McpError::internal_error(format!("关联代码位置失败: {}", e), None))?;
+                        match anchor.line {
+                            Some(line) => format!("\n📍 位置: {}:{}", anchor.file, line),
+                            None => format!("\n📍 位置: {}", anchor.file),
+                        }
+                    }
+                    None => String::new(),
+                };
+
+                format!("✅ 记忆已添加，ID: {}\n📝 内容: {}\n📂 分类: {:?}{}{}", id, request.content, category, anchor_hint, index_hint)
             }
             "回忆" => {
                 let info = manager.get_project_info()
                     .map_err(|e| McpError::internal_error(format!("获取项目信息失败: {}", e), None))?;
                 format!("{}{}", info, index_hint)
             }
+            "统计" => {
+                let memories = manager.get_all_memories()
+                    .map_err(|e| McpError::internal_error(format!("获取记忆失败: {}", e), None))?;
+                let now = chrono::Utc::now();
+                let active: Vec<_> = memories.iter().filter(|m| !m.is_expired(now)).collect();
+                let count = |category: MemoryCategory| active.iter().filter(|m| m.category == category).count();
+                let words = manager.word_count()
+                    .map_err(|e| McpError::internal_error(format!("统计词数失败: {}", e), None))?;
+                let chars = manager.char_count()
+                    .map_err(|e| McpError::internal_error(format!("统计字符数失败: {}", e), None))?;
+
+                format!(
+                    "📊 记忆统计: 共 {} 条（规范 {} / 偏好 {} / 模式 {} / 背景 {}，其中代码标注 {} 条）\n📏 约 {} 词 / {} 字符{}",
+                    active.len(),
+                    count(MemoryCategory::Rule),
+                    count(MemoryCategory::Preference),
+                    count(MemoryCategory::Pattern),
+                    count(MemoryCategory::Context),
+                    active.iter().filter(|m| m.file.is_some()).count(),
+                    words,
+                    chars,
+                    index_hint
+                )
+            }
+            "设置过期" => {
+                let id = request.memory_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+                    .ok_or_else(|| McpError::invalid_params("缺少记忆 ID".to_string(), None))?;
+                let expiry = chrono::DateTime::parse_from_rfc3339(request.content.trim())
+                    .map_err(|e| McpError::invalid_params(format!("过期时间格式错误（应为 RFC3339）: {}", e), None))?
+                    .with_timezone(&chrono::Utc);
+
+                manager.set_expiry(id, expiry)
+                    .map_err(|e| McpError::internal_error(format!("设置过期时间失败: {}", e), None))?;
+
+                format!("✅ 记忆 {} 将于 {} 过期{}", id, expiry.to_rfc3339(), index_hint)
+            }
+            "优先级" => {
+                let id = request.memory_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+                    .ok_or_else(|| McpError::invalid_params("缺少记忆 ID".to_string(), None))?;
+                let priority: u8 = request.content.trim().parse()
+                    .map_err(|_| McpError::invalid_params(format!("优先级应为 0-255 的整数: {}", request.content.trim()), None))?;
+
+                manager.set_priority(id, priority)
+                    .map_err(|e| McpError::internal_error(format!("设置优先级失败: {}", e), None))?;
+
+                format!("✅ 记忆 {} 的优先级已设为 {}{}", id, priority, index_hint)
+            }
+            "关联" | "取消关联" => {
+                let id = request.memory_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+                    .ok_or_else(|| McpError::invalid_params("缺少记忆 ID".to_string(), None))?;
+                let other = request.content.trim();
+                if other.is_empty() {
+                    return Err(McpError::invalid_params("缺少要关联的另一条记忆 ID".to_string(), None));
+                }
+
+                if request.action == "关联" {
+                    let relation = request.relation.as_deref().map(str::trim).filter(|r| !r.is_empty())
+                        .unwrap_or(DEFAULT_MEMORY_RELATION);
+                    manager.link(id, other, relation)
+                        .map_err(|e| McpError::internal_error(format!("关联记忆失败: {}", e), None))?;
+                    format!("✅ 已关联记忆 {} 与 {}（{}）{}", id, other, relation, index_hint)
+                } else {
+                    let removed = manager.unlink(id, other)
+                        .map_err(|e| McpError::internal_error(format!("取消关联失败: {}", e), None))?;
+                    if removed {
+                        format!("✅ 已取消记忆 {} 与 {} 的关联{}", id, other, index_hint)
+                    } else {
+                        format!("ℹ️ 记忆 {} 与 {} 之间没有关联{}", id, other, index_hint)
+                    }
+                }
+            }
+            "导入文档" => {
+                let doc = request.content.trim();
+                if doc.is_empty() {
+                    return Err(McpError::invalid_params("缺少文档路径".to_string(), None));
+                }
+                let doc_path = resolve_project_document(&request.project_path, doc)
+                    .map_err(|e| McpError::invalid_params(e, None))?;
+
+                let report = manager.import_markdown(&doc_path)
+                    .map_err(|e| McpError::internal_error(format!("导入文档失败: {}", e), None))?;
+
+                format!("✅ 已从 {} 导入 {} 条记忆，跳过重复 {} 条{}", doc_path.display(), report.imported, report.skipped, index_hint)
+            }
             _ => {
                 return Err(McpError::invalid_params(
                     format!("未知的操作类型: {}", request.action),
@@ -69,8 +212,42 @@ impl MemoryTool {
             }
         };
 
-        Ok(CallToolResult::success(vec![Content::text(result)]))
+        Ok(result)
+    }
+}
+
+/// 将项目内的绝对路径转换为相对项目根目录的路径，其他路径原样返回
+fn relative_to_project(project_path: &str, file: &str) -> String {
+    let path = std::path::Path::new(file);
+    if !path.is_absolute() {
+        return file.to_string();
     }
+    MemoryManager::normalize_project_path(project_path)
+        .ok()
+        .and_then(|root| {
+            let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            path.strip_prefix(&root).ok().map(|rel| rel.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| file.to_string())
+}
+
+/// 解析要导入的文档路径（相对路径相对项目根目录），规范化后必须位于项目目录内
+fn resolve_project_document(project_path: &str, doc: &str) -> Result<std::path::PathBuf, String> {
+    let root = MemoryManager::normalize_project_path(project_path)
+        .map_err(|e| format!("项目路径无效: {}", e))?;
+    let doc_path = std::path::Path::new(doc);
+    let doc_path = if doc_path.is_absolute() {
+        doc_path.to_path_buf()
+    } else {
+        root.join(doc_path)
+    };
+    let canonical = doc_path
+        .canonicalize()
+        .map_err(|e| format!("无法访问文档: {}\n错误: {}", doc_path.display(), e))?;
+    if !canonical.starts_with(&root) {
+        return Err(format!("只能导入项目目录内的文档: {}", doc_path.display()));
+    }
+    Ok(canonical)
 }
 
 /// 检查 sou 工具是否启用
@@ -83,21 +260,16 @@ fn is_sou_enabled() -> bool {
 
 /// 尝试触发后台索引（仅在项目未初始化或索引失败时）
 async fn try_trigger_background_index(project_root: &str) -> Result<()> {
-    use super::super::acemcp::mcp::{get_initial_index_state, ensure_initial_index_background, InitialIndexState};
-
-    // 获取 acemcp 配置
-    let config = crate::config::load_standalone_config()
-        .map_err(|e| anyhow::anyhow!("读取配置文件失败: {}", e))?;
-
-    let acemcp_config = super::super::acemcp::types::AcemcpConfig {
-        base_url: config.mcp_config.acemcp_base_url,
-        token: config.mcp_config.acemcp_token,
-        batch_size: config.mcp_config.acemcp_batch_size,
-        max_lines_per_blob: config.mcp_config.acemcp_max_lines_per_blob,
-        text_extensions: config.mcp_config.acemcp_text_extensions,
-        exclude_patterns: config.mcp_config.acemcp_exclude_patterns,
-        smart_wait_range: Some((1, 5)),
-    };
+    use super::super::acemcp::mcp::{get_initial_index_state, ensure_initial_index_background, AcemcpTool, InitialIndexState};
+
+    // 与 sou 工具共用内存中的配置，配置文件的修改无需重启即可生效
+    let acemcp_config = AcemcpTool::get_acemcp_config().await?;
+
+    // 项目关闭了 auto_index 时不触发后台索引
+    if !acemcp_config.auto_index_enabled(project_root) {
+        log_debug!("项目已关闭 auto_index，跳过后台索引: {}", project_root);
+        return Ok(());
+    }
 
     // 检查索引状态
     let initial_state = get_initial_index_state(project_root);
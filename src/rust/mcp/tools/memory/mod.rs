@@ -2,11 +2,16 @@
 //!
 //! 提供全局记忆管理功能，用于存储和管理重要的开发规范、用户偏好和最佳实践
 
+pub mod commands;
 pub mod manager;
+pub mod sqlite;
+pub mod store;
 pub mod types;
 pub mod mcp;
 
 // 重新导出主要类型和功能
 pub use manager::MemoryManager;
-pub use types::{MemoryEntry, MemoryCategory, MemoryMetadata};
+pub use sqlite::SqliteMemoryManager;
+pub use store::{MemoryStore, SharedMemoryStore, open_memory_store, restore_from_remote, shared_memory_store, sync_to_remote};
+pub use types::{MemoryEntry, MemoryAnchor, MemoryCategory, MemoryLink, MemoryMetadata, SyncReport, ImportReport, DEFAULT_MEMORY_RELATION};
 pub use mcp::MemoryTool;
@@ -0,0 +1,535 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, Row};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::manager::MemoryManager;
+use super::store::MemoryStore;
+use super::types::{MemoryAnchor, MemoryCategory, MemoryEntry, MemoryLink, DEFAULT_MEMORY_PRIORITY};
+use crate::constants::mcp::SQLITE_MEMORY_DB_NAME;
+
+/// 当前数据库结构版本（记录在 `PRAGMA user_version` 中）
+/// 1: 初始结构；2: 增加代码标注列 file / line；3: 增加优先级列 priority；4: 增加记忆关联表 memory_links
+const SCHEMA_VERSION: i64 = 4;
+
+/// 记忆关联表，每条关联按两个方向各记录一行
+const CREATE_LINKS_TABLE: &str = "CREATE TABLE IF NOT EXISTS memory_links (
+    id_a TEXT NOT NULL,
+    id_b TEXT NOT NULL,
+    relation TEXT NOT NULL,
+    PRIMARY KEY (id_a, id_b)
+);";
+
+const SELECT_COLUMNS: &str =
+    "id, category, content, created_at, updated_at, access_count, pinned, expires_at, tags, file, line, priority";
+
+/// 基于 SQLite 的记忆管理器
+///
+/// 数据库文件与 Markdown 记忆放在同一目录（见 `MemoryManager::resolve_memory_dir`），
+/// 首次打开时自动导入已有的分类文件与过期时间
+pub struct SqliteMemoryManager {
+    conn: Mutex<Connection>,
+    db_path: PathBuf,
+}
+
+impl SqliteMemoryManager {
+    /// 打开（必要时创建并迁移）项目的记忆数据库
+    pub fn new(project_path: &str) -> Result<Self> {
+        let normalized_path = MemoryManager::normalize_project_path(project_path)?;
+        let memory_dir = MemoryManager::resolve_memory_dir(&normalized_path);
+
+        fs::create_dir_all(&memory_dir)
+            .map_err(|e| anyhow::anyhow!(
+                "无法创建记忆目录: {}\n错误: {}\n这可能是因为目录没有写入权限。",
+                memory_dir.display(),
+                e
+            ))?;
+
+        Self::open(&memory_dir)
+    }
+
+    /// 打开指定记忆目录下的数据库
+    fn open(memory_dir: &Path) -> Result<Self> {
+        let db_path = memory_dir.join(SQLITE_MEMORY_DB_NAME);
+        let mut conn = Connection::open(&db_path)
+            .map_err(|e| anyhow::anyhow!("打开记忆数据库失败: {}\n错误: {}", db_path.display(), e))?;
+
+        // WAL 模式允许多个进程同时读取，写入冲突时等待而不是直接失败
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+
+        Self::migrate(&mut conn, memory_dir)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            db_path,
+        })
+    }
+
+    /// 数据库文件路径
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    /// 创建表结构，并在首次打开时导入 Markdown 分类文件中的已有记忆；旧版本数据库按版本逐步升级
+    fn migrate(conn: &mut Connection, memory_dir: &Path) -> Result<()> {
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        if version >= 1 {
+            let tx = conn.transaction()?;
+            if version < 2 {
+                tx.execute_batch(
+                    "ALTER TABLE memories ADD COLUMN file TEXT;
+                    ALTER TABLE memories ADD COLUMN line INTEGER;
+                    CREATE INDEX IF NOT EXISTS idx_memories_file ON memories(file);",
+                )?;
+            }
+            if version < 3 {
+                tx.execute_batch("ALTER TABLE memories ADD COLUMN priority INTEGER NOT NULL DEFAULT 128;")?;
+            }
+            if version < 4 {
+                tx.execute_batch(CREATE_LINKS_TABLE)?;
+            }
+            tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            tx.commit()?;
+            return Ok(());
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS memories (
+                id TEXT PRIMARY KEY,
+                category TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                access_count INTEGER NOT NULL DEFAULT 0,
+                pinned INTEGER NOT NULL DEFAULT 0,
+                expires_at TEXT,
+                tags TEXT NOT NULL DEFAULT '[]',
+                file TEXT,
+                line INTEGER,
+                priority INTEGER NOT NULL DEFAULT 128
+            );
+            CREATE INDEX IF NOT EXISTS idx_memories_category ON memories(category);
+            CREATE INDEX IF NOT EXISTS idx_memories_expires_at ON memories(expires_at);
+            CREATE INDEX IF NOT EXISTS idx_memories_file ON memories(file);",
+        )?;
+        tx.execute_batch(CREATE_LINKS_TABLE)?;
+
+        // 旧格式不记录创建时间，导入的记忆以迁移时间作为创建时间
+        let legacy = MemoryManager::load_legacy_entries(memory_dir)?;
+        for entry in &legacy {
+            Self::upsert(&tx, entry)?;
+        }
+
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        tx.commit()?;
+
+        if !legacy.is_empty() {
+            crate::log_important!(info, "已将 {} 条记忆从 Markdown 文件迁移到 SQLite: {}", legacy.len(), memory_dir.display());
+        }
+        Ok(())
+    }
+
+    /// 写入记忆；ID 已存在时只刷新更新时间
+    fn upsert(conn: &Connection, entry: &MemoryEntry) -> Result<()> {
+        conn.execute(
+            "INSERT INTO memories (id, category, content, created_at, updated_at, access_count, pinned, expires_at, tags, file, line, priority)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT(id) DO UPDATE SET updated_at = excluded.updated_at",
+            params![
+                entry.id,
+                category_key(entry.category),
+                entry.content,
+                timestamp(entry.created_at),
+                timestamp(entry.updated_at),
+                entry.access_count as i64,
+                entry.pinned,
+                entry.expires_at.map(timestamp),
+                serde_json::to_string(&entry.tags)?,
+                entry.file,
+                entry.line,
+                entry.priority,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow::anyhow!("记忆数据库连接锁已损坏"))
+    }
+
+    /// 执行查询并转换为记忆条目
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<MemoryEntry>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params, row_to_entry)?;
+        let mut memories = Vec::new();
+        for row in rows {
+            memories.push(row?);
+        }
+        Ok(memories)
+    }
+
+    /// 记录一次回忆，累加访问次数
+    fn record_access(&self, ids: &[String]) -> Result<()> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        for id in ids {
+            tx.execute("UPDATE memories SET access_count = access_count + 1 WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl MemoryStore for SqliteMemoryManager {
+    fn add_memory(&self, content: &str, category: MemoryCategory) -> Result<String> {
+        let id = MemoryManager::memory_id(category, content);
+        let now = Utc::now();
+
+        let entry = MemoryEntry {
+            id: id.clone(),
+            content: content.trim().to_string(),
+            category,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            access_count: 0,
+            pinned: false,
+            tags: Vec::new(),
+            file: None,
+            line: None,
+            priority: DEFAULT_MEMORY_PRIORITY,
+        };
+
+        Self::upsert(&*self.lock()?, &entry)?;
+        Ok(id)
+    }
+
+    fn set_expiry(&self, id: &str, expiry: DateTime<Utc>) -> Result<()> {
+        let updated = self.lock()?.execute(
+            "UPDATE memories SET expires_at = ?1 WHERE id = ?2",
+            params![timestamp(expiry), id],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("未找到记忆: {}", id);
+        }
+        Ok(())
+    }
+
+    fn set_anchor(&self, id: &str, anchor: &MemoryAnchor) -> Result<()> {
+        let updated = self.lock()?.execute(
+            "UPDATE memories SET file = ?1, line = ?2 WHERE id = ?3",
+            params![anchor.file, anchor.line, id],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("未找到记忆: {}", id);
+        }
+        Ok(())
+    }
+
+    fn set_priority(&self, id: &str, priority: u8) -> Result<()> {
+        let updated = self.lock()?.execute(
+            "UPDATE memories SET priority = ?1 WHERE id = ?2",
+            params![priority, id],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("未找到记忆: {}", id);
+        }
+        Ok(())
+    }
+
+    fn link(&self, id_a: &str, id_b: &str, relation: &str) -> Result<()> {
+        if id_a == id_b {
+            anyhow::bail!("不能将记忆关联到自身: {}", id_a);
+        }
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        for id in [id_a, id_b] {
+            let exists: bool = tx.query_row("SELECT EXISTS(SELECT 1 FROM memories WHERE id = ?1)", params![id], |row| row.get(0))?;
+            if !exists {
+                anyhow::bail!("未找到记忆: {}", id);
+            }
+        }
+        for (from, to) in [(id_a, id_b), (id_b, id_a)] {
+            tx.execute(
+                "INSERT INTO memory_links (id_a, id_b, relation) VALUES (?1, ?2, ?3)
+                ON CONFLICT(id_a, id_b) DO UPDATE SET relation = excluded.relation",
+                params![from, to, relation],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn unlink(&self, id_a: &str, id_b: &str) -> Result<bool> {
+        let removed = self.lock()?.execute(
+            "DELETE FROM memory_links WHERE (id_a = ?1 AND id_b = ?2) OR (id_a = ?2 AND id_b = ?1)",
+            params![id_a, id_b],
+        )?;
+        Ok(removed > 0)
+    }
+
+    fn links(&self, id: &str) -> Result<Vec<MemoryLink>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare("SELECT id_b, relation FROM memory_links WHERE id_a = ?1 ORDER BY id_b ASC")?;
+        let links = stmt
+            .query_map(params![id], |row| Ok(MemoryLink { id: row.get(0)?, relation: row.get(1)? }))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(links)
+    }
+
+    fn get_all_memories(&self) -> Result<Vec<MemoryEntry>> {
+        self.query(
+            &format!("SELECT {} FROM memories ORDER BY updated_at DESC", SELECT_COLUMNS),
+            [],
+        )
+    }
+
+    fn get_memories_by_category(&self, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
+        self.query(
+            &format!(
+                "SELECT {} FROM memories WHERE category = ?1 ORDER BY pinned DESC, priority DESC, created_at ASC, rowid ASC",
+                SELECT_COLUMNS
+            ),
+            params![category_key(category)],
+        )
+    }
+
+    fn memories_for_file(&self, path: &str) -> Result<Vec<MemoryEntry>> {
+        let file = MemoryAnchor::new(path, None).file;
+        self.query(
+            &format!("SELECT {} FROM memories WHERE file = ?1 ORDER BY line ASC", SELECT_COLUMNS),
+            params![file],
+        )
+    }
+
+    fn purge_expired(&self) -> Result<usize> {
+        let conn = self.lock()?;
+        let removed = conn.execute(
+            "DELETE FROM memories WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+            params![timestamp(Utc::now())],
+        )?;
+        if removed > 0 {
+            conn.execute(
+                "DELETE FROM memory_links WHERE id_a NOT IN (SELECT id FROM memories) OR id_b NOT IN (SELECT id FROM memories)",
+                [],
+            )?;
+        }
+        Ok(removed)
+    }
+
+    fn get_project_info(&self) -> Result<String> {
+        if let Err(e) = self.purge_expired() {
+            crate::log_debug!("清理过期记忆失败: {}", e);
+        }
+
+        let ids: Vec<String> = self.get_all_memories()?.into_iter().map(|m| m.id).collect();
+        let info = super::store::format_project_info(self)?;
+        if let Err(e) = self.record_access(&ids) {
+            crate::log_debug!("记录记忆访问次数失败: {}", e);
+        }
+        Ok(info)
+    }
+}
+
+/// 分类在数据库中的存储值
+fn category_key(category: MemoryCategory) -> &'static str {
+    match category {
+        MemoryCategory::Rule => "rule",
+        MemoryCategory::Preference => "preference",
+        MemoryCategory::Pattern => "pattern",
+        MemoryCategory::Context => "context",
+    }
+}
+
+fn parse_category(key: &str) -> MemoryCategory {
+    match key {
+        "rule" => MemoryCategory::Rule,
+        "preference" => MemoryCategory::Preference,
+        "pattern" => MemoryCategory::Pattern,
+        _ => MemoryCategory::Context,
+    }
+}
+
+/// 统一的时间戳格式（固定宽度的 UTC RFC3339），保证可以直接按字符串比较
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn row_to_entry(row: &Row<'_>) -> rusqlite::Result<MemoryEntry> {
+    let category: String = row.get(1)?;
+    let created_at: String = row.get(3)?;
+    let updated_at: String = row.get(4)?;
+    let access_count: i64 = row.get(5)?;
+    let expires_at: Option<String> = row.get(7)?;
+    let tags: String = row.get(8)?;
+
+    Ok(MemoryEntry {
+        id: row.get(0)?,
+        category: parse_category(&category),
+        content: row.get(2)?,
+        created_at: parse_timestamp(&created_at),
+        updated_at: parse_timestamp(&updated_at),
+        access_count: access_count.max(0) as u64,
+        pinned: row.get(6)?,
+        expires_at: expires_at.as_deref().map(parse_timestamp),
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        file: row.get(9)?,
+        line: row.get(10)?,
+        priority: row.get(11)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时目录中打开 SQLite 记忆存储；目录在返回的 TempDir 释放时删除
+    fn open_temp_store() -> (tempfile::TempDir, SqliteMemoryManager) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SqliteMemoryManager::open(dir.path()).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn test_migrates_markdown_memories_on_first_open() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("rules.md"), "# 开发规范和规则\n\n- 错误处理统一使用 anyhow\n").unwrap();
+
+        let store = SqliteMemoryManager::open(dir.path()).unwrap();
+        let rules = store.get_memories_by_category(MemoryCategory::Rule).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].content, "错误处理统一使用 anyhow");
+
+        let id = store.add_memory("临时约定", MemoryCategory::Context).unwrap();
+        assert_eq!(store.get_memory(&id).unwrap().map(|m| m.content), Some("临时约定".to_string()));
+        assert!(store.get_memory("missing").unwrap().is_none());
+        store.set_expiry(&id, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+        assert_eq!(store.purge_expired().unwrap(), 1);
+        drop(store);
+
+        // 再次打开不会重复导入
+        let store = SqliteMemoryManager::open(dir.path()).unwrap();
+        assert_eq!(store.get_all_memories().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_anchored_memories_grouped_by_file() {
+        let (_dir, store) = open_temp_store();
+
+        let late = store.add_memory("这里刻意使用 O(n^2)，不要优化", MemoryCategory::Rule).unwrap();
+        store.set_anchor(&late, &MemoryAnchor::new("./src/sort.rs", Some(40))).unwrap();
+        let early = store.add_memory("输入已保证有序", MemoryCategory::Context).unwrap();
+        store.set_anchor(&early, &MemoryAnchor::new("src\\sort.rs", Some(12))).unwrap();
+        store.add_memory("提交信息使用中文", MemoryCategory::Preference).unwrap();
+
+        let anchored = store.memories_for_file("src/sort.rs").unwrap();
+        assert_eq!(anchored.iter().map(|m| m.line).collect::<Vec<_>>(), vec![Some(12), Some(40)]);
+
+        let info = store.get_project_info().unwrap();
+        assert!(info.contains("**偏好**: 提交信息使用中文"));
+        assert!(info.contains("**代码标注**: `src/sort.rs` L12 输入已保证有序; L40 这里刻意使用 O(n^2)，不要优化"));
+        assert!(!info.contains("**规范**"));
+    }
+
+    #[test]
+    fn test_linked_memories_shown_in_project_info() {
+        let (_dir, store) = open_temp_store();
+
+        let decision = store.add_memory("索引状态统一写入 projects_status.json", MemoryCategory::Rule).unwrap();
+        let limitation = store.add_memory("状态文件不支持多进程并发写入", MemoryCategory::Context).unwrap();
+        store.link(&decision, &limitation, "限制").unwrap();
+        assert!(store.link(&decision, &decision, "自身").is_err());
+        assert!(store.link(&decision, "missing", "相关").is_err());
+
+        let linked = store.get_linked(&limitation).unwrap();
+        assert_eq!(linked.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec![decision.as_str()]);
+        let info = store.get_project_info().unwrap();
+        assert!(info.contains("索引状态统一写入 projects_status.json（关联: 限制 → 状态文件不支持多进程并发写入）"), "{}", info);
+
+        assert!(store.unlink(&limitation, &decision).unwrap());
+        assert!(!store.unlink(&limitation, &decision).unwrap());
+        assert!(store.links(&decision).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merge_from_skips_duplicates() {
+        let (_source_dir, source) = open_temp_store();
+        let (_target_dir, target) = open_temp_store();
+
+        source.add_memory("错误处理统一使用 anyhow", MemoryCategory::Rule).unwrap();
+        let anchored = source.add_memory("输入已保证有序", MemoryCategory::Context).unwrap();
+        source.set_anchor(&anchored, &MemoryAnchor::new("src/sort.rs", Some(12))).unwrap();
+        source.set_priority(&anchored, 200).unwrap();
+        target.add_memory("错误处理统一使用 anyhow", MemoryCategory::Rule).unwrap();
+
+        let report = target.merge_from(&source.get_all_memories().unwrap()).unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+        let copied = target.get_memory(&anchored).unwrap().unwrap();
+        assert_eq!((copied.file.as_deref(), copied.line, copied.priority), (Some("src/sort.rs"), Some(12), 200));
+    }
+
+    #[test]
+    fn test_recall_orders_by_priority() {
+        let (_dir, store) = open_temp_store();
+
+        store.add_memory("使用 4 空格缩进", MemoryCategory::Rule).unwrap();
+        let important = store.add_memory("禁止直接修改生成的代码", MemoryCategory::Rule).unwrap();
+        store.set_priority(&important, 200).unwrap();
+
+        let info = store.get_project_info().unwrap();
+        assert!(info.contains("**规范**: 禁止直接修改生成的代码; 使用 4 空格缩进"));
+        assert!(store.set_priority("missing", 1).is_err());
+    }
+
+    #[test]
+    fn test_import_markdown_infers_categories_and_skips_duplicates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let doc = dir.path().join("CONVENTIONS.md");
+        fs::write(&doc, "# Team Conventions\n\n- 错误处理统一使用 anyhow\n- 公共函数必须有文档注释，\n  说明参数含义\n\n## Preferences\n\n* 提交信息使用中文\n\n## Patterns\n\n### 配置读取\n\n所有配置通过 load_standalone_config 读取。\n\n```rust\n- 这不是记忆\n```\n").unwrap();
+
+        let store = SqliteMemoryManager::open(dir.path()).unwrap();
+        store.add_memory("提交信息使用中文", MemoryCategory::Preference).unwrap();
+
+        let report = store.import_markdown(&doc).unwrap();
+        assert_eq!((report.imported, report.skipped), (3, 1));
+        let rules: Vec<String> = store.get_memories_by_category(MemoryCategory::Rule).unwrap().into_iter().map(|m| m.content).collect();
+        assert_eq!(rules, vec!["错误处理统一使用 anyhow", "公共函数必须有文档注释， 说明参数含义"]);
+        let patterns = store.get_memories_by_category(MemoryCategory::Pattern).unwrap();
+        assert_eq!(patterns[0].content, "配置读取: 所有配置通过 load_standalone_config 读取。");
+
+        // 再次导入全部跳过；空文档导入 0 条
+        assert_eq!(store.import_markdown(&doc).unwrap().imported, 0);
+        fs::write(&doc, "").unwrap();
+        assert_eq!(store.import_markdown(&doc).unwrap().imported, 0);
+    }
+
+    #[test]
+    fn test_word_and_char_counts_skip_expired_memories() {
+        let (_dir, store) = open_temp_store();
+
+        store.add_memory("错误处理使用 anyhow", MemoryCategory::Rule).unwrap();
+        store.add_memory("prefer small commits", MemoryCategory::Preference).unwrap();
+        let expired = store.add_memory("临时约定", MemoryCategory::Context).unwrap();
+        store.set_expiry(&expired, Utc::now() - chrono::Duration::seconds(1)).unwrap();
+
+        // 每个汉字计为一个词：错误处理使用(6) + anyhow(1) + 3 个英文单词
+        assert_eq!(store.word_count().unwrap(), 10);
+        assert_eq!(store.char_count().unwrap(), "错误处理使用 anyhow".chars().count() + "prefer small commits".len());
+        assert!(store.get_project_info().unwrap().starts_with("📚 项目记忆总览（约 10 词 / "));
+    }
+}
@@ -0,0 +1,470 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use tokio::sync::RwLock;
+
+use super::manager::MemoryManager;
+use super::sqlite::SqliteMemoryManager;
+use super::types::{ImportReport, MemoryAnchor, MemoryCategory, MemoryEntry, MemoryLink, SyncReport, DEFAULT_MEMORY_PRIORITY};
+use crate::mcp::tools::acemcp::mcp::request_memories;
+use crate::mcp::tools::acemcp::types::AcemcpConfig;
+
+/// 回忆时关联记忆预览的最大字符数
+const LINK_PREVIEW_CHARS: usize = 30;
+
+/// 记忆存储后端
+///
+/// `MemoryManager` 使用项目内的 Markdown 分类文件，`SqliteMemoryManager` 使用 SQLite 数据库，
+/// 通过配置项 `memory_backend` 选择
+pub trait MemoryStore: Send + Sync {
+    /// 添加记忆条目，返回记忆 ID
+    fn add_memory(&self, content: &str, category: MemoryCategory) -> Result<String>;
+
+    /// 为记忆设置过期时间，过期后在下次回忆时自动删除
+    fn set_expiry(&self, id: &str, expiry: DateTime<Utc>) -> Result<()>;
+
+    /// 将记忆关联到文件/行，作为代码标注
+    fn set_anchor(&self, id: &str, anchor: &MemoryAnchor) -> Result<()>;
+
+    /// 设置记忆的回忆优先级（越大越靠前）
+    fn set_priority(&self, id: &str, priority: u8) -> Result<()>;
+
+    /// 在两条记忆之间建立关联（双向），已存在关联时更新关联关系
+    fn link(&self, id_a: &str, id_b: &str, relation: &str) -> Result<()>;
+
+    /// 删除两条记忆之间的关联，返回关联此前是否存在
+    fn unlink(&self, id_a: &str, id_b: &str) -> Result<bool>;
+
+    /// 指定记忆的全部关联（按关联的记忆 ID 排序）
+    fn links(&self, id: &str) -> Result<Vec<MemoryLink>>;
+
+    /// 获取所有记忆
+    fn get_all_memories(&self) -> Result<Vec<MemoryEntry>>;
+
+    /// 获取指定分类的记忆
+    fn get_memories_by_category(&self, category: MemoryCategory) -> Result<Vec<MemoryEntry>>;
+
+    /// 删除已过期的记忆，返回删除数量
+    fn purge_expired(&self) -> Result<usize>;
+
+    /// 按 ID 获取记忆，不存在时返回 None
+    fn get_memory(&self, id: &str) -> Result<Option<MemoryEntry>> {
+        Ok(self.get_all_memories()?.into_iter().find(|m| m.id == id))
+    }
+
+    /// 获取与指定记忆关联的记忆，已删除的记忆跳过
+    fn get_linked(&self, id: &str) -> Result<Vec<MemoryEntry>> {
+        let mut by_id: HashMap<String, MemoryEntry> = self.get_all_memories()?.into_iter().map(|m| (m.id.clone(), m)).collect();
+        Ok(self.links(id)?.into_iter().filter_map(|link| by_id.remove(&link.id)).collect())
+    }
+
+    /// 获取关联到指定文件的记忆（按行号排序）
+    fn memories_for_file(&self, path: &str) -> Result<Vec<MemoryEntry>> {
+        let file = MemoryAnchor::new(path, None).file;
+        let mut memories: Vec<MemoryEntry> = self
+            .get_all_memories()?
+            .into_iter()
+            .filter(|m| m.file.as_deref() == Some(file.as_str()))
+            .collect();
+        memories.sort_by_key(|m| m.line);
+        Ok(memories)
+    }
+
+    /// 从 Markdown 规范文档（如 CONVENTIONS.md）批量导入记忆
+    ///
+    /// 每个列表项、或标题下的段落作为一条记忆，分类按所在章节的标题推断（见 `parse_markdown_memories`）；
+    /// 与已有记忆重复的条目跳过。空文档导入 0 条
+    fn import_markdown(&self, path: &Path) -> Result<ImportReport> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("读取文档失败: {}\n错误: {}", path.display(), e))?;
+
+        let mut existing: HashSet<String> = self.get_all_memories()?.into_iter().map(|m| m.id).collect();
+        let mut report = ImportReport::default();
+        for (category, content) in parse_markdown_memories(&text) {
+            if !existing.insert(MemoryManager::memory_id(category, &content)) {
+                report.skipped += 1;
+                continue;
+            }
+            self.add_memory(&content, category)?;
+            report.imported += 1;
+        }
+        Ok(report)
+    }
+
+    /// 将其它项目的记忆合并到当前存储：跳过已过期的记忆，与已有记忆重复（ID 相同）的条目跳过，
+    /// 新增的记忆保留原有的代码标注、优先级与过期时间
+    fn merge_from(&self, source: &[MemoryEntry]) -> Result<ImportReport> {
+        let now = Utc::now();
+        let mut existing: HashSet<String> = self.get_all_memories()?.into_iter().map(|m| m.id).collect();
+        let mut report = ImportReport::default();
+        for entry in source.iter().filter(|m| !m.is_expired(now)) {
+            if !existing.insert(MemoryManager::memory_id(entry.category, &entry.content)) {
+                report.skipped += 1;
+                continue;
+            }
+            let id = self.add_memory(&entry.content, entry.category)?;
+            if let Some(file) = &entry.file {
+                self.set_anchor(&id, &MemoryAnchor::new(file, entry.line))?;
+            }
+            if entry.priority != DEFAULT_MEMORY_PRIORITY {
+                self.set_priority(&id, entry.priority)?;
+            }
+            if let Some(expiry) = entry.expires_at {
+                self.set_expiry(&id, expiry)?;
+            }
+            report.imported += 1;
+        }
+        Ok(report)
+    }
+
+    /// 未过期记忆的总词数，用于估算注入提示词时占用的上下文
+    fn word_count(&self) -> Result<usize> {
+        let now = Utc::now();
+        Ok(self.get_all_memories()?.iter().filter(|m| !m.is_expired(now)).map(|m| count_words(&m.content)).sum())
+    }
+
+    /// 未过期记忆的总字符数
+    fn char_count(&self) -> Result<usize> {
+        let now = Utc::now();
+        Ok(self.get_all_memories()?.iter().filter(|m| !m.is_expired(now)).map(|m| m.content.chars().count()).sum())
+    }
+
+    /// 获取项目信息供MCP调用方分析 - 压缩简化版本
+    fn get_project_info(&self) -> Result<String> {
+        // 惰性清理已过期的记忆
+        if let Err(e) = self.purge_expired() {
+            crate::log_debug!("清理过期记忆失败: {}", e);
+        }
+        format_project_info(self)
+    }
+}
+
+/// 按分类压缩汇总未过期的记忆
+pub(crate) fn format_project_info<S: MemoryStore + ?Sized>(store: &S) -> Result<String> {
+    let now = Utc::now();
+
+    // 汇总所有记忆规则并压缩
+    let all_memories = store.get_all_memories()?;
+    if all_memories.is_empty() {
+        return Ok("📭 暂无项目记忆".to_string());
+    }
+
+    let mut compressed_info = Vec::new();
+    let mut words = 0;
+    let mut chars = 0;
+    for memory in all_memories.iter().filter(|m| !m.is_expired(now)) {
+        words += count_words(&memory.content);
+        chars += memory.content.chars().count();
+    }
+
+    // 关联的记忆附在记忆条目之后，已过期的关联记忆不展示
+    let active: HashMap<&str, &MemoryEntry> = all_memories
+        .iter()
+        .filter(|m| !m.is_expired(now))
+        .map(|m| (m.id.as_str(), m))
+        .collect();
+    let with_links = |memory: &MemoryEntry, text: String| -> Result<String> {
+        let linked: Vec<String> = store
+            .links(&memory.id)?
+            .iter()
+            .filter_map(|link| active.get(link.id.as_str()).map(|m| format!("{} → {}", link.relation, link_preview(&m.content))))
+            .collect();
+        Ok(if linked.is_empty() { text } else { format!("{}（关联: {}）", text, linked.join("、")) })
+    };
+
+    // 按分类压缩汇总
+    let categories = [
+        (MemoryCategory::Rule, "规范"),
+        (MemoryCategory::Preference, "偏好"),
+        (MemoryCategory::Pattern, "模式"),
+        (MemoryCategory::Context, "背景"),
+    ];
+
+    for (category, title) in categories.iter() {
+        // 置顶与优先级高的记忆排在前面，上下文较小时被截断的是不重要的记忆；同优先级保持原有顺序
+        let mut memories = store.get_memories_by_category(*category)?;
+        memories.sort_by(|a, b| (b.pinned, b.priority).cmp(&(a.pinned, a.priority)));
+        if !memories.is_empty() {
+            let mut items = Vec::new();
+            for memory in memories {
+                // 代码标注在下方按文件分组展示
+                if memory.is_expired(now) || memory.file.is_some() {
+                    continue;
+                }
+                let content = compress_content(&memory.content);
+                if !content.is_empty() {
+                    items.push(with_links(&memory, content)?);
+                }
+            }
+            if !items.is_empty() {
+                compressed_info.push(format!("**{}**: {}", title, items.join("; ")));
+            }
+        }
+    }
+
+    // 代码标注按文件分组，文件内按行号排序
+    let mut anchored: BTreeMap<String, Vec<&MemoryEntry>> = BTreeMap::new();
+    for memory in all_memories.iter().filter(|m| !m.is_expired(now)) {
+        if let Some(file) = memory.file.clone() {
+            anchored.entry(file).or_default().push(memory);
+        }
+    }
+    if !anchored.is_empty() {
+        let mut groups = Vec::new();
+        for (file, mut memories) in anchored {
+            memories.sort_by_key(|m| m.line);
+            let mut items = Vec::new();
+            for m in memories {
+                let text = match m.line {
+                    Some(line) => format!("L{} {}", line, compress_content(&m.content)),
+                    None => compress_content(&m.content),
+                };
+                items.push(with_links(m, text)?);
+            }
+            groups.push(format!("`{}` {}", file, items.join("; ")));
+        }
+        compressed_info.push(format!("**代码标注**: {}", groups.join(" / ")));
+    }
+
+    if compressed_info.is_empty() {
+        Ok("📭 暂无有效项目记忆".to_string())
+    } else {
+        Ok(format!("📚 项目记忆总览（约 {} 词 / {} 字符）: {}", words, chars, compressed_info.join(" | ")))
+    }
+}
+
+/// 根据章节标题推断记忆分类，无法识别时返回 None
+fn category_for_heading(heading: &str) -> Option<MemoryCategory> {
+    let heading = heading.to_lowercase();
+    let has = |keys: &[&str]| keys.iter().any(|k| heading.contains(k));
+    if has(&["rule", "convention", "规范", "规则", "约定"]) {
+        Some(MemoryCategory::Rule)
+    } else if has(&["preference", "偏好"]) {
+        Some(MemoryCategory::Preference)
+    } else if has(&["pattern", "best practice", "模式", "最佳实践"]) {
+        Some(MemoryCategory::Pattern)
+    } else if has(&["context", "background", "背景", "上下文"]) {
+        Some(MemoryCategory::Context)
+    } else {
+        None
+    }
+}
+
+/// 将 Markdown 文档解析为 (分类, 内容) 列表
+///
+/// - 列表项（`-`、`*`、`+`、`1.`）各为一条记忆，缩进的续行并入上一项
+/// - 标题下的普通段落与标题合并为一条记忆（`标题: 段落`）
+/// - 分类取所在章节及其上级标题中最近一个可识别的标题（Rules / Preferences / Patterns 等），
+///   都无法识别时为 Context；代码块内容忽略
+pub(crate) fn parse_markdown_memories(text: &str) -> Vec<(MemoryCategory, String)> {
+    let mut memories: Vec<(MemoryCategory, String)> = Vec::new();
+    // 当前标题路径：(标题级别, 推断的分类)
+    let mut sections: Vec<(usize, Option<MemoryCategory>)> = Vec::new();
+    let mut category = MemoryCategory::Context;
+    let mut heading = String::new();
+    let mut current: Option<String> = None;
+    let mut paragraph = false;
+    let mut in_code = false;
+
+    let flush = |current: &mut Option<String>, memories: &mut Vec<(MemoryCategory, String)>, category: MemoryCategory| {
+        if let Some(content) = current.take() {
+            let content = compress_content(&content);
+            if !content.is_empty() {
+                memories.push((category, content));
+            }
+        }
+    };
+
+    for raw in text.lines() {
+        let line = raw.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        if line.is_empty() {
+            flush(&mut current, &mut memories, category);
+            paragraph = false;
+            continue;
+        }
+
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if level > 0 && level <= 6 && line[level..].starts_with(' ') {
+            flush(&mut current, &mut memories, category);
+            paragraph = false;
+            heading = line[level..].trim().to_string();
+            while sections.last().map(|(l, _)| *l >= level).unwrap_or(false) {
+                sections.pop();
+            }
+            sections.push((level, category_for_heading(&heading)));
+            category = sections.iter().rev().find_map(|(_, c)| *c).unwrap_or(MemoryCategory::Context);
+            continue;
+        }
+
+        let bullet = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|p| line.strip_prefix(p))
+            .or_else(|| {
+                let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+                if digits > 0 { line[digits..].strip_prefix(". ") } else { None }
+            });
+        if let Some(item) = bullet {
+            flush(&mut current, &mut memories, category);
+            paragraph = false;
+            let item = ["[ ] ", "[x] ", "[X] "].iter().find_map(|p| item.strip_prefix(p)).unwrap_or(item);
+            current = Some(item.to_string());
+            continue;
+        }
+
+        match current.as_mut() {
+            // 列表项的续行，或段落的后续行
+            Some(content) if paragraph || raw.starts_with([' ', '\t']) => {
+                content.push(' ');
+                content.push_str(line);
+            }
+            _ => {
+                flush(&mut current, &mut memories, category);
+                paragraph = true;
+                current = Some(if heading.is_empty() { line.to_string() } else { format!("{}: {}", heading, line) });
+            }
+        }
+    }
+    flush(&mut current, &mut memories, category);
+    memories
+}
+
+/// 统计词数：连续的非 CJK 字符（以空白分隔）计为一个词，每个 CJK 字符单独计为一个词
+pub(crate) fn count_words(content: &str) -> usize {
+    let is_cjk = |c: char| matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF);
+    let mut count = 0;
+    for token in content.split_whitespace() {
+        let mut in_word = false;
+        for c in token.chars() {
+            if is_cjk(c) {
+                count += 1;
+                in_word = false;
+            } else if !in_word {
+                count += 1;
+                in_word = true;
+            }
+        }
+    }
+    count
+}
+
+/// 关联记忆的简短预览：压缩空白后保留前 `LINK_PREVIEW_CHARS` 个字符
+fn link_preview(content: &str) -> String {
+    let content = compress_content(content);
+    if content.chars().count() <= LINK_PREVIEW_CHARS {
+        return content;
+    }
+    let mut preview: String = content.chars().take(LINK_PREVIEW_CHARS).collect();
+    preview.push('…');
+    preview
+}
+
+/// 去除多余空格和换行，压缩内容
+fn compress_content(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// 配置的记忆存储后端名称（小写）
+fn configured_backend() -> Option<String> {
+    crate::config::load_standalone_config()
+        .ok()
+        .and_then(|config| config.mcp_config.memory_backend)
+        .map(|backend| backend.trim().to_lowercase())
+}
+
+/// 按配置打开项目的记忆存储（`memory_backend` 为 `sqlite` 时使用 SQLite，否则使用 Markdown 文件）
+pub fn open_memory_store(project_path: &str) -> Result<Box<dyn MemoryStore>> {
+    match configured_backend().as_deref() {
+        Some("sqlite") => Ok(Box::new(SqliteMemoryManager::new(project_path)?)),
+        _ => Ok(Box::new(MemoryManager::new(project_path)?)),
+    }
+}
+
+/// 进程内共享的记忆存储：修改记忆时取写锁，只读查询时取读锁，
+/// 避免并发的记忆操作同时读写同一个存储文件
+pub type SharedMemoryStore = Arc<RwLock<Box<dyn MemoryStore>>>;
+
+/// 存储后端与记忆目录 -> 共享的记忆存储；只保存弱引用，所有使用方释放后存储随之关闭，长时间运行的进程不会积累打开过的存储
+static MEMORY_STORES: Lazy<RwLock<HashMap<String, Weak<RwLock<Box<dyn MemoryStore>>>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// 获取项目的共享记忆存储，首次访问时按配置打开
+///
+/// 以存储后端与记忆目录为键，修改 `memory_backend` 或记忆目录后会打开新的存储
+pub async fn shared_memory_store(project_path: &str) -> Result<SharedMemoryStore> {
+    let normalized = MemoryManager::normalize_project_path(project_path)?;
+    let key = format!(
+        "{}:{}",
+        configured_backend().unwrap_or_default(),
+        MemoryManager::resolve_memory_dir(&normalized).display()
+    );
+
+    if let Some(store) = MEMORY_STORES.read().await.get(&key).and_then(Weak::upgrade) {
+        return Ok(store);
+    }
+
+    let mut stores = MEMORY_STORES.write().await;
+    if let Some(store) = stores.get(&key).and_then(Weak::upgrade) {
+        return Ok(store);
+    }
+    let store: SharedMemoryStore = Arc::new(RwLock::new(open_memory_store(project_path)?));
+    // 顺带清理已释放的存储留下的条目
+    stores.retain(|_, weak| weak.strong_count() > 0);
+    stores.insert(key, Arc::downgrade(&store));
+    Ok(store)
+}
+
+/// 记忆远程同步使用的项目标识：规范化后的项目完整路径（统一使用 `/` 分隔）
+fn remote_project_key(project_path: &str) -> Result<String> {
+    let normalized = MemoryManager::normalize_project_path(project_path)?;
+    Ok(normalized.to_string_lossy().replace('\\', "/"))
+}
+
+/// 将项目的全部记忆备份到 acemcp 服务端的 `/memories` 接口
+///
+/// 服务端按记忆 ID 执行 upsert，重复同步不会产生重复记录；记忆 ID 由分类与内容生成，在不同机器上保持一致
+pub async fn sync_to_remote(project_path: &str, config: &AcemcpConfig) -> Result<SyncReport> {
+    let project = remote_project_key(project_path)?;
+    let memories = shared_memory_store(project_path).await?.read().await.get_all_memories()?;
+
+    let payload = serde_json::json!({
+        "project": project,
+        "memories": memories,
+    });
+    let value = request_memories(config, &project, Some(&payload)).await?;
+    let upserted = value
+        .get("upserted")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| anyhow::anyhow!("同步记忆失败: 响应缺少 upserted 字段: {}", value))? as usize;
+
+    Ok(SyncReport {
+        project,
+        total: memories.len(),
+        upserted,
+    })
+}
+
+/// 从 acemcp 服务端恢复项目的记忆，只合并本地不存在的条目（见 `MemoryStore::merge_from`），返回新增数量
+pub async fn restore_from_remote(project_path: &str, config: &AcemcpConfig) -> Result<usize> {
+    let project = remote_project_key(project_path)?;
+    let value = request_memories(config, &project, None).await?;
+    let memories = value
+        .get("memories")
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("恢复记忆失败: 响应缺少 memories 字段"))?;
+    let remote: Vec<MemoryEntry> = serde_json::from_value(memories)
+        .map_err(|e| anyhow::anyhow!("恢复记忆失败: memories 格式错误: {}", e))?;
+
+    let store = shared_memory_store(project_path).await?;
+    let report = store.write().await.merge_from(&remote)?;
+    Ok(report.imported)
+}
@@ -9,8 +9,74 @@ pub struct MemoryEntry {
     pub category: MemoryCategory,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// 过期时间，过期后在下次回忆时自动删除；为 None 表示永不过期
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 被回忆的次数（仅 SQLite 后端记录）
+    #[serde(default)]
+    pub access_count: u64,
+    /// 是否置顶
+    #[serde(default)]
+    pub pinned: bool,
+    /// 标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 关联的文件（相对项目根目录，使用 `/` 分隔），设置后记忆作为该文件的代码标注
+    #[serde(default)]
+    pub file: Option<String>,
+    /// 关联的行号（从 1 开始）
+    #[serde(default)]
+    pub line: Option<u32>,
+    /// 回忆优先级（0-255，默认 128），回忆时按优先级从高到低排列
+    #[serde(default = "default_priority")]
+    pub priority: u8,
 }
 
+/// 记忆的默认优先级
+pub const DEFAULT_MEMORY_PRIORITY: u8 = 128;
+
+fn default_priority() -> u8 {
+    DEFAULT_MEMORY_PRIORITY
+}
+
+impl MemoryEntry {
+    /// 判断记忆在给定时间点是否已过期
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.map(|t| t <= now).unwrap_or(false)
+    }
+}
+
+/// 记忆关联的代码位置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MemoryAnchor {
+    pub file: String,
+    #[serde(default)]
+    pub line: Option<u32>,
+}
+
+impl MemoryAnchor {
+    /// 统一路径分隔符并去掉开头的 `./`，保证同一文件的不同写法得到相同的标注路径
+    pub fn new(file: &str, line: Option<u32>) -> Self {
+        let mut file = file.trim().replace('\\', "/");
+        while let Some(rest) = file.strip_prefix("./") {
+            file = rest.to_string();
+        }
+        Self { file, line }
+    }
+}
+
+/// 记忆之间的关联（从某条记忆看向另一条记忆）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MemoryLink {
+    /// 关联的另一条记忆 ID
+    pub id: String,
+    /// 关联关系，如 "实现"、"限制"
+    pub relation: String,
+}
+
+/// 未指定关联关系时使用的默认值
+pub const DEFAULT_MEMORY_RELATION: &str = "相关";
+
 /// 记忆分类
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum MemoryCategory {
@@ -20,6 +86,38 @@ pub enum MemoryCategory {
     Context,     // 项目上下文信息
 }
 
+impl MemoryCategory {
+    /// 按请求中的分类名称（rule / preference / pattern / context）解析，未知名称归为项目上下文
+    pub fn from_key(key: &str) -> Self {
+        match key {
+            "rule" => Self::Rule,
+            "preference" => Self::Preference,
+            "pattern" => Self::Pattern,
+            _ => Self::Context,
+        }
+    }
+}
+
+/// 记忆远程同步结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// 远程使用的项目标识
+    pub project: String,
+    /// 本地记忆总数
+    pub total: usize,
+    /// 服务端确认写入（新增或更新）的数量
+    pub upserted: usize,
+}
+
+/// 从 Markdown 文档批量导入记忆的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// 新增的记忆数量
+    pub imported: usize,
+    /// 与已有记忆（或文档内）重复而跳过的数量
+    pub skipped: usize,
+}
+
 /// 记忆元数据
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MemoryMetadata {
@@ -22,11 +22,11 @@ fn default_is_markdown() -> bool {
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct JiyiRequest {
-    #[schemars(description = "操作类型：记忆(添加记忆), 回忆(获取项目信息)")]
+    #[schemars(description = "操作类型：记忆(添加记忆), 回忆(获取项目信息), 统计(记忆条数与词数/字符数), 设置过期(为记忆设置过期时间), 优先级(设置记忆的回忆优先级), 导入文档(从 Markdown 规范文档批量导入记忆), 复制(将当前项目的全部记忆复制到另一个项目), 关联(在两条记忆之间建立关联), 取消关联(删除两条记忆之间的关联)")]
     pub action: String,
     #[schemars(description = "项目路径（必需）")]
     pub project_path: String,
-    #[schemars(description = "记忆内容（记忆操作时必需）")]
+    #[schemars(description = "记忆内容（记忆操作时必需）；设置过期操作时为 RFC3339 格式的过期时间；优先级操作时为 0-255 的整数（默认 128，越大越靠前）；导入文档操作时为文档路径（相对项目路径或绝对路径）；复制操作时为目标项目路径；关联、取消关联操作时为另一条记忆的 ID")]
     #[serde(default)]
     pub content: String,
     #[schemars(
@@ -34,6 +34,18 @@ pub struct JiyiRequest {
     )]
     #[serde(default = "default_category")]
     pub category: String,
+    #[schemars(description = "记忆 ID（设置过期、优先级、关联、取消关联操作时必需）")]
+    #[serde(default)]
+    pub memory_id: Option<String>,
+    #[schemars(description = "关联的文件路径（可选，相对项目根目录），记忆操作时将记忆作为该文件的代码标注")]
+    #[serde(default)]
+    pub file: Option<String>,
+    #[schemars(description = "关联的行号（可选，从 1 开始），需同时提供 file")]
+    #[serde(default)]
+    pub line: Option<u32>,
+    #[schemars(description = "关联关系（可选，关联操作时使用，如 实现、限制；默认 相关）")]
+    #[serde(default)]
+    pub relation: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -42,6 +54,21 @@ pub struct AcemcpRequest {
     pub project_root_path: String,
     #[schemars(description = "用于查找相关代码上下文的自然语言搜索查询")]
     pub query: String,
+    #[schemars(description = "最多返回的结果条数（可选）")]
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    #[schemars(description = "返回文本的最大字符数（可选，默认 20000 或服务配置的上限，不能超过该上限）；超出时在行尾截断并追加 [...truncated]")]
+    #[serde(default)]
+    pub max_output_length: Option<usize>,
+    #[schemars(description = "之前结果中已出现过的文件路径（可选），检索时排除这些文件以获得互补结果")]
+    #[serde(default)]
+    pub exclude_paths: Option<Vec<String>>,
+    #[schemars(description = "是否以查询词对各结果片段打分重排并在每个片段下方显示得分（可选，默认 false）")]
+    #[serde(default)]
+    pub local_rerank: bool,
+    #[schemars(description = "结果输出格式（可选）：plain（默认，原始文本）、markdown（片段包裹在按扩展名标注语言的代码块中）、json（结构化结果 {hits, total, truncated}）、citations（只返回按相关度排序的 路径:起始行-结束行 引用，不含代码内容）")]
+    #[serde(default)]
+    pub output_format: crate::mcp::tools::acemcp::types::OutputFormat,
 }
 
 fn default_category() -> String {
@@ -0,0 +1,517 @@
+//! acemcp 离线集成测试：使用 `MockAcemcpServer` 端到端验证索引与检索
+
+mod test_utils;
+
+use std::collections::HashMap;
+
+use sanshu::config::{load_standalone_config, save_standalone_config, AcemcpProjectOverride};
+use sanshu::mcp::tools::acemcp::config_reload::current_config;
+use sanshu::mcp::tools::acemcp::error::AcemcpError;
+use sanshu::mcp::tools::acemcp::mcp::{delete_remote_index_with_config, rebuild_projects_json_with_config, search_batch_with_config, search_with_config, update_index, validate_index_with_config};
+use sanshu::mcp::tools::acemcp::types::{AcemcpRequest, IndexStatus};
+use sanshu::mcp::tools::acemcp::AcemcpTool;
+use serde_json::json;
+use test_utils::{blob_paths_file, isolate_home, serial, MockAcemcpServer, SyntheticProject, BATCH_SEARCH_PATH, DELETE_PATH, HEALTH_PATH, LIST_BLOBS_PATH, SEARCH_PATH, UPLOAD_PATH, WEBHOOK_PATH};
+
+fn request(project: &SyntheticProject, query: &str) -> AcemcpRequest {
+    AcemcpRequest {
+        project_root_path: project.path(),
+        query: query.to_string(),
+        top_k: None,
+        max_output_length: None,
+        exclude_paths: None,
+        local_rerank: false,
+        output_format: Default::default(),
+    }
+}
+
+fn synthetic_project() -> SyntheticProject {
+    SyntheticProject::new(&[
+        ("src/tokenizer.rs", "pub fn tokenize(input: &str) -> Vec<String> {\n    input.split_whitespace().map(String::from).collect()\n}\n"),
+        ("src/storage.rs", "pub struct Storage {\n    pub path: String,\n}\n"),
+        ("README.md", "# Demo\n\nA synthetic project for integration tests.\n"),
+        ("notes.txt", "tokenize is mentioned here but .txt is not indexed\n"),
+    ])
+}
+
+#[tokio::test]
+async fn test_index_then_search_returns_matching_chunks() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+
+    let blob_names = update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 3);
+    assert_eq!(server.uploaded_paths(), vec!["README.md", "src/storage.rs", "src/tokenizer.rs"]);
+
+    let text = search_with_config(&config, &request(&project, "tokenize")).await.unwrap();
+    assert!(text.contains("src/tokenizer.rs"));
+    assert!(!text.contains("src/storage.rs"));
+    assert!(!text.contains("notes.txt"));
+
+    // 再次索引时内容未变化，不会重复上传
+    let uploads = server.request_count(UPLOAD_PATH);
+    update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(server.request_count(UPLOAD_PATH), uploads);
+}
+
+#[tokio::test]
+async fn test_base64_upload_encoding_keeps_blob_names() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let mut config = server.config();
+    config.upload_chunk_encoding = Some("base64".to_string());
+    let project = SyntheticProject::new(&[("src/加法.rs", "// 中文注释\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n")]);
+
+    // 服务端解码后计算的 blob 名称与本地按原始内容计算的一致，再次索引时不会重复上传
+    let blob_names = update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 1);
+    let payload = &server.requests(UPLOAD_PATH)[0];
+    assert_eq!(payload["blobs"][0]["encoding"], "base64");
+    assert!(payload["blobs"][0]["content"].as_str().unwrap().is_ascii());
+    assert!(payload["blobs"][0]["path"].as_str().unwrap().is_ascii());
+    assert_eq!(server.uploaded_paths(), vec!["src/加法.rs"]);
+
+    update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(server.request_count(UPLOAD_PATH), 1);
+}
+
+#[tokio::test]
+async fn test_additional_roots_indexed_under_one_project() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let backend = SyntheticProject::new(&[("src/main.rs", "fn main() {\n    shared::run();\n}\n")]);
+    let shared = SyntheticProject::new(&[("lib.rs", "pub fn run() {}\n"), ("target/out.rs", "// ignored\n"), (".gitignore", "target/\n")]);
+    let shared_name = shared.root.file_name().unwrap().to_string_lossy().to_string();
+    let mut config = server.config();
+    config.project_overrides = Some(
+        [(backend.path(), AcemcpProjectOverride { additional_roots: Some(vec![shared.path()]), ..Default::default() })]
+            .into_iter()
+            .collect(),
+    );
+
+    // 附加根目录的 blob 路径以目录名为前缀，各自应用 .gitignore，全部记录在主项目下
+    let blob_names = update_index(&config, &backend.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 2);
+    let mut paths: Vec<String> = server.requests(UPLOAD_PATH)[0]["blobs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["path"].as_str().unwrap().to_string())
+        .collect();
+    paths.sort();
+    let mut expected = vec!["src/main.rs".to_string(), format!("{}/lib.rs", shared_name)];
+    expected.sort();
+    assert_eq!(paths, expected);
+
+    let text = search_with_config(&config, &request(&backend, "run")).await.unwrap();
+    assert!(!text.is_empty());
+    assert_eq!(server.requests(SEARCH_PATH)[0]["blobs"]["added_blobs"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_changed_since_skips_old_files_and_keeps_their_blobs() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let mut config = server.config();
+    let project = SyntheticProject::new(&[("src/old.rs", "pub fn old() {}\n"), ("src/new.rs", "pub fn new() {}\n")]);
+    assert_eq!(update_index(&config, &project.path()).await.unwrap().len(), 2);
+
+    // 两个文件都被修改，但 old.rs 的修改时间早于下限：不上传其新内容，保留原有 blob
+    std::fs::write(project.root.join("src/old.rs"), "pub fn old() -> u32 { 1 }\n").unwrap();
+    std::fs::write(project.root.join("src/new.rs"), "pub fn new() -> u32 { 2 }\n").unwrap();
+    let old_time = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_577_836_800);
+    std::fs::File::options().write(true).open(project.root.join("src/old.rs")).unwrap().set_modified(old_time).unwrap();
+    config.index_only_changed_since = Some("2024-01-01".to_string());
+
+    let blob_names = update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 2);
+    let uploads = server.requests(UPLOAD_PATH);
+    assert_eq!(uploads.len(), 2);
+    let paths: Vec<&str> = uploads[1]["blobs"].as_array().unwrap().iter().map(|b| b["path"].as_str().unwrap()).collect();
+    assert_eq!(paths, vec!["src/new.rs"]);
+}
+
+#[tokio::test]
+async fn test_upload_retries_after_server_error() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+
+    server.fail_next(UPLOAD_PATH, 503, 1);
+    let blob_names = update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 3);
+    assert_eq!(server.request_count(UPLOAD_PATH), 2);
+}
+
+#[tokio::test]
+async fn test_delete_remote_index_clears_server_and_local_records() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+    update_index(&config, &project.path()).await.unwrap();
+
+    // 批次失败时保留本地记录，便于重新执行
+    server.fail_next(DELETE_PATH, 400, 1);
+    assert!(delete_remote_index_with_config(&config, &project.path()).await.is_err());
+    assert_eq!(server.uploaded_paths().len(), 3);
+    assert_eq!(AcemcpTool::get_index_status(project.path()).status, IndexStatus::Synced);
+
+    assert_eq!(delete_remote_index_with_config(&config, &project.path()).await.unwrap(), 3);
+    assert!(server.uploaded_paths().is_empty());
+    assert_eq!(AcemcpTool::get_index_status(project.path()).status, IndexStatus::Idle);
+
+    // 再次执行时本地已无记录，不发起请求
+    assert_eq!(delete_remote_index_with_config(&config, &project.path()).await.unwrap(), 0);
+    assert_eq!(server.request_count(DELETE_PATH), 2);
+}
+
+#[tokio::test]
+async fn test_requests_carry_request_id_and_user_agent() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+
+    // 重试的每次请求使用不同的请求 ID，并携带默认的 User-Agent
+    server.fail_next(UPLOAD_PATH, 503, 1);
+    update_index(&config, &project.path()).await.unwrap();
+    let headers = server.tracing_headers(UPLOAD_PATH);
+    assert_eq!(headers.len(), 2);
+    assert!(headers.iter().all(|(id, ua)| id.is_some() && ua.as_deref().unwrap_or_default().starts_with("sanshu/")));
+    assert_ne!(headers[0].0, headers[1].0);
+
+    // 错误信息中带上失败请求的 ID，便于与服务端日志对照
+    server.fail_next(SEARCH_PATH, 400, 1);
+    let err = search_with_config(&config, &request(&project, "tokenize")).await.unwrap_err();
+    let request_id = server.tracing_headers(SEARCH_PATH)[0].0.clone().unwrap();
+    assert!(format!("{:#}", err).contains(&request_id), "{:#}", err);
+}
+
+#[tokio::test]
+async fn test_upload_retry_reuses_idempotency_key() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let mut config = server.config();
+    config.use_idempotency_keys = Some(true);
+    let project = synthetic_project();
+
+    server.fail_next(UPLOAD_PATH, 503, 1);
+    update_index(&config, &project.path()).await.unwrap();
+    let keys = server.idempotency_keys(UPLOAD_PATH);
+    assert_eq!(keys.len(), 2);
+    assert!(keys[0].is_some());
+    assert_eq!(keys[0], keys[1]);
+
+    // 默认不携带该请求头
+    let project = synthetic_project();
+    update_index(&server.config(), &project.path()).await.unwrap();
+    assert_eq!(server.idempotency_keys(UPLOAD_PATH).last(), Some(&None));
+}
+
+#[tokio::test]
+async fn test_index_status_reloads_after_external_edit() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let project = synthetic_project();
+    update_index(&server.config(), &project.path()).await.unwrap();
+    assert_eq!(AcemcpTool::get_index_status(project.path()).status, IndexStatus::Synced);
+
+    // 状态文件被其他进程修改后，缓存按修改时间失效并重新读取
+    let status_path = std::path::PathBuf::from(std::env::var("ACEMCP_DATA_DIR").unwrap()).join("projects_status.json");
+    let mut all: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&status_path).unwrap()).unwrap();
+    let root = std::fs::canonicalize(&project.root).unwrap().to_string_lossy().replace('\\', "/");
+    all["projects"][root.as_str()]["status"] = json!("failed");
+    all["projects"][root.as_str()]["last_error"] = json!("edited by another process");
+    std::fs::write(&status_path, serde_json::to_string_pretty(&all).unwrap()).unwrap();
+    assert_eq!(AcemcpTool::get_index_status(project.path()).status, IndexStatus::Failed);
+}
+
+#[tokio::test]
+async fn test_wrong_token_fails_before_upload() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let mut config = server.config();
+    config.token = Some("wrong-token".to_string());
+    let project = synthetic_project();
+
+    let err = update_index(&config, &project.path()).await.unwrap_err();
+    assert!(err.to_string().contains("token"), "{}", err);
+    assert_eq!(server.request_count(HEALTH_PATH), 1);
+    assert_eq!(server.request_count(UPLOAD_PATH), 0);
+    let status = AcemcpTool::get_index_status(project.path());
+    assert_eq!(status.status, IndexStatus::Failed);
+    assert_eq!(status.last_error.as_deref(), Some(err.to_string().as_str()));
+
+    // 探测成功后会缓存结果，短时间内再次索引不再探测
+    update_index(&server.config(), &project.path()).await.unwrap();
+    update_index(&server.config(), &project.path()).await.unwrap();
+    assert_eq!(server.request_count(HEALTH_PATH), 2);
+}
+
+#[tokio::test]
+async fn test_index_records_status_history() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let project = synthetic_project();
+
+    update_index(&server.config(), &project.path()).await.unwrap();
+    let history = AcemcpTool::index_status_history(project.path(), 50);
+    let transitions: Vec<(IndexStatus, IndexStatus)> =
+        history.iter().map(|e| (e.from_status.clone(), e.to_status.clone())).collect();
+    assert_eq!(transitions, vec![(IndexStatus::Idle, IndexStatus::Indexing), (IndexStatus::Indexing, IndexStatus::Synced)]);
+    assert_eq!(AcemcpTool::index_status_history(project.path(), 1).len(), 1);
+}
+
+#[tokio::test]
+async fn test_index_completion_posts_webhook() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let project = synthetic_project();
+    let mut config = server.config();
+    config.webhook_url = Some(format!("{}{}", server.base_url(), WEBHOOK_PATH));
+    config.webhook_headers = Some([("Authorization".to_string(), format!("Bearer {}", test_utils::MOCK_TOKEN))].into_iter().collect());
+
+    update_index(&config, &project.path()).await.unwrap();
+    // 通知在后台发送，不阻塞索引
+    for _ in 0..50 {
+        if server.request_count(WEBHOOK_PATH) > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let notifications = server.requests(WEBHOOK_PATH);
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0]["uploaded_blobs"], json!(3));
+    assert_eq!(notifications[0]["indexed_files"], json!(3));
+    assert!(notifications[0]["project_root"].as_str().is_some());
+    assert!(notifications[0]["duration_ms"].is_u64());
+    assert!(notifications[0]["timestamp"].as_str().is_some());
+}
+
+#[tokio::test]
+async fn test_validate_index_reports_stale_and_unindexed() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let project = synthetic_project();
+    let config = server.config();
+
+    update_index(&config, &project.path()).await.unwrap();
+    let uploads = server.request_count(UPLOAD_PATH);
+    let clean = validate_index_with_config(&config, &project.path()).unwrap();
+    assert_eq!((clean.stale_blobs, clean.unindexed_files), (0, 0));
+    assert!(!clean.reindex_recommended);
+
+    let root = std::path::PathBuf::from(project.path());
+    std::fs::remove_file(root.join("src/storage.rs")).unwrap();
+    std::fs::write(root.join("src/cache.rs"), "pub struct Cache;\n").unwrap();
+    let report = validate_index_with_config(&config, &project.path()).unwrap();
+    assert_eq!(report.stale_blobs, 1);
+    assert_eq!(report.unindexed_paths, vec!["src/cache.rs".to_string()]);
+    assert!(report.reindex_recommended);
+    // 校验是只读的，不会触发上传
+    assert_eq!(server.request_count(UPLOAD_PATH), uploads);
+}
+
+#[tokio::test]
+async fn test_rebuild_projects_json_avoids_reupload() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let project = synthetic_project();
+    let mut config = server.config();
+    config.project_namespace = Some("demo".to_string());
+    let mut blob_names = update_index(&config, &project.path()).await.unwrap();
+    blob_names.sort();
+    let uploads = server.request_count(UPLOAD_PATH);
+
+    // 另一个命名空间下的项目，其 blob 不应出现在重建结果中
+    let other = SyntheticProject::new(&[("src/other.rs", "pub fn other() {}\n")]);
+    let mut other_config = server.config();
+    other_config.project_namespace = Some("other".to_string());
+    update_index(&other_config, &other.path()).await.unwrap();
+
+    let projects_file = std::path::PathBuf::from(std::env::var("ACEMCP_DATA_DIR").unwrap()).join("projects.json");
+    let blob_paths_file = blob_paths_file(&project.path());
+    std::fs::remove_file(&projects_file).unwrap();
+    std::fs::remove_file(&blob_paths_file).unwrap();
+
+    // 服务端不支持该接口时保留现状，不报错
+    server.fail_next(LIST_BLOBS_PATH, 404, 1);
+    let message = rebuild_projects_json_with_config(&config, &project.path()).await.unwrap();
+    assert!(message.contains("不支持"));
+    assert!(!projects_file.exists());
+
+    rebuild_projects_json_with_config(&config, &project.path()).await.unwrap();
+    let root = std::fs::canonicalize(project.path()).unwrap().to_string_lossy().replace('\\', "/");
+    let projects: HashMap<String, Vec<String>> = serde_json::from_str(&std::fs::read_to_string(&projects_file).unwrap()).unwrap();
+    assert_eq!(projects.get(&root), Some(&blob_names));
+
+    // blob 路径映射按本地文件同步重建
+    let blob_paths: HashMap<String, String> = serde_json::from_str(&std::fs::read_to_string(&blob_paths_file).unwrap()).unwrap();
+    let mut names: Vec<String> = blob_paths.keys().cloned().collect();
+    names.sort();
+    assert_eq!(names, blob_names);
+    let mut paths: Vec<String> = blob_paths.into_values().collect();
+    paths.sort();
+    assert_eq!(paths, vec!["README.md", "src/storage.rs", "src/tokenizer.rs"]);
+
+    update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(server.request_count(UPLOAD_PATH), uploads + 1);
+}
+
+#[tokio::test]
+async fn test_truncated_json_response_is_retried() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+
+    server.truncate_next(UPLOAD_PATH, 1);
+    let blob_names = update_index(&config, &project.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 3);
+    assert_eq!(server.request_count(UPLOAD_PATH), 2);
+
+    server.truncate_next(SEARCH_PATH, 1);
+    let text = search_with_config(&config, &request(&project, "tokenize")).await.unwrap();
+    assert!(text.contains("src/tokenizer.rs"), "{}", text);
+    assert_eq!(server.request_count(SEARCH_PATH), 2);
+
+    // 持续返回截断的响应时，重试用尽后给出明确的错误
+    server.truncate_next(SEARCH_PATH, 3);
+    let err = search_with_config(&config, &request(&project, "tokenize")).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("格式错误的响应"), "{:#}", err);
+}
+
+#[tokio::test]
+async fn test_search_does_not_retry_client_errors() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+    update_index(&config, &project.path()).await.unwrap();
+
+    server.fail_next(SEARCH_PATH, 400, 1);
+    let err = search_with_config(&config, &request(&project, "tokenize")).await.unwrap_err();
+    assert!(err.to_string().contains("400"));
+    assert_eq!(server.request_count(SEARCH_PATH), 1);
+
+    server.set_search_response(json!({"formatted_retrieval": "fixed response"}));
+    let text = search_with_config(&config, &request(&project, "anything")).await.unwrap();
+    assert!(text.contains("fixed response"));
+}
+
+#[tokio::test]
+async fn test_search_before_indexing_fails() {
+    isolate_home();
+    let server = MockAcemcpServer::start().await;
+    let project = synthetic_project();
+
+    let err = search_with_config(&server.config(), &request(&project, "tokenize")).await.unwrap_err();
+    assert!(err.to_string().contains("尚未索引"));
+    assert!(matches!(err.downcast_ref::<AcemcpError>(), Some(AcemcpError::NotIndexed(_))));
+    assert_eq!(server.request_count(SEARCH_PATH), 0);
+}
+
+#[tokio::test]
+async fn test_batch_search_uses_one_round_trip_per_project() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+    let unindexed = synthetic_project();
+    update_index(&config, &project.path()).await.unwrap();
+
+    let requests = vec![request(&project, "tokenize"), request(&unindexed, "tokenize"), request(&project, "storage")];
+    let results = search_batch_with_config(&config, &requests).await;
+    assert_eq!(results.len(), 3);
+    assert!(results[0].as_ref().unwrap().contains("src/tokenizer.rs"));
+    assert!(matches!(results[1].as_ref().unwrap_err().downcast_ref::<AcemcpError>(), Some(AcemcpError::NotIndexed(_))));
+    assert!(results[2].as_ref().unwrap().contains("src/storage.rs"));
+    assert_eq!(server.request_count(BATCH_SEARCH_PATH), 1);
+    assert_eq!(server.request_count(SEARCH_PATH), 0);
+    assert_eq!(server.requests(BATCH_SEARCH_PATH)[0]["information_requests"], json!(["tokenize", "storage"]));
+}
+
+#[tokio::test]
+async fn test_batch_search_falls_back_when_unsupported() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    let config = server.config();
+    let project = synthetic_project();
+    update_index(&config, &project.path()).await.unwrap();
+
+    server.fail_next(BATCH_SEARCH_PATH, 404, 1);
+    let requests = vec![request(&project, "tokenize"), request(&project, "storage")];
+    let results = search_batch_with_config(&config, &requests).await;
+    assert!(results[0].as_ref().unwrap().contains("src/tokenizer.rs"));
+    assert!(results[1].as_ref().unwrap().contains("src/storage.rs"));
+    assert_eq!(server.request_count(SEARCH_PATH), 2);
+
+    // 已知服务端不支持批量接口，之后不再探测
+    search_batch_with_config(&config, &requests).await;
+    assert_eq!(server.request_count(BATCH_SEARCH_PATH), 1);
+    assert_eq!(server.request_count(SEARCH_PATH), 4);
+}
+
+#[tokio::test]
+async fn test_short_blob_names_response_is_checked() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let server = MockAcemcpServer::start().await;
+    server.limit_upload_names(2);
+
+    // 默认只记录警告，保留服务端返回的名称
+    let project = synthetic_project();
+    let blob_names = update_index(&server.config(), &project.path()).await.unwrap();
+    assert_eq!(blob_names.len(), 2);
+
+    // 严格模式下该批次按失败处理，不写入任何名称
+    let project = synthetic_project();
+    let mut config = server.config();
+    config.strict_blob_names = Some(true);
+    let err = update_index(&config, &project.path()).await.unwrap_err();
+    assert!(err.to_string().contains("索引后未找到 blobs"));
+}
+
+#[tokio::test]
+async fn test_config_file_changes_are_hot_reloaded() {
+    isolate_home();
+    let _guard = serial().lock().await;
+    let mut app_config = load_standalone_config().unwrap();
+    app_config.mcp_config.acemcp_batch_size = Some(7);
+    save_standalone_config(&app_config).unwrap();
+    assert_eq!(current_config().unwrap().batch_size, Some(7));
+
+    // 修改配置文件后，监听到文件事件即替换内存中的配置，无需显式重新加载
+    app_config.mcp_config.acemcp_batch_size = Some(13);
+    save_standalone_config(&app_config).unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while current_config().unwrap().batch_size != Some(13) && std::time::Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(current_config().unwrap().batch_size, Some(13));
+
+    app_config.mcp_config.acemcp_batch_size = None;
+    save_standalone_config(&app_config).unwrap();
+}
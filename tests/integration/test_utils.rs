@@ -0,0 +1,371 @@
+//! 集成测试公共工具
+//!
+//! `MockAcemcpServer` 在本地随机端口上模拟 acemcp 服务端的上传与检索接口，
+//! 使 `update_index` / `search_with_config` 可以在离线环境下端到端运行
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use ring::digest::{Context, SHA256};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+
+use sanshu::mcp::tools::acemcp::types::AcemcpConfig;
+
+/// 上传接口路径
+pub const UPLOAD_PATH: &str = "/batch-upload";
+/// 批量删除接口路径
+pub const DELETE_PATH: &str = "/batch-delete";
+/// 检索接口路径
+pub const SEARCH_PATH: &str = "/agents/codebase-retrieval";
+/// 批量检索接口路径
+pub const BATCH_SEARCH_PATH: &str = "/agents/codebase-retrieval/batch";
+/// 已确认 blob 列表接口路径
+pub const LIST_BLOBS_PATH: &str = "/list-blobs";
+/// 健康检查接口路径（索引前的连通性探测）
+pub const HEALTH_PATH: &str = "/health";
+/// 索引完成通知接收路径（配置为 webhook_url）
+pub const WEBHOOK_PATH: &str = "/webhook";
+/// 模拟服务端接受的 token
+pub const MOCK_TOKEN: &str = "mock-token";
+
+#[derive(Default)]
+struct MockState {
+    /// blob 名称 -> (路径, 内容)
+    blobs: HashMap<String, (String, String)>,
+    /// blob 名称 -> 上传时携带的命名空间（未携带时为 None）
+    blob_namespaces: HashMap<String, Option<String>>,
+    /// 每个接口收到的请求载荷
+    requests: HashMap<String, Vec<Value>>,
+    /// 每个接口收到的请求携带的 Idempotency-Key（未携带时为 None）
+    idempotency_keys: HashMap<String, Vec<Option<String>>>,
+    /// 每个接口收到的请求携带的 (X-Request-Id, User-Agent)
+    tracing_headers: HashMap<String, Vec<(Option<String>, Option<String>)>>,
+    /// 每个接口接下来要返回的错误状态码（按顺序消耗）
+    failures: HashMap<String, VecDeque<u16>>,
+    /// 每个接口接下来要返回被截断的 200 响应的次数
+    truncations: HashMap<String, usize>,
+    /// 设置后检索接口直接返回该响应
+    search_response: Option<Value>,
+    /// 设置后上传接口每批最多返回这么多个 blob 名称
+    upload_name_limit: Option<usize>,
+}
+
+/// 模拟的 acemcp 服务端，离开作用域时自动关闭
+pub struct MockAcemcpServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockAcemcpServer {
+    /// 在 127.0.0.1 的随机端口上启动服务端
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("绑定端口失败");
+        let addr = listener.local_addr().expect("读取监听地址失败");
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let service_state = state.clone();
+        let make_svc = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = Server::from_tcp(listener)
+            .expect("创建模拟服务端失败")
+            .serve(make_svc)
+            .with_graceful_shutdown(async {
+                let _ = rx.await;
+            });
+        tokio::spawn(server);
+
+        Self { addr, state, shutdown: Some(tx) }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// 指向该服务端的最小可用配置
+    pub fn config(&self) -> AcemcpConfig {
+        AcemcpConfig {
+            base_url: Some(self.base_url()),
+            token: Some(MOCK_TOKEN.to_string()),
+            text_extensions: Some(vec![".rs".to_string(), ".md".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    /// 让 `path` 接口接下来的 `times` 次请求返回 `status`
+    pub fn fail_next(&self, path: &str, status: u16, times: usize) {
+        let mut state = self.state.lock().unwrap();
+        let queue = state.failures.entry(path.to_string()).or_default();
+        for _ in 0..times {
+            queue.push_back(status);
+        }
+    }
+
+    /// 让 `path` 接口接下来的 `times` 次请求返回状态码 200、但 JSON 被截断的响应体，模拟代理中途断开
+    pub fn truncate_next(&self, path: &str, times: usize) {
+        *self.state.lock().unwrap().truncations.entry(path.to_string()).or_default() += times;
+    }
+
+    /// 固定检索接口的响应内容
+    pub fn set_search_response(&self, response: Value) {
+        self.state.lock().unwrap().search_response = Some(response);
+    }
+
+    /// 让上传接口每批最多返回 `limit` 个 blob 名称，模拟响应与上传内容不一致的服务端
+    pub fn limit_upload_names(&self, limit: usize) {
+        self.state.lock().unwrap().upload_name_limit = Some(limit);
+    }
+
+    /// `path` 接口收到的请求次数（包括注入失败的请求）
+    pub fn request_count(&self, path: &str) -> usize {
+        self.state.lock().unwrap().requests.get(path).map(Vec::len).unwrap_or(0)
+    }
+
+    /// `path` 接口收到的请求载荷
+    pub fn requests(&self, path: &str) -> Vec<Value> {
+        self.state.lock().unwrap().requests.get(path).cloned().unwrap_or_default()
+    }
+
+    /// `path` 接口每次请求携带的 Idempotency-Key
+    pub fn idempotency_keys(&self, path: &str) -> Vec<Option<String>> {
+        self.state.lock().unwrap().idempotency_keys.get(path).cloned().unwrap_or_default()
+    }
+
+    /// `path` 接口每次请求携带的 (X-Request-Id, User-Agent)
+    pub fn tracing_headers(&self, path: &str) -> Vec<(Option<String>, Option<String>)> {
+        self.state.lock().unwrap().tracing_headers.get(path).cloned().unwrap_or_default()
+    }
+
+    /// 已上传 blob 的路径（排序后）
+    pub fn uploaded_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.state.lock().unwrap().blobs.values().map(|(p, _)| p.clone()).collect();
+        paths.sort();
+        paths
+    }
+}
+
+impl Drop for MockAcemcpServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+async fn handle(state: Arc<Mutex<MockState>>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    // 查询参数中的 project（测试使用的命名空间不含需要转义的字符，不做解码）
+    let project = req
+        .uri()
+        .query()
+        .and_then(|q| q.split('&').find_map(|pair| pair.strip_prefix("project=")))
+        .map(str::to_string);
+    let authorized = req
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v == format!("Bearer {}", MOCK_TOKEN))
+        .unwrap_or(false);
+    let idempotency_key = req
+        .headers()
+        .get("Idempotency-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let tracing = (header("X-Request-Id"), header("User-Agent"));
+    let body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+    let payload: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let mut state = state.lock().unwrap();
+    state.requests.entry(path.clone()).or_default().push(payload.clone());
+    state.idempotency_keys.entry(path.clone()).or_default().push(idempotency_key);
+    state.tracing_headers.entry(path.clone()).or_default().push(tracing);
+
+    if let Some(status) = state.failures.get_mut(&path).and_then(VecDeque::pop_front) {
+        return Ok(respond(status, json!({"error": "injected failure"})));
+    }
+    if !authorized {
+        return Ok(respond(401, json!({"error": "unauthorized"})));
+    }
+    if let Some(remaining) = state.truncations.get_mut(&path).filter(|n| **n > 0) {
+        *remaining -= 1;
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"blob_names": ["#))
+            .unwrap());
+    }
+
+    let response = match path.as_str() {
+        UPLOAD_PATH => {
+            let namespace = payload.get("namespace").and_then(Value::as_str).map(str::to_string);
+            let mut names = Vec::new();
+            for blob in payload.get("blobs").and_then(Value::as_array).into_iter().flatten() {
+                let mut blob_path = blob.get("path").and_then(Value::as_str).unwrap_or_default().to_string();
+                let mut content = blob.get("content").and_then(Value::as_str).unwrap_or_default().to_string();
+                // upload_chunk_encoding = base64 时路径与内容经过编码，按约定解码后再计算 blob 名称
+                if blob.get("encoding").and_then(Value::as_str) == Some("base64") {
+                    use base64::Engine;
+                    let decode = |text: &str| String::from_utf8(base64::engine::general_purpose::STANDARD.decode(text).unwrap_or_default()).unwrap_or_default();
+                    blob_path = decode(&blob_path);
+                    content = decode(&content);
+                }
+                let name = blob_name(&blob_path, &content);
+                state.blobs.insert(name.clone(), (blob_path, content));
+                state.blob_namespaces.insert(name.clone(), namespace.clone());
+                names.push(name);
+            }
+            names.truncate(state.upload_name_limit.unwrap_or(usize::MAX));
+            respond(200, json!({"blob_names": names}))
+        }
+        DELETE_PATH => {
+            // 只确认实际存在并被删除的 blob
+            let deleted: Vec<&str> = payload
+                .get("blob_names")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .filter(|name| state.blobs.remove(*name).is_some())
+                .collect();
+            respond(200, json!({"blob_names": deleted}))
+        }
+        SEARCH_PATH => {
+            let response = state.search_response.clone().unwrap_or_else(|| search(&state, &payload));
+            respond(200, response)
+        }
+        BATCH_SEARCH_PATH => {
+            // 每个查询使用同一份 blob 列表分别检索，按查询顺序返回
+            let results: Vec<Value> = payload
+                .get("information_requests")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|query| {
+                    let mut single = payload.clone();
+                    single["information_request"] = query.clone();
+                    search(&state, &single)
+                })
+                .collect();
+            respond(200, json!({"results": results}))
+        }
+        HEALTH_PATH | WEBHOOK_PATH => respond(200, json!({})),
+        LIST_BLOBS_PATH => {
+            // 只列出以请求的 project 为命名空间上传的 blob
+            let mut names: Vec<&String> = state
+                .blobs
+                .keys()
+                .filter(|name| state.blob_namespaces.get(*name).cloned().flatten() == project)
+                .collect();
+            names.sort();
+            respond(200, json!({"blob_names": names}))
+        }
+        _ => respond(404, json!({"error": "not found"})),
+    };
+    Ok(response)
+}
+
+/// 返回内容包含任一查询词（不区分大小写）的 blob
+fn search(state: &MockState, payload: &Value) -> Value {
+    let query = payload.get("information_request").and_then(Value::as_str).unwrap_or_default().to_lowercase();
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    let sections: Vec<String> = payload
+        .pointer("/blobs/added_blobs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|name| state.blobs.get(name.as_str()?))
+        .filter(|(_, content)| {
+            let content = content.to_lowercase();
+            terms.iter().any(|t| content.contains(t))
+        })
+        .map(|(path, content)| format!("Path: {}\n{}", path, content))
+        .collect();
+
+    let text = if sections.is_empty() { "No relevant code found.".to_string() } else { sections.join("\n\n") };
+    json!({"formatted_retrieval": text})
+}
+
+/// 项目的 blob 路径映射文件（与客户端一致：数据目录下以规范化项目根目录的哈希前 16 位为目录名）
+pub fn blob_paths_file(project_root: &str) -> PathBuf {
+    let normalized = std::fs::canonicalize(project_root).unwrap().to_string_lossy().replace('\\', "/");
+    PathBuf::from(std::env::var("ACEMCP_DATA_DIR").unwrap())
+        .join("projects")
+        .join(&blob_name(&normalized, "")[..16])
+        .join("blob_paths.json")
+}
+
+/// 与客户端一致的 blob 名称：sha256(路径 + 内容)
+fn blob_name(path: &str, content: &str) -> String {
+    let mut ctx = Context::new(&SHA256);
+    ctx.update(path.as_bytes());
+    ctx.update(content.as_bytes());
+    hex::encode(ctx.finish().as_ref())
+}
+
+fn respond(status: u16, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// 将 HOME 与记忆目录指向临时目录，避免索引状态写入真实的 ~/.acemcp、
+/// 首次索引写入的记忆文件落到合成项目中；同一测试进程中只设置一次
+pub fn isolate_home() {
+    static HOME: OnceLock<PathBuf> = OnceLock::new();
+    HOME.get_or_init(|| {
+        let home = std::env::temp_dir().join(format!("sanshu-it-home-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&home).unwrap();
+        std::env::set_var("HOME", &home);
+        std::env::remove_var("XDG_CONFIG_HOME");
+        std::env::remove_var("XDG_DATA_HOME");
+        std::env::set_var("SANSHU_MEMORY_DIR", home.join("memory"));
+        std::env::set_var("ACEMCP_DATA_DIR", home.join("acemcp-data"));
+        home
+    });
+}
+
+/// 索引状态保存在共享的 projects.json 中，写入这些文件的测试需要串行执行
+pub fn serial() -> &'static tokio::sync::Mutex<()> {
+    static LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+/// 在临时目录中创建合成项目，`files` 为 (相对路径, 内容)
+pub struct SyntheticProject {
+    pub root: PathBuf,
+}
+
+impl SyntheticProject {
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        let root = std::env::temp_dir().join(format!("sanshu-it-project-{}", uuid::Uuid::new_v4()));
+        for (rel, content) in files {
+            let path = root.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        Self { root }
+    }
+
+    pub fn path(&self) -> String {
+        self.root.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for SyntheticProject {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}